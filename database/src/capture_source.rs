@@ -0,0 +1,369 @@
+use anyhow::{Result, anyhow};
+use dblib::config::CONFIG;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path};
+use std::time::Duration;
+
+const SOURCE_COLUMNS: &str = "id, name, folder, iface, packets_per_file, filter, max_bytes, max_age, retention_age, retention_bytes";
+
+//--- Smallest segment size limit, a segment holds at least a full packet
+pub const MIN_SEGMENT_BYTES: u64 = 256 * 1024;
+//--- Largest rotation and retention age, 100 years
+pub const MAX_AGE_SECS: u64 = 100 * 365 * 24 * 3600;
+
+//--- Several capture threads share master.db, writers wait for each other
+pub fn open_master() -> Result<Connection> {
+    let conn = Connection::open(format!("{}/master.db", &CONFIG.master_index_path))?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+
+    Ok(conn)
+}
+
+//------------------------------------------------------------------
+//--- Capture source, a row of the capture table. Each source has its
+//--- interface, BPF filter, storage folder and segment rotation
+//--- limits, the segments it writes are tagged with its id
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaptureSource {
+    #[serde(default)]
+    pub id: u32,
+    pub name: String,
+    //--- Empty for db_path, relative folders are under db_path
+    #[serde(default)]
+    pub folder: String,
+    #[serde(default)]
+    pub iface: String,
+    //--- 0 for the capture default
+    #[serde(default)]
+    pub packets_per_file: usize,
+    #[serde(default)]
+    pub filter: String,
+    //--- Segment size in bytes and age in seconds, 0 for the defaults
+    #[serde(default)]
+    pub max_bytes: u64,
+    #[serde(default)]
+    pub max_age: u64,
+    //--- Age in seconds and total bytes of segments kept, 0 for the defaults
+    #[serde(default)]
+    pub retention_age: u64,
+    #[serde(default)]
+    pub retention_bytes: u64,
+}
+
+impl CaptureSource {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            name: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            folder: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            iface: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+            packets_per_file: row.get::<_, Option<usize>>(4)?.unwrap_or_default(),
+            filter: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+            max_bytes: row.get::<_, Option<u64>>(6)?.unwrap_or_default(),
+            max_age: row.get::<_, Option<u64>>(7)?.unwrap_or_default(),
+            retention_age: row.get::<_, Option<u64>>(8)?.unwrap_or_default(),
+            retention_bytes: row.get::<_, Option<u64>>(9)?.unwrap_or_default(),
+        })
+    }
+
+    pub fn list() -> Result<Vec<Self>> {
+        let conn = open_master()?;
+        let mut stmt = conn.prepare(&format!(
+            "select {} from capture order by id;",
+            SOURCE_COLUMNS
+        ))?;
+        let sources = stmt
+            .query_map([], Self::from_row)?
+            .collect::<rusqlite::Result<Vec<Self>>>()?;
+
+        Ok(sources)
+    }
+
+    pub fn by_name(name: &str) -> Result<Option<Self>> {
+        let conn = open_master()?;
+        let source = conn
+            .query_row(
+                &format!("select {} from capture where name = ?;", SOURCE_COLUMNS),
+                [name],
+                Self::from_row,
+            )
+            .optional()?;
+
+        Ok(source)
+    }
+
+    //--- Sources are unique by name, saving an existing name updates it
+    pub fn save(&mut self) -> Result<()> {
+        if self.name.is_empty() || self.name == "*" {
+            return Err(anyhow!("Invalid capture source name: '{}'", self.name));
+        }
+        self.check_limits()?;
+        let storage = match CONFIG.storage_path.as_str() {
+            "" => &CONFIG.db_path,
+            path => path,
+        };
+        folder_inside(Path::new(storage), Path::new(&CONFIG.db_path), &self.folder)?;
+
+        let conn = open_master()?;
+        if let Some(existing) = Self::by_name(&self.name)? {
+            self.id = existing.id;
+            conn.execute(
+                "update capture set folder = ?, iface = ?, packets_per_file = ?, filter = ?, max_bytes = ?, max_age = ?, retention_age = ?, retention_bytes = ? where id = ?;",
+                params![
+                    self.folder,
+                    self.iface,
+                    self.packets_per_file,
+                    self.filter,
+                    self.max_bytes,
+                    self.max_age,
+                    self.retention_age,
+                    self.retention_bytes,
+                    self.id
+                ],
+            )?;
+        } else {
+            conn.execute(
+                "insert into capture (name, folder, iface, packets_per_file, filter, max_bytes, max_age, retention_age, retention_bytes) values (?, ?, ?, ?, ?, ?, ?, ?, ?);",
+                params![
+                    self.name,
+                    self.folder,
+                    self.iface,
+                    self.packets_per_file,
+                    self.filter,
+                    self.max_bytes,
+                    self.max_age,
+                    self.retention_age,
+                    self.retention_bytes
+                ],
+            )?;
+            self.id = conn.last_insert_rowid() as u32;
+        }
+
+        Ok(())
+    }
+
+    //--- Limits of 0 are the configured defaults, the others must rotate
+    //--- segments before retention deletes them
+    fn check_limits(&self) -> Result<()> {
+        if self.max_bytes > 0 && self.max_bytes < MIN_SEGMENT_BYTES {
+            return Err(anyhow!(
+                "Segment size {} below {} bytes",
+                self.max_bytes,
+                MIN_SEGMENT_BYTES
+            ));
+        }
+        if self.max_age > MAX_AGE_SECS || self.retention_age > MAX_AGE_SECS {
+            return Err(anyhow!("Age above {} seconds", MAX_AGE_SECS));
+        }
+        if self.max_bytes > i64::MAX as u64 || self.retention_bytes > i64::MAX as u64 {
+            return Err(anyhow!("Size above {} bytes", i64::MAX));
+        }
+        if self.retention_bytes > 0 && self.retention_bytes < self.max_bytes {
+            return Err(anyhow!(
+                "Retention size {} below the segment size {}",
+                self.retention_bytes,
+                self.max_bytes
+            ));
+        }
+        if self.retention_age > 0 && self.retention_age < self.max_age {
+            return Err(anyhow!(
+                "Retention age {} below the segment age {}",
+                self.retention_age,
+                self.max_age
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+//--- Folder of a source, relative to db_path, must resolve inside the
+//--- storage directory. The folder is created by the capture so links
+//--- are resolved on its deepest existing parent
+fn folder_inside(storage: &Path, db_path: &Path, folder: &str) -> Result<()> {
+    if folder.is_empty() {
+        return Ok(());
+    }
+
+    let requested = db_path.join(folder);
+    if requested
+        .components()
+        .any(|component| component == Component::ParentDir)
+    {
+        return Err(anyhow!("Capture folder {} has a parent reference", folder));
+    }
+
+    let storage = storage
+        .canonicalize()
+        .map_err(|e| anyhow!("Storage directory {}: {}", storage.display(), e))?;
+    let mut existing = requested.as_path();
+    while !existing.exists() {
+        existing = existing
+            .parent()
+            .ok_or_else(|| anyhow!("Capture folder {} has no existing parent", folder))?;
+    }
+    let resolved = existing
+        .canonicalize()?
+        .join(requested.strip_prefix(existing)?);
+
+    if resolved.starts_with(&storage) {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Capture folder {} is outside the storage directory",
+            folder
+        ))
+    }
+}
+
+//--- Source ids of a FROM clause, None when every segment is searched
+pub fn resolve_from(from: &[String], sources: &[CaptureSource]) -> Result<Option<HashSet<u32>>> {
+    if from.is_empty() || from.iter().any(|name| name == "*") {
+        return Ok(None);
+    }
+
+    let mut source_ids: HashSet<u32> = HashSet::new();
+    for name in from {
+        match sources.iter().find(|source| &source.name == name) {
+            Some(source) => {
+                source_ids.insert(source.id);
+            }
+            None => return Err(anyhow!("Unknown capture source: {}", name)),
+        }
+    }
+
+    Ok(Some(source_ids))
+}
+
+//--- Segments written by the sources of a FROM clause
+pub fn source_segments(from: &[String]) -> Result<Option<HashSet<u32>>> {
+    let Some(source_ids) = resolve_from(from, &CaptureSource::list()?)? else {
+        return Ok(None);
+    };
+
+    let conn = open_master()?;
+    let mut stmt = conn.prepare("select file_id, capture_id from segment;")?;
+    let segments = stmt
+        .query_map([], |row| Ok((row.get::<_, u32>(0)?, row.get::<_, u32>(1)?)))?
+        .filter_map(|segment| segment.ok())
+        .filter(|(_, capture_id)| source_ids.contains(capture_id))
+        .map(|(file_id, _)| file_id)
+        .collect();
+
+    Ok(Some(segments))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_clause() {
+        let sources = vec![
+            CaptureSource {
+                id: 1,
+                name: "lan".to_string(),
+                ..Default::default()
+            },
+            CaptureSource {
+                id: 2,
+                name: "dmz".to_string(),
+                ..Default::default()
+            },
+        ];
+        let names = |list: &[&str]| list.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+
+        assert!(resolve_from(&[], &sources).unwrap().is_none(), "No source");
+        assert!(
+            resolve_from(&names(&["lan", "*"]), &sources)
+                .unwrap()
+                .is_none(),
+            "Wildcard"
+        );
+        assert_eq!(
+            resolve_from(&names(&["dmz", "lan"]), &sources).unwrap(),
+            Some(HashSet::from([1, 2])),
+            "Two sources"
+        );
+        assert!(
+            resolve_from(&names(&["wan"]), &sources).is_err(),
+            "Unknown source"
+        );
+    }
+
+    #[test]
+    fn storage_folder() {
+        let storage = std::env::temp_dir().join(format!("storage_folder_{}", std::process::id()));
+        let db_path = storage.join("db");
+        std::fs::create_dir_all(&db_path).unwrap();
+
+        assert!(folder_inside(&storage, &db_path, "").is_ok(), "db_path");
+        assert!(
+            folder_inside(&storage, &db_path, "lan/new").is_ok(),
+            "New folder"
+        );
+        assert!(
+            folder_inside(&storage, &db_path, storage.join("dmz").to_str().unwrap()).is_ok(),
+            "Absolute folder"
+        );
+        assert!(
+            folder_inside(&storage, &db_path, "/etc").is_err(),
+            "Outside"
+        );
+        assert!(
+            folder_inside(&storage, &db_path, "../../x").is_err(),
+            "Parent"
+        );
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/tmp", db_path.join("link")).unwrap();
+            assert!(
+                folder_inside(&storage, &db_path, "link/x").is_err(),
+                "Link out"
+            );
+        }
+
+        std::fs::remove_dir_all(storage).unwrap();
+    }
+
+    #[test]
+    fn source_limits() {
+        let source = |max_bytes, max_age, retention_bytes, retention_age| CaptureSource {
+            name: "lan".to_string(),
+            max_bytes,
+            max_age,
+            retention_bytes,
+            retention_age,
+            ..Default::default()
+        };
+
+        assert!(source(0, 0, 0, 0).check_limits().is_ok(), "Defaults");
+        assert!(
+            source(1 << 30, 3600, 1 << 40, 86_400)
+                .check_limits()
+                .is_ok(),
+            "Limits"
+        );
+        assert!(source(100, 0, 0, 0).check_limits().is_err(), "Tiny segment");
+        assert!(
+            source(0, u64::MAX, 0, 0).check_limits().is_err(),
+            "Age overflow"
+        );
+        assert!(
+            source(0, 0, u64::MAX, 0).check_limits().is_err(),
+            "Size overflow"
+        );
+        assert!(
+            source(1 << 30, 0, 1 << 20, 0).check_limits().is_err(),
+            "Retention below a segment"
+        );
+        assert!(
+            source(0, 3600, 0, 60).check_limits().is_err(),
+            "Retention before rotation"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::capture_source::open_master;
+use anyhow::Result;
+use rusqlite::params;
+use serde::Serialize;
+
+//------------------------------------------------------------------
+//--- Counters of one capture segment, from its first packet until it
+//--- is closed. Drops are the packets lost while it was open, by the
+//--- capture queue, by the kernel or by a failed write, a segment with
+//--- drops is not a complete record of its link
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SegmentStats {
+    pub file_id: u32,
+    pub capture_id: u32,
+    pub opened_at: u64,
+    pub closed_at: u64,
+    pub packets: u64,
+    pub bytes: u64,
+    pub queue_dropped: u64,
+    pub kernel_dropped: u64,
+    pub if_dropped: u64,
+    pub queue_high_water: u64,
+    //--- Packets not written and a failed close of the segment
+    pub write_errors: u64,
+}
+
+impl SegmentStats {
+    pub fn dropped(&self) -> u64 {
+        self.queue_dropped + self.kernel_dropped + self.if_dropped + self.write_errors
+    }
+
+    pub fn save(&self) -> Result<()> {
+        open_master()?.execute(
+            "insert or replace into segment_stats (file_id, capture_id, opened_at, closed_at,
+                packets, bytes, queue_dropped, kernel_dropped, if_dropped, queue_high_water,
+                write_errors)
+             values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            params![
+                self.file_id,
+                self.capture_id,
+                self.opened_at,
+                self.closed_at,
+                self.packets,
+                self.bytes,
+                self.queue_dropped,
+                self.kernel_dropped,
+                self.if_dropped,
+                self.queue_high_water,
+                self.write_errors
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    //--- Segments of one source, or of every source, oldest first
+    pub fn list(capture_id: Option<u32>) -> Result<Vec<SegmentStats>> {
+        let conn = open_master()?;
+        let mut stmt = conn.prepare(
+            "select file_id, capture_id, opened_at, closed_at, packets, bytes,
+                queue_dropped, kernel_dropped, if_dropped, queue_high_water, write_errors
+             from segment_stats where ?1 is null or capture_id = ?1 order by file_id;",
+        )?;
+        let stats = stmt
+            .query_map([capture_id], |row| {
+                Ok(SegmentStats {
+                    file_id: row.get(0)?,
+                    capture_id: row.get::<_, Option<u32>>(1)?.unwrap_or_default(),
+                    opened_at: row.get(2)?,
+                    closed_at: row.get(3)?,
+                    packets: row.get(4)?,
+                    bytes: row.get(5)?,
+                    queue_dropped: row.get(6)?,
+                    kernel_dropped: row.get(7)?,
+                    if_dropped: row.get(8)?,
+                    queue_high_water: row.get(9)?,
+                    write_errors: row.get::<_, Option<u64>>(10)?.unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<SegmentStats>>>()?;
+
+        Ok(stats)
+    }
+}
+
+//------------------------------------------------------------------
+//--- Totals of a capture since its handle was opened, saved while it
+//--- runs. Received is what the capture handle gave to the queue, the
+//--- kernel counters are the last pcap statistics of the handle
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CaptureStats {
+    pub iface: String,
+    pub capture_id: u32,
+    pub started_at: u64,
+    pub updated_at: u64,
+    pub received: u64,
+    pub queue_dropped: u64,
+    pub kernel_received: u64,
+    pub kernel_dropped: u64,
+    pub if_dropped: u64,
+    pub queue_high_water: u64,
+    pub write_errors: u64,
+}
+
+impl CaptureStats {
+    pub fn save(&self) -> Result<()> {
+        open_master()?.execute(
+            "insert or replace into capture_stats (iface, capture_id, started_at, updated_at,
+                received, queue_dropped, kernel_received, kernel_dropped, if_dropped,
+                queue_high_water, write_errors)
+             values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            params![
+                self.iface,
+                self.capture_id,
+                self.started_at,
+                self.updated_at,
+                self.received,
+                self.queue_dropped,
+                self.kernel_received,
+                self.kernel_dropped,
+                self.if_dropped,
+                self.queue_high_water,
+                self.write_errors
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub fn list() -> Result<Vec<CaptureStats>> {
+        let conn = open_master()?;
+        let mut stmt = conn.prepare(
+            "select iface, capture_id, started_at, updated_at, received, queue_dropped,
+                kernel_received, kernel_dropped, if_dropped, queue_high_water, write_errors
+             from capture_stats order by iface;",
+        )?;
+        let stats = stmt
+            .query_map([], |row| {
+                Ok(CaptureStats {
+                    iface: row.get(0)?,
+                    capture_id: row.get::<_, Option<u32>>(1)?.unwrap_or_default(),
+                    started_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    received: row.get(4)?,
+                    queue_dropped: row.get(5)?,
+                    kernel_received: row.get(6)?,
+                    kernel_dropped: row.get(7)?,
+                    if_dropped: row.get(8)?,
+                    queue_high_water: row.get(9)?,
+                    write_errors: row.get::<_, Option<u64>>(10)?.unwrap_or_default(),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<CaptureStats>>>()?;
+
+        Ok(stats)
+    }
+}
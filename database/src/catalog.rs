@@ -0,0 +1,464 @@
+use crate::capture_source::open_master;
+use crate::file_manager::segment_filename;
+use crate::index_manager::MasterIndex;
+use crate::reindex::{Fingerprint, INDEX_VERSION};
+use anyhow::{Result, anyhow};
+use crc32fast::Hasher;
+use log::info;
+use pcap::segment::SegmentReader;
+use rusqlite::{Connection, OptionalExtension, Row, TransactionBehavior, params};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+//------------------------------------------------------------------
+//--- Life of a segment in the catalog. Open while it is written,
+//--- closed once complete on disk, indexed once its packet index is
+//--- written and searchable. Archived segments have left the store and
+//--- keep their row
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentState {
+    Open,
+    Closed,
+    Indexed,
+    Archived,
+}
+
+impl SegmentState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SegmentState::Open => "open",
+            SegmentState::Closed => "closed",
+            SegmentState::Indexed => "indexed",
+            SegmentState::Archived => "archived",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "open" => Ok(SegmentState::Open),
+            "closed" => Ok(SegmentState::Closed),
+            "indexed" => Ok(SegmentState::Indexed),
+            "archived" => Ok(SegmentState::Archived),
+            _ => Err(anyhow!("Unknown segment state: {}", value)),
+        }
+    }
+}
+
+//------------------------------------------------------------------
+//--- Row of the segment catalog, the segment table of master.db. The
+//--- bytes are those of the pcap stream, stored_bytes those of the
+//--- file on disk. The checksum is the CRC32 of the stored file when it
+//--- was indexed. capture_id 0 for imported segments
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogEntry {
+    pub file_id: u32,
+    pub capture_id: u32,
+    pub link_type: u32,
+    pub start_timestamp: u32,
+    pub end_timestamp: u32,
+    pub packets: u64,
+    pub bytes: u64,
+    pub stored_bytes: u64,
+    pub dropped: u64,
+    pub index_version: u32,
+    pub compressed: bool,
+    pub checksum: String,
+    pub state: SegmentState,
+    pub legal_hold: bool,
+}
+
+const CATALOG_COLUMNS: &str = "file_id, capture_id, link_type, start_timestamp, end_timestamp, \
+     packets, bytes, segment_len, dropped, index_version, compressed, checksum, state, legal_hold";
+
+impl CatalogEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let state: Option<String> = row.get(12)?;
+
+        Ok(Self {
+            file_id: row.get(0)?,
+            capture_id: row.get::<_, Option<u32>>(1)?.unwrap_or_default(),
+            link_type: row.get::<_, Option<u32>>(2)?.unwrap_or_default(),
+            start_timestamp: row.get::<_, Option<u32>>(3)?.unwrap_or_default(),
+            end_timestamp: row.get::<_, Option<u32>>(4)?.unwrap_or_default(),
+            packets: row.get::<_, Option<u64>>(5)?.unwrap_or_default(),
+            bytes: row.get::<_, Option<u64>>(6)?.unwrap_or_default(),
+            stored_bytes: row.get::<_, Option<u64>>(7)?.unwrap_or_default(),
+            dropped: row.get::<_, Option<u64>>(8)?.unwrap_or_default(),
+            index_version: row.get::<_, Option<u32>>(9)?.unwrap_or_default(),
+            compressed: row.get::<_, Option<bool>>(10)?.unwrap_or_default(),
+            checksum: row.get::<_, Option<String>>(11)?.unwrap_or_default(),
+            state: state
+                .as_deref()
+                .map_or(Ok(SegmentState::Closed), SegmentState::parse)
+                .unwrap_or(SegmentState::Closed),
+            legal_hold: row.get::<_, Option<bool>>(13)?.unwrap_or_default(),
+        })
+    }
+}
+
+//--- File id of a new segment and its open row in one write transaction,
+//--- capture sources and imports running side by side never share an id
+pub fn allocate(capture_id: u32, link_type: u32) -> Result<u32> {
+    let mut conn = open_master()?;
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+
+    let file_id: u32 = tx.query_row("select file_id from config where id = 1;", [], |row| {
+        row.get(0)
+    })?;
+    tx.execute("update config set file_id = ? where id = 1;", [file_id + 1])?;
+    tx.execute(
+        "insert or replace into segment (file_id, capture_id, link_type, state)
+         values (?, ?, ?, ?);",
+        params![
+            file_id,
+            (capture_id > 0).then_some(capture_id),
+            link_type,
+            SegmentState::Open.as_str()
+        ],
+    )?;
+    tx.commit()?;
+
+    Ok(file_id)
+}
+
+//--- The segment is complete on disk, with the packets its capture lost
+pub fn close(file_id: u32, dropped: u64) -> Result<()> {
+    open_master()?.execute(
+        "update segment set state = ?, dropped = ? where file_id = ?;",
+        params![SegmentState::Closed.as_str(), dropped, file_id],
+    )?;
+
+    Ok(())
+}
+
+//--- CRC32 of the file as stored
+pub fn checksum(fname: &str) -> Result<String> {
+    let mut reader = BufReader::new(File::open(fname)?);
+    let mut hasher = Hasher::new();
+    let mut buffer = [0u8; 64 * 1024];
+
+    loop {
+        let len = reader.read(&mut buffer)?;
+        if len == 0 {
+            break;
+        }
+        hasher.update(&buffer[..len]);
+    }
+
+    Ok(format!("{:08x}", hasher.finalize()))
+}
+
+//--- Length of the pcap stream and compression of a stored segment
+fn stream_info(fname: &str) -> io::Result<(u64, bool)> {
+    let reader = SegmentReader::open(fname)?;
+    Ok((reader.uncompressed_len(), reader.is_compressed()))
+}
+
+//------------------------------------------------------------------
+//--- The packet index of the segment is written: its time range and
+//--- packets, the index version, the stored file and its checksum.
+//--- Segments of an import or an older version get their row here
+//------------------------------------------------------------------
+pub fn set_indexed(index: &MasterIndex, packets: u64) -> Result<()> {
+    set_indexed_version(index, packets, INDEX_VERSION)
+}
+
+//--- Row of an index written by another version, recovery keeps the
+//--- version so the index is read with its own layout
+pub fn set_indexed_version(index: &MasterIndex, packets: u64, index_version: u32) -> Result<()> {
+    let fname = segment_filename(index.file_ptr);
+    let fingerprint = Fingerprint::of(index.file_ptr)?;
+    let (bytes, compressed) = stream_info(&fname)?;
+    let checksum = checksum(&fname)?;
+
+    open_master()?.execute(
+        "insert into segment (file_id, start_timestamp, end_timestamp, packets, bytes,
+            index_version, segment_mtime, segment_len, compressed, checksum, state)
+         values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+         on conflict(file_id) do update set start_timestamp = ?2, end_timestamp = ?3,
+            packets = ?4, bytes = ?5, index_version = ?6, segment_mtime = ?7,
+            segment_len = ?8, compressed = ?9, checksum = ?10, state = ?11;",
+        params![
+            index.file_ptr,
+            index.start_timestamp,
+            index.end_timestamp,
+            packets,
+            bytes,
+            index_version,
+            fingerprint.mtime,
+            fingerprint.len,
+            compressed,
+            checksum,
+            SegmentState::Indexed.as_str()
+        ],
+    )?;
+
+    Ok(())
+}
+
+//--- A compressed segment keeps its packets, a current index stays current
+pub fn set_stored(file_id: u32) -> Result<()> {
+    let fname = segment_filename(file_id);
+    let fingerprint = Fingerprint::of(file_id)?;
+    let (_, compressed) = stream_info(&fname)?;
+    let checksum = checksum(&fname)?;
+
+    open_master()?.execute(
+        "update segment set segment_mtime = ?, segment_len = ?, compressed = ?, checksum = ?
+         where file_id = ? and index_version = ?;",
+        params![
+            fingerprint.mtime,
+            fingerprint.len,
+            compressed,
+            checksum,
+            file_id,
+            INDEX_VERSION
+        ],
+    )?;
+
+    Ok(())
+}
+
+pub fn entry(file_id: u32) -> Result<Option<CatalogEntry>> {
+    let entry = open_master()?
+        .query_row(
+            &format!("select {} from segment where file_id = ?;", CATALOG_COLUMNS),
+            [file_id],
+            CatalogEntry::from_row,
+        )
+        .optional()?;
+
+    Ok(entry)
+}
+
+//--- Segments of one source, or of every source, in file id order
+pub fn list(capture_id: Option<u32>) -> Result<Vec<CatalogEntry>> {
+    let conn = open_master()?;
+    let mut stmt = conn.prepare(&format!(
+        "select {} from segment where ?1 is null or capture_id = ?1 order by file_id;",
+        CATALOG_COLUMNS
+    ))?;
+    let entries = stmt
+        .query_map([capture_id], CatalogEntry::from_row)?
+        .collect::<rusqlite::Result<Vec<CatalogEntry>>>()?;
+
+    Ok(entries)
+}
+
+//--- Time range of every indexed segment
+pub fn time_ranges() -> Result<Vec<MasterIndex>> {
+    ranges(None)
+}
+
+//--- Indexed segments the planner searches. Indexes of an older version
+//--- are read in their own layout until the segment is indexed again, an
+//--- index of a newer version is not read
+pub fn searchable_ranges() -> Result<Vec<MasterIndex>> {
+    ranges(Some(INDEX_VERSION))
+}
+
+fn ranges(max_version: Option<u32>) -> Result<Vec<MasterIndex>> {
+    ranges_of(&open_master()?, max_version)
+}
+
+fn ranges_of(conn: &Connection, max_version: Option<u32>) -> Result<Vec<MasterIndex>> {
+    let mut stmt = conn.prepare(
+        "select start_timestamp, end_timestamp, file_id from segment
+         where state = ?1 and (?2 is null or coalesce(index_version, 0) <= ?2)
+         order by file_id;",
+    )?;
+    let ranges = stmt
+        .query_map(
+            params![SegmentState::Indexed.as_str(), max_version],
+            |row| {
+                Ok(MasterIndex {
+                    start_timestamp: row.get::<_, Option<u32>>(0)?.unwrap_or_default(),
+                    end_timestamp: row.get::<_, Option<u32>>(1)?.unwrap_or_default(),
+                    file_ptr: row.get(2)?,
+                })
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<MasterIndex>>>()?;
+
+    Ok(ranges)
+}
+
+//------------------------------------------------------------------
+//--- Stores written before the catalog: the rows of master.pidx give
+//--- the time range of the indexed segments and the file is kept as
+//--- master.pidx.migrated. Packet counts and checksums are filled in
+//--- when the segments are indexed again
+//------------------------------------------------------------------
+pub fn migrate_master_index(conn: &Connection, master_index_path: &str) -> Result<()> {
+    let fname = format!("{}/master.pidx", master_index_path);
+    let Ok(content) = fs::read(&fname) else {
+        return Ok(());
+    };
+
+    let mut count = 0;
+    for row in content.chunks_exact(12) {
+        let start = u32::from_be_bytes([row[0], row[1], row[2], row[3]]);
+        let end = u32::from_be_bytes([row[4], row[5], row[6], row[7]]);
+        let file_id = u32::from_be_bytes([row[8], row[9], row[10], row[11]]);
+
+        conn.execute(
+            "insert into segment (file_id, start_timestamp, end_timestamp, state)
+             values (?1, ?2, ?3, ?4)
+             on conflict(file_id) do update set start_timestamp = ?2, end_timestamp = ?3,
+                state = ?4;",
+            params![file_id, start, end, SegmentState::Indexed.as_str()],
+        )?;
+        count += 1;
+    }
+
+    fs::rename(&fname, format!("{}.migrated", fname))?;
+    info!(
+        "Migrated {} master index rows to the segment catalog",
+        count
+    );
+
+    Ok(())
+}
+
+//--- Protocol counts kept in packetdb.db before the catalog
+pub fn migrate_proto_stats(conn: &Connection, master_index_path: &str) -> Result<()> {
+    let fname = format!("{}/packetdb.db", master_index_path);
+    if !Path::new(&fname).exists() {
+        return Ok(());
+    }
+
+    conn.execute("attach database ? as legacy;", [&fname])?;
+    let tables: usize = conn.query_row(
+        "select count(*) from legacy.sqlite_master where type = 'table' and name = 'proto_stats';",
+        [],
+        |row| row.get(0),
+    )?;
+    if tables > 0 {
+        conn.execute(
+            "insert into proto_stats (file_id, proto, count)
+             select file_id, proto, count from legacy.proto_stats;",
+            [],
+        )?;
+    }
+    conn.execute("detach database legacy;", [])?;
+
+    fs::rename(&fname, format!("{}.migrated", fname))?;
+    info!("Migrated the protocol statistics to master.db");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_states() {
+        for state in [
+            SegmentState::Open,
+            SegmentState::Closed,
+            SegmentState::Indexed,
+            SegmentState::Archived,
+        ] {
+            assert_eq!(
+                SegmentState::parse(state.as_str()).unwrap(),
+                state,
+                "Round trip"
+            );
+        }
+        assert!(SegmentState::parse("lost").is_err(), "Unknown state");
+    }
+
+    #[test]
+    fn older_versions_searchable() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!(
+            "create table segment (file_id integer primary key, start_timestamp integer,
+                end_timestamp integer, index_version integer, state varchar(10));
+             insert into segment values (1, 100, 199, null, 'indexed');
+             insert into segment values (2, 200, 299, 1, 'indexed');
+             insert into segment values (3, 300, 399, {}, 'indexed');
+             insert into segment values (4, 400, 499, {}, 'indexed');
+             insert into segment values (5, 500, 599, {}, 'indexed');
+             insert into segment values (6, 600, 699, {}, 'closed');",
+            INDEX_VERSION - 1,
+            INDEX_VERSION,
+            INDEX_VERSION + 1,
+            INDEX_VERSION
+        ))
+        .unwrap();
+
+        let file_ids = |max_version| -> Vec<u32> {
+            ranges_of(&conn, max_version)
+                .unwrap()
+                .into_iter()
+                .map(|index| index.file_ptr)
+                .collect()
+        };
+        assert_eq!(
+            file_ids(Some(INDEX_VERSION)),
+            vec![1, 2, 3, 4],
+            "Older and current versions searched, newer and unindexed not"
+        );
+        assert_eq!(file_ids(None), vec![1, 2, 3, 4, 5], "Every indexed segment");
+    }
+
+    #[test]
+    fn master_index_migration() {
+        let dir = std::env::temp_dir().join(format!("catalog_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.to_string_lossy().to_string();
+
+        let mut rows: Vec<u8> = Vec::new();
+        for (start, end, file_id) in [(100u32, 199u32, 1u32), (200, 299, 2)] {
+            rows.extend(start.to_be_bytes());
+            rows.extend(end.to_be_bytes());
+            rows.extend(file_id.to_be_bytes());
+        }
+        fs::write(format!("{}/master.pidx", path), rows).unwrap();
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "create table segment (file_id integer primary key, capture_id integer,
+                start_timestamp integer, end_timestamp integer, state varchar(10));",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "insert into segment (file_id, capture_id) values (2, 7);",
+            [],
+        )
+        .unwrap();
+
+        migrate_master_index(&conn, &path).unwrap();
+        let migrated: Vec<(u32, Option<u32>, u32, u32, String)> = conn
+            .prepare("select file_id, capture_id, start_timestamp, end_timestamp, state from segment order by file_id;")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+
+        assert_eq!(
+            migrated,
+            vec![
+                (1, None, 100, 199, "indexed".to_string()),
+                (2, Some(7), 200, 299, "indexed".to_string())
+            ],
+            "Rows migrated, source kept"
+        );
+        assert!(
+            Path::new(&format!("{}/master.pidx.migrated", path)).exists(),
+            "Master index kept aside"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,23 +1,73 @@
+use crate::capture_source::source_segments;
+use crate::catalog::searchable_ranges;
 use crate::exec_plan::ExecutionPlan;
-use crate::index_manager::IndexManager;
+use crate::export::{ExportOptions, ExportWriter, TimeMerge};
+use crate::file_manager::{live_segments, segment_filename};
+use crate::flow_index::search_flows;
+use crate::index_manager::{IndexManager, MasterIndex};
 use crate::proto_index::ProtoIndex;
+use crate::reindex::{IndexUpdate, update_indexes};
 use dblib::config::CONFIG;
 use frame::layer_index::LayerIndex;
 use frame::packet::Packet;
+use frame::packet_id::PacketId;
 use pcap::packet_ptr::PacketPtr;
 use pcap::pcapfile::PcapFile;
+use pcap::seek_packet::SeekPacket;
 use pql::cursor::Cursor;
 use pql::interpreter::Interpreter;
-use pql::parse::Parse;
+use pql::parse::{Parse, PqlStatement};
 use pql::query_result::QueryResult;
 
 use anyhow::Result;
 use log::{debug, info};
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::time::SystemTime;
 
+//--- Segments a query reads and where their packet pointers come from
+struct SearchPlan {
+    segments: Vec<u32>,
+    live: Vec<u32>,
+    proto_search: u32,
+    master: Vec<MasterIndex>,
+}
+
+impl SearchPlan {
+    //--- A protocol index when the query has one, the packet index of the
+    //--- segment or its live index otherwise
+    fn pointers(&self, expr: &PqlStatement, file_id: u32) -> Result<PacketPtr> {
+        if self.proto_search > LayerIndex::ARP as u32 && !self.live.contains(&file_id) {
+            ProtoIndex::new(file_id, self.proto_search).read()
+        } else {
+            IndexManager::default().search_index(expr, file_id)
+        }
+    }
+
+    //--- Reader of the matching packets of a segment, None when its index
+    //--- can not be read or retention deleted it since the plan was made
+    fn seek(&self, expr: &PqlStatement, file_id: u32) -> Option<SeekPacket> {
+        let ptr = match self.pointers(expr, file_id) {
+            Ok(ptr) => ptr,
+            Err(msg) => {
+                eprintln!("Error reading DB: {}", msg);
+                return None;
+            }
+        };
+
+        match SeekPacket::open(&segment_filename(file_id), ptr) {
+            Ok(seek_pkt) => Some(seek_pkt),
+            Err(msg) => {
+                debug!("Search, no segment {}: {}", file_id, msg);
+                None
+            }
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DbEngine {
     exec_plan: ExecutionPlan,
@@ -37,91 +87,250 @@ impl DbEngine {
         let mut parse = Parse::new();
 
         self.exec_plan.start("Start search");
-        // if let Ok(expr) = parse.parse_select(query) {
         match parse.parse_select(query) {
+            Ok(expr) if expr.is_flow_query() => {
+                let flows = search_flows(&expr).map_err(|error| error.to_string());
+                self.exec_plan.stop();
+                self.exec_plan.show();
+                flows
+            }
             Ok(expr) => {
                 debug!("--> Select query: {:?}", expr.search_type);
 
                 let mut query_result = QueryResult::new(expr.clone());
 
-                let mut file_count = 0;
-                let interpreter = Interpreter::new(expr.clone());
+                let searched = if query_result.count_reach() {
+                    Ok(())
+                } else {
+                    self.search(&expr, |pkt| {
+                        query_result.add(pkt);
+                        query_result.count_reach()
+                    })
+                };
+
+                self.exec_plan.stop();
+                self.exec_plan.show();
+                searched.map(|_| query_result.get_result())
+            }
+            Err(error) => Err(format!("Error processing query: {:?}", error)),
+        }
+    }
 
-                self.offset = 0;
-                let mut proto_search: u32 = 0;
-                let mut pkt_searched: usize = 0;
+    //--- Export mode, the matching packets are written as a capture file in
+    //--- timestamp order as they are read. With top the newest packets are
+    //--- kept and sorted, without top every matching packet is streamed
+    pub fn export<W: Write>(
+        &mut self,
+        query: &str,
+        options: &ExportOptions,
+        writer: &mut W,
+    ) -> Result<usize, String> {
+        println!("Exporting: {}", query);
+        let mut parse = Parse::new();
 
-                if expr.has_id_search() {
-                    debug!("In ID search");
-                    let pkt_result = self.get_id_packets(expr.id_search);
-                    for p in pkt_result {
-                        query_result.add(p);
-                    }
+        self.exec_plan.start("Start export");
+        let expr = parse
+            .parse_select(query)
+            .map_err(|error| format!("Error processing query: {:?}", error))?;
+
+        let comment_query = options.comments.then_some(query);
+        let mut output = ExportWriter::new(options.format, comment_query, writer);
+        let written = if expr.top > 0 || expr.has_id_search() {
+            self.export_top(&expr, &mut output)
+        } else {
+            self.export_all(&expr, &mut output)
+        };
+        let result = written.and_then(|count| {
+            output
+                .finish()
+                .map(|_| count)
+                .map_err(|error| format!("Error writing export: {}", error))
+        });
+
+        self.exec_plan.stop();
+        self.exec_plan.show();
+        result
+    }
 
-                    self.exec_plan.stop();
-                    self.exec_plan.show();
-                    Ok(query_result.get_result())
-                } else {
-                    let files_list: Result<Vec<u32>>;
-
-                    if let Some(proto_id) = self.has_proto(&expr.search_type) {
-                        info!("FOUND PROTO INDEX {:?}", proto_id);
-                        proto_search = proto_id as u32;
-                        files_list = self.get_proto_files(proto_search);
-                    } else {
-                        files_list = self.get_index_files();
-                    }
-                    match files_list {
-                        Ok(search_list) => {
-                            while !query_result.count_reach() {
-                                for file_id in &search_list {
-                                    file_count += 1;
-
-                                    let pkt_index: Result<PacketPtr> =
-                                        if proto_search > LayerIndex::ARP as u32 {
-                                            let mut proto_index =
-                                                ProtoIndex::new(*file_id, proto_search);
-                                            proto_index.read()
-                                        } else {
-                                            let mut index = IndexManager::default();
-                                            index.search_index(&expr, *file_id)
-                                        };
-
-                                    match pkt_index {
-                                        Ok(ptr) => {
-                                            let (nbr_searched, c) =
-                                                interpreter.run_pgm_seek(&ptr, expr.top);
-                                            pkt_searched += nbr_searched;
-
-                                            for r in c {
-                                                query_result.add(r);
-                                                if query_result.count_reach() {
-                                                    break;
-                                                }
-                                            }
-                                            if query_result.count_reach() {
-                                                break;
-                                            }
-                                        }
-                                        Err(msg) => eprintln!("Error reading DB: {}", msg),
-                                    }
-                                }
-                            }
-                            info!(
-                                "---> Searched {} files, with {} packets",
-                                file_count, pkt_searched
-                            );
-                        }
-                        Err(e) => println!("Error with index error:{}", e),
-                    }
+    //--- At most top packets, the newest first as a query returns them
+    fn export_top<W: Write>(
+        &mut self,
+        expr: &PqlStatement,
+        output: &mut ExportWriter<'_, W>,
+    ) -> Result<usize, String> {
+        let mut packets: Vec<Packet> = Vec::new();
+        let mut skipped: usize = 0;
+        self.search(expr, |pkt| {
+            if skipped < expr.offset {
+                skipped += 1;
+            } else {
+                packets.push(pkt);
+            }
+            expr.top > 0 && packets.len() >= expr.top
+        })?;
+        packets.sort_by_key(|pkt| pkt.timestamp_ns());
+
+        for pkt in &packets {
+            output
+                .write(pkt)
+                .map_err(|error| format!("Error writing export: {}", error))?;
+        }
 
-                    self.exec_plan.stop();
-                    self.exec_plan.show();
-                    Ok(query_result.get_result())
+        Ok(packets.len())
+    }
+
+    //--- Every matching packet, the segments merged in timestamp order. A
+    //--- segment is opened when the merge reaches its first second, offset
+    //--- skips the oldest packets
+    fn export_all<W: Write>(
+        &mut self,
+        expr: &PqlStatement,
+        output: &mut ExportWriter<'_, W>,
+    ) -> Result<usize, String> {
+        let interpreter = Interpreter::new(expr.clone());
+        let plan = self.plan_search(expr)?;
+
+        let starts: HashMap<u32, u32> = plan
+            .master
+            .iter()
+            .map(|index| (index.file_ptr, index.start_timestamp))
+            .collect();
+        let mut segments: Vec<(u64, u32)> = plan
+            .segments
+            .iter()
+            .map(|file_id| {
+                let start = starts.get(file_id).copied().unwrap_or_default() as u64;
+                (start * 1_000_000_000, *file_id)
+            })
+            .collect();
+        segments.sort();
+
+        let merge = TimeMerge::new(segments, |file_id| {
+            let mut seek_pkt = plan.seek(expr, file_id)?;
+            Some(std::iter::from_fn(move || seek_pkt.next()).filter(|pkt| interpreter.eval(pkt)))
+        });
+
+        let mut count: usize = 0;
+        for pkt in merge.skip(expr.offset) {
+            output
+                .write(&pkt)
+                .map_err(|error| format!("Error writing export: {}", error))?;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    //--- Matching packets are given to add until it returns true. The
+    //--- segments searched are the ones of the FROM capture sources
+    fn search<F: FnMut(Packet) -> bool>(
+        &mut self,
+        expr: &PqlStatement,
+        mut add: F,
+    ) -> Result<(), String> {
+        let interpreter = Interpreter::new(expr.clone());
+        self.offset = 0;
+
+        if expr.has_id_search() {
+            debug!("In ID search");
+            for pkt in self.get_id_packets(expr.id_search.clone()) {
+                if add(pkt) {
+                    break;
+                }
+            }
+            return Ok(());
+        }
+
+        let plan = self.plan_search(expr)?;
+        let mut file_count = 0;
+        let mut pkt_searched: usize = 0;
+
+        'files: for file_id in &plan.segments {
+            file_count += 1;
+
+            if let Some(seek_pkt) = plan.seek(expr, *file_id) {
+                let (nbr_searched, c) = interpreter.run_pgm_seek(seek_pkt, expr.top);
+                pkt_searched += nbr_searched;
+
+                for r in c {
+                    if add(r) {
+                        break 'files;
+                    }
                 }
             }
-            Err(error) => Err(format!("Error processing query: {:?}", error)),
         }
+        info!(
+            "---> Searched {} files, with {} packets",
+            file_count, pkt_searched
+        );
+
+        Ok(())
+    }
+
+    //--- Segments of the FROM capture sources in the query interval,
+    //--- newest first with the open capture segments
+    fn plan_search(&self, expr: &PqlStatement) -> Result<SearchPlan, String> {
+        let source_files = source_segments(&expr.from).map_err(|error| error.to_string())?;
+        let master = searchable_ranges().map_err(|error| error.to_string())?;
+
+        let mut proto_search: u32 = 0;
+        let files_list: Result<Vec<u32>>;
+
+        if let Some(proto_id) = self.has_proto(&expr.search_type) {
+            info!("FOUND PROTO INDEX {:?}", proto_id);
+            proto_search = proto_id as u32;
+            files_list = self.get_proto_files(proto_search);
+        } else {
+            files_list = self.get_index_files();
+        }
+
+        let mut search_list = match files_list {
+            Ok(search_list) => search_list,
+            Err(e) => {
+                println!("Error with index error:{}", e);
+                return Ok(SearchPlan {
+                    segments: Vec::new(),
+                    live: Vec::new(),
+                    proto_search,
+                    master,
+                });
+            }
+        };
+
+        //--- Open capture segments are the newest, searched first
+        //--- with their live index
+        let live_files: Vec<u32> = live_segments()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|file_id| !search_list.contains(file_id))
+            .collect();
+        search_list.splice(0..0, live_files.iter().copied());
+
+        if let Some(source_files) = &source_files {
+            search_list.retain(|file_id| source_files.contains(file_id));
+        }
+
+        let index_mgr = IndexManager::default();
+        let selected = expr
+            .interval
+            .as_ref()
+            .map(|interval| index_mgr.search_master_index(interval.from_secs(), interval.to_secs()))
+            .transpose()
+            .map_err(|error| error.to_string())?;
+        let candidates = search_list.len();
+        search_list = plan_segments(search_list, &master, selected.as_deref().unwrap_or(&master));
+        debug!(
+            "Time range kept {} of {} segments",
+            search_list.len(),
+            candidates
+        );
+
+        Ok(SearchPlan {
+            segments: search_list,
+            live: live_files,
+            proto_search,
+            master,
+        })
     }
 
     fn has_proto(&self, search_type: &HashSet<LayerIndex>) -> Option<LayerIndex> {
@@ -156,20 +365,31 @@ impl DbEngine {
         None
     }
 
-    fn get_id_packets(&self, id_list: Vec<u64>) -> Vec<Packet> {
+    pub fn get_packet(&self, id: PacketId) -> Option<Packet> {
+        self.get_id_packets(vec![id]).into_iter().next()
+    }
+
+    fn get_id_packets(&self, id_list: Vec<PacketId>) -> Vec<Packet> {
         let mut result: Vec<Packet> = Vec::new();
 
-        for id in id_list {
-            let file_id: u32 = (id >> 32) as u32;
-            let ptr: u32 = (id & 0xffff) as u32;
+        for PacketId {
+            file_id,
+            offset: ptr,
+        } in id_list
+        {
             debug!("ID search for {}:{}", file_id, ptr);
-            let mut pcapfile = PcapFile::new(file_id, &CONFIG.db_path);
+
+            //--- A segment purged since the id was given is not found
+            let mut pcapfile = match PcapFile::open(&segment_filename(file_id), file_id) {
+                Ok(pcapfile) => pcapfile,
+                Err(msg) => {
+                    debug!("ID search, no segment {}: {}", file_id, msg);
+                    continue;
+                }
+            };
 
             if let Some(pkt) = pcapfile.seek(ptr) {
-                debug!(
-                    "ID pkt ID: {}",
-                    pkt.get_field("frame.id").unwrap().to_usize()
-                );
+                debug!("ID pkt ID: {}", pkt.packet_id());
                 result.push(pkt);
             }
         }
@@ -206,7 +426,12 @@ impl DbEngine {
     //     chunk_size
     // }
 
+    //--- Protocol indexes of the searchable segments of the catalog
     fn get_proto_files(&self, proto_id: u32) -> Result<Vec<u32>> {
+        let searchable: HashSet<u32> = searchable_ranges()?
+            .iter()
+            .map(|index| index.file_ptr)
+            .collect();
         let paths = fs::read_dir(format!("{}/{:x}", &CONFIG.proto_index_path, proto_id))?;
         let mut file_id_list: Vec<u32> = Vec::new();
 
@@ -218,40 +443,108 @@ impl DbEngine {
                 .unwrap()
                 .parse::<u32>()?;
 
-            file_id_list.push(id);
+            if searchable.contains(&id) {
+                file_id_list.push(id);
+            }
         }
         file_id_list.sort();
         file_id_list.reverse();
         Ok(file_id_list)
     }
 
+    //--- Indexed segments of the catalog
     fn get_index_files(&self) -> Result<Vec<u32>> {
-        let paths = fs::read_dir(&CONFIG.index_path)?;
-        let mut file_id_list: Vec<u32> = Vec::new();
-
-        for path in paths {
-            let id: u32 = Path::new(&path.unwrap().file_name())
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .parse::<u32>()?;
-
-            file_id_list.push(id);
-        }
+        let mut file_id_list: Vec<u32> = searchable_ranges()?
+            .iter()
+            .map(|index| index.file_ptr)
+            .collect();
         file_id_list.sort();
         file_id_list.reverse();
         Ok(file_id_list)
     }
 
-    pub fn create_index(&self) {
+    //--- Only the segments without a current index are read
+    pub fn create_index(&self) -> Result<IndexUpdate> {
         let t_init = SystemTime::now();
-        let index_manager = IndexManager::default();
-        index_manager.create_index();
+        let update = update_indexes()?;
 
         info!(
-            "DB index creation time: {}ms",
+            "DB index update time: {}ms",
             t_init.elapsed().unwrap().as_millis()
         );
+
+        Ok(update)
+    }
+}
+
+//------------------------------------------------------------------
+//--- Segments to search in time order, newest first, so a query stops
+//--- at the newest packets that satisfy its top. Segments of the master
+//--- index that are not selected by the time range are not searched.
+//--- Segments without a master row, like open capture segments, have
+//--- an unknown range and are searched first
+//------------------------------------------------------------------
+fn plan_segments(files: Vec<u32>, master: &[MasterIndex], selected: &[MasterIndex]) -> Vec<u32> {
+    let known: HashSet<u32> = master.iter().map(|index| index.file_ptr).collect();
+    let end_timestamps: HashMap<u32, u32> = selected
+        .iter()
+        .map(|index| (index.file_ptr, index.end_timestamp))
+        .collect();
+
+    let mut files: Vec<u32> = files
+        .into_iter()
+        .filter(|file_id| !known.contains(file_id) || end_timestamps.contains_key(file_id))
+        .collect();
+    files.sort_by_key(|file_id| {
+        Reverse((
+            end_timestamps.get(file_id).copied().unwrap_or(u32::MAX),
+            *file_id,
+        ))
+    });
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index_manager::master_overlap;
+
+    fn master(file_ptr: u32, start_timestamp: u32, end_timestamp: u32) -> MasterIndex {
+        MasterIndex {
+            start_timestamp,
+            end_timestamp,
+            file_ptr,
+        }
+    }
+
+    #[test]
+    fn segment_plan() {
+        //--- Segment 4 is an import of older packets, 9 is open
+        let rows = || {
+            vec![
+                master(1, 100, 199),
+                master(2, 200, 299),
+                master(3, 300, 399),
+                master(4, 10, 50),
+            ]
+        };
+        let files = vec![9, 4, 3, 2, 1];
+
+        assert_eq!(
+            plan_segments(files.clone(), &rows(), &rows()),
+            vec![9, 3, 2, 1, 4],
+            "Time order without range"
+        );
+        assert_eq!(
+            plan_segments(files.clone(), &rows(), &master_overlap(rows(), 150, 250)),
+            vec![9, 2, 1],
+            "Segments of the range"
+        );
+        assert_eq!(
+            plan_segments(files, &rows(), &master_overlap(rows(), 0, 60)),
+            vec![9, 4],
+            "Range before the oldest capture"
+        );
     }
 }
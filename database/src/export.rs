@@ -0,0 +1,277 @@
+use frame::packet::Packet;
+use pcap::pcap_writer::PcapWriter;
+use pcap::pcapng_writer::PcapngWriter;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::io::{Result, Write};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Pcap,
+    Pcapng,
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Pcap => "application/vnd.tcpdump.pcap",
+            ExportFormat::Pcapng => "application/x-pcapng",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pcap => "pcap",
+            ExportFormat::Pcapng => "pcapng",
+        }
+    }
+}
+
+//--- Comments are only written in pcapng, classic pcap has no place for them
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub format: ExportFormat,
+    #[serde(default)]
+    pub comments: bool,
+}
+
+//--- Packet comment followed by the query text and the packet ID
+fn export_comment(pkt: &Packet, query: &str) -> String {
+    let comment = format!("packetdb query: {}\npacketdb id: {}", query, pkt.id_field());
+
+    match pkt.comment() {
+        Some(original) => format!("{}\n{}", original, comment),
+        None => comment,
+    }
+}
+
+//--- Capture file written one packet at a time
+pub enum ExportWriter<'q, W: Write> {
+    Pcap(PcapWriter<W>),
+    Pcapng(PcapngWriter<W>, Option<&'q str>),
+}
+
+impl<'q, W: Write> ExportWriter<'q, W> {
+    pub fn new(format: ExportFormat, comment_query: Option<&'q str>, writer: W) -> Self {
+        match format {
+            ExportFormat::Pcap => ExportWriter::Pcap(PcapWriter::new(writer)),
+            ExportFormat::Pcapng => ExportWriter::Pcapng(PcapngWriter::new(writer), comment_query),
+        }
+    }
+
+    pub fn write(&mut self, pkt: &Packet) -> Result<()> {
+        match self {
+            ExportWriter::Pcap(pcap) => pcap.write_packet(pkt),
+            ExportWriter::Pcapng(pcapng, Some(query)) => {
+                let comment = export_comment(pkt, query);
+                pcapng.write_packet_with_comment(pkt, Some(&comment))
+            }
+            ExportWriter::Pcapng(pcapng, None) => pcapng.write_packet(pkt),
+        }
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            ExportWriter::Pcap(pcap) => pcap.flush(),
+            ExportWriter::Pcapng(pcapng, _) => pcapng.flush(),
+        }
+    }
+}
+
+pub fn write_packets<W: Write>(
+    packets: &[Packet],
+    format: ExportFormat,
+    comment_query: Option<&str>,
+    writer: &mut W,
+) -> Result<()> {
+    let mut output = ExportWriter::new(format, comment_query, writer);
+    for pkt in packets {
+        output.write(pkt)?;
+    }
+    output.finish()
+}
+
+//------------------------------------------------------------------
+//--- k-way merge of the packet streams of segments, each one in time
+//--- order. Segments are given with the time of their first packet,
+//--- at the latest, and a segment is opened only when the merge reaches
+//--- that time so segments that do not overlap are read one at a time
+//------------------------------------------------------------------
+pub struct TimeMerge<I, F>
+where
+    I: Iterator<Item = Packet>,
+    F: FnMut(u32) -> Option<I>,
+{
+    pending: VecDeque<(u64, u32)>,
+    open: F,
+    streams: Vec<Option<I>>,
+    heads: Vec<Option<Packet>>,
+    heap: BinaryHeap<Reverse<(u64, usize)>>,
+}
+
+impl<I, F> TimeMerge<I, F>
+where
+    I: Iterator<Item = Packet>,
+    F: FnMut(u32) -> Option<I>,
+{
+    //--- segments are (start_ns, file_id) in start order
+    pub fn new(segments: Vec<(u64, u32)>, open: F) -> Self {
+        Self {
+            pending: segments.into(),
+            open,
+            streams: Vec::new(),
+            heads: Vec::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    //--- Next packet of a stream as its head, the stream is dropped,
+    //--- and its file closed, when it ends
+    fn advance(&mut self, slot: usize) {
+        let next = self.streams[slot].as_mut().and_then(|stream| stream.next());
+        match next {
+            Some(pkt) => {
+                self.heap.push(Reverse((pkt.timestamp_ns(), slot)));
+                self.heads[slot] = Some(pkt);
+            }
+            None => self.streams[slot] = None,
+        }
+    }
+}
+
+impl<I, F> Iterator for TimeMerge<I, F>
+where
+    I: Iterator<Item = Packet>,
+    F: FnMut(u32) -> Option<I>,
+{
+    type Item = Packet;
+
+    fn next(&mut self) -> Option<Packet> {
+        //--- Segments starting before the oldest head can hold older packets
+        while let Some(&(start, file_id)) = self.pending.front() {
+            let oldest = self.heap.peek().map(|Reverse((ts, _))| *ts);
+            if oldest.is_some_and(|ts| ts < start) {
+                break;
+            }
+            self.pending.pop_front();
+
+            if let Some(stream) = (self.open)(file_id) {
+                self.streams.push(Some(stream));
+                self.heads.push(None);
+                self.advance(self.streams.len() - 1);
+            }
+        }
+
+        let Reverse((_, slot)) = self.heap.pop()?;
+        let pkt = self.heads[slot].take();
+        self.advance(slot);
+
+        pkt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+    use frame::packet::LINKTYPE_ETHERNET;
+
+    #[test]
+    fn pcapng_comments() {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+        let mut pkt = Packet::new();
+        pkt.set_packet(vec![0xaa; 60], header, 2, 24, true, LINKTYPE_ETHERNET);
+        pkt.set_comment(Some("seen".to_string()));
+
+        let mut output: Vec<u8> = Vec::new();
+        write_packets(
+            &[pkt.clone()],
+            ExportFormat::Pcapng,
+            Some("select ip.src from s1 where ip.ttl == 64"),
+            &mut output,
+        )
+        .unwrap();
+        let text = String::from_utf8_lossy(&output);
+
+        assert!(text.contains("seen"), "Original comment");
+        assert!(
+            text.contains("packetdb query: select ip.src"),
+            "Query comment"
+        );
+        assert!(
+            text.contains(&format!("packetdb id: {}", pkt.id_field())),
+            "ID comment"
+        );
+
+        output.clear();
+        write_packets(&[pkt], ExportFormat::Pcap, None, &mut output).unwrap();
+        assert_eq!(output.len(), 24 + 16 + 60, "Classic pcap");
+    }
+
+    fn packet(file_id: u32, secs: u32) -> Packet {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], secs);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+        let mut pkt = Packet::new();
+        pkt.set_packet(vec![0xaa; 60], header, file_id, 24, true, LINKTYPE_ETHERNET);
+        pkt
+    }
+
+    #[test]
+    fn merge_segments() {
+        let segments: Vec<(u32, Vec<u32>)> = vec![
+            (1, vec![100, 103, 105]),
+            (2, vec![101, 102, 110]),
+            (3, vec![200, 201]),
+            (4, vec![]),
+        ];
+        let mut opened: Vec<u32> = Vec::new();
+
+        let merged: Vec<(u32, u64)> = TimeMerge::new(
+            vec![
+                (100_000_000_000, 1),
+                (101_000_000_000, 2),
+                (150_000_000_000, 4),
+                (200_000_000_000, 3),
+            ],
+            |file_id| {
+                opened.push(file_id);
+                segments
+                    .iter()
+                    .find(|(id, _)| *id == file_id)
+                    .map(|(id, times)| {
+                        times
+                            .iter()
+                            .map(|secs| packet(*id, *secs))
+                            .collect::<Vec<Packet>>()
+                            .into_iter()
+                    })
+            },
+        )
+        .map(|pkt| (pkt.file_id, pkt.timestamp_ns() / 1_000_000_000))
+        .collect();
+
+        assert_eq!(
+            merged,
+            vec![
+                (1, 100),
+                (2, 101),
+                (2, 102),
+                (1, 103),
+                (1, 105),
+                (2, 110),
+                (3, 200),
+                (3, 201)
+            ],
+            "Timestamp order across segments"
+        );
+        assert_eq!(opened, vec![1, 2, 4, 3], "Segments opened in start order");
+    }
+}
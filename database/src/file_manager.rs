@@ -1,9 +1,14 @@
 use anyhow::Result;
 use filetime::FileTime;
 use std::fs;
+use std::io::ErrorKind;
 use std::path::Path;
 
+use crate::catalog::set_stored;
 use dblib::config::CONFIG;
+use log::{info, warn};
+use pcap::compressed::compress_file;
+use pcap::segment::compression_level;
 
 pub fn clean_indexes() {
     clean_index();
@@ -45,6 +50,39 @@ pub fn index_filename(file_id: u32) -> String {
     format!("{}/{}.pidx", &CONFIG.index_path, file_id)
 }
 
+//--- Flow table of a segment, written with its packet index
+pub fn flow_filename(file_id: u32) -> String {
+    format!("{}/{}.flow", &CONFIG.index_path, file_id)
+}
+
+//--- Index of an open capture segment, appended while the segment is written
+pub fn live_index_path() -> String {
+    format!("{}/live", &CONFIG.master_index_path)
+}
+
+pub fn live_index_filename(file_id: u32) -> String {
+    format!("{}/{}.pidx", live_index_path(), file_id)
+}
+
+//--- Open segments with a live index, newest first
+pub fn live_segments() -> Result<Vec<u32>> {
+    let mut file_ids: Vec<u32> = match fs::read_dir(live_index_path()) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                name.strip_suffix(".pidx")?.parse::<u32>().ok()
+            })
+            .collect(),
+        Err(error) if error.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(error) => return Err(error.into()),
+    };
+    file_ids.sort();
+    file_ids.reverse();
+
+    Ok(file_ids)
+}
+
 pub fn path_exists(path_name: &str) -> bool {
     let path = Path::new(path_name);
     path.exists() && path.is_dir()
@@ -80,3 +118,91 @@ pub fn proto_index_list() -> Vec<String> {
 
     result
 }
+
+pub fn segment_filename(file_id: u32) -> String {
+    format!("{}/{}.pcap", &CONFIG.db_path, file_id)
+}
+
+//--- Every segment of db_path, closed or not, in file id order
+pub fn segment_ids() -> Result<Vec<u32>> {
+    let mut file_ids: Vec<u32> = fs::read_dir(&CONFIG.db_path)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".pcap")?
+                .parse::<u32>()
+                .ok()
+        })
+        .collect();
+    file_ids.sort();
+
+    Ok(file_ids)
+}
+
+//--- A segment is closed once its packet index is written, in file id order
+pub fn closed_segments() -> Result<Vec<u32>> {
+    Ok(segment_ids()?
+        .into_iter()
+        .filter(|file_id| Path::new(&index_filename(*file_id)).exists())
+        .collect())
+}
+
+//--- Compress the plain segments that are closed. Returns the segments compressed
+pub fn compress_closed_segments() -> Result<Vec<u32>> {
+    let mut compressed: Vec<u32> = Vec::new();
+    for file_id in closed_segments()? {
+        let fname = segment_filename(file_id);
+
+        match compress_file(&fname, CONFIG.block_size, compression_level()) {
+            Ok(true) => {
+                info!("Compressed segment {}", file_id);
+                if let Err(msg) = set_stored(file_id) {
+                    warn!("Could not record segment {}: {}", file_id, msg);
+                }
+                compressed.push(file_id);
+            }
+            Ok(false) => {}
+            Err(msg) => warn!("Could not compress segment {}: {}", file_id, msg),
+        }
+    }
+
+    Ok(compressed)
+}
+
+//--- Segment file, the file it links to in a source folder, its packet
+//--- index, flow table and protocol indexes. Files already gone are skipped
+pub fn delete_segment_files(file_id: u32) -> Result<()> {
+    let fname = segment_filename(file_id);
+    if let Ok(stored) = fs::read_link(&fname) {
+        remove_if_exists(&stored)?;
+    }
+    remove_if_exists(Path::new(&fname))?;
+
+    delete_index_files(file_id)
+}
+
+//--- Packet index, flow table and protocol indexes of a segment, a
+//--- segment indexed again does not keep the protocol indexes it no
+//--- longer has
+pub fn delete_index_files(file_id: u32) -> Result<()> {
+    remove_if_exists(Path::new(&index_filename(file_id)))?;
+    remove_if_exists(Path::new(&flow_filename(file_id)))?;
+
+    for entry in fs::read_dir(&CONFIG.proto_index_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            remove_if_exists(&entry.path().join(format!("{}.pidx", file_id)))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,232 @@
+use crate::catalog::{self, SegmentState};
+use crate::file_manager::{flow_filename, segment_filename};
+use anyhow::Result;
+use field::pfield::{Field, FieldType};
+use frame::flow::{FLOW_RECORD_LEN, FlowRecord, FlowTable};
+use log::{debug, warn};
+use pcap::pcapfile::PcapFile;
+use pql::cursor::Cursor;
+use pql::interpreter::Interpreter;
+use pql::parse::PqlStatement;
+use pql::record::Record;
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{BufWriter, ErrorKind, Write};
+
+//--- Records of one conversation in consecutive segments are stitched
+//--- when the gap between them is at most this long
+pub const FLOW_TIMEOUT_NS: u64 = 120 * 1_000_000_000;
+
+//--- Flow table of a segment: {index_path}/{file_id}.flow, fixed size
+//--- records in the order of their first packet
+pub fn write_flows(file_id: u32, table: &FlowTable) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(flow_filename(file_id))?);
+    for record in table.records() {
+        writer.write_all(&record.to_bytes())?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+//--- Flow table of a segment indexed before flow tables, read from the
+//--- segment. Returns the number of flows
+pub fn build_flows(file_id: u32) -> Result<usize> {
+    let mut pfile = PcapFile::open(&segment_filename(file_id), file_id)?;
+    let mut table = FlowTable::default();
+    while let Some(pkt) = pfile.next() {
+        table.add(&pkt);
+    }
+    write_flows(file_id, &table)?;
+
+    Ok(table.len())
+}
+
+pub fn read_flows(file_id: u32) -> Result<Vec<FlowRecord>> {
+    let content = fs::read(flow_filename(file_id))?;
+
+    Ok(content
+        .chunks_exact(FLOW_RECORD_LEN)
+        .filter_map(|chunk| chunk.try_into().ok())
+        .map(FlowRecord::from_bytes)
+        .collect())
+}
+
+//------------------------------------------------------------------
+//--- A segment ends in the middle of conversations, the records of a
+//--- capture source with the same 5-tuple and VLAN are merged when
+//--- the next one starts within FLOW_TIMEOUT_NS of the end of the
+//--- previous one. Records are given with the capture id of their
+//--- segment
+//------------------------------------------------------------------
+pub fn stitch(mut records: Vec<(u32, FlowRecord)>) -> Vec<FlowRecord> {
+    records.sort_by_key(|(capture_id, flow)| (*capture_id, flow.key.canonical(), flow.start_ns));
+
+    let mut flows: Vec<FlowRecord> = Vec::new();
+    let mut previous: Option<(u32, FlowRecord)> = None;
+
+    for (capture_id, flow) in records {
+        match previous.as_mut() {
+            Some((prev_capture, prev))
+                if *prev_capture == capture_id
+                    && prev.key.canonical() == flow.key.canonical()
+                    && flow.start_ns <= prev.end_ns.saturating_add(FLOW_TIMEOUT_NS) =>
+            {
+                prev.merge(&flow);
+            }
+            _ => {
+                if let Some((_, prev)) = previous.replace((capture_id, flow)) {
+                    flows.push(prev);
+                }
+            }
+        }
+    }
+    flows.extend(previous.map(|(_, prev)| prev));
+
+    flows
+}
+
+//------------------------------------------------------------------
+//--- select ... from flows: the flow tables of the indexed segments in
+//--- the interval are read and stitched, then filtered. Newest flows
+//--- first. Segments still written by a capture have no flow table
+//--- until they are closed, segments indexed before flow tables have
+//--- none until the next index update and are skipped
+//------------------------------------------------------------------
+pub fn search_flows(expr: &PqlStatement) -> Result<Cursor> {
+    let interpreter = Interpreter::new(expr.clone());
+    let mut records: Vec<(u32, FlowRecord)> = Vec::new();
+
+    let segments: Vec<catalog::CatalogEntry> = catalog::list(None)?
+        .into_iter()
+        .filter(|entry| entry.state == SegmentState::Indexed)
+        .filter(|entry| {
+            expr.interval.as_ref().is_none_or(|interval| {
+                entry.start_timestamp <= interval.to_secs()
+                    && entry.end_timestamp >= interval.from_secs()
+            })
+        })
+        .collect();
+
+    for entry in &segments {
+        match read_flows(entry.file_id) {
+            Ok(flows) => records.extend(flows.into_iter().map(|flow| (entry.capture_id, flow))),
+            Err(msg)
+                if msg
+                    .downcast_ref::<std::io::Error>()
+                    .is_some_and(|error| error.kind() == ErrorKind::NotFound) =>
+            {
+                debug!("Segment {} has no flow table", entry.file_id)
+            }
+            Err(msg) => warn!(
+                "Could not read the flows of segment {}: {}",
+                entry.file_id, msg
+            ),
+        }
+    }
+    debug!(
+        "Read {} flow records of {} segments",
+        records.len(),
+        segments.len()
+    );
+
+    let mut flows: Vec<FlowRecord> = stitch(records)
+        .into_iter()
+        .filter(|flow| interpreter.eval_flow(flow))
+        .collect();
+    flows.sort_by_key(|flow| Reverse((flow.start_ns, flow.first_packet)));
+
+    let mut cursor = Cursor::default();
+    for flow in flows.iter().skip(expr.offset).take(expr.top) {
+        cursor.add_record(flow_record(expr, flow));
+    }
+
+    Ok(cursor)
+}
+
+//--- Selected fields, the first packet id and the start of the flow
+fn flow_record(expr: &PqlStatement, flow: &FlowRecord) -> Record {
+    let mut record = Record::default();
+
+    for field in &expr.select {
+        if let Some(value) = flow.get_field(&field.name) {
+            record.add(value);
+        }
+    }
+    record.add(Field::set_field(
+        FieldType::Int64(flow.first_packet.to_u64().unwrap_or_default()),
+        "flow.first_id",
+    ));
+    record.add(Field::set_field(
+        FieldType::Int64(flow.start_ns),
+        "flow.start_ns",
+    ));
+
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame::flow::FlowKey;
+    use frame::packet_id::PacketId;
+
+    const SEC: u64 = 1_000_000_000;
+
+    fn flow(file_id: u32, key: FlowKey, start: u64, end: u64) -> FlowRecord {
+        FlowRecord {
+            key,
+            start_ns: start * SEC,
+            end_ns: end * SEC,
+            packets: [2, 1],
+            bytes: [200, 100],
+            tcp_flags: 0,
+            first_packet: PacketId::new(file_id, start),
+            segments: 1,
+        }
+    }
+
+    #[test]
+    fn stitch_segments() {
+        let key = FlowKey {
+            src: 0x0a00_0001,
+            dst: 0x0a00_0002,
+            sport: 40_000,
+            dport: 53,
+            protocol: 17,
+            vlan: 0,
+        };
+        let other = FlowKey { dport: 123, ..key };
+
+        let flows = stitch(vec![
+            (1, flow(2, key.reversed(), 60, 90)),
+            (1, flow(1, key, 0, 59)),
+            (1, flow(4, key, 500, 510)),
+            (2, flow(3, key, 60, 70)),
+            (1, flow(2, other, 60, 61)),
+        ]);
+
+        assert_eq!(flows.len(), 4, "Flows: {:?}", flows);
+        let stitched = flows
+            .iter()
+            .find(|flow| flow.segments == 2)
+            .expect("Stitched flow");
+        assert_eq!(stitched.key, key, "Direction of the first record");
+        assert_eq!(
+            (stitched.start_ns, stitched.end_ns),
+            (0, 90 * SEC),
+            "Time range"
+        );
+        assert_eq!(stitched.packets, [3, 3], "Reverse record counts");
+        assert_eq!(stitched.first_packet, PacketId::new(1, 0), "First packet");
+        assert!(
+            flows.iter().any(|flow| flow.start_ns == 500 * SEC),
+            "Gap over the timeout is a new flow"
+        );
+        assert_eq!(
+            flows.iter().filter(|flow| flow.segments == 1).count(),
+            3,
+            "Other capture and other 5-tuple kept apart"
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::catalog::{allocate, close};
+use crate::index_manager::IndexManager;
+use anyhow::{Result, anyhow};
+use dblib::config::CONFIG;
+use frame::packet::Packet;
+use log::{info, warn};
+use pcap::pcap_writer::PcapWriter;
+use pcap::pcapfile::{PcapFile, is_capture_file};
+use pcap::pcapng_writer::PcapngWriter;
+use pcap::segment::{SegmentFile, create_segment};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+//--- Packets per segment when db_segment_size is not set, same as the sniffer
+const DEFAULT_SEGMENT_PACKETS: usize = 50_000;
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportStats {
+    pub files: usize,
+    pub packets: usize,
+    pub segments: Vec<u32>,
+    pub skipped: Vec<String>,
+}
+
+//--- Segments are written in the format of the imported file so pcapng
+//--- interfaces and comments are kept, compressed when configured
+enum SegmentWriter {
+    Pcap(PcapWriter<SegmentFile>),
+    Pcapng(PcapngWriter<SegmentFile>),
+}
+
+impl SegmentWriter {
+    fn create(file_id: u32, pcapng: bool) -> Result<Self> {
+        let file = create_segment(file_id, "")?;
+
+        if pcapng {
+            Ok(SegmentWriter::Pcapng(PcapngWriter::new(file)))
+        } else {
+            Ok(SegmentWriter::Pcap(PcapWriter::new(file)))
+        }
+    }
+
+    fn write_packet(&mut self, pkt: &Packet) -> Result<()> {
+        match self {
+            SegmentWriter::Pcap(writer) => writer.write_packet(pkt)?,
+            SegmentWriter::Pcapng(writer) => writer.write_packet(pkt)?,
+        }
+
+        Ok(())
+    }
+
+    //--- The segment is complete on disk, or the error is returned
+    fn close(self) -> Result<()> {
+        let mut file = match self {
+            SegmentWriter::Pcap(writer) => writer.into_inner(),
+            SegmentWriter::Pcapng(writer) => writer.into_inner(),
+        };
+        file.finish()?;
+
+        Ok(())
+    }
+}
+
+//------------------------------------------------------------------
+//--- Path of an import requested over the API, resolved against
+//--- import_path. Links and .. are resolved before the prefix check so
+//--- the request can not leave the directory
+//------------------------------------------------------------------
+pub fn import_request_path(path: &str) -> Result<PathBuf> {
+    if CONFIG.import_path.is_empty() {
+        return Err(anyhow!("Imports over the API are disabled, no import_path"));
+    }
+    inside_dir(Path::new(&CONFIG.import_path), path)
+}
+
+fn inside_dir(dir: &Path, path: &str) -> Result<PathBuf> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| anyhow!("Import directory {}: {}", dir.display(), e))?;
+    let requested = dir
+        .join(path)
+        .canonicalize()
+        .map_err(|e| anyhow!("Import path {}: {}", path, e))?;
+
+    if requested.starts_with(&dir) {
+        Ok(requested)
+    } else {
+        Err(anyhow!(
+            "Import path {} is outside the import directory",
+            path
+        ))
+    }
+}
+
+//------------------------------------------------------------------
+//--- Import of pcap and pcapng files from disk. Each file is split in
+//--- segments of db_segment_size packets, allocated in the catalog
+//--- without a capture source and indexed as the sniffer segments are
+//------------------------------------------------------------------
+#[derive(Default, Debug)]
+pub struct Importer {
+    index_mgr: IndexManager,
+}
+
+impl Importer {
+    //--- A file or every capture file of a directory, in name order
+    pub fn import(&mut self, path: &str) -> Result<ImportStats> {
+        let path = Path::new(path);
+        let mut stats = ImportStats::default();
+
+        if path.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(path)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| file.is_file())
+                .collect();
+            files.sort();
+
+            for file in files {
+                self.import_file(&file, &mut stats)?;
+            }
+        } else if path.is_file() {
+            self.import_file(path, &mut stats)?;
+        } else {
+            return Err(anyhow!("Import path not found: {}", path.display()));
+        }
+
+        Ok(stats)
+    }
+
+    fn import_file(&mut self, path: &Path, stats: &mut ImportStats) -> Result<()> {
+        let fname = path.to_string_lossy().to_string();
+
+        if !is_capture_file(&fname) {
+            warn!("Skipping {}, not a pcap or pcapng file", fname);
+            stats.skipped.push(fname);
+            return Ok(());
+        }
+
+        let mut pfile = PcapFile::open(&fname, 0)?;
+        let segment_size = match CONFIG.db_segment_size {
+            0 => DEFAULT_SEGMENT_PACKETS,
+            size => size,
+        };
+        let mut segment: Option<(u32, SegmentWriter)> = None;
+        let mut count: usize = 0;
+
+        while let Some(pkt) = pfile.next() {
+            if count == segment_size
+                && let Some((file_id, writer)) = segment.take()
+            {
+                self.close_segment(file_id, writer, stats)?;
+                count = 0;
+            }
+
+            if segment.is_none() {
+                let file_id = allocate(0, pkt.link_type())?;
+                segment = Some((file_id, SegmentWriter::create(file_id, pfile.is_pcapng())?));
+            }
+
+            if let Some((_, writer)) = &mut segment {
+                writer.write_packet(&pkt)?;
+            }
+
+            count += 1;
+            stats.packets += 1;
+        }
+
+        if let Some((file_id, writer)) = segment.take() {
+            self.close_segment(file_id, writer, stats)?;
+        }
+
+        info!("Imported {}", fname);
+        stats.files += 1;
+
+        Ok(())
+    }
+
+    //--- The segment is complete on disk before the indexes are built
+    fn close_segment(
+        &mut self,
+        file_id: u32,
+        writer: SegmentWriter,
+        stats: &mut ImportStats,
+    ) -> Result<()> {
+        writer.close()?;
+
+        close(file_id, 0)?;
+        self.index_mgr.index_one_file(file_id);
+        stats.segments.push(file_id);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_path_inside_dir() {
+        let root = std::env::temp_dir().join(format!("import_{}", std::process::id()));
+        let dir = root.join("imports");
+        fs::create_dir_all(dir.join("day1")).unwrap();
+        fs::write(root.join("secret.pcap"), b"").unwrap();
+        fs::write(dir.join("day1/a.pcap"), b"").unwrap();
+
+        assert_eq!(
+            inside_dir(&dir, "day1/a.pcap").unwrap(),
+            dir.canonicalize().unwrap().join("day1/a.pcap"),
+            "Relative path in the directory"
+        );
+        assert!(
+            inside_dir(&dir, dir.join("day1").to_str().unwrap()).is_ok(),
+            "Absolute path in the directory"
+        );
+        assert!(
+            inside_dir(&dir, "../secret.pcap").is_err(),
+            "Parent directory escape"
+        );
+        assert!(
+            inside_dir(&dir, root.join("secret.pcap").to_str().unwrap()).is_err(),
+            "Absolute path outside the directory"
+        );
+        assert!(inside_dir(&dir, "missing.pcap").is_err(), "Missing file");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
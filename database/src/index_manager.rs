@@ -1,24 +1,26 @@
+use crate::capture_source::open_master;
+use crate::catalog::{self, searchable_ranges, set_indexed};
 use crate::file_manager;
+use crate::flow_index::write_flows;
 use crate::proto_index::ProtoIndexMgr;
-use anyhow::{Result, anyhow};
-use byteorder::{BigEndian, ByteOrder, WriteBytesExt};
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder};
 use dblib::config::CONFIG;
 use field::ipv4_address::IPv4;
+use frame::flow::FlowTable;
 use frame::layer_index::LayerIndex;
 use frame::packet::Packet;
-use log::{error, info};
+use log::{info, warn};
 use pcap::packet_ptr::PacketPtr;
 use pcap::pcapfile::PcapFile;
 use pql::parse::PqlStatement;
-use rayon::prelude::*;
-use rusqlite::Connection;
 use std::collections::{HashMap, HashSet};
-use std::fs;
 use std::fs::File;
 use std::io::BufReader;
 use std::io::BufWriter;
+use std::io::ErrorKind;
 use std::io::Read;
-use std::path::Path;
+use std::io::Write;
 use std::time::Instant;
 use std::{f64, fmt};
 
@@ -54,6 +56,47 @@ use std::{f64, fmt};
 //     Rdp = 0x8_000_000,
 // }
 
+//--- Bytes of a packet record in the {file_id}.pidx files: timestamp,
+//--- 64-bit pointer, layers, ip.dst and ip.src. Index version 1 had a
+//--- 32-bit pointer, its segments are read until they are indexed again
+pub const INDEX_ENTRY_LEN: usize = 24;
+pub const V1_INDEX_ENTRY_LEN: usize = 20;
+
+//--- Record length of an index version, 0 for an index written before
+//--- versions were recorded
+pub fn index_entry_len(index_version: u32) -> usize {
+    if index_version < 2 {
+        V1_INDEX_ENTRY_LEN
+    } else {
+        INDEX_ENTRY_LEN
+    }
+}
+
+//--- Packet record of either index layout
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct IndexRecord {
+    timestamp: u32,
+    ptr: u64,
+    layers: u32,
+    ip_dst: u32,
+    ip_src: u32,
+}
+
+impl IndexRecord {
+    fn parse(buffer: &[u8]) -> Self {
+        //--- The pointer is the only field that grew in version 2
+        let ptr_len = buffer.len() - 16;
+
+        Self {
+            timestamp: BigEndian::read_u32(&buffer[0..4]),
+            ptr: BigEndian::read_uint(&buffer[4..4 + ptr_len], ptr_len),
+            layers: BigEndian::read_u32(&buffer[4 + ptr_len..8 + ptr_len]),
+            ip_dst: BigEndian::read_u32(&buffer[8 + ptr_len..12 + ptr_len]),
+            ip_src: BigEndian::read_u32(&buffer[12 + ptr_len..16 + ptr_len]),
+        }
+    }
+}
+
 const STAT_SQL: &str =
     "INSERT INTO proto_stats (file_id, proto, count) values (:file_id, :proto, :count)";
 
@@ -91,7 +134,7 @@ impl ProtoStat {
     }
 
     pub fn save(&mut self) -> Result<()> {
-        let mut conn = Connection::open(format!("{}/packetdb.db", &CONFIG.master_index_path))?;
+        let mut conn = open_master()?;
 
         let tx = conn.transaction()?;
         tx.prepare(STAT_SQL)?;
@@ -119,7 +162,7 @@ impl ProtoStat {
     }
 
     pub fn get_count_stats(&self, proto: u32) -> usize {
-        let conn = Connection::open(format!("{}/packetdb.db", &CONFIG.master_index_path)).unwrap();
+        let conn = open_master().unwrap();
 
         let mut stmt = conn
             .prepare("select cast (avg(count) as int) from proto_stats where (proto & ?) = ?;")
@@ -143,6 +186,33 @@ impl ProtoStat {
     }
 }
 
+//--- Protocols with their own index, a packet goes in the first one found
+const PROTO_INDEXES: [LayerIndex; 12] = [
+    LayerIndex::ARP,
+    LayerIndex::DNS,
+    LayerIndex::DHCP,
+    LayerIndex::ICMP,
+    LayerIndex::SSH,
+    LayerIndex::HTTPS,
+    LayerIndex::HTTP,
+    LayerIndex::TELNET,
+    LayerIndex::RDP,
+    LayerIndex::SMB,
+    LayerIndex::SIP,
+    LayerIndex::NTP,
+];
+
+pub fn proto_index_of(pindex: u32) -> Option<u32> {
+    if pindex < LayerIndex::ARP as u32 {
+        return None;
+    }
+
+    PROTO_INDEXES
+        .iter()
+        .map(|proto| proto.clone() as u32)
+        .find(|proto| pindex & proto == *proto)
+}
+
 #[derive(Default, Debug)]
 pub struct MasterIndex {
     pub start_timestamp: u32,
@@ -155,63 +225,76 @@ pub struct IndexManager {}
 
 impl IndexManager {
     pub fn search_index(&mut self, pql: &PqlStatement, file_id: u32) -> Result<PacketPtr> {
-        let idx_filename = &format!("{}/{}.pidx", &CONFIG.index_path, file_id);
-        let mut file = BufReader::new(File::open(idx_filename)?);
-        let mut buffer = [0; 20];
-        let mut packet_ptr = PacketPtr {
-            file_id,
-            ..Default::default()
+        //--- The open segment of a capture has a live index until it is
+        //--- closed, always of the current version
+        let (file, entry_len) = match File::open(file_manager::index_filename(file_id)) {
+            Err(error) if error.kind() == ErrorKind::NotFound => (
+                File::open(file_manager::live_index_filename(file_id))?,
+                INDEX_ENTRY_LEN,
+            ),
+            file => {
+                let index_version = catalog::entry(file_id)?.map_or(0, |entry| entry.index_version);
+                (file?, index_entry_len(index_version))
+            }
         };
 
+        Ok(PacketPtr {
+            file_id,
+            pkt_ptr: self.scan_index(BufReader::new(file), entry_len, pql),
+        })
+    }
+
+    //--- Pointers of the records of an index that match the query
+    fn scan_index<R: Read>(&self, mut reader: R, entry_len: usize, pql: &PqlStatement) -> Vec<u64> {
+        let mut buffer = vec![0; entry_len];
+        let mut pkt_ptr: Vec<u64> = Vec::new();
         let search_value = self.build_search_index(&pql.search_type);
 
-        while file.read_exact(&mut buffer).is_ok() {
-            if let Some(interval) = &pql.interval {
-                let timestamp = BigEndian::read_u32(&buffer[0..4]);
-                if timestamp >= interval.from
-                    && timestamp <= interval.to
-                    && self.match_index(&buffer, search_value, &pql.ip_list)
-                {
-                    packet_ptr.pkt_ptr.push(BigEndian::read_u32(&buffer[4..8]));
-                }
-            } else if self.match_index(&buffer, search_value, &pql.ip_list) {
-                packet_ptr.pkt_ptr.push(BigEndian::read_u32(&buffer[4..8]));
+        while reader.read_exact(&mut buffer).is_ok() {
+            let record = IndexRecord::parse(&buffer);
+
+            //--- The index holds seconds, the exact bounds are checked on the packet
+            let in_interval = pql.interval.as_ref().is_none_or(|interval| {
+                record.timestamp >= interval.from_secs() && record.timestamp <= interval.to_secs()
+            });
+            if in_interval && self.match_index(&record, search_value, &pql.ip_list) {
+                pkt_ptr.push(record.ptr);
             }
         }
 
-        Ok(packet_ptr)
+        pkt_ptr
     }
 
-    fn match_index(&self, buffer: &[u8], search_value: u32, ip_list: &Vec<IPv4>) -> bool {
-        let cindex = BigEndian::read_u32(&buffer[8..12]);
-        let ip_dst = BigEndian::read_u32(&buffer[12..16]);
-        let ip_src = BigEndian::read_u32(&buffer[16..20]);
+    fn match_index(&self, record: &IndexRecord, search_value: u32, ip_list: &Vec<IPv4>) -> bool {
         let mut ip_found = true;
 
         if ip_list.is_empty() {
             ip_found = false;
             for ip in ip_list {
-                if IPv4::new(ip.address, ip.mask).is_in_subnet(ip_dst)
-                    || IPv4::new(ip.address, ip.mask).is_in_subnet(ip_src)
+                if IPv4::new(ip.address, ip.mask).is_in_subnet(record.ip_dst)
+                    || IPv4::new(ip.address, ip.mask).is_in_subnet(record.ip_src)
                 {
                     ip_found = true;
                 }
             }
         }
 
-        ((cindex & search_value) == search_value) && ip_found
+        ((record.layers & search_value) == search_value) && ip_found
     }
 
     pub fn index_one_file(&self, file_id: u32) -> bool {
-        let master_index = self.index_file(file_id);
-
-        self.save_master(master_index);
-
-        true
+        self.index_file(file_id).is_some()
     }
 
-    pub fn index_file(&self, filename: u32) -> MasterIndex {
-        let mut pfile = PcapFile::new(filename, &CONFIG.db_path);
+    //--- None when the segment is gone, retention may delete it first
+    pub fn index_file(&self, filename: u32) -> Option<MasterIndex> {
+        let mut pfile = match PcapFile::open(&file_manager::segment_filename(filename), filename) {
+            Ok(pfile) => pfile,
+            Err(msg) => {
+                warn!("Could not index segment {}: {}", filename, msg);
+                return None;
+            }
+        };
         let idx_filename = &format!("{}/{}.pidx", &CONFIG.index_path, filename);
         let mut writer = BufWriter::new(File::create(idx_filename).unwrap());
         let mut mindex = MasterIndex::default();
@@ -222,6 +305,7 @@ impl IndexManager {
         let start = Instant::now();
         let mut count = 0;
         let mut proto_idx_mgr = ProtoIndexMgr::new(filename);
+        let mut flow_table = FlowTable::default();
 
         while let Some(pkt) = pfile.next() {
             count += 1;
@@ -231,53 +315,14 @@ impl IndexManager {
                 first_index = true;
                 mindex.start_timestamp = ts
             }
-            writer.write_u32::<BigEndian>(ts).unwrap();
-            writer.write_u32::<BigEndian>(pkt.pkt_ptr).unwrap();
-
-            let pindex = self.build_index(&pkt);
-
-            if pindex >= LayerIndex::ARP as u32 {
-                if pindex & (LayerIndex::ARP as u32) == LayerIndex::ARP as u32 {
-                    proto_idx_mgr.add(LayerIndex::ARP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::DNS as u32) == LayerIndex::DNS as u32 {
-                    proto_idx_mgr.add(LayerIndex::DNS as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::DHCP as u32) == LayerIndex::DHCP as u32 {
-                    proto_idx_mgr.add(LayerIndex::DHCP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::ICMP as u32) == LayerIndex::ICMP as u32 {
-                    proto_idx_mgr.add(LayerIndex::ICMP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::SSH as u32) == LayerIndex::SSH as u32 {
-                    proto_idx_mgr.add(LayerIndex::SSH as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::HTTPS as u32) == LayerIndex::HTTPS as u32 {
-                    proto_idx_mgr.add(LayerIndex::HTTPS as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::HTTP as u32) == LayerIndex::HTTP as u32 {
-                    proto_idx_mgr.add(LayerIndex::HTTP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::TELNET as u32) == LayerIndex::TELNET as u32 {
-                    proto_idx_mgr.add(LayerIndex::TELNET as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::RDP as u32) == LayerIndex::RDP as u32 {
-                    proto_idx_mgr.add(LayerIndex::RDP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::SMB as u32) == LayerIndex::SMB as u32 {
-                    proto_idx_mgr.add(LayerIndex::SMB as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::SIP as u32) == LayerIndex::SIP as u32 {
-                    proto_idx_mgr.add(LayerIndex::SIP as u32, pkt.pkt_ptr);
-                } else if pindex & (LayerIndex::NTP as u32) == LayerIndex::NTP as u32 {
-                    proto_idx_mgr.add(LayerIndex::NTP as u32, pkt.pkt_ptr);
-                }
-            }
 
-            writer.write_u32::<BigEndian>(pindex).unwrap();
-            // proto_stat.add(pindex);
-
-            if let Some(ip_dst) = pkt.get_field("ip.dst") {
-                writer.write_u32::<BigEndian>(ip_dst.to_u32()).unwrap();
-            } else {
-                writer.write_u32::<BigEndian>(0).unwrap();
-            }
-
-            if let Some(ip_src) = pkt.get_field("ip.src") {
-                writer.write_u32::<BigEndian>(ip_src.to_u32()).unwrap();
-            } else {
-                writer.write_u32::<BigEndian>(0).unwrap();
+            let (entry, pindex) = self.index_entry(&pkt);
+            if let Some(proto) = proto_index_of(pindex) {
+                proto_idx_mgr.add(proto, pkt.pkt_ptr);
             }
+            writer.write_all(&entry).unwrap();
+            flow_table.add(&pkt);
+            // proto_stat.add(pindex);
         }
 
         proto_idx_mgr.save();
@@ -299,134 +344,67 @@ impl IndexManager {
         mindex.end_timestamp = ts;
         mindex.file_ptr = filename;
 
-        mindex
+        //--- The index and flow table are complete on disk before the
+        //--- segment is searchable
+        if let Err(msg) = writer
+            .flush()
+            .map_err(anyhow::Error::from)
+            .and_then(|_| write_flows(filename, &flow_table))
+            .and_then(|_| set_indexed(&mindex, count as u64))
+        {
+            warn!(
+                "Could not record the index of segment {}: {}",
+                filename, msg
+            );
+        }
+
+        Some(mindex)
+    }
+
+    //--- Index record of a packet: timestamp, pointer, layers, ip.dst
+    //--- and ip.src. The layers are returned for the protocol indexes
+    pub fn index_entry(&self, pkt: &Packet) -> ([u8; INDEX_ENTRY_LEN], u32) {
+        let mut entry = [0u8; INDEX_ENTRY_LEN];
+        let pindex = self.build_index(pkt);
+
+        BigEndian::write_u32(
+            &mut entry[0..4],
+            pkt.get_field("frame.timestamp").map_or(0, |ts| ts.to_u32()),
+        );
+        BigEndian::write_u64(&mut entry[4..12], pkt.pkt_ptr);
+        BigEndian::write_u32(&mut entry[12..16], pindex);
+        BigEndian::write_u32(
+            &mut entry[16..20],
+            pkt.get_field("ip.dst").map_or(0, |ip| ip.to_u32()),
+        );
+        BigEndian::write_u32(
+            &mut entry[20..24],
+            pkt.get_field("ip.src").map_or(0, |ip| ip.to_u32()),
+        );
+
+        (entry, pindex)
     }
 
     fn build_index(&self, pkt: &Packet) -> u32 {
-        let mut index: u32 = 0;
+        //--- Decoded layers come from the dissectors registry, the
+        //--- remaining application protocols are detected by port
+        let mut index: u32 = pkt.layer_mask();
 
-        if pkt.has_ethernet() {
-            index += LayerIndex::ETH as u32
-        }
-        if pkt.has_arp() {
-            index += LayerIndex::ARP as u32
-        }
-        if pkt.has_ipv4() {
-            index += LayerIndex::IPv4 as u32
-        }
-        if pkt.has_icmp() {
-            index += LayerIndex::ICMP as u32
-        }
-        if pkt.has_udp() {
-            index += LayerIndex::UDP as u32
-        }
-        if pkt.has_tcp() {
-            index += LayerIndex::TCP as u32
-        }
-        if pkt.has_https() {
-            index += LayerIndex::HTTPS as u32
-        }
-        if pkt.has_dns() {
-            index += LayerIndex::DNS as u32
-        }
-        if pkt.has_dhcp() {
-            index += LayerIndex::DHCP as u32
-        }
-        if pkt.has_ssh() {
-            index += LayerIndex::SSH as u32
-        }
         if pkt.has_telnet() {
-            index += LayerIndex::TELNET as u32
-        }
-        if pkt.has_http() {
-            index += LayerIndex::HTTP as u32
+            index |= LayerIndex::TELNET as u32
         }
         if pkt.has_rdp() {
-            index += LayerIndex::RDP as u32
-        }
-        if pkt.has_ntp() {
-            index += LayerIndex::NTP as u32
-        }
-        if pkt.has_smb() {
-            index += LayerIndex::SMB as u32
+            index |= LayerIndex::RDP as u32
         }
         if pkt.has_smtp() {
-            index += LayerIndex::SMTP as u32
+            index |= LayerIndex::SMTP as u32
         }
         if pkt.has_snmp() {
-            index += LayerIndex::SMTP as u32
+            index |= LayerIndex::SMTP as u32
         }
         index
     }
 
-    fn get_packet_files(&self) -> Result<Vec<u32>> {
-        let pcap_path = &CONFIG.db_path.to_string();
-        let paths = fs::read_dir(pcap_path).unwrap();
-
-        let mut file_id_list: Vec<u32> = Vec::new();
-
-        for path in paths {
-            let id: u32 = Path::new(&path.unwrap().file_name())
-                .file_stem()
-                .ok_or(anyhow!("Error extrating file stem"))?
-                .to_str()
-                .ok_or(anyhow!("Error converting file stem to string"))?
-                .parse::<u32>()?;
-
-            file_id_list.push(id);
-        }
-        file_id_list.sort();
-        file_id_list.reverse();
-        Ok(file_id_list)
-    }
-
-    pub fn create_index(&self) {
-        file_manager::clean_indexes();
-        // remove_dir_contents(&CONFIG.index_path).unwrap();
-        match self.get_packet_files() {
-            Ok(files_list) => {
-                let result: Vec<MasterIndex> = (files_list)
-                    .into_par_iter()
-                    .map(|pkt| self.index_file(pkt))
-                    .collect();
-
-                self.create_master(result);
-            }
-            Err(msg) => error!("Error index files: {}", msg),
-        }
-    }
-
-    pub fn save_master(&self, master_index: MasterIndex) {
-        let index_file = format!("{}/master.pidx", CONFIG.master_index_path);
-        let mut writer = BufWriter::new(
-            fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(index_file)
-                .unwrap(),
-        );
-        writer
-            .write_u32::<BigEndian>(master_index.start_timestamp)
-            .unwrap();
-        writer
-            .write_u32::<BigEndian>(master_index.end_timestamp)
-            .unwrap();
-        writer
-            .write_u32::<BigEndian>(master_index.file_ptr)
-            .unwrap();
-    }
-
-    pub fn create_master(&self, master_index: Vec<MasterIndex>) {
-        let index_file = format!("{}/master.pidx", CONFIG.master_index_path);
-        println!("Master index file: {}", index_file);
-        let mut writer = BufWriter::new(File::create(index_file).unwrap());
-        for p in master_index {
-            writer.write_u32::<BigEndian>(p.start_timestamp).unwrap();
-            writer.write_u32::<BigEndian>(p.end_timestamp).unwrap();
-            writer.write_u32::<BigEndian>(p.file_ptr).unwrap();
-        }
-    }
-
     pub fn build_search_index(&self, search_type: &HashSet<LayerIndex>) -> u32 {
         // println!("Proto types: {:?}", search_type);
         let mut ret_type: u32 = 0;
@@ -462,49 +440,136 @@ impl IndexManager {
                 LayerIndex::BGP => ret_type += LayerIndex::BGP as u32,
                 LayerIndex::SMB => ret_type += LayerIndex::SMB as u32,
                 LayerIndex::RDP => ret_type += LayerIndex::RDP as u32,
+                LayerIndex::RADIOTAP => ret_type += LayerIndex::RADIOTAP as u32,
+                LayerIndex::WLAN => ret_type += LayerIndex::WLAN as u32,
             }
         }
 
         ret_type
     }
 
-    pub fn search_master_index(&self, start_ts: u32, end_ts: u32) -> Vec<MasterIndex> {
-        let mut index_list: Vec<MasterIndex> = Vec::new();
-
-        let idx_filename = &format!("{}/master.pidx", &CONFIG.master_index_path);
-        let mut file = BufReader::new(File::open(idx_filename).unwrap());
-        let mut buffer = [0; 12];
-        let mut start_found = false;
-
-        while file.read_exact(&mut buffer).is_ok() {
-            let idx_start = BigEndian::read_u32(&buffer[0..4]);
-            let idx_end = BigEndian::read_u32(&buffer[4..8]);
-            if !start_found && (start_ts >= idx_start && start_ts <= idx_end) {
-                start_found = true;
-                let index = MasterIndex {
-                    start_timestamp: idx_start,
-                    end_timestamp: idx_end,
-                    file_ptr: BigEndian::read_u32(&buffer[8..12]),
-                };
-                index_list.push(index);
-            } else if start_found && (idx_end <= end_ts) {
-                let index = MasterIndex {
-                    start_timestamp: idx_start,
-                    end_timestamp: idx_end,
-                    file_ptr: BigEndian::read_u32(&buffer[8..12]),
-                };
-                index_list.push(index);
-            } else if start_found && (idx_end >= end_ts) {
-                let index = MasterIndex {
-                    start_timestamp: idx_start,
-                    end_timestamp: idx_end,
-                    file_ptr: BigEndian::read_u32(&buffer[8..12]),
-                };
-                index_list.push(index);
-                break;
-            }
+    //--- Rows of the segments with packets between start_ts and end_ts, in
+    //--- time order. A range starting before the oldest segment or ending
+    //--- after the newest selects every segment it overlaps
+    pub fn search_master_index(&self, start_ts: u32, end_ts: u32) -> Result<Vec<MasterIndex>> {
+        Ok(master_overlap(searchable_ranges()?, start_ts, end_ts))
+    }
+}
+
+pub fn master_overlap(rows: Vec<MasterIndex>, start_ts: u32, end_ts: u32) -> Vec<MasterIndex> {
+    let mut rows: Vec<MasterIndex> = rows
+        .into_iter()
+        .filter(|index| index.start_timestamp <= end_ts && index.end_timestamp >= start_ts)
+        .collect();
+    rows.sort_by_key(|index| (index.start_timestamp, index.end_timestamp, index.file_ptr));
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use pql::parse::Parse;
+
+    fn master(file_ptr: u32, start_timestamp: u32, end_timestamp: u32) -> MasterIndex {
+        MasterIndex {
+            start_timestamp,
+            end_timestamp,
+            file_ptr,
         }
+    }
+
+    fn file_ids(rows: Vec<MasterIndex>) -> Vec<u32> {
+        rows.into_iter().map(|index| index.file_ptr).collect()
+    }
 
-        index_list
+    #[test]
+    fn master_time_range() {
+        let rows = || {
+            vec![
+                master(3, 300, 399),
+                master(1, 100, 199),
+                master(2, 200, 299),
+            ]
+        };
+
+        assert_eq!(
+            file_ids(master_overlap(rows(), 0, 150)),
+            vec![1],
+            "Range starting before the oldest segment"
+        );
+        assert_eq!(
+            file_ids(master_overlap(rows(), 150, 250)),
+            vec![1, 2],
+            "Range across two segments"
+        );
+        assert_eq!(
+            file_ids(master_overlap(rows(), 0, 1_000)),
+            vec![1, 2, 3],
+            "Every segment in time order"
+        );
+        assert_eq!(
+            file_ids(master_overlap(rows(), 250, 260)),
+            vec![2],
+            "Range inside one segment"
+        );
+        assert!(
+            master_overlap(rows(), 400, 500).is_empty(),
+            "Range after the newest segment"
+        );
+    }
+
+    //--- Records of an index: timestamp, pointer, layers, no addresses
+    fn index_bytes(entry_len: usize, records: &[(u32, u64, u32)]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        for (timestamp, ptr, layers) in records {
+            bytes.write_u32::<BigEndian>(*timestamp).unwrap();
+            bytes.write_uint::<BigEndian>(*ptr, entry_len - 16).unwrap();
+            bytes.write_u32::<BigEndian>(*layers).unwrap();
+            bytes.write_u64::<BigEndian>(0).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn v1_index_readable() {
+        let pql = Parse::new()
+            .parse_select("select frame.id from * where tcp.dport == 80 and ip.dst == 10.0.0.1")
+            .unwrap();
+        let index_mgr = IndexManager::default();
+        let tcp = index_mgr.build_search_index(&pql.search_type);
+        assert_ne!(tcp & LayerIndex::TCP as u32, 0, "TCP search");
+
+        let records = [
+            (1_700_000_000, 24, tcp),
+            (
+                1_700_000_001,
+                130,
+                LayerIndex::ETH as u32 | LayerIndex::UDP as u32,
+            ),
+            (1_700_000_002, 0xffff_fff0, tcp),
+        ];
+
+        assert_eq!(index_entry_len(0), V1_INDEX_ENTRY_LEN, "Before versions");
+        assert_eq!(index_entry_len(1), V1_INDEX_ENTRY_LEN, "Version 1");
+        assert_eq!(index_entry_len(2), INDEX_ENTRY_LEN, "Version 2");
+
+        let v1 = index_bytes(V1_INDEX_ENTRY_LEN, &records);
+        assert_eq!(v1.len(), 3 * V1_INDEX_ENTRY_LEN, "Version 1 layout");
+        assert_eq!(
+            index_mgr.scan_index(v1.as_slice(), index_entry_len(1), &pql),
+            vec![24, 0xffff_fff0],
+            "32-bit pointers of a version 1 index"
+        );
+        assert_eq!(
+            index_mgr.scan_index(
+                index_bytes(INDEX_ENTRY_LEN, &records).as_slice(),
+                index_entry_len(2),
+                &pql
+            ),
+            vec![24, 0xffff_fff0],
+            "Same records in the current layout"
+        );
     }
 }
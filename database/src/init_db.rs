@@ -1,3 +1,5 @@
+use crate::catalog::{migrate_master_index, migrate_proto_stats};
+use crate::retention::migrate_hold_log;
 use anyhow::Result;
 use dblib::config::CONFIG;
 use log::info;
@@ -21,6 +23,13 @@ impl InitDb {
             [],
         )?;
 
+        //--- next_fileid increments this row, it must exist before the
+        //--- first segment is written
+        conn.execute(
+            "insert or ignore into config (id, file_id) values (1, 0);",
+            [],
+        )?;
+
         conn.execute(
             r#"create table if not exists capture (
                 id integer primary key,
@@ -28,11 +37,180 @@ impl InitDb {
                 folder varchar(250),
                 iface varchar(20),
                 packets_per_file integer,
-                filter varchar(2048)
+                filter varchar(2048),
+                max_bytes integer,
+                max_age integer,
+                retention_age integer,
+                retention_bytes integer
             );"#,
             [],
         )?;
+        add_missing_columns(
+            &conn,
+            "capture",
+            &[
+                ("max_bytes", "integer"),
+                ("max_age", "integer"),
+                ("retention_age", "integer"),
+                ("retention_bytes", "integer"),
+            ],
+        )?;
+
+        //--- Segment catalog, one row per segment from its file id
+        //--- allocation. Imported segments have no capture source.
+        //--- Retention never deletes a segment under legal hold. The index
+        //--- version, mtime and length are those of the last indexing
+        conn.execute(
+            r#"create table if not exists segment (
+                file_id integer primary key,
+                capture_id integer,
+                legal_hold integer default 0,
+                index_version integer,
+                segment_mtime integer,
+                segment_len integer,
+                link_type integer,
+                start_timestamp integer,
+                end_timestamp integer,
+                packets integer,
+                bytes integer,
+                dropped integer default 0,
+                compressed integer default 0,
+                checksum varchar(16),
+                state varchar(10)
+            );"#,
+            [],
+        )?;
+        add_missing_columns(
+            &conn,
+            "segment",
+            &[
+                ("legal_hold", "integer default 0"),
+                ("index_version", "integer"),
+                ("segment_mtime", "integer"),
+                ("segment_len", "integer"),
+                ("link_type", "integer"),
+                ("start_timestamp", "integer"),
+                ("end_timestamp", "integer"),
+                ("packets", "integer"),
+                ("bytes", "integer"),
+                ("dropped", "integer default 0"),
+                ("compressed", "integer default 0"),
+                ("checksum", "varchar(16)"),
+                ("state", "varchar(10)"),
+            ],
+        )?;
+        conn.execute(
+            "create index if not exists segment_time on segment (start_timestamp, end_timestamp);",
+            [],
+        )?;
+
+        //--- Packets of each indexed layer combination per segment
+        conn.execute(
+            r#"create table if not exists proto_stats (
+                file_id integer,
+                proto integer,
+                count integer
+            );"#,
+            [],
+        )?;
+
+        migrate_master_index(&conn, &CONFIG.master_index_path)?;
+        migrate_proto_stats(&conn, &CONFIG.master_index_path)?;
+
+        conn.execute(
+            r#"create table if not exists retention_log (
+                id integer primary key,
+                deleted_at integer,
+                file_id integer,
+                capture_id integer,
+                end_timestamp integer,
+                bytes integer,
+                reason varchar(20)
+            );"#,
+            [],
+        )?;
+
+        //--- Legal hold changes, the hold set and who set it
+        conn.execute(
+            r#"create table if not exists legal_hold_log (
+                id integer primary key,
+                changed_at integer,
+                file_id integer,
+                capture_id integer,
+                legal_hold integer,
+                actor varchar(255)
+            );"#,
+            [],
+        )?;
+        migrate_hold_log(&conn)?;
+
+        //--- Counters of each capture segment, written when it is closed
+        conn.execute(
+            r#"create table if not exists segment_stats (
+                file_id integer primary key,
+                capture_id integer,
+                opened_at integer,
+                closed_at integer,
+                packets integer,
+                bytes integer,
+                queue_dropped integer,
+                kernel_dropped integer,
+                if_dropped integer,
+                queue_high_water integer,
+                write_errors integer default 0
+            );"#,
+            [],
+        )?;
+        add_missing_columns(
+            &conn,
+            "segment_stats",
+            &[("write_errors", "integer default 0")],
+        )?;
+
+        //--- Totals of each running capture since it was opened
+        conn.execute(
+            r#"create table if not exists capture_stats (
+                iface varchar(255) primary key,
+                capture_id integer,
+                started_at integer,
+                updated_at integer,
+                received integer,
+                queue_dropped integer,
+                kernel_received integer,
+                kernel_dropped integer,
+                if_dropped integer,
+                queue_high_water integer,
+                write_errors integer default 0
+            );"#,
+            [],
+        )?;
+        add_missing_columns(
+            &conn,
+            "capture_stats",
+            &[("write_errors", "integer default 0")],
+        )?;
 
         Ok(())
     }
 }
+
+//--- Columns added to a table after its first release, a database created
+//--- by an older version gets them on startup
+fn add_missing_columns(conn: &Connection, table: &str, columns: &[(&str, &str)]) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("pragma table_info({});", table))?;
+    let existing = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for (name, column_type) in columns {
+        if !existing.iter().any(|column| column == name) {
+            info!("Adding column {}.{}", table, name);
+            conn.execute(
+                &format!("alter table {} add column {} {};", table, name, column_type),
+                [],
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,7 +1,16 @@
-pub mod dbconfig;
+pub mod capture_source;
+pub mod capture_stats;
+pub mod catalog;
 pub mod dbengine;
 pub mod exec_plan;
+pub mod export;
 pub mod file_manager;
+pub mod flow_index;
+pub mod import;
 pub mod index_manager;
 pub mod init_db;
+pub mod live_index;
 pub mod proto_index;
+pub mod recovery;
+pub mod reindex;
+pub mod retention;
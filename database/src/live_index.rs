@@ -0,0 +1,108 @@
+use crate::catalog::set_indexed;
+use crate::file_manager::{index_filename, live_index_filename, live_index_path};
+use crate::flow_index::write_flows;
+use crate::index_manager::{IndexManager, MasterIndex, proto_index_of};
+use crate::proto_index::ProtoIndexMgr;
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder};
+use frame::flow::FlowTable;
+use frame::packet::Packet;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+
+//------------------------------------------------------------------
+//--- Packet index of the segment a capture is writing. Records are
+//--- kept in memory until the segment data they point to is flushed,
+//--- then appended to {master_index_path}/live/{file_id}.pidx where
+//--- searches find them. Once the segment is closed the live file
+//--- becomes its packet index next to the flow table built from the
+//--- same packets, the segment is only read again for the checksum
+//--- of its catalog row
+//------------------------------------------------------------------
+pub struct LiveIndex {
+    file_id: u32,
+    file: File,
+    pending: Vec<u8>,
+    index_mgr: IndexManager,
+    proto_idx_mgr: ProtoIndexMgr,
+    flow_table: FlowTable,
+    master: MasterIndex,
+    count: usize,
+}
+
+impl LiveIndex {
+    pub fn create(file_id: u32) -> Result<Self> {
+        fs::create_dir_all(live_index_path())?;
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(live_index_filename(file_id))?;
+        //--- Held until the index is closed, recovery leaves it alone
+        file.lock()?;
+
+        Ok(Self {
+            file_id,
+            file,
+            pending: Vec::new(),
+            index_mgr: IndexManager::default(),
+            proto_idx_mgr: ProtoIndexMgr::new(file_id),
+            flow_table: FlowTable::default(),
+            master: MasterIndex {
+                file_ptr: file_id,
+                ..Default::default()
+            },
+            count: 0,
+        })
+    }
+
+    pub fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    pub fn add(&mut self, pkt: &Packet) {
+        let (entry, pindex) = self.index_mgr.index_entry(pkt);
+        if let Some(proto) = proto_index_of(pindex) {
+            self.proto_idx_mgr.add(proto, pkt.pkt_ptr);
+        }
+
+        let timestamp = BigEndian::read_u32(&entry[0..4]);
+        if self.count == 0 {
+            self.master.start_timestamp = timestamp;
+        }
+        self.master.end_timestamp = timestamp;
+        self.count += 1;
+
+        self.pending.extend_from_slice(&entry);
+        self.flow_table.add(pkt);
+    }
+
+    //--- Only called once the packets added so far are on disk
+    pub fn publish(&mut self) -> Result<()> {
+        if !self.pending.is_empty() {
+            self.file.write_all(&self.pending)?;
+            self.file.flush()?;
+            self.pending.clear();
+        }
+
+        Ok(())
+    }
+
+    //--- Protocol indexes and flow table first, then the packet index,
+    //--- the catalog row makes the segment searchable by its time range
+    pub fn close(mut self) -> Result<()> {
+        self.publish()?;
+        self.proto_idx_mgr.save();
+        write_flows(self.file_id, &self.flow_table)?;
+
+        let live = live_index_filename(self.file_id);
+        let index = index_filename(self.file_id);
+        if fs::rename(&live, &index).is_err() {
+            fs::copy(&live, &index)?;
+            fs::remove_file(&live)?;
+        }
+        set_indexed(&self.master, self.count as u64)?;
+
+        Ok(())
+    }
+}
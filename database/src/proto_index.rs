@@ -12,6 +12,9 @@ use std::io::BufWriter;
 use std::io::Seek;
 use std::io::{BufReader, Read};
 
+//--- Version 1 held 32-bit pointers, version 2 holds 64-bit pointers
+const PROTO_INDEX_VERSION: u16 = 2;
+
 #[derive(Clone)]
 struct ProtoHeader {
     magic_no: u32,
@@ -23,7 +26,7 @@ struct ProtoHeader {
 #[derive(Clone)]
 pub struct ProtoIndex {
     header: ProtoHeader,
-    ptr_list: Vec<u32>,
+    ptr_list: Vec<u64>,
     proto_id: u32,
     file_id: u32,
 }
@@ -33,7 +36,7 @@ impl ProtoIndex {
         Self {
             header: ProtoHeader {
                 magic_no: 0xa1b2c3d4,
-                version: 1,
+                version: PROTO_INDEX_VERSION,
                 options: 0,
                 count: 0,
             },
@@ -68,12 +71,17 @@ impl ProtoIndex {
         buffer.resize(2, 0);
         reader.read_exact(&mut buffer)?;
         self.header.version = BigEndian::read_u16(&buffer);
-        if self.header.version != 0x01 {
-            return Err(anyhow!(
-                "Invalid version was expecting 1 read {}",
-                self.header.version
-            ));
-        }
+        let ptr_len = match self.header.version {
+            1 => 4,
+            PROTO_INDEX_VERSION => 8,
+            version => {
+                return Err(anyhow!(
+                    "Invalid version was expecting {} read {}",
+                    PROTO_INDEX_VERSION,
+                    version
+                ));
+            }
+        };
 
         buffer.resize(2, 0);
         reader.read_exact(&mut buffer)?;
@@ -87,16 +95,16 @@ impl ProtoIndex {
         //     self.header.count, self.header.count, self.file_id, self.proto_id,
         // );
 
-        buffer.resize(4, 0);
+        buffer.resize(ptr_len, 0);
         for _ in 0..self.header.count {
             reader.read_exact(&mut buffer)?;
-            result.pkt_ptr.push(BigEndian::read_u32(&buffer));
+            result.pkt_ptr.push(BigEndian::read_uint(&buffer, ptr_len));
         }
 
         Ok(result)
     }
 
-    pub fn add(&mut self, ptr: &u32) {
+    pub fn add(&mut self, ptr: &u64) {
         self.ptr_list.push(*ptr);
     }
 
@@ -155,7 +163,7 @@ impl ProtoIndex {
             );
 
             for ptr in &self.ptr_list {
-                writer.write_u32::<BigEndian>(*ptr).unwrap();
+                writer.write_u64::<BigEndian>(*ptr).unwrap();
             }
         }
         self.update_count();
@@ -181,7 +189,7 @@ impl ProtoIndex {
 
         //--Write ptr list
         for ptr in &self.ptr_list {
-            writer.write_u32::<BigEndian>(*ptr).unwrap();
+            writer.write_u64::<BigEndian>(*ptr).unwrap();
         }
     }
     // pub fn create_index(&mut self) {
@@ -215,7 +223,7 @@ mod tests {
         let mut proto_index = ProtoIndex::new(99_999_999, 128);
 
         for i in 0..10 {
-            proto_index.add(&(i as u32));
+            proto_index.add(&(i as u64));
         }
 
         proto_index.create_index();
@@ -243,7 +251,7 @@ impl ProtoIndexMgr {
         self.index_list.insert(proto_id, index);
     }
 
-    pub fn add(&mut self, proto_id: u32, ptr: u32) {
+    pub fn add(&mut self, proto_id: u32, ptr: u64) {
         if let Some(index) = self.index_list.get_mut(&proto_id) {
             index.add(&ptr);
         } else {
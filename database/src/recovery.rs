@@ -0,0 +1,163 @@
+use crate::capture_source::open_master;
+use crate::catalog::{self, set_indexed_version, time_ranges};
+use crate::file_manager::{
+    delete_segment_files, index_filename, live_index_filename, live_segments, segment_filename,
+    segment_ids,
+};
+use crate::index_manager::{IndexManager, MasterIndex, index_entry_len};
+use anyhow::Result;
+use byteorder::{BigEndian, ByteOrder};
+use log::{info, warn};
+use pcap::pcapfile::PcapFile;
+use pcap::repair::repair_segment;
+use pcap::segment::{compression_level, is_locked};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Serialize)]
+pub struct RecoveryStats {
+    //--- Segments cut after their last complete record
+    pub repaired: Vec<u32>,
+    //--- Segments without a complete packet, deleted
+    pub removed: Vec<u32>,
+    pub indexed: Vec<u32>,
+    //--- Indexed segments added back to the catalog
+    pub master: Vec<u32>,
+}
+
+//--- Time range and packets of a segment from the first and last packet
+//--- of its index, with the index version. The records are read with the
+//--- length of the version in the catalog, as a search reads them
+fn master_from_index(file_id: u32) -> Result<Option<(MasterIndex, u64, u32)>> {
+    let index_version = catalog::entry(file_id)?.map_or(0, |entry| entry.index_version);
+    let index = fs::read(index_filename(file_id))?;
+
+    Ok(index_range(&index, index_entry_len(index_version), file_id)
+        .map(|(master, count)| (master, count, index_version)))
+}
+
+fn index_range(index: &[u8], entry_len: usize, file_id: u32) -> Option<(MasterIndex, u64)> {
+    let count = index.len() / entry_len;
+    if count == 0 {
+        return None;
+    }
+    let last = (count - 1) * entry_len;
+
+    Some((
+        MasterIndex {
+            start_timestamp: BigEndian::read_u32(&index[0..4]),
+            end_timestamp: BigEndian::read_u32(&index[last..last + 4]),
+            file_ptr: file_id,
+        },
+        count as u64,
+    ))
+}
+
+//------------------------------------------------------------------
+//--- Startup pass over the segments a crash left behind. A segment
+//--- without its packet index was open: its partial trailing record
+//--- is removed and it is indexed again, with its protocol indexes and
+//--- catalog row. An indexed segment the catalog does not know as
+//--- indexed gets its row back. Segments and live indexes still locked by a
+//--- running capture or import are not touched
+//------------------------------------------------------------------
+pub fn recover() -> Result<RecoveryStats> {
+    let index_mgr = IndexManager::default();
+    let mut stats = RecoveryStats::default();
+    let mut master: HashSet<u32> = time_ranges()?.iter().map(|index| index.file_ptr).collect();
+
+    for file_id in segment_ids()? {
+        let fname = segment_filename(file_id);
+        let live = live_index_filename(file_id);
+        if is_locked(&fname) || is_locked(&live) {
+            continue;
+        }
+
+        if Path::new(&index_filename(file_id)).exists() {
+            if !master.contains(&file_id)
+                && let Some((index, packets, index_version)) = master_from_index(file_id)?
+            {
+                info!("Recovery added segment {} to the catalog", file_id);
+                set_indexed_version(&index, packets, index_version)?;
+                master.insert(file_id);
+                stats.master.push(file_id);
+            }
+            continue;
+        }
+
+        match repair_segment(&fname, compression_level()) {
+            Ok(Some(removed)) => {
+                info!(
+                    "Recovery removed {} bytes at the end of segment {}",
+                    removed, file_id
+                );
+                stats.repaired.push(file_id);
+            }
+            Ok(None) => {}
+            Err(msg) => {
+                warn!("Could not repair segment {}: {}", file_id, msg);
+                continue;
+            }
+        }
+
+        let _ = fs::remove_file(&live);
+
+        let empty =
+            PcapFile::open(&fname, file_id).map_or(true, |mut pfile| pfile.next().is_none());
+        if empty {
+            info!("Recovery deleted segment {} without packets", file_id);
+            delete_segment_files(file_id)?;
+            open_master()?.execute("delete from segment where file_id = ?;", [file_id])?;
+            stats.removed.push(file_id);
+        } else {
+            index_mgr.index_one_file(file_id);
+            stats.indexed.push(file_id);
+        }
+    }
+
+    //--- Live indexes of segments that are gone
+    for file_id in live_segments()? {
+        let live = live_index_filename(file_id);
+        if !Path::new(&segment_filename(file_id)).exists() && !is_locked(&live) {
+            let _ = fs::remove_file(live);
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index_manager::{INDEX_ENTRY_LEN, V1_INDEX_ENTRY_LEN};
+
+    #[test]
+    fn range_of_index_versions() {
+        let index = |entry_len: usize| {
+            let mut index = vec![0u8; 3 * entry_len];
+            for (record, ts) in [100u32, 200, 300].iter().enumerate() {
+                BigEndian::write_u32(&mut index[record * entry_len..], *ts);
+            }
+            index
+        };
+
+        let (v1, count) = index_range(&index(V1_INDEX_ENTRY_LEN), index_entry_len(1), 5).unwrap();
+        assert_eq!(count, 3, "Version 1 records");
+        assert_eq!(
+            (v1.start_timestamp, v1.end_timestamp),
+            (100, 300),
+            "Version 1 range"
+        );
+
+        let (v2, count) = index_range(&index(INDEX_ENTRY_LEN), index_entry_len(2), 5).unwrap();
+        assert_eq!(count, 3, "Version 2 records");
+        assert_eq!(v2.end_timestamp, 300, "Version 2 last packet");
+
+        assert!(
+            index_range(&[], INDEX_ENTRY_LEN, 5).is_none(),
+            "Empty index"
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use crate::capture_source::open_master;
+use crate::catalog::time_ranges;
+use crate::file_manager::{
+    delete_index_files, flow_filename, index_filename, live_index_filename, segment_filename,
+    segment_ids,
+};
+use crate::flow_index::build_flows;
+use crate::index_manager::{IndexManager, MasterIndex};
+use crate::recovery::{RecoveryStats, recover};
+use anyhow::{Result, anyhow};
+use chrono::{Local, NaiveDateTime, TimeZone};
+use log::{info, warn};
+use pcap::segment::is_locked;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+//--- Format of the packet and protocol indexes, segments indexed by an
+//--- older version, or before versions were recorded, are indexed again.
+//--- Version 2 has 64-bit packet pointers. The flow table is a file of
+//--- its own, a segment without one only gets its flow table built
+pub const INDEX_VERSION: u32 = 2;
+
+//--- Modification time in nanoseconds and length of the stored segment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fingerprint {
+    pub mtime: i64,
+    pub len: u64,
+}
+
+impl Fingerprint {
+    //--- A segment in a source folder is followed through its link
+    pub fn of(file_id: u32) -> Result<Self> {
+        let metadata = fs::metadata(segment_filename(file_id))?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as i64);
+
+        Ok(Self {
+            mtime,
+            len: metadata.len(),
+        })
+    }
+}
+
+//--- Version and segment fingerprint recorded when it was last indexed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IndexState {
+    pub version: u32,
+    pub fingerprint: Fingerprint,
+}
+
+//--- Why the index of a segment is not current
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stale {
+    Missing,
+    Version,
+    Modified,
+    Flows,
+}
+
+pub fn check(
+    state: Option<IndexState>,
+    current: Fingerprint,
+    index_exists: bool,
+    flows_exist: bool,
+) -> Option<Stale> {
+    if !index_exists {
+        return Some(Stale::Missing);
+    }
+
+    match state {
+        Some(state) if state.version != INDEX_VERSION => Some(Stale::Version),
+        Some(state) if state.fingerprint != current => Some(Stale::Modified),
+        Some(_) if !flows_exist => Some(Stale::Flows),
+        Some(_) => None,
+        None => Some(Stale::Version),
+    }
+}
+
+fn index_states() -> Result<HashMap<u32, IndexState>> {
+    let conn = open_master()?;
+    let mut stmt = conn.prepare(
+        "select file_id, index_version, segment_mtime, segment_len from segment
+         where index_version is not null;",
+    )?;
+    let states = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                IndexState {
+                    version: row.get(1)?,
+                    fingerprint: Fingerprint {
+                        mtime: row.get::<_, Option<i64>>(2)?.unwrap_or_default(),
+                        len: row.get::<_, Option<u64>>(3)?.unwrap_or_default(),
+                    },
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<HashMap<u32, IndexState>>>()?;
+
+    Ok(states)
+}
+
+//--- Segments still written by a capture or an import are left alone
+fn is_open(file_id: u32) -> bool {
+    is_locked(&segment_filename(file_id)) || is_locked(&live_index_filename(file_id))
+}
+
+//--- Segments whose index is missing, of an older version or older
+//--- than the segment, or without a flow table, in file id order
+pub fn stale_segments() -> Result<Vec<(u32, Stale)>> {
+    let states = index_states()?;
+    let mut stale: Vec<(u32, Stale)> = Vec::new();
+
+    for file_id in segment_ids()? {
+        if is_open(file_id) {
+            continue;
+        }
+
+        let current = Fingerprint::of(file_id)?;
+        let index_exists = Path::new(&index_filename(file_id)).exists();
+        let flows_exist = Path::new(&flow_filename(file_id)).exists();
+        if let Some(reason) = check(
+            states.get(&file_id).copied(),
+            current,
+            index_exists,
+            flows_exist,
+        ) {
+            stale.push((file_id, reason));
+        }
+    }
+
+    Ok(stale)
+}
+
+//------------------------------------------------------------------
+//--- Segments indexed again in place: their packet and protocol
+//--- indexes are written again and their catalog rows updated. The
+//--- other segments and their rows are not touched. Returns the
+//--- segments indexed
+//------------------------------------------------------------------
+pub fn reindex_segments(file_ids: &[u32]) -> Result<Vec<u32>> {
+    let index_mgr = IndexManager::default();
+
+    let indexes: Vec<MasterIndex> = file_ids
+        .par_iter()
+        .filter(|file_id| {
+            let open = is_open(**file_id);
+            if open {
+                warn!("Segment {} is open, not indexed", file_id);
+            }
+            !open
+        })
+        .filter_map(|file_id| match delete_index_files(*file_id) {
+            Ok(()) => index_mgr.index_file(*file_id),
+            Err(msg) => {
+                warn!(
+                    "Could not remove the indexes of segment {}: {}",
+                    file_id, msg
+                );
+                None
+            }
+        })
+        .collect();
+
+    let mut indexed: Vec<u32> = indexes.iter().map(|index| index.file_ptr).collect();
+    indexed.sort();
+
+    Ok(indexed)
+}
+
+//--- Flow tables of segments whose packet index is current, the
+//--- segment is read once and its other indexes are not touched
+pub fn build_flow_tables(file_ids: &[u32]) -> Vec<u32> {
+    let mut built: Vec<u32> = file_ids
+        .par_iter()
+        .copied()
+        .filter(|file_id| !is_open(*file_id))
+        .filter(|file_id| match build_flows(*file_id) {
+            Ok(flows) => {
+                info!("Built {} flows of segment {}", flows, file_id);
+                true
+            }
+            Err(msg) => {
+                warn!("Could not build the flows of segment {}: {}", file_id, msg);
+                false
+            }
+        })
+        .collect();
+    built.sort();
+
+    built
+}
+
+//--- Segments missing only their flow table get it built, the other
+//--- stale segments are indexed again
+fn refresh_segments(stale: &[(u32, Stale)]) -> Result<Vec<u32>> {
+    let (flows, full): (Vec<u32>, Vec<u32>) = (
+        stale
+            .iter()
+            .filter(|(_, reason)| *reason == Stale::Flows)
+            .map(|(file_id, _)| *file_id)
+            .collect(),
+        stale
+            .iter()
+            .filter(|(_, reason)| *reason != Stale::Flows)
+            .map(|(file_id, _)| *file_id)
+            .collect(),
+    );
+
+    let mut indexed = reindex_segments(&full)?;
+    indexed.extend(build_flow_tables(&flows));
+    indexed.sort();
+
+    Ok(indexed)
+}
+
+//--- Segments indexed by an older version, after an upgrade of the
+//--- index format, and segments indexed before flow tables. Their other
+//--- indexes are left alone
+pub fn upgrade_indexes() -> Result<Vec<u32>> {
+    let stale: Vec<(u32, Stale)> = stale_segments()?
+        .into_iter()
+        .filter(|(_, reason)| matches!(reason, Stale::Version | Stale::Flows))
+        .collect();
+
+    refresh_segments(&stale)
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct IndexUpdate {
+    pub recovery: RecoveryStats,
+    pub stale: Vec<(u32, Stale)>,
+    pub indexed: Vec<u32>,
+}
+
+//------------------------------------------------------------------
+//--- Incremental indexing of the store. Segments without an index
+//--- were left by a crash or copied in db_path, recovery repairs and
+//--- indexes them. Segments with an outdated index are indexed again,
+//--- segments with a current index are not read
+//------------------------------------------------------------------
+pub fn update_indexes() -> Result<IndexUpdate> {
+    let recovery = recover()?;
+    let stale = stale_segments()?;
+    for (file_id, reason) in &stale {
+        info!("Index of segment {} is not current: {:?}", file_id, reason);
+    }
+
+    let indexed = refresh_segments(&stale)?;
+
+    Ok(IndexUpdate {
+        recovery,
+        stale,
+        indexed,
+    })
+}
+
+//--- Segments to index again: every segment, a list of file ids, or the
+//--- segments of the master index with packets in a time range
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReindexTarget {
+    All,
+    Files(Vec<u32>),
+    Range(u32, u32),
+}
+
+//--- Unix seconds or a local time: 2024-02-01 14:35:00
+fn parse_time(value: &str) -> Result<u32> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u32>() {
+        return Ok(secs);
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")?;
+    let time = Local
+        .from_local_datetime(&time)
+        .single()
+        .ok_or(anyhow!("Ambiguous local time: {}", value))?;
+
+    u32::try_from(time.timestamp()).map_err(|_| anyhow!("Time out of range: {}", value))
+}
+
+//--- all, 12,15,20 or FROM..TO
+impl FromStr for ReindexTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("all") {
+            return Ok(ReindexTarget::All);
+        }
+
+        if let Some((from, to)) = value.split_once("..") {
+            let (from, to) = (parse_time(from)?, parse_time(to)?);
+            if from > to {
+                return Err(anyhow!("Time range ends before it starts: {}", value));
+            }
+            return Ok(ReindexTarget::Range(from, to));
+        }
+
+        let file_ids = value
+            .split(',')
+            .map(|file_id| {
+                file_id
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("Invalid file id: {}", file_id))
+            })
+            .collect::<Result<Vec<u32>>>()?;
+
+        Ok(ReindexTarget::Files(file_ids))
+    }
+}
+
+impl ReindexTarget {
+    pub fn segments(&self) -> Result<Vec<u32>> {
+        let existing = segment_ids()?;
+
+        let mut file_ids: Vec<u32> = match self {
+            ReindexTarget::All => existing,
+            ReindexTarget::Files(file_ids) => file_ids
+                .iter()
+                .copied()
+                .filter(|file_id| {
+                    let found = existing.contains(file_id);
+                    if !found {
+                        warn!("Segment {} not found", file_id);
+                    }
+                    found
+                })
+                .collect(),
+            ReindexTarget::Range(from, to) => time_ranges()?
+                .into_iter()
+                .filter(|index| index.start_timestamp <= *to && index.end_timestamp >= *from)
+                .map(|index| index.file_ptr)
+                .filter(|file_id| existing.contains(file_id))
+                .collect(),
+        };
+        file_ids.sort();
+        file_ids.dedup();
+
+        Ok(file_ids)
+    }
+}
+
+pub fn reindex(target: &ReindexTarget) -> Result<Vec<u32>> {
+    reindex_segments(&target.segments()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_staleness() {
+        let fingerprint = Fingerprint {
+            mtime: 1_700_000_000_000_000_000,
+            len: 4096,
+        };
+        let current = IndexState {
+            version: INDEX_VERSION,
+            fingerprint,
+        };
+
+        assert_eq!(
+            check(Some(current), fingerprint, true, true),
+            None,
+            "Current"
+        );
+        assert_eq!(
+            check(Some(current), fingerprint, false, true),
+            Some(Stale::Missing),
+            "No index"
+        );
+        assert_eq!(
+            check(None, fingerprint, true, true),
+            Some(Stale::Version),
+            "Never recorded"
+        );
+        assert_eq!(
+            check(
+                Some(IndexState {
+                    version: INDEX_VERSION - 1,
+                    fingerprint
+                }),
+                fingerprint,
+                true,
+                true
+            ),
+            Some(Stale::Version),
+            "Older version"
+        );
+        assert_eq!(
+            check(Some(current), fingerprint, true, false),
+            Some(Stale::Flows),
+            "Current packet index without a flow table"
+        );
+        assert_eq!(
+            check(
+                Some(current),
+                Fingerprint {
+                    len: 8192,
+                    ..fingerprint
+                },
+                true,
+                true
+            ),
+            Some(Stale::Modified),
+            "Segment changed"
+        );
+    }
+
+    #[test]
+    fn reindex_targets() {
+        assert_eq!(
+            "all".parse::<ReindexTarget>().unwrap(),
+            ReindexTarget::All,
+            "Every segment"
+        );
+        assert_eq!(
+            "12, 15,20".parse::<ReindexTarget>().unwrap(),
+            ReindexTarget::Files(vec![12, 15, 20]),
+            "File ids"
+        );
+        assert_eq!(
+            "1700000000..1700000600".parse::<ReindexTarget>().unwrap(),
+            ReindexTarget::Range(1_700_000_000, 1_700_000_600),
+            "Time range"
+        );
+        assert!(
+            "1700000600..1700000000".parse::<ReindexTarget>().is_err(),
+            "Reversed range"
+        );
+        assert!("12,x".parse::<ReindexTarget>().is_err(), "Invalid file id");
+    }
+}
@@ -0,0 +1,628 @@
+use crate::capture_source::{CaptureSource, open_master};
+use crate::catalog::SegmentState;
+use crate::file_manager::delete_segment_files;
+use anyhow::Result;
+use dblib::config::CONFIG;
+use log::{info, warn};
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, params};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Reason {
+    Age,
+    Bytes,
+}
+
+impl Reason {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Reason::Age => "age",
+            Reason::Bytes => "bytes",
+        }
+    }
+}
+
+//--- Indexed segment as retention sees it, capture_id 0 for no source
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentInfo {
+    pub file_id: u32,
+    pub capture_id: u32,
+    //--- Last packet timestamp
+    pub end_timestamp: u32,
+    pub bytes: u64,
+    pub legal_hold: bool,
+}
+
+//--- Limits of one source, 0 for no limit
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetentionLimits {
+    pub max_age: u64,
+    pub max_bytes: u64,
+}
+
+impl RetentionLimits {
+    //--- Source settings first, then the configuration defaults
+    pub fn of(source: Option<&CaptureSource>) -> Self {
+        let (age, bytes) = source.map_or((0, 0), |source| {
+            (source.retention_age, source.retention_bytes)
+        });
+
+        Self {
+            max_age: if age > 0 {
+                age
+            } else {
+                CONFIG.retention_max_age
+            },
+            max_bytes: if bytes > 0 {
+                bytes
+            } else {
+                CONFIG.retention_max_bytes
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Purged {
+    pub segment: SegmentInfo,
+    pub reason: Reason,
+}
+
+//------------------------------------------------------------------
+//--- Segments of one source to delete, oldest first. Segments past the
+//--- max age go first, then the oldest until the source fits in max
+//--- bytes. Segments under legal hold are never selected but their
+//--- bytes still count
+//------------------------------------------------------------------
+pub fn select_expired(
+    segments: &[SegmentInfo],
+    limits: RetentionLimits,
+    now: u32,
+) -> Vec<(u32, Reason)> {
+    let mut ordered: Vec<&SegmentInfo> = segments.iter().collect();
+    ordered.sort_by_key(|segment| (segment.end_timestamp, segment.file_id));
+
+    let mut total: u64 = ordered.iter().map(|segment| segment.bytes).sum();
+    let mut expired: Vec<(u32, Reason)> = Vec::new();
+
+    for segment in ordered.iter().filter(|segment| !segment.legal_hold) {
+        let too_old =
+            limits.max_age > 0 && (segment.end_timestamp as u64) + limits.max_age < now as u64;
+        let too_big = limits.max_bytes > 0 && total > limits.max_bytes;
+
+        if too_old {
+            expired.push((segment.file_id, Reason::Age));
+        } else if too_big {
+            expired.push((segment.file_id, Reason::Bytes));
+        } else {
+            continue;
+        }
+        total -= segment.bytes;
+    }
+
+    expired
+}
+
+//--- Legal hold on a segment, imported segments get an untagged row.
+//--- The change is logged with the actor that made it
+pub fn set_legal_hold(file_id: u32, hold: bool, actor: &str) -> Result<()> {
+    write_legal_hold(&mut open_master()?, file_id, hold, actor, now_secs())
+}
+
+fn write_legal_hold(
+    conn: &mut Connection,
+    file_id: u32,
+    hold: bool,
+    actor: &str,
+    now: u64,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "insert into segment (file_id, legal_hold) values (?, ?)
+         on conflict(file_id) do update set legal_hold = excluded.legal_hold;",
+        params![file_id, hold],
+    )?;
+    tx.execute(
+        "insert into legal_hold_log (changed_at, file_id, capture_id, legal_hold, actor)
+         select ?, file_id, capture_id, legal_hold, ? from segment where file_id = ?;",
+        params![now, actor, file_id],
+    )?;
+    tx.commit()?;
+    info!(
+        "Legal hold of segment {} set to {} by {}",
+        file_id, hold, actor
+    );
+
+    Ok(())
+}
+
+//--- Hold changes were logged in retention_log with the deletions, they
+//--- are moved to legal_hold_log once
+pub fn migrate_hold_log(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("pragma table_info(retention_log);")?;
+    let has_actor = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|column| column.ok())
+        .any(|column| column == "actor");
+    let actor = if has_actor { "actor" } else { "''" };
+
+    let tx = conn.unchecked_transaction()?;
+    let moved = tx.execute(
+        &format!(
+            "insert into legal_hold_log (changed_at, file_id, capture_id, legal_hold, actor)
+             select deleted_at, file_id, capture_id, reason = 'hold', {} from retention_log
+             where reason in ('hold', 'release') order by id;",
+            actor
+        ),
+        [],
+    )?;
+    tx.execute(
+        "delete from retention_log where reason in ('hold', 'release');",
+        [],
+    )?;
+    tx.commit()?;
+
+    if moved > 0 {
+        info!("Moved {} legal hold changes to legal_hold_log", moved);
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HoldEntry {
+    pub changed_at: u64,
+    pub file_id: u32,
+    pub capture_id: u32,
+    pub legal_hold: bool,
+    pub actor: String,
+}
+
+pub fn hold_log() -> Result<Vec<HoldEntry>> {
+    let conn = open_master()?;
+    let mut stmt = conn.prepare(
+        "select changed_at, file_id, capture_id, legal_hold, actor
+         from legal_hold_log order by id;",
+    )?;
+    let log = stmt
+        .query_map([], |row| {
+            Ok(HoldEntry {
+                changed_at: row.get(0)?,
+                file_id: row.get(1)?,
+                capture_id: row.get::<_, Option<u32>>(2)?.unwrap_or_default(),
+                legal_hold: row.get::<_, Option<bool>>(3)?.unwrap_or_default(),
+                actor: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<HoldEntry>>>()?;
+
+    Ok(log)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LogEntry {
+    pub deleted_at: u64,
+    pub file_id: u32,
+    pub capture_id: u32,
+    pub end_timestamp: u32,
+    pub bytes: u64,
+    pub reason: String,
+}
+
+pub fn deletion_log() -> Result<Vec<LogEntry>> {
+    let conn = open_master()?;
+    let mut stmt = conn.prepare(
+        "select deleted_at, file_id, capture_id, end_timestamp, bytes, reason
+         from retention_log order by id;",
+    )?;
+    let log = stmt
+        .query_map([], |row| {
+            Ok(LogEntry {
+                deleted_at: row.get(0)?,
+                file_id: row.get(1)?,
+                capture_id: row.get::<_, Option<u32>>(2)?.unwrap_or_default(),
+                end_timestamp: row.get::<_, Option<u32>>(3)?.unwrap_or_default(),
+                bytes: row.get(4)?,
+                reason: row.get(5)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<LogEntry>>>()?;
+
+    Ok(log)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+//------------------------------------------------------------------
+//--- Enforcement of the retention limits of every capture source.
+//--- Only indexed segments of the catalog are considered, each deletion
+//--- removes the segment and its indexes, archives its catalog row and
+//--- is logged
+//------------------------------------------------------------------
+#[derive(Default, Debug)]
+pub struct Retention {}
+
+impl Retention {
+    pub fn segments(&self) -> Result<Vec<SegmentInfo>> {
+        indexed_segments(&open_master()?)
+    }
+
+    pub fn enforce(&mut self) -> Result<Vec<Purged>> {
+        let sources: HashMap<u32, CaptureSource> = CaptureSource::list()?
+            .into_iter()
+            .map(|source| (source.id, source))
+            .collect();
+
+        let mut by_source: HashMap<u32, Vec<SegmentInfo>> = HashMap::new();
+        for segment in self.segments()? {
+            by_source
+                .entry(segment.capture_id)
+                .or_default()
+                .push(segment);
+        }
+
+        let now = now_secs();
+        let mut purged: Vec<Purged> = Vec::new();
+        for (capture_id, segments) in &by_source {
+            let limits = RetentionLimits::of(sources.get(capture_id));
+            for (file_id, reason) in select_expired(segments, limits, now as u32) {
+                if let Some(segment) = segments.iter().find(|segment| segment.file_id == file_id) {
+                    purged.push(Purged {
+                        segment: segment.clone(),
+                        reason,
+                    });
+                }
+            }
+        }
+
+        //--- Files are deleted once the deletion is committed, a segment
+        //--- that fails is left for the next run
+        let mut conn = open_master()?;
+        let mut deleted: HashSet<u32> = HashSet::new();
+        for entry in &purged {
+            let segment = &entry.segment;
+            match log_deletion(&mut conn, segment, entry.reason, now) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!(
+                        "Segment {} placed under legal hold, not deleted",
+                        segment.file_id
+                    );
+                    continue;
+                }
+                Err(msg) => {
+                    warn!(
+                        "Could not log the deletion of segment {}: {}",
+                        segment.file_id, msg
+                    );
+                    continue;
+                }
+            }
+            deleted.insert(segment.file_id);
+
+            match delete_segment_files(segment.file_id) {
+                Ok(()) => info!(
+                    "Retention deleted segment {} of source {}, {} bytes, by {}",
+                    segment.file_id,
+                    segment.capture_id,
+                    segment.bytes,
+                    entry.reason.as_str()
+                ),
+                Err(msg) => warn!(
+                    "Could not delete the files of segment {}: {}",
+                    segment.file_id, msg
+                ),
+            }
+        }
+
+        purged.retain(|entry| deleted.contains(&entry.segment.file_id));
+
+        Ok(purged)
+    }
+}
+
+//--- Indexed segments of the catalog, open segments are not indexed yet
+//--- and archived ones are already deleted
+fn indexed_segments(conn: &Connection) -> Result<Vec<SegmentInfo>> {
+    let mut stmt = conn.prepare(
+        "select file_id, capture_id, end_timestamp, coalesce(segment_len, bytes), legal_hold
+         from segment where state = ? order by file_id;",
+    )?;
+    let segments = stmt
+        .query_map([SegmentState::Indexed.as_str()], |row| {
+            Ok(SegmentInfo {
+                file_id: row.get(0)?,
+                capture_id: row.get::<_, Option<u32>>(1)?.unwrap_or_default(),
+                end_timestamp: row.get::<_, Option<u32>>(2)?.unwrap_or_default(),
+                bytes: row.get::<_, Option<u64>>(3)?.unwrap_or_default(),
+                legal_hold: row.get::<_, Option<bool>>(4)?.unwrap_or_default(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<SegmentInfo>>>()?;
+
+    Ok(segments)
+}
+
+//--- Log row and catalog rows of a deleted segment in one transaction,
+//--- false when a legal hold was placed since the segment was selected.
+//--- The write lock is taken first so no hold commits in between
+fn log_deletion(
+    conn: &mut Connection,
+    segment: &SegmentInfo,
+    reason: Reason,
+    now: u64,
+) -> Result<bool> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let held = tx
+        .query_row(
+            "select legal_hold from segment where file_id = ?;",
+            [segment.file_id],
+            |row| row.get::<_, Option<bool>>(0),
+        )
+        .optional()?
+        .flatten()
+        .unwrap_or_default();
+    if held {
+        return Ok(false);
+    }
+
+    tx.execute(
+        "insert into retention_log (deleted_at, file_id, capture_id, end_timestamp, bytes, reason)
+         values (?, ?, ?, ?, ?, ?);",
+        params![
+            now,
+            segment.file_id,
+            segment.capture_id,
+            segment.end_timestamp,
+            segment.bytes,
+            reason.as_str()
+        ],
+    )?;
+    tx.execute(
+        "update segment set state = ? where file_id = ?;",
+        params![SegmentState::Archived.as_str(), segment.file_id],
+    )?;
+    tx.execute(
+        "delete from segment_stats where file_id = ?;",
+        [segment.file_id],
+    )?;
+    tx.execute(
+        "delete from proto_stats where file_id = ?;",
+        [segment.file_id],
+    )?;
+    tx.commit()?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(file_id: u32, end_timestamp: u32, legal_hold: bool) -> SegmentInfo {
+        SegmentInfo {
+            file_id,
+            capture_id: 1,
+            end_timestamp,
+            bytes: 100,
+            legal_hold,
+        }
+    }
+
+    #[test]
+    fn expired_segments() {
+        let segments = vec![
+            segment(3, 3_000, false),
+            segment(1, 1_000, true),
+            segment(2, 2_000, false),
+            segment(4, 4_000, false),
+        ];
+
+        assert!(
+            select_expired(&segments, RetentionLimits::default(), 10_000).is_empty(),
+            "No limit"
+        );
+        assert_eq!(
+            select_expired(
+                &segments,
+                RetentionLimits {
+                    max_age: 7_500,
+                    max_bytes: 0
+                },
+                10_000
+            ),
+            vec![(2, Reason::Age)],
+            "Held segment kept"
+        );
+        assert_eq!(
+            select_expired(
+                &segments,
+                RetentionLimits {
+                    max_age: 7_500,
+                    max_bytes: 200
+                },
+                10_000
+            ),
+            vec![(2, Reason::Age), (3, Reason::Bytes)],
+            "Age then size, held bytes counted"
+        );
+    }
+
+    fn master_tables() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "create table segment (file_id integer primary key, capture_id integer,
+                end_timestamp integer, segment_len integer, bytes integer,
+                legal_hold integer default 0, state varchar(10));
+             create table segment_stats (file_id integer primary key);
+             create table proto_stats (file_id integer, proto integer, count integer);
+             create table retention_log (id integer primary key, deleted_at integer,
+                file_id integer, capture_id integer, end_timestamp integer, bytes integer,
+                reason varchar(20), actor varchar(255));
+             create table legal_hold_log (id integer primary key, changed_at integer,
+                file_id integer, capture_id integer, legal_hold integer, actor varchar(255));
+             insert into segment (file_id, capture_id, end_timestamp, segment_len, bytes, state)
+                values (4, 2, 900, 124, 100, 'indexed');",
+        )
+        .unwrap();
+
+        conn
+    }
+
+    #[test]
+    fn legal_hold_logged() {
+        let mut conn = master_tables();
+
+        write_legal_hold(&mut conn, 4, true, "alice", 1_000).unwrap();
+        write_legal_hold(&mut conn, 4, false, "bob", 2_000).unwrap();
+
+        let held: bool = conn
+            .query_row(
+                "select legal_hold from segment where file_id = 4;",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!held, "Hold released");
+
+        let log: Vec<(u64, u32, bool, String)> = conn
+            .prepare(
+                "select changed_at, capture_id, legal_hold, actor from legal_hold_log order by id;",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            log,
+            vec![
+                (1_000, 2, true, "alice".to_string()),
+                (2_000, 2, false, "bob".to_string())
+            ],
+            "Who and when of each change"
+        );
+
+        let deletions: usize = conn
+            .query_row("select count(*) from retention_log;", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(deletions, 0, "Not in the deletion log");
+    }
+
+    #[test]
+    fn hold_rechecked_on_delete() {
+        let mut conn = master_tables();
+        let mut expired = segment(4, 900, false);
+        expired.capture_id = 2;
+
+        write_legal_hold(&mut conn, 4, true, "alice", 1_000).unwrap();
+        assert!(
+            !log_deletion(&mut conn, &expired, Reason::Age, 2_000).unwrap(),
+            "Held since selected"
+        );
+
+        write_legal_hold(&mut conn, 4, false, "alice", 3_000).unwrap();
+        assert!(
+            log_deletion(&mut conn, &expired, Reason::Age, 4_000).unwrap(),
+            "Released"
+        );
+
+        let log: Vec<(u64, u32, String)> = conn
+            .prepare("select deleted_at, file_id, reason from retention_log;")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(log, vec![(4_000, 4, "age".to_string())], "One deletion");
+
+        let state: String = conn
+            .query_row("select state from segment where file_id = 4;", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(state, "archived", "Catalog row archived");
+        assert!(
+            indexed_segments(&conn).unwrap().is_empty(),
+            "Archived segment no longer a candidate"
+        );
+    }
+
+    #[test]
+    fn candidates_from_catalog() {
+        let conn = master_tables();
+        conn.execute_batch(
+            "insert into segment (file_id, capture_id, end_timestamp, bytes, legal_hold, state)
+                values (5, 2, 950, 300, 1, 'indexed'), (6, 2, 990, 50, 0, 'open'),
+                (7, 2, 800, 70, 0, 'closed'), (8, null, 700, 90, 0, 'archived');",
+        )
+        .unwrap();
+
+        let segments: Vec<(u32, u32, u32, u64, bool)> = indexed_segments(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|s| {
+                (
+                    s.file_id,
+                    s.capture_id,
+                    s.end_timestamp,
+                    s.bytes,
+                    s.legal_hold,
+                )
+            })
+            .collect();
+        assert_eq!(
+            segments,
+            vec![(4, 2, 900, 124, false), (5, 2, 950, 300, true)],
+            "Only indexed segments, stored size before packet bytes"
+        );
+    }
+
+    #[test]
+    fn hold_log_migrated() {
+        let conn = master_tables();
+        conn.execute_batch(
+            "insert into retention_log (deleted_at, file_id, capture_id, bytes, reason, actor)
+                values (1, 4, 2, 0, 'hold', 'alice'), (2, 5, 2, 100, 'age', null),
+                (3, 4, 2, 0, 'release', 'bob');",
+        )
+        .unwrap();
+
+        migrate_hold_log(&conn).unwrap();
+        migrate_hold_log(&conn).unwrap();
+
+        let holds: Vec<(u64, bool, String)> = conn
+            .prepare("select changed_at, legal_hold, actor from legal_hold_log order by id;")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            holds,
+            vec![
+                (1, true, "alice".to_string()),
+                (3, false, "bob".to_string())
+            ],
+            "Hold changes moved once"
+        );
+
+        let reasons: Vec<String> = conn
+            .prepare("select reason from retention_log;")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(reasons, vec!["age".to_string()], "Deletions kept");
+    }
+}
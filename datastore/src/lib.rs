@@ -22,7 +22,10 @@ use std::path::Path;
 use std::time::Instant;
 
 pub fn packet_to_db(packet_file: u32, table_name: &str) -> Result<()> {
-    let mut pcap_file = PcapFile::new(packet_file, &CONFIG.db_path);
+    let mut pcap_file = PcapFile::open(
+        &format!("{}/{}.pcap", &CONFIG.db_path, packet_file),
+        packet_file,
+    )?;
 
     let mut db = DBTableWriter::new(&format!("{}/import/{}", &CONFIG.root_path, table_name));
     db.create_table(
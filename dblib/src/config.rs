@@ -16,8 +16,58 @@ pub struct Config {
     pub index_path: String,
     pub proto_index_path: String,
     pub master_index_path: String,
+    //--- Packets per segment written by imports and capture sources
+    //--- without their own limit, 0 for the default
     pub db_segment_size: usize,
+    //--- Segment size and age in seconds that rotate capture segments,
+    //--- for sources without their own limits. 0 for no limit
+    #[serde(default)]
+    pub segment_max_bytes: u64,
+    #[serde(default)]
+    pub segment_max_age: u64,
+    //--- Age in seconds and total bytes kept per capture source, for
+    //--- sources without their own retention. 0 keeps everything
+    #[serde(default)]
+    pub retention_max_age: u64,
+    #[serde(default)]
+    pub retention_max_bytes: u64,
+    //--- Seconds between retention runs of the capture, 0 for the default
+    #[serde(default)]
+    pub retention_interval: u64,
+    //--- Packets queued between the capture handle and the segment
+    //--- writer, 0 for the default
+    #[serde(default)]
+    pub capture_queue_size: usize,
+    //--- What a capture does with a packet when its queue is full, "drop"
+    //--- counts and drops it, "block" waits and lets the kernel drop
+    #[serde(default)]
+    pub capture_overflow: String,
+    //--- Uncompressed bytes per block of compressed segments, 0 for the default
     pub block_size: usize,
+    //--- Ask the capture handle for nanosecond timestamps and write
+    //--- nanosecond pcap segments
+    #[serde(default)]
+    pub capture_nanosecond: bool,
+    //--- Write capture segments as pcapng instead of classic pcap
+    #[serde(default)]
+    pub capture_pcapng: bool,
+    //--- Write segments compressed in seekable zstd blocks
+    #[serde(default)]
+    pub segment_compression: bool,
+    //--- Zstd level of compressed segments, 0 for the default
+    #[serde(default)]
+    pub compression_level: i32,
+    //--- Directory the API server imports from, requested paths must be
+    //--- inside it. Empty disables imports over the API
+    #[serde(default)]
+    pub import_path: String,
+    //--- Bearer token that places and releases legal holds and saves
+    //--- capture sources over the API. Empty disables both
+    #[serde(default)]
+    pub hold_token: String,
+    //--- Directory capture source folders must be inside, empty for db_path
+    #[serde(default)]
+    pub storage_path: String,
 }
 
 #[allow(clippy::needless_return)]
@@ -11,11 +11,13 @@ pub const IPV6: u16 = 9;
 pub const BYTE_ARRAY: u16 = 0x0a;
 pub const STRING: u16 = 0x0b;
 pub const FIELD_ARRAY: u16 = 0x0c;
+pub const SINT8: u16 = 0x0d;
 
 pub fn get_type_len(field_type: u16) -> u16 {
     match field_type {
         BOOL => 1,
         INT8 => 1,
+        SINT8 => 1,
         INT16 => 2,
         INT32 => 4,
         INT64 => 8,
@@ -27,3 +29,23 @@ pub fn get_type_len(field_type: u16) -> u16 {
         _ => 0,
     }
 }
+
+pub fn type_name(field_type: u16) -> &'static str {
+    match field_type {
+        BOOL => "bool",
+        INT8 => "int8",
+        SINT8 => "sint8",
+        INT16 => "int16",
+        INT32 => "int32",
+        INT64 => "int64",
+        IPV4 => "ipv4",
+        MACADDR => "macaddr",
+        TIMESTAMP => "timestamp",
+        TIMEVALUE => "timevalue",
+        IPV6 => "ipv6",
+        BYTE_ARRAY => "byte_array",
+        STRING => "string",
+        FIELD_ARRAY => "field_array",
+        _ => "unknown",
+    }
+}
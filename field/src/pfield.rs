@@ -19,6 +19,7 @@ pub enum FieldType {
     Int32(u32),
     Int16(u16),
     Int8(u8),
+    SInt8(i8),
     Ipv4(u32, u8),
     Ipv6(u128, u8),
     Timestamp(u32),
@@ -37,6 +38,7 @@ impl fmt::Display for FieldType {
             Self::Int32(value) => write!(f, "{}", value),
             Self::Int16(value) => write!(f, "{}", value),
             Self::Int8(value) => write!(f, "{}", value),
+            Self::SInt8(value) => write!(f, "{}", value),
             Self::Ipv4(address, mask) => write!(f, "{}", IPv4::new(*address, *mask)),
             Self::Ipv6(address, mask) => write!(f, "{}", IPv6::new(*address, *mask)),
             Self::MacAddr(address) => write!(f, "{}", MacAddr::set_from_int(address)),
@@ -80,6 +82,7 @@ impl SerializeField for Field {
                 }
             }
             field_type::INT8 => ret_field_type = FieldType::Int8(value[0]),
+            field_type::SINT8 => ret_field_type = FieldType::SInt8(value[0] as i8),
             field_type::INT16 => ret_field_type = FieldType::Int16(BigEndian::read_u16(&value)),
             field_type::INT32 => ret_field_type = FieldType::Int32(BigEndian::read_u32(&value)),
             field_type::INT64 => ret_field_type = FieldType::Int64(BigEndian::read_u64(&value)),
@@ -134,6 +137,9 @@ impl SerializeField for Field {
             FieldType::Int8(value) => {
                 result.write_u8(*value).unwrap();
             }
+            FieldType::SInt8(value) => {
+                result.write_i8(*value).unwrap();
+            }
             FieldType::Ipv4(address, _) => {
                 result.write_u32::<BigEndian>(*address).unwrap();
             }
@@ -190,6 +196,7 @@ impl Field {
         match &self.field {
             FieldType::Bool(_) => 1,
             FieldType::Int8(_) => 1,
+            FieldType::SInt8(_) => 1,
             FieldType::Int16(_) => 2,
             FieldType::Int32(_) => 4,
             FieldType::Int64(_) => 8,
@@ -208,6 +215,7 @@ impl Field {
         match &self.field {
             FieldType::Bool(_) => field_type::BOOL,
             FieldType::Int8(_) => field_type::INT8,
+            FieldType::SInt8(_) => field_type::SINT8,
             FieldType::Int16(_) => field_type::INT16,
             FieldType::Int32(_) => field_type::INT32,
             FieldType::Int64(_) => field_type::INT64,
@@ -228,6 +236,7 @@ impl Field {
             FieldType::Int32(value) => json!(value),
             FieldType::Int16(value) => json!(value),
             FieldType::Int8(value) => json!(value),
+            FieldType::SInt8(value) => json!(value),
             FieldType::Ipv4(address, mask) => json!(IPv4::new(*address, *mask).to_string()),
             FieldType::Ipv6(address, mask) => json!(IPv6::new(*address, *mask).to_string()),
             FieldType::MacAddr(value) => json!(MacAddr::set_from_int(value).to_string()),
@@ -284,6 +293,8 @@ impl Field {
             FieldType::Int16(value) => value as u64,
             FieldType::Int32(value) => value as u64,
             FieldType::Int64(value) => value,
+            //--- Two's complement, to_i64 keeps the sign
+            FieldType::SInt8(value) => value as u64,
             FieldType::Bool(value) => value as u64,
             FieldType::Ipv4(value, _) => value as u64,
             FieldType::MacAddr(value) => value,
@@ -291,6 +302,13 @@ impl Field {
         }
     }
 
+    pub fn to_i64(&self) -> i64 {
+        match self.field {
+            FieldType::SInt8(value) => value as i64,
+            _ => self.to_u64() as i64,
+        }
+    }
+
     pub fn to_usize(&self) -> usize {
         match self.field {
             FieldType::Int8(value) => value as usize,
@@ -0,0 +1,34 @@
+#![no_main]
+
+//--- cargo fuzz run packet, from the frame directory
+use frame::packet::{LINKTYPE_ETHERNET, LINKTYPE_IEEE802_11, LINKTYPE_IEEE802_11_RADIOTAP, Packet};
+use frame::packet_display::PacketDisplay;
+use frame::registry;
+use libfuzzer_sys::fuzz_target;
+
+const LINK_TYPES: [u32; 3] = [
+    LINKTYPE_ETHERNET,
+    LINKTYPE_IEEE802_11,
+    LINKTYPE_IEEE802_11_RADIOTAP,
+];
+
+fuzz_target!(|data: &[u8]| {
+    //--- First byte selects the link type, the rest is the captured frame
+    let Some((selector, raw)) = data.split_first() else {
+        return;
+    };
+    let link_type = LINK_TYPES[*selector as usize % LINK_TYPES.len()];
+
+    let mut header = [0u8; 16];
+    header[8..12].copy_from_slice(&(raw.len() as u32).to_le_bytes());
+    header[12..16].copy_from_slice(&(raw.len() as u32).to_le_bytes());
+
+    let mut pkt = Packet::new();
+    pkt.set_packet(raw.to_vec(), header, 0, 0, true, link_type);
+
+    for field_def in registry::field_defs() {
+        let _ = pkt.get_field(field_def.name);
+        let _ = pkt.get_field_byte(field_def.name.to_string(), 0, 4);
+    }
+    let _ = pkt.summary();
+});
@@ -1,7 +1,10 @@
+use crate::detail::LayerNode;
 use byteorder::{BigEndian, ByteOrder};
 
-use crate::layer::Layer;
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
+use field::field_type;
 use field::ipv4_address::IPv4;
 use field::mac_address::MacAddr;
 use field::pfield::{Field, FieldType};
@@ -12,6 +15,21 @@ use field::pfield::{Field, FieldType};
 *
 */
 
+const ETHER_ARP_PROTO: u16 = 0x0806;
+const ARP_LEN: usize = 28;
+
+const ARP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("arp.sender_mac", field_type::MACADDR, 8, 6),
+    FieldDef::at("arp.sender_ip", field_type::IPV4, 14, 4),
+    FieldDef::at("arp.target_mac", field_type::MACADDR, 18, 6),
+    FieldDef::at("arp.target_ip", field_type::IPV4, 24, 4),
+    FieldDef::at("arp.hwd_type", field_type::INT16, 0, 2),
+    FieldDef::at("arp.proto_type", field_type::INT16, 2, 2),
+    FieldDef::at("arp.opcode", field_type::INT16, 6, 2),
+    FieldDef::at("arp.proto_size", field_type::INT8, 5, 1),
+    FieldDef::at("arp.hwd_size", field_type::INT8, 4, 1),
+];
+
 #[derive(Debug, Clone, Default)]
 pub struct Arp {
     raw_packet: Vec<u8>,
@@ -70,6 +88,7 @@ impl Layer for Arp {
             "arp.hwd_type" => Some(Field::set_field(FieldType::Int16(self.get_htype()), field)),
             "arp.proto_type" => Some(Field::set_field(FieldType::Int16(self.get_ptype()), field)),
             "arp.hwd_size" => Some(Field::set_field(FieldType::Int8(self.get_hlen()), field)),
+            "arp.proto_size" => Some(Field::set_field(FieldType::Int8(self.get_plen()), field)),
 
             "arp.opcode" => Some(Field::set_field(FieldType::Int16(self.get_opcode()), field)),
             "arp.sender_mac" => Some(Field::set_field(FieldType::MacAddr(self.get_sha()), field)),
@@ -87,6 +106,38 @@ impl Layer for Arp {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ArpDissector;
+
+impl Dissector for ArpDissector {
+    fn name(&self) -> &'static str {
+        "arp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::ARP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::EtherType(ETHER_ARP_PROTO)]
+    }
+
+    fn min_len(&self) -> usize {
+        ARP_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        ARP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        let mut arp = Arp::default();
+        arp.set_packet(packet.to_vec());
+
+        Box::new(arp)
+    }
+}
+
 impl PacketDisplay for Arp {
     fn summary(&self) -> String {
         format!(
@@ -99,6 +150,12 @@ impl PacketDisplay for Arp {
     }
 
     fn show_detail(&self) -> String {
-        "Arp detail".to_string()
+        LayerNode::of(
+            ArpDissector.name(),
+            ArpDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
     }
 }
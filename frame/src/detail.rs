@@ -0,0 +1,177 @@
+use crate::layer::{FieldDef, Layer};
+use field::field_type;
+use field::pfield::Field;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+
+//------------------------------------------------------------------
+//--- Dissection tree of a packet, offsets are positions in the raw
+//--- packet bytes, the same bytes dumped by print_hex::format_hex
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldNode {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: &'static str,
+    pub value: Value,
+    pub offset: Option<usize>,
+    pub len: Option<usize>,
+}
+
+impl FieldNode {
+    //--- range is (offset, len) in the layer, base the layer offset
+    pub fn new(name: &str, field: &Field, range: Option<(usize, usize)>, base: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            field_type: field_type::type_name(field.get_int_type()),
+            value: field.to_json(),
+            offset: range.map(|(offset, _)| base + offset),
+            len: range.map(|(_, len)| len),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LayerNode {
+    pub name: String,
+    pub offset: usize,
+    pub len: usize,
+    pub fields: Vec<FieldNode>,
+}
+
+impl LayerNode {
+    //--- A layer decoded on its own, offsets from the start of its bytes.
+    //--- The range of a field is kept when inside the layer
+    pub fn of(name: &str, fields: &[FieldDef], layer: &dyn Layer, len: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            offset: 0,
+            len,
+            fields: fields
+                .iter()
+                .filter_map(|def| {
+                    let field = layer.get_field(def.name)?;
+                    let range = layer
+                        .field_range(def.name)
+                        .or(def.range)
+                        .filter(|(offset, field_len)| offset + field_len <= len);
+
+                    Some(FieldNode::new(def.name, &field, range, 0))
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PacketTree {
+    pub layers: Vec<LayerNode>,
+}
+
+impl PacketTree {
+    pub fn layer(&self, name: &str) -> Option<&LayerNode> {
+        self.layers.iter().find(|layer| layer.name == name)
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldNode> {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.fields.iter())
+            .find(|field| field.name == name)
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+impl fmt::Display for FieldNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.value {
+            Value::String(value) => write!(f, "{}: {}", self.name, value)?,
+            value => write!(f, "{}: {}", self.name, value)?,
+        }
+        write!(f, " ({})", self.field_type)?;
+
+        if let (Some(offset), Some(len)) = (self.offset, self.len) {
+            write!(f, " [0x{:04x}, {}]", offset, len)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for LayerNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} [0x{:04x}, {} bytes]",
+            self.name, self.offset, self.len
+        )?;
+
+        for field in &self.fields {
+            writeln!(f, "    {}", field)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for PacketTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for layer in &self.layers {
+            write!(f, "{}", layer)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn detail_text() {
+        let tree = PacketTree {
+            layers: vec![LayerNode {
+                name: "udp".to_string(),
+                offset: 0x22,
+                len: 8,
+                fields: vec![
+                    FieldNode {
+                        name: "udp.sport".to_string(),
+                        field_type: "int16",
+                        value: json!(53),
+                        offset: Some(0x22),
+                        len: Some(2),
+                    },
+                    FieldNode {
+                        name: "udp.proto".to_string(),
+                        field_type: "string",
+                        value: json!("dns"),
+                        offset: None,
+                        len: None,
+                    },
+                ],
+            }],
+        };
+
+        assert_eq!(
+            tree.to_string(),
+            "udp [0x0022, 8 bytes]\n    udp.sport: 53 (int16) [0x0022, 2]\n    udp.proto: dns (string)\n",
+            "Text tree"
+        );
+        assert_eq!(
+            tree.to_json()["layers"][0]["fields"][0]["type"],
+            "int16",
+            "JSON type"
+        );
+        assert!(
+            tree.to_json()["layers"][0]["fields"][1]["offset"].is_null(),
+            "No offset"
+        );
+    }
+}
@@ -1,14 +1,44 @@
-use crate::layer::Layer;
+use crate::layer::{DecodeError, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use byteorder::{BigEndian, ByteOrder};
 use std::fmt;
 use std::str;
 
-use crate::print_hex::print_hex;
+use field::field_type;
 use field::pfield::{Field, FieldType};
 use field::{ipv4_address::IPv4, mac_address::MacAddr};
 
 const SERVER_NAME_OFFSET: usize = 0x2c;
 const BOOT_FILE_OFFSET: usize = 0x6c;
+const OPTIONS_OFFSET: usize = 240;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+
+const DHCP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("dhcp.xid", field_type::INT32, 4, 4),
+    FieldDef::new("dhcp.client_ip", field_type::IPV4),
+    FieldDef::at("dhcp.opcode", field_type::INT8, 0, 1),
+    FieldDef::new("dhcp.domain_name", field_type::STRING),
+    FieldDef::new("dhcp.ip_lease_time", field_type::TIMEVALUE),
+    FieldDef::new("dhcp.domain_srv", field_type::FIELD_ARRAY),
+    FieldDef::new("dhcp.renewal_time", field_type::TIMEVALUE),
+    FieldDef::new("dhcp.rebinding_time", field_type::TIMEVALUE),
+    FieldDef::new("dhcp.router", field_type::IPV4),
+    FieldDef::new("dhcp.subnet_mask", field_type::IPV4),
+    FieldDef::new("dhcp.server_id", field_type::IPV4),
+    FieldDef::new("dhcp.requested_ip", field_type::IPV4),
+    FieldDef::new("dhcp.client_hwnd_type", field_type::INT8),
+    FieldDef::new("dhcp.client_mac", field_type::MACADDR),
+    FieldDef::new("dhcp.hostname", field_type::STRING),
+    FieldDef::new("dhcp.client_fqdn_flags", field_type::INT8),
+    FieldDef::new("dhcp.client_fqdn_a_result", field_type::INT8),
+    FieldDef::new("dhcp.client_fqdn_ptr_result", field_type::INT8),
+    FieldDef::new("dhcp.client_fqdn_name", field_type::STRING),
+    FieldDef::new("dhcp.vendor_id", field_type::STRING),
+    FieldDef::new("dhcp.vendor_info", field_type::BYTE_ARRAY),
+    FieldDef::new("dhcp.params_req_list", field_type::BYTE_ARRAY),
+];
 
 const OPTIONS_MSG_TYPE: u8 = 0x35;
 const OPTIONS_CLIENT_ID: u8 = 0x3d;
@@ -30,6 +60,23 @@ const OPTIONS_DHCP_IP_LEASE_TIME: u8 = 0x33;
 
 const OPTIONS_END: u8 = 0xff;
 
+//--- Shortest value accepted for the decoded options, shorter is malformed
+fn option_min_len(id: u8) -> usize {
+    match id {
+        OPTIONS_MSG_TYPE | OPTIONS_DHCP_DOMAIN_NAME => 1,
+        OPTIONS_CLIENT_FQDN => 3,
+        OPTIONS_CLIENT_ID => 7,
+        OPTIONS_REQUESTED_IP_ADDR
+        | OPTIONS_DHCP_SERVER_ID
+        | OPTIONS_DHCP_SUBNET_MASK
+        | OPTIONS_DHCP_ROUTER
+        | OPTIONS_DHCP_RENEWAL_TIME
+        | OPTIONS_DHCP_REBINDING_TIME
+        | OPTIONS_DHCP_IP_LEASE_TIME => 4,
+        _ => 0,
+    }
+}
+
 #[derive(Debug, Clone)]
 struct IpAddrLeaseTime {
     _length: u8,
@@ -102,9 +149,7 @@ impl DomainName {
     }
 
     pub fn value(&self) -> String {
-        str::from_utf8(&self._raw_data[0..(self._length as usize) - 1])
-            .unwrap()
-            .to_string()
+        String::from_utf8_lossy(&self._raw_data[0..(self._length as usize) - 1]).to_string()
     }
 }
 
@@ -248,9 +293,7 @@ impl Hostname {
     }
 
     pub fn value(&self) -> String {
-        str::from_utf8(&self._raw_data[0..self._length as usize])
-            .unwrap()
-            .to_string()
+        String::from_utf8_lossy(&self._raw_data[0..self._length as usize]).to_string()
     }
 }
 
@@ -281,9 +324,7 @@ impl ClientFQDN {
     }
 
     pub fn client_name(&self) -> String {
-        str::from_utf8(&self._raw_data[3..3 + (self._length - 3) as usize])
-            .unwrap()
-            .to_string()
+        String::from_utf8_lossy(&self._raw_data[3..3 + (self._length - 3) as usize]).to_string()
     }
 }
 
@@ -328,9 +369,7 @@ impl VendorClassID {
     }
 
     pub fn value(&self) -> String {
-        str::from_utf8(&self._raw_data[0..self._length as usize])
-            .unwrap()
-            .to_string()
+        String::from_utf8_lossy(&self._raw_data[0..self._length as usize]).to_string()
     }
 }
 
@@ -399,6 +438,7 @@ pub struct Dhcp<'a> {
     renewal_time: Option<RenewalTime>,
     rebinding_time: Option<RebindingTime>,
     ip_addr_lease_time: Option<IpAddrLeaseTime>,
+    error: Option<DecodeError>,
 }
 
 impl<'a> fmt::Display for Dhcp<'a> {
@@ -425,7 +465,7 @@ impl<'a> Dhcp<'a> {
     pub fn new(raw_data: &'a [u8]) -> Self {
         let mut my_self = Self {
             raw_data,
-            option_index: 240,
+            option_index: OPTIONS_OFFSET,
             magic_no: 0,
             param_req_list: None,
             vendor_class_id: None,
@@ -443,13 +483,18 @@ impl<'a> Dhcp<'a> {
             renewal_time: None,
             rebinding_time: None,
             ip_addr_lease_time: None,
+            error: None,
         };
 
-        my_self.get_options();
+        my_self.error = my_self.get_options().err();
 
         my_self
     }
 
+    pub fn error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
     pub fn op(&self) -> u8 {
         self.raw_data[0]
     }
@@ -634,33 +679,13 @@ impl<'a> Dhcp<'a> {
 
     //-------------------------------------------------------------
     fn get_name(&self, offset: usize) -> String {
-        let mut index = 0;
-        let mut current_char: u8;
+        let name = self.raw_data.get(offset..).unwrap_or_default();
+        let end = name.iter().position(|c| *c == 0x00).unwrap_or(name.len());
 
-        print_hex(self.raw_data.to_vec());
-
-        loop {
-            current_char = self.raw_data[offset + index];
-            if current_char == 0x00 {
-                break;
-            }
-
-            index += 1;
-            if (offset + index) > self.raw_data.len() {
-                break;
-            }
-        }
-
-        if index > 0 {
-            str::from_utf8(&self.raw_data[offset..offset + index])
-                .unwrap()
-                .to_string()
-        } else {
-            String::new()
-        }
+        String::from_utf8_lossy(&name[..end]).to_string()
     }
 
-    fn fetch(&mut self) -> Option<DhcpOption> {
+    fn fetch(&mut self) -> Result<Option<DhcpOption>, DecodeError> {
         let mut options = DhcpOption::default();
 
         if self.option_index + 1 < self.raw_data.len() {
@@ -670,21 +695,33 @@ impl<'a> Dhcp<'a> {
             options.length = self.raw_data[self.option_index] as usize;
 
             self.option_index += 1;
-            options.data =
-                self.raw_data[self.option_index..self.option_index + options.length].to_vec();
+            options.data = self
+                .raw_data
+                .get(self.option_index..self.option_index + options.length)
+                .ok_or(DecodeError::Truncated)?
+                .to_vec();
 
             self.option_index += options.length;
 
-            return Some(options);
+            if options.length < option_min_len(options.id) {
+                return Err(DecodeError::Invalid);
+            }
+
+            return Ok(Some(options));
         }
 
-        None
+        Ok(None)
     }
 
-    pub fn get_options(&mut self) {
-        self.magic_no = BigEndian::read_u32(&self.raw_data[0xec..0xec + 4]);
+    //--- Options decoded before an error are kept
+    pub fn get_options(&mut self) -> Result<(), DecodeError> {
+        self.magic_no = self
+            .raw_data
+            .get(0xec..0xec + 4)
+            .map(BigEndian::read_u32)
+            .ok_or(DecodeError::Truncated)?;
 
-        while let Some(options) = self.fetch() {
+        while let Some(options) = self.fetch()? {
             // println!("---> Options: {:?} magic no: {:x}", options, self.magic_no);
             match options.id {
                 OPTIONS_MSG_TYPE => {
@@ -762,6 +799,8 @@ impl<'a> Dhcp<'a> {
                 _ => break,
             }
         }
+
+        Ok(())
     }
 }
 
@@ -804,9 +843,46 @@ impl<'a> Layer for Dhcp<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct DhcpDissector;
+
+impl Dissector for DhcpDissector {
+    fn name(&self) -> &'static str {
+        "dhcp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::DHCP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[
+            Trigger::UdpPort(DHCP_SERVER_PORT),
+            Trigger::UdpPort(DHCP_CLIENT_PORT),
+        ]
+    }
+
+    fn min_len(&self) -> usize {
+        OPTIONS_OFFSET
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        DHCP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Dhcp::new(packet))
+    }
+
+    fn check(&self, packet: &[u8]) -> Result<(), DecodeError> {
+        Dhcp::new(packet).error().map_or(Ok(()), Err)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::print_hex::print_hex;
 
     #[test]
     fn dhcp_header_decode() {
@@ -902,7 +978,7 @@ mod tests {
         ];
 
         let mut dhcp = Dhcp::new(&packet);
-        dhcp.get_options();
+        dhcp.get_options().unwrap();
 
         assert_eq!(dhcp.boot_file_name(), "FGH", "DHCP server name");
 
@@ -998,7 +1074,7 @@ mod tests {
         print_hex(packet.clone());
 
         let mut dhcp = Dhcp::new(&packet);
-        dhcp.get_options();
+        dhcp.get_options().unwrap();
 
         assert_eq!(
             dhcp.msg_type.is_some(),
@@ -1058,7 +1134,7 @@ mod tests {
         print_hex(packet.clone());
 
         let mut dhcp = Dhcp::new(&packet);
-        dhcp.get_options();
+        dhcp.get_options().unwrap();
 
         assert_eq!(
             dhcp.msg_type.is_some(),
@@ -1135,7 +1211,7 @@ mod tests {
         print_hex(packet.clone());
 
         let mut dhcp = Dhcp::new(&packet);
-        dhcp.get_options();
+        dhcp.get_options().unwrap();
 
         assert_eq!(
             dhcp.msg_type.is_some(),
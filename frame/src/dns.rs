@@ -1,7 +1,8 @@
-use crate::layer::Layer;
-use crate::print_hex::print_hex;
+use crate::layer::{DecodeError, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use ::chrono::prelude::*;
 use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
 use field::ipv4_address::IPv4;
 use field::ipv6_address::IPv6;
 use field::pfield::{Field, FieldType};
@@ -21,6 +22,108 @@ pub const DNS_TYPE_RRSIG: u16 = 0x2e;
 
 pub const DNS_CLASS_IN: u16 = 1;
 
+const DNS_PORT: u16 = 53;
+const DNS_HEADER_LEN: usize = 12;
+
+const DNS_OPCODE_MAX: u16 = 6;
+const DNS_OPCODE_UNASSIGNED: u16 = 3;
+const DNS_RCODE_MAX: u16 = 10;
+const DNS_MAX_RR_COUNT: u16 = 256;
+const DNS_MAX_LABEL_LEN: usize = 63;
+const DNS_MAX_NAME_LEN: usize = 255;
+const DNS_CLASS_CH: u16 = 3;
+const DNS_CLASS_HS: u16 = 4;
+const DNS_CLASS_ANY: u16 = 255;
+const DNS_MAX_POINTER_JUMPS: usize = 64;
+
+const DNS_FIELDS: &[FieldDef] = &[
+    FieldDef::at("dns.id", field_type::INT16, 0, 2),
+    FieldDef::at("dns.opcode", field_type::INT8, 2, 2),
+    FieldDef::at("dns.answer_count", field_type::INT16, 6, 2),
+    FieldDef::at("dns.question_count", field_type::INT16, 4, 2),
+    FieldDef::new("dns.answers", field_type::FIELD_ARRAY),
+    FieldDef::new("dns.has_rrsig", field_type::BOOL),
+    FieldDef::new("dns.has_aaaa", field_type::BOOL),
+    FieldDef::new("dns.type_aaaa", field_type::FIELD_ARRAY),
+    FieldDef::new("dns.type_a", field_type::FIELD_ARRAY),
+];
+
+//--- Header sanity and a well formed first question, used to find DNS
+//--- on non standard ports
+pub fn is_dns(payload: &[u8]) -> bool {
+    if payload.len() < DNS_HEADER_LEN {
+        return false;
+    }
+
+    let flags = BigEndian::read_u16(&payload[2..4]);
+    let opcode = (flags >> 11) & 0x0f;
+    let zero = flags & 0x0040;
+    let rcode = flags & 0x000f;
+
+    if opcode > DNS_OPCODE_MAX || opcode == DNS_OPCODE_UNASSIGNED || zero != 0 {
+        return false;
+    }
+
+    if rcode > DNS_RCODE_MAX || BigEndian::read_u16(&payload[4..6]) != 1 {
+        return false;
+    }
+
+    if payload[6..12]
+        .chunks(2)
+        .any(|count| BigEndian::read_u16(count) > DNS_MAX_RR_COUNT)
+    {
+        return false;
+    }
+
+    //--- Question name, compression is not expected in the first name
+    let mut ptr = DNS_HEADER_LEN;
+    loop {
+        let Some(label_len) = payload.get(ptr).map(|len| *len as usize) else {
+            return false;
+        };
+        ptr += 1;
+
+        if label_len == 0 {
+            break;
+        }
+
+        if label_len > DNS_MAX_LABEL_LEN || ptr + label_len - DNS_HEADER_LEN > DNS_MAX_NAME_LEN {
+            return false;
+        }
+        ptr += label_len;
+    }
+
+    if payload.len() < ptr + 4 {
+        return false;
+    }
+
+    //--- Top bit is the mDNS unicast response flag
+    let qclass = BigEndian::read_u16(&payload[ptr + 2..ptr + 4]) & 0x7fff;
+    matches!(
+        qclass,
+        DNS_CLASS_IN | DNS_CLASS_CH | DNS_CLASS_HS | DNS_CLASS_ANY
+    )
+}
+
+//--- Bounds checked readers, a short record is reported as truncated
+fn read_u8(raw_data: &[u8], index: usize) -> Result<u8, DecodeError> {
+    raw_data.get(index).copied().ok_or(DecodeError::Truncated)
+}
+
+fn read_u16(raw_data: &[u8], index: usize) -> Result<u16, DecodeError> {
+    Ok(BigEndian::read_u16(read_slice(raw_data, index, 2)?))
+}
+
+fn read_u32(raw_data: &[u8], index: usize) -> Result<u32, DecodeError> {
+    Ok(BigEndian::read_u32(read_slice(raw_data, index, 4)?))
+}
+
+fn read_slice(raw_data: &[u8], index: usize, len: usize) -> Result<&[u8], DecodeError> {
+    raw_data
+        .get(index..index.saturating_add(len))
+        .ok_or(DecodeError::Truncated)
+}
+
 const INDEX_TYPE_A: u16 = 0;
 const INDEX_TYPE_AAAA: u16 = 2;
 const INDEX_TYPE_CNAME: u16 = 4;
@@ -208,64 +311,49 @@ impl fmt::Display for Answer {
 }
 
 impl<'a> Answer {
-    fn get_txt(
-        &self,
-        raw_data: &'a [u8],
-        offset: usize,
-        id: u16,
-    ) -> Result<(String, usize), String> {
+    fn get_txt(&self, raw_data: &'a [u8], offset: usize) -> Result<(String, usize), DecodeError> {
         let mut index = offset;
 
-        let txt_len = raw_data[index] as usize;
+        let txt_len = read_u8(raw_data, index)? as usize;
         index += 1;
 
-        match str::from_utf8(&raw_data[index..index + txt_len]) {
-            Ok(txt_value) => {
-                let txt = String::from(txt_value);
-                Ok((txt.clone(), index + txt.len()))
-            }
-            Err(msg) => Err(format!(
-                "Error reading TXT field: {} ID: {:x}, Length: {}:{:x}",
-                msg, id, txt_len, txt_len
-            )),
-        }
+        let txt = read_slice(raw_data, index, txt_len)?;
+
+        Ok((String::from_utf8_lossy(txt).to_string(), index + txt_len))
     }
 
-    pub fn decode(&mut self, raw_data: &'a [u8], offset: usize, id: u16) {
+    pub fn decode(&mut self, raw_data: &'a [u8], offset: usize) -> Result<(), DecodeError> {
         let mut index: usize = offset;
         let start_pos;
         let mut label_size: usize;
 
-        (self.name, label_size) = get_name(raw_data, offset, id);
+        (self.name, label_size) = get_name(raw_data, offset)?;
         index += label_size;
 
-        self.rtype = BigEndian::read_u16(&raw_data[index..index + 2]);
+        self.rtype = read_u16(raw_data, index)?;
         index += 2;
-        self.class = BigEndian::read_u16(&raw_data[index..index + 2]);
+        self.class = read_u16(raw_data, index)?;
         index += 2;
-        self.ttl = BigEndian::read_u32(&raw_data[index..index + 4]);
+        self.ttl = read_u32(raw_data, index)?;
         index += 4;
-        self.rdlength = BigEndian::read_u16(&raw_data[index..index + 2]) as usize;
+        self.rdlength = read_u16(raw_data, index)? as usize;
         index += 2;
 
         match self.rtype {
             DNS_TYPE_CNAME => {
-                (self.cname, _) = get_name(raw_data, index, id);
+                (self.cname, _) = get_name(raw_data, index)?;
                 self.asize = index + self.rdlength;
             }
             DNS_TYPE_A => {
-                self.ipv4_addr = BigEndian::read_u32(&raw_data[index..index + 4]);
+                self.ipv4_addr = read_u32(raw_data, index)?;
                 self.asize = index + 4;
             }
             DNS_TYPE_AAAA => {
-                self.ipv6_addr = BigEndian::read_u128(&raw_data[index..index + 16]);
+                self.ipv6_addr = BigEndian::read_u128(read_slice(raw_data, index, 16)?);
                 self.asize = index + 16;
             }
             DNS_TYPE_TXT => {
-                if let Ok((txt, size)) = self.get_txt(raw_data, index, id) {
-                    self.txt = txt;
-                    self.asize = size;
-                }
+                (self.txt, self.asize) = self.get_txt(raw_data, index)?;
             }
 
             DNS_TYPE_SRV => {
@@ -281,16 +369,16 @@ impl<'a> Answer {
                     srv_rec.protocol = "undefined".to_string();
                 }
 
-                srv_rec.priority = BigEndian::read_u16(&raw_data[index..index + 2]);
+                srv_rec.priority = read_u16(raw_data, index)?;
                 index += 2;
 
-                srv_rec.weight = BigEndian::read_u16(&raw_data[index..index + 2]);
+                srv_rec.weight = read_u16(raw_data, index)?;
                 index += 2;
 
-                srv_rec.port = BigEndian::read_u16(&raw_data[index..index + 2]);
+                srv_rec.port = read_u16(raw_data, index)?;
                 index += 2;
 
-                (srv_rec.target, label_size) = get_name(raw_data, index, id);
+                (srv_rec.target, label_size) = get_name(raw_data, index)?;
 
                 self.asize = index + label_size;
                 self.srv = srv_rec;
@@ -302,19 +390,20 @@ impl<'a> Answer {
                 // };
 
                 let mut dns_key = DnsKey {
-                    flags: BigEndian::read_u16(&raw_data[index..index + 2]),
+                    flags: read_u16(raw_data, index)?,
                     ..Default::default()
                 };
 
                 index += 2;
 
-                dns_key.protocol = raw_data[index];
+                dns_key.protocol = read_u8(raw_data, index)?;
                 index += 1;
 
-                dns_key.algorithm = raw_data[index];
+                dns_key.algorithm = read_u8(raw_data, index)?;
                 index += 1;
 
-                dns_key.public_key = raw_data[index..index + self.rdlength - 4].to_vec();
+                let key_len = self.rdlength.checked_sub(4).ok_or(DecodeError::Invalid)?;
+                dns_key.public_key = read_slice(raw_data, index, key_len)?.to_vec();
 
                 self.asize = index + dns_key.public_key.len();
                 self.dns_key = Some(dns_key);
@@ -325,57 +414,60 @@ impl<'a> Answer {
                 start_pos = index;
                 self.asize = index + self.rdlength;
 
-                rrsig.type_covered = BigEndian::read_u16(&raw_data[index..index + 2]);
+                rrsig.type_covered = read_u16(raw_data, index)?;
                 index += 2;
 
-                rrsig.algorithm = raw_data[index];
+                rrsig.algorithm = read_u8(raw_data, index)?;
                 index += 1;
 
-                rrsig.labels = raw_data[index];
+                rrsig.labels = read_u8(raw_data, index)?;
                 index += 1;
 
-                rrsig.orig_ttl = BigEndian::read_u32(&raw_data[index..index + 4]);
+                rrsig.orig_ttl = read_u32(raw_data, index)?;
                 index += 4;
 
-                rrsig.sig_expiration = BigEndian::read_u32(&raw_data[index..index + 4]);
+                rrsig.sig_expiration = read_u32(raw_data, index)?;
                 index += 4;
 
-                rrsig.sig_inception = BigEndian::read_u32(&raw_data[index..index + 4]);
+                rrsig.sig_inception = read_u32(raw_data, index)?;
                 index += 4;
 
-                rrsig.key_tag = BigEndian::read_u16(&raw_data[index..index + 2]);
+                rrsig.key_tag = read_u16(raw_data, index)?;
                 index += 2;
 
-                (rrsig.signer_name, label_size) = get_name(raw_data, index, id);
+                (rrsig.signer_name, label_size) = get_name(raw_data, index)?;
                 index += label_size;
 
-                let sig_len = index - start_pos;
-                rrsig.signature = raw_data[index..index + (self.rdlength - sig_len)].to_vec();
+                let sig_len = self
+                    .rdlength
+                    .checked_sub(index - start_pos)
+                    .ok_or(DecodeError::Invalid)?;
+                rrsig.signature = read_slice(raw_data, index, sig_len)?.to_vec();
 
                 self.rrsig = Some(rrsig);
             }
             DNS_TYPE_SOA => {
                 let mut soa = SOARecord::default();
 
-                (soa.name, label_size) = get_name(raw_data, index, id);
+                (soa.name, label_size) = get_name(raw_data, index)?;
                 index += label_size;
 
-                (soa.auth_mailbox, label_size) = get_name(raw_data, index, id);
+                (soa.auth_mailbox, label_size) = get_name(raw_data, index)?;
                 index += label_size;
 
-                soa.serial_no = BigEndian::read_u32(&raw_data[index..index + 4]);
+                soa.serial_no = read_u32(raw_data, index)?;
                 index += 4;
 
-                soa.refresh_interval = BigEndian::read_u32(&raw_data[index..index + 4]);
+                soa.refresh_interval = read_u32(raw_data, index)?;
                 index += 4;
 
-                soa.retry_interval = BigEndian::read_u32(&raw_data[index..index + 4]);
+                soa.retry_interval = read_u32(raw_data, index)?;
                 index += 4;
 
-                soa.expire_limit = BigEndian::read_u32(&raw_data[index..index + 4]);
+                soa.expire_limit = read_u32(raw_data, index)?;
                 index += 4;
 
-                soa.min_ttl = BigEndian::read_u32(&raw_data[index..index + 4]);
+                soa.min_ttl = read_u32(raw_data, index)?;
                 index += 4;
 
                 self.asize = index;
@@ -384,6 +476,8 @@ impl<'a> Answer {
 
             _ => {}
         }
+
+        Ok(())
     }
 }
 
@@ -409,21 +503,23 @@ impl fmt::Display for Query {
 }
 
 impl<'a> Query {
-    pub fn decode(&mut self, raw_data: &'a [u8], offset: usize, id: u16) {
+    pub fn decode(&mut self, raw_data: &'a [u8], offset: usize) -> Result<(), DecodeError> {
         let label_size: usize;
 
-        (self.name, label_size) = get_name(raw_data, offset, id);
+        (self.name, label_size) = get_name(raw_data, offset)?;
 
         let mut index = offset + label_size;
-        self.rtype = BigEndian::read_u16(&raw_data[index..index + 2]);
+        self.rtype = read_u16(raw_data, index)?;
         index += 2;
-        self.class = BigEndian::read_u16(&raw_data[index..index + 2]);
+        self.class = read_u16(raw_data, index)?;
 
         if self.name == "<Root>" {
             self.qsize = 1 + 2 + 2;
         } else {
             self.qsize = self.name.len() + 2 + 2 + 2;
         }
+
+        Ok(())
     }
 }
 
@@ -434,6 +530,7 @@ pub struct Dns<'a> {
     query_list: Vec<Query>,
     answer_list: Vec<Answer>,
     type_index: u16,
+    error: Option<DecodeError>,
 }
 
 impl<'a> Dns<'a> {
@@ -444,6 +541,7 @@ impl<'a> Dns<'a> {
             query_list: Vec::new(),
             answer_list: Vec::new(),
             type_index: 0,
+            error: None,
         };
 
         my_self.decode();
@@ -483,33 +581,49 @@ impl<'a> Dns<'a> {
             _ => false,
         }
     }
+    //--- Records decoded before an error are kept
     pub fn decode(&mut self) {
-        self.offset = 12;
+        self.offset = DNS_HEADER_LEN;
+
+        if self.raw_packet.len() < DNS_HEADER_LEN {
+            self.error = Some(DecodeError::Truncated);
+            return;
+        }
 
-        self.process_queries();
-        self.process_answers();
+        self.error = self
+            .process_queries()
+            .and_then(|_| self.process_answers())
+            .err();
     }
 
-    fn process_queries(&mut self) {
+    pub fn error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
+    fn process_queries(&mut self) -> Result<(), DecodeError> {
         for _ in 0..self.question_count() {
             let mut query = Query::default();
-            query.decode(self.raw_packet, self.offset, self.id());
+            query.decode(self.raw_packet, self.offset)?;
             self.offset += query.qsize;
 
             self.set_index(query.rtype);
             self.query_list.push(query);
         }
+
+        Ok(())
     }
 
-    fn process_answers(&mut self) {
+    fn process_answers(&mut self) -> Result<(), DecodeError> {
         for _ in 0..self.answer_count() {
             let mut answer = Answer::default();
-            answer.decode(self.raw_packet, self.offset, self.id());
+            answer.decode(self.raw_packet, self.offset)?;
 
             self.offset = answer.asize;
             self.set_index(answer.rtype);
             self.answer_list.push(answer);
         }
+
+        Ok(())
     }
 
     pub fn id(&self) -> u16 {
@@ -658,7 +772,44 @@ impl<'a> Layer for Dns<'a> {
     }
 }
 
-fn get_name(raw_packet: &[u8], start_pos: usize, id: u16) -> (String, usize) {
+#[derive(Debug, Clone, Copy)]
+pub struct DnsDissector;
+
+impl Dissector for DnsDissector {
+    fn name(&self) -> &'static str {
+        "dns"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::DNS
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::UdpPort(DNS_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        DNS_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        DNS_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Dns::new(packet))
+    }
+
+    fn heuristic(&self, parent: &LayerIndex, payload: &[u8]) -> bool {
+        *parent == LayerIndex::UDP && is_dns(payload)
+    }
+
+    fn check(&self, packet: &[u8]) -> Result<(), DecodeError> {
+        Dns::new(packet).error().map_or(Ok(()), Err)
+    }
+}
+
+fn get_name(raw_packet: &[u8], start_pos: usize) -> Result<(String, usize), DecodeError> {
     let mut offset = start_pos;
     let mut count: usize;
     let mut temp_name: String;
@@ -666,17 +817,11 @@ fn get_name(raw_packet: &[u8], start_pos: usize, id: u16) -> (String, usize) {
     let mut label_ptr: u16;
     let mut label_offset = 0;
     let mut ptr = false;
+    let mut jumps: usize = 0;
 
     temp_name = String::new();
     loop {
-        assert!(
-            offset < raw_packet.len(),
-            "Offset: {}, Packet len: {}",
-            offset,
-            raw_packet.len(),
-        );
-
-        count = raw_packet[offset] as usize;
+        count = read_u8(raw_packet, offset)? as usize;
 
         if count == 0 {
             if offset == start_pos {
@@ -684,49 +829,43 @@ fn get_name(raw_packet: &[u8], start_pos: usize, id: u16) -> (String, usize) {
             }
             break;
         }
+
+        //--- Compression pointers may loop on a hostile packet
         while count & 0xc0 == 0xc0 {
             ptr = true;
+            jumps += 1;
+            if jumps > DNS_MAX_POINTER_JUMPS {
+                return Err(DecodeError::Invalid);
+            }
 
-            label_ptr = BigEndian::read_u16(&raw_packet[offset..offset + 2]);
+            label_ptr = read_u16(raw_packet, offset)?;
             offset = (label_ptr & 0x3fff) as usize;
-            count = raw_packet[offset] as usize;
+            count = read_u8(raw_packet, offset)? as usize;
         }
 
-        offset += 1;
-
-        if (offset + count) > raw_packet.len() {
-            eprintln!("==========================================================");
-            eprintln!(
-                "Error: ID: {:x} offset: {:x} count: {:x}, offset + count: {}, byte len: {}",
-                id,
-                offset,
-                count,
-                offset + count,
-                raw_packet.len()
-            );
-            print_hex(raw_packet.to_vec());
-            eprintln!("==========================================================");
+        if count == 0 {
             break;
         }
 
+        offset += 1;
+
         if !temp_name.is_empty() {
             seperator = "."
         }
 
-        match str::from_utf8(&raw_packet[offset..offset + count]) {
-            Ok(name) => {
-                temp_name = format!("{}{}{}", temp_name, seperator, name);
-                if !ptr {
-                    label_offset = temp_name.len() + 1;
-                }
-            }
-            Err(msg) => {
-                eprintln!(
-                    "Error reading label: {}, ID: {:x}, offset: {:x}, count: {}, value: {:x}",
-                    msg, id, offset, count, &raw_packet[offset]
-                );
-                print_hex(raw_packet[offset..offset + count].to_vec());
-            }
+        //--- A label running past the packet only ends the name, the rest
+        //--- of the record may still be readable
+        let Ok(label) = read_slice(raw_packet, offset, count) else {
+            break;
+        };
+        temp_name = format!(
+            "{}{}{}",
+            temp_name,
+            seperator,
+            String::from_utf8_lossy(label)
+        );
+        if !ptr {
+            label_offset = temp_name.len() + 1;
         }
 
         offset += count;
@@ -738,7 +877,7 @@ fn get_name(raw_packet: &[u8], start_pos: usize, id: u16) -> (String, usize) {
         label_offset += 1;
     }
 
-    (temp_name, label_offset)
+    Ok((temp_name, label_offset))
 }
 
 //------------------------------------------------------
@@ -747,6 +886,7 @@ fn get_name(raw_packet: &[u8], start_pos: usize, id: u16) -> (String, usize) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::print_hex::print_hex;
 
     #[test]
     fn dns_id() {
@@ -760,6 +900,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.id(), 0x117e, "DNS ID");
@@ -779,6 +920,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.flags(), 0x8180, "DNS Flags");
@@ -796,6 +938,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.question_count(), 1, "DNS Question");
@@ -813,6 +956,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.is_query(), false, "DNS is query");
@@ -830,6 +974,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.is_response(), true, "DNS is response");
@@ -847,6 +992,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.is_query(), true, "DNS is query");
@@ -864,6 +1010,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.is_response(), false, "DNS is response");
@@ -881,6 +1028,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.opcode(), 0, "DNS is query");
@@ -898,6 +1046,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.is_authoritative(), true, "DNS is authoritative");
@@ -915,6 +1064,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.recursion_desired(), true, "DNS recursion desired");
@@ -932,6 +1082,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(
@@ -953,6 +1104,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.answer_authenticated(), false, "DNS is authenticated");
@@ -970,6 +1122,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.non_authenticated(), false, "DNS is non authenticated");
@@ -987,6 +1140,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.reply_code(), 0, "DNS reply code = 0");
@@ -1004,6 +1158,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.question_count(), 1, "DNS question count");
@@ -1022,6 +1177,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.answer_count(), 1, "DNS answer count");
@@ -1040,6 +1196,7 @@ mod tests {
             query_list: Vec::new(),
             offset: 0,
             type_index: 0,
+            error: None,
         };
 
         assert_eq!(pkt.answer_count(), 1, "DNS authority");
@@ -1242,7 +1399,7 @@ mod tests {
         println!("------------------------------------------");
         print_hex(packet.clone());
         let mut answer = Answer::default();
-        answer.decode(&packet, 0, 0xff);
+        answer.decode(&packet, 0).unwrap();
 
         assert_eq!(answer.rrsig.is_some(), true, "DNS rrsig present");
         assert_eq!(
@@ -1328,7 +1485,7 @@ mod tests {
         println!("------------------------------------------");
         print_hex(packet.clone());
         let mut answer = Answer::default();
-        answer.decode(&packet, 0x22, 0xeeee);
+        answer.decode(&packet, 0x22).unwrap();
 
         assert_eq!(answer.soa.is_some(), true, "DNS SOA answer");
         println!(
@@ -1416,7 +1573,7 @@ mod tests {
         println!("------------------------------------------");
         print_hex(packet.clone());
         let mut answer = Answer::default();
-        answer.decode(&packet, 0, 0xff);
+        answer.decode(&packet, 0).unwrap();
 
         println!("AAAA : {}", answer);
         assert_eq!(
@@ -1442,7 +1599,7 @@ mod tests {
         println!("------------------------------------------");
         print_hex(packet.clone());
         let mut query = Query::default();
-        query.decode(&packet, 0, 0xfe);
+        query.decode(&packet, 0).unwrap();
 
         assert_eq!(query.rtype, DNS_TYPE_TXT, "DNS ipv6 address");
 
@@ -1621,4 +1778,26 @@ mod tests {
 
         // println!("DNS: {:?}", dns);
     }
+
+    #[test]
+    fn dns_heuristic_query() {
+        let packet: Vec<u8> = vec![
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x65,
+            0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00,
+            0x01,
+        ];
+
+        assert!(is_dns(&packet), "DNS query");
+        assert!(!is_dns(&packet[..20]), "Truncated question");
+    }
+
+    #[test]
+    fn dns_heuristic_reject_ntp() {
+        let packet: Vec<u8> = vec![
+            0x23, 0x02, 0x06, 0xe8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+
+        assert!(!is_dns(&packet), "NTP client request");
+    }
 }
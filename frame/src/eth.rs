@@ -1,11 +1,25 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use crate::packet::LINKTYPE_ETHERNET;
 use crate::packet_display::PacketDisplay;
+use field::field_type;
 use field::mac_address::MacAddr;
 use field::pfield::{Field, FieldType};
 
 use byteorder::{BigEndian, ByteOrder};
 
 const ETHER_8021Q: u16 = 0x8100;
+const ETHER_HEADER_LEN: usize = 14;
+const ETHER_VLAN_HEADER_LEN: usize = 18;
+
+const ETH_FIELDS: &[FieldDef] = &[
+    FieldDef::at("eth.src", field_type::MACADDR, 6, 6),
+    FieldDef::at("eth.dst", field_type::MACADDR, 0, 6),
+    FieldDef::new("eth.type", field_type::INT16),
+    FieldDef::new("eth.vlan", field_type::INT16),
+    FieldDef::new("eth.packet", field_type::BYTE_ARRAY),
+];
 
 #[derive(Debug, Clone)]
 pub struct EtherFrame<'a> {
@@ -33,20 +47,37 @@ impl<'a> EtherFrame<'a> {
         if self.header() != ETHER_8021Q {
             self.header()
         } else {
-            BigEndian::read_u16(&self.raw_packet[16..18])
+            self.raw_packet
+                .get(16..18)
+                .map(BigEndian::read_u16)
+                .unwrap_or_default()
+        }
+    }
+
+    pub fn header_len(&self) -> usize {
+        if self.header() == ETHER_8021Q {
+            ETHER_VLAN_HEADER_LEN
+        } else {
+            ETHER_HEADER_LEN
         }
     }
 
     pub fn vlan_id(&self) -> u16 {
         if self.header() == ETHER_8021Q {
-            BigEndian::read_u16(&self.raw_packet[14..16])
+            self.raw_packet
+                .get(14..16)
+                .map(BigEndian::read_u16)
+                .unwrap_or_default()
         } else {
             1
         }
     }
 
     pub fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
-        self.raw_packet[offset..offset + len].to_vec()
+        self.raw_packet
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+            .to_vec()
     }
 }
 
@@ -68,6 +99,60 @@ impl<'a> Layer for EtherFrame<'a> {
     fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
         None
     }
+
+    fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
+        EtherFrame::payload_range(self, offset, len)
+    }
+
+    //--- The 802.1Q tag move the ethertype after the VLAN id
+    fn field_range(&self, field_name: &str) -> Option<(usize, usize)> {
+        let tagged = self.header() == ETHER_8021Q;
+
+        match field_name {
+            "eth.type" if tagged => Some((16, 2)),
+            "eth.type" => Some((12, 2)),
+            "eth.vlan" if tagged => Some((14, 2)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EthDissector;
+
+impl Dissector for EthDissector {
+    fn name(&self) -> &'static str {
+        "eth"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::ETH
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::LinkType(LINKTYPE_ETHERNET)]
+    }
+
+    fn min_len(&self) -> usize {
+        ETHER_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        ETH_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(EtherFrame::new(packet))
+    }
+
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let eth = EtherFrame::new(packet);
+
+        Some(Dispatch::new(
+            eth.header_len(),
+            vec![Trigger::EtherType(eth.ethertype())],
+        ))
+    }
 }
 
 impl<'a> PacketDisplay for EtherFrame<'a> {
@@ -82,7 +167,13 @@ impl<'a> PacketDisplay for EtherFrame<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "Eth detail".to_string()
+        LayerNode::of(
+            EthDissector.name(),
+            EthDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
     }
 }
 
@@ -0,0 +1,388 @@
+use crate::layer::FieldDef;
+use crate::layer_index::LayerIndex;
+use crate::packet::Packet;
+use crate::packet_id::PacketId;
+use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
+use field::pfield::{Field, FieldType};
+use std::collections::HashMap;
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+//--- Bytes of a flow record in the {file_id}.flow files
+pub const FLOW_RECORD_LEN: usize = 76;
+
+//--- Source of a query on the flow tables: select ... from flows
+pub const FLOWS: &str = "flows";
+
+//--- Src and dst are the endpoints of the first packet of the flow,
+//--- the forward direction. Timestamps are the first and last packet
+pub const FLOW_FIELDS: &[FieldDef] = &[
+    FieldDef::new("flow.src", field_type::IPV4),
+    FieldDef::new("flow.dst", field_type::IPV4),
+    FieldDef::new("flow.sport", field_type::INT16),
+    FieldDef::new("flow.dport", field_type::INT16),
+    FieldDef::new("flow.protocol", field_type::INT8),
+    FieldDef::new("flow.vlan", field_type::INT16),
+    FieldDef::new("flow.start", field_type::INT32),
+    FieldDef::new("flow.end", field_type::INT32),
+    FieldDef::new("flow.start_ns", field_type::INT64),
+    FieldDef::new("flow.end_ns", field_type::INT64),
+    FieldDef::new("flow.duration_ms", field_type::INT64),
+    FieldDef::new("flow.packets", field_type::INT64),
+    FieldDef::new("flow.bytes", field_type::INT64),
+    FieldDef::new("flow.packets_fwd", field_type::INT64),
+    FieldDef::new("flow.packets_rev", field_type::INT64),
+    FieldDef::new("flow.bytes_fwd", field_type::INT64),
+    FieldDef::new("flow.bytes_rev", field_type::INT64),
+    FieldDef::new("flow.tcp_flags", field_type::INT8),
+    FieldDef::new("flow.first_id", field_type::INT64),
+    FieldDef::new("flow.first_packet", field_type::STRING),
+    FieldDef::new("flow.segments", field_type::INT32),
+];
+
+pub fn is_flow_field(field: &str) -> bool {
+    FLOW_FIELDS.iter().any(|def| def.name == field)
+}
+
+//--- 5-tuple and VLAN of a packet as it was sent
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FlowKey {
+    pub src: u32,
+    pub dst: u32,
+    pub sport: u16,
+    pub dport: u16,
+    pub protocol: u8,
+    pub vlan: u16,
+}
+
+impl FlowKey {
+    //--- IPv4 packets only, ports are 0 for protocols without them
+    pub fn of(pkt: &Packet) -> Option<Self> {
+        let src = pkt.get_field("ip.src")?.to_u32();
+        let dst = pkt.get_field("ip.dst")?.to_u32();
+        let protocol = pkt.get_field("ip.protocol").map_or(0, |p| p.to_u64() as u8);
+        let (sport, dport) = ["tcp", "udp"]
+            .iter()
+            .find_map(|proto| {
+                let sport = pkt.get_field(&format!("{}.sport", proto))?;
+                let dport = pkt.get_field(&format!("{}.dport", proto))?;
+                Some((sport.to_u64() as u16, dport.to_u64() as u16))
+            })
+            .unwrap_or_default();
+        let vlan = pkt.get_field("eth.vlan").map_or(0, |v| v.to_u64() as u16);
+
+        Some(Self {
+            src,
+            dst,
+            sport,
+            dport,
+            protocol,
+            vlan,
+        })
+    }
+
+    pub fn reversed(&self) -> Self {
+        Self {
+            src: self.dst,
+            dst: self.src,
+            sport: self.dport,
+            dport: self.sport,
+            ..*self
+        }
+    }
+
+    //--- Same key for both directions of a conversation
+    pub fn canonical(&self) -> Self {
+        if (self.src, self.sport) <= (self.dst, self.dport) {
+            *self
+        } else {
+            self.reversed()
+        }
+    }
+}
+
+//------------------------------------------------------------------
+//--- Conversation record of a flow table. Counts are per direction,
+//--- index 0 forward and 1 reverse, bytes are the wire length of the
+//--- packets. Segments is 1 in a flow table and the number of segments
+//--- merged once flows are stitched
+//------------------------------------------------------------------
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FlowRecord {
+    pub key: FlowKey,
+    pub start_ns: u64,
+    pub end_ns: u64,
+    pub packets: [u64; 2],
+    pub bytes: [u64; 2],
+    pub tcp_flags: u8,
+    pub first_packet: PacketId,
+    pub segments: u32,
+}
+
+impl FlowRecord {
+    fn new(key: FlowKey, pkt: &Packet) -> Self {
+        Self {
+            key,
+            start_ns: pkt.timestamp_ns(),
+            end_ns: pkt.timestamp_ns(),
+            first_packet: pkt.packet_id(),
+            segments: 1,
+            ..Default::default()
+        }
+    }
+
+    fn direction(&self, key: &FlowKey) -> usize {
+        if *key == self.key { 0 } else { 1 }
+    }
+
+    fn add(&mut self, key: &FlowKey, pkt: &Packet) {
+        let direction = self.direction(key);
+        let ts = pkt.timestamp_ns();
+
+        self.start_ns = self.start_ns.min(ts);
+        self.end_ns = self.end_ns.max(ts);
+        self.packets[direction] += 1;
+        self.bytes[direction] += pkt
+            .get_field("frame.origlen")
+            .map_or(pkt.raw_bytes().len() as u64, |len| len.to_u64());
+        if let Some(flags) = pkt
+            .get_layer_bytes(LayerIndex::TCP)
+            .and_then(|tcp| tcp.get(13))
+        {
+            self.tcp_flags |= flags;
+        }
+    }
+
+    //--- Record of the same conversation from a later segment
+    pub fn merge(&mut self, other: &FlowRecord) {
+        let direction = self.direction(&other.key);
+        if other.start_ns < self.start_ns {
+            self.first_packet = other.first_packet;
+        }
+
+        self.start_ns = self.start_ns.min(other.start_ns);
+        self.end_ns = self.end_ns.max(other.end_ns);
+        self.packets[direction] += other.packets[0];
+        self.packets[1 - direction] += other.packets[1];
+        self.bytes[direction] += other.bytes[0];
+        self.bytes[1 - direction] += other.bytes[1];
+        self.tcp_flags |= other.tcp_flags;
+        self.segments += other.segments;
+    }
+
+    pub fn get_field(&self, field: &str) -> Option<Field> {
+        let value = match field {
+            "flow.src" => FieldType::Ipv4(self.key.src, 32),
+            "flow.dst" => FieldType::Ipv4(self.key.dst, 32),
+            "flow.sport" => FieldType::Int16(self.key.sport),
+            "flow.dport" => FieldType::Int16(self.key.dport),
+            "flow.protocol" => FieldType::Int8(self.key.protocol),
+            "flow.vlan" => FieldType::Int16(self.key.vlan),
+            "flow.start" => FieldType::Int32((self.start_ns / NANOS_PER_SEC) as u32),
+            "flow.end" => FieldType::Int32((self.end_ns / NANOS_PER_SEC) as u32),
+            "flow.start_ns" => FieldType::Int64(self.start_ns),
+            "flow.end_ns" => FieldType::Int64(self.end_ns),
+            "flow.duration_ms" => FieldType::Int64((self.end_ns - self.start_ns) / NANOS_PER_MILLI),
+            "flow.packets" => FieldType::Int64(self.packets[0] + self.packets[1]),
+            "flow.bytes" => FieldType::Int64(self.bytes[0] + self.bytes[1]),
+            "flow.packets_fwd" => FieldType::Int64(self.packets[0]),
+            "flow.packets_rev" => FieldType::Int64(self.packets[1]),
+            "flow.bytes_fwd" => FieldType::Int64(self.bytes[0]),
+            "flow.bytes_rev" => FieldType::Int64(self.bytes[1]),
+            "flow.tcp_flags" => FieldType::Int8(self.tcp_flags),
+            "flow.first_id" => FieldType::Int64(self.first_packet.to_u64().unwrap_or_default()),
+            "flow.first_packet" => FieldType::String(self.first_packet.to_string()),
+            "flow.segments" => FieldType::Int32(self.segments),
+            _ => return None,
+        };
+
+        Some(Field::set_field(value, field))
+    }
+
+    pub fn to_bytes(&self) -> [u8; FLOW_RECORD_LEN] {
+        let mut record = [0u8; FLOW_RECORD_LEN];
+
+        BigEndian::write_u32(&mut record[0..4], self.key.src);
+        BigEndian::write_u32(&mut record[4..8], self.key.dst);
+        BigEndian::write_u16(&mut record[8..10], self.key.sport);
+        BigEndian::write_u16(&mut record[10..12], self.key.dport);
+        record[12] = self.key.protocol;
+        record[13] = self.tcp_flags;
+        BigEndian::write_u16(&mut record[14..16], self.key.vlan);
+        BigEndian::write_u64(&mut record[16..24], self.start_ns);
+        BigEndian::write_u64(&mut record[24..32], self.end_ns);
+        BigEndian::write_u64(&mut record[32..40], self.packets[0]);
+        BigEndian::write_u64(&mut record[40..48], self.packets[1]);
+        BigEndian::write_u64(&mut record[48..56], self.bytes[0]);
+        BigEndian::write_u64(&mut record[56..64], self.bytes[1]);
+        BigEndian::write_u32(&mut record[64..68], self.first_packet.file_id);
+        BigEndian::write_u64(&mut record[68..76], self.first_packet.offset);
+
+        record
+    }
+
+    pub fn from_bytes(record: &[u8; FLOW_RECORD_LEN]) -> Self {
+        Self {
+            key: FlowKey {
+                src: BigEndian::read_u32(&record[0..4]),
+                dst: BigEndian::read_u32(&record[4..8]),
+                sport: BigEndian::read_u16(&record[8..10]),
+                dport: BigEndian::read_u16(&record[10..12]),
+                protocol: record[12],
+                vlan: BigEndian::read_u16(&record[14..16]),
+            },
+            tcp_flags: record[13],
+            start_ns: BigEndian::read_u64(&record[16..24]),
+            end_ns: BigEndian::read_u64(&record[24..32]),
+            packets: [
+                BigEndian::read_u64(&record[32..40]),
+                BigEndian::read_u64(&record[40..48]),
+            ],
+            bytes: [
+                BigEndian::read_u64(&record[48..56]),
+                BigEndian::read_u64(&record[56..64]),
+            ],
+            first_packet: PacketId::new(
+                BigEndian::read_u32(&record[64..68]),
+                BigEndian::read_u64(&record[68..76]),
+            ),
+            segments: 1,
+        }
+    }
+}
+
+//--- Flows of one segment, a conversation is one record whatever the
+//--- direction of its packets
+#[derive(Debug, Default)]
+pub struct FlowTable {
+    flows: HashMap<FlowKey, FlowRecord>,
+}
+
+impl FlowTable {
+    pub fn add(&mut self, pkt: &Packet) {
+        let Some(key) = FlowKey::of(pkt) else {
+            return;
+        };
+
+        self.flows
+            .entry(key.canonical())
+            .or_insert_with(|| FlowRecord::new(key, pkt))
+            .add(&key, pkt);
+    }
+
+    pub fn len(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flows.is_empty()
+    }
+
+    //--- In the order of their first packet
+    pub fn records(&self) -> Vec<FlowRecord> {
+        let mut records: Vec<FlowRecord> = self.flows.values().cloned().collect();
+        records.sort_by_key(|flow| (flow.start_ns, flow.first_packet));
+
+        records
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(key: FlowKey, start_ns: u64, end_ns: u64, packets: [u64; 2]) -> FlowRecord {
+        FlowRecord {
+            key,
+            start_ns,
+            end_ns,
+            packets,
+            bytes: [packets[0] * 100, packets[1] * 60],
+            tcp_flags: 0x02,
+            first_packet: PacketId::new(1, start_ns),
+            segments: 1,
+        }
+    }
+
+    #[test]
+    fn flow_key_direction() {
+        let key = FlowKey {
+            src: 0x0a00_0002,
+            dst: 0x0a00_0001,
+            sport: 51_000,
+            dport: 443,
+            protocol: 6,
+            vlan: 10,
+        };
+
+        assert_eq!(
+            key.canonical(),
+            key.reversed().canonical(),
+            "Both directions"
+        );
+        assert_eq!(key.reversed().reversed(), key, "Reversed twice");
+        assert_eq!(key.canonical().vlan, 10, "VLAN kept");
+    }
+
+    #[test]
+    fn flow_record_merge() {
+        let key = FlowKey {
+            src: 0x0a00_0001,
+            dst: 0x0a00_0002,
+            sport: 51_000,
+            dport: 443,
+            protocol: 6,
+            vlan: 0,
+        };
+        let mut first = flow(key, 100, 200, [3, 2]);
+        let mut later = flow(key.reversed(), 300, 400, [4, 1]);
+        later.tcp_flags = 0x11;
+
+        first.merge(&later);
+        assert_eq!(first.key, key, "Forward direction kept");
+        assert_eq!((first.start_ns, first.end_ns), (100, 400), "Time range");
+        assert_eq!(first.packets, [4, 6], "Reverse record counts swapped");
+        assert_eq!(first.bytes, [300 + 60, 120 + 400], "Bytes swapped");
+        assert_eq!(first.tcp_flags, 0x13, "Flags union");
+        assert_eq!(first.first_packet, PacketId::new(1, 100), "First packet");
+        assert_eq!(first.segments, 2, "Segments stitched");
+    }
+
+    #[test]
+    fn flow_record_bytes() {
+        let key = FlowKey {
+            src: 0x0a00_0001,
+            dst: 0x0a00_0002,
+            sport: 53,
+            dport: 40_000,
+            protocol: 17,
+            vlan: 4094,
+        };
+        let mut record = flow(
+            key,
+            1_700_000_000_000_000_000,
+            1_700_000_001_500_000_000,
+            [2, 2],
+        );
+        record.first_packet = PacketId::new(7, 6_000_000_000);
+
+        assert_eq!(
+            FlowRecord::from_bytes(&record.to_bytes()),
+            record,
+            "Round trip"
+        );
+        assert_eq!(
+            record.get_field("flow.duration_ms").unwrap().to_u64(),
+            1_500,
+            "Duration"
+        );
+        assert_eq!(
+            record.get_field("flow.bytes").unwrap().to_u64(),
+            320,
+            "Bytes both ways"
+        );
+        assert!(record.get_field("flow.unknown").is_none(), "Unknown field");
+    }
+}
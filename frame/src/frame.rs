@@ -1,18 +1,46 @@
-use crate::layer::Layer;
+use crate::layer::{FieldDef, Layer};
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_MICRO: u64 = 1_000;
+
+//--- Pcap record header fields, file_id, pkt_ptr, id, packet_id, detection, the
+//--- malformed flags and the pcapng interface and comment are answered by
+//--- Packet. frame.offset is the sub-second part as stored, in micro or
+//--- nanoseconds depending on the file magic
+pub const FRAME_FIELDS: &[FieldDef] = &[
+    FieldDef::new("frame.timestamp", field_type::INT32),
+    FieldDef::new("frame.timestamp_ns", field_type::INT64),
+    FieldDef::new("frame.offset", field_type::INT32),
+    FieldDef::new("frame.origlen", field_type::INT32),
+    FieldDef::new("frame.inclen", field_type::INT32),
+    FieldDef::new("frame.file_id", field_type::INT32),
+    FieldDef::new("frame.pkt_ptr", field_type::INT64),
+    FieldDef::new("frame.id", field_type::INT64),
+    FieldDef::new("frame.packet_id", field_type::STRING),
+    FieldDef::new("frame.detection", field_type::STRING),
+    FieldDef::new("frame.malformed", field_type::BOOL),
+    FieldDef::new("frame.error_layer", field_type::STRING),
+    FieldDef::new("frame.interface_id", field_type::INT32),
+    FieldDef::new("frame.interface_name", field_type::STRING),
+    FieldDef::new("frame.comment", field_type::STRING),
+];
+
 #[derive(Debug, Clone, Default)]
 pub struct Frame<'a> {
     raw_packet: &'a [u8],
     little_endian: bool,
+    nanosecond: bool,
 }
 
 impl<'a> Frame<'a> {
-    pub fn new(packet: &'a [u8], little_endian: bool) -> Self {
+    pub fn new(packet: &'a [u8], little_endian: bool, nanosecond: bool) -> Self {
         Self {
             raw_packet: packet,
             little_endian,
+            nanosecond,
         }
     }
 
@@ -30,6 +58,17 @@ impl<'a> Frame<'a> {
             BigEndian::read_u32(&self.raw_packet[4..8])
         }
     }
+    //--- Nanoseconds since the epoch whatever the file resolution
+    pub fn timestamp_ns(&self) -> u64 {
+        let fraction = if self.nanosecond {
+            self.ts_offset() as u64
+        } else {
+            self.ts_offset() as u64 * NANOS_PER_MICRO
+        };
+
+        self.timestamp() as u64 * NANOS_PER_SEC + fraction
+    }
+
     pub fn inc_len(&self) -> u32 {
         if self.little_endian {
             LittleEndian::read_u32(&self.raw_packet[8..12])
@@ -54,6 +93,10 @@ impl<'a> Layer for Frame<'a> {
     fn get_field(&self, field: &str) -> Option<Field> {
         match field {
             "frame.timestamp" => Some(Field::set_field(FieldType::Int32(self.timestamp()), field)),
+            "frame.timestamp_ns" => Some(Field::set_field(
+                FieldType::Int64(self.timestamp_ns()),
+                field,
+            )),
             "frame.offset" => Some(Field::set_field(FieldType::Int32(self.ts_offset()), field)),
             "frame.inclen" => Some(Field::set_field(FieldType::Int32(self.inc_len()), field)),
             "frame.origlen" => Some(Field::set_field(FieldType::Int32(self.orig_len()), field)),
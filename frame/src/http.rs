@@ -0,0 +1,215 @@
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use field::field_type;
+use field::pfield::{Field, FieldType};
+
+const HTTP_PORT: u16 = 80;
+const MAX_LINE_LEN: usize = 2048;
+
+const METHODS: [&str; 9] = [
+    "GET", "POST", "PUT", "HEAD", "DELETE", "OPTIONS", "PATCH", "CONNECT", "TRACE",
+];
+const VERSION_PREFIX: &str = "HTTP/1.";
+
+const HTTP_FIELDS: &[FieldDef] = &[
+    FieldDef::new("http.method", field_type::STRING),
+    FieldDef::new("http.uri", field_type::STRING),
+    FieldDef::new("http.version", field_type::STRING),
+    FieldDef::new("http.status", field_type::INT16),
+];
+
+//--- First line of the payload, None when no CRLF in the first MAX_LINE_LEN bytes
+fn first_line(payload: &[u8]) -> Option<&str> {
+    let window = &payload[..payload.len().min(MAX_LINE_LEN)];
+    let end = window.windows(2).position(|w| w == b"\r\n")?;
+
+    std::str::from_utf8(&window[..end]).ok()
+}
+
+//--- Request line: METHOD SP URI SP HTTP/1.x
+//--- Status line:  HTTP/1.x SP 3DIGIT SP reason
+fn parse_line(line: &str) -> Option<HttpLine<'_>> {
+    let mut parts = line.splitn(3, ' ');
+    let first = parts.next()?;
+    let second = parts.next()?;
+
+    if first.starts_with(VERSION_PREFIX) {
+        let status = second
+            .parse::<u16>()
+            .ok()
+            .filter(|s| (100..600).contains(s))?;
+
+        Some(HttpLine::Status {
+            version: first,
+            status,
+        })
+    } else if METHODS.contains(&first) {
+        let version = parts.next()?;
+        if !version.starts_with(VERSION_PREFIX) {
+            return None;
+        }
+
+        Some(HttpLine::Request {
+            method: first,
+            uri: second,
+            version,
+        })
+    } else {
+        None
+    }
+}
+
+pub fn is_http(payload: &[u8]) -> bool {
+    first_line(payload).and_then(parse_line).is_some()
+}
+
+#[derive(Debug, Clone)]
+enum HttpLine<'a> {
+    Request {
+        method: &'a str,
+        uri: &'a str,
+        version: &'a str,
+    },
+    Status {
+        version: &'a str,
+        status: u16,
+    },
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Http<'a> {
+    raw_packet: &'a [u8],
+}
+
+impl<'a> Http<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self { raw_packet: packet }
+    }
+
+    fn line(&self) -> Option<HttpLine<'a>> {
+        first_line(self.raw_packet).and_then(parse_line)
+    }
+
+    pub fn method(&self) -> Option<String> {
+        match self.line()? {
+            HttpLine::Request { method, .. } => Some(method.to_string()),
+            HttpLine::Status { .. } => None,
+        }
+    }
+
+    pub fn uri(&self) -> Option<String> {
+        match self.line()? {
+            HttpLine::Request { uri, .. } => Some(uri.to_string()),
+            HttpLine::Status { .. } => None,
+        }
+    }
+
+    pub fn version(&self) -> Option<String> {
+        match self.line()? {
+            HttpLine::Request { version, .. } | HttpLine::Status { version, .. } => {
+                Some(version.to_string())
+            }
+        }
+    }
+
+    pub fn status(&self) -> Option<u16> {
+        match self.line()? {
+            HttpLine::Status { status, .. } => Some(status),
+            HttpLine::Request { .. } => None,
+        }
+    }
+}
+
+impl<'a> Layer for Http<'a> {
+    fn get_name(&self) -> String {
+        "http".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "http.method" => self
+                .method()
+                .map(|method| Field::set_field(FieldType::String(method), field)),
+            "http.uri" => self
+                .uri()
+                .map(|uri| Field::set_field(FieldType::String(uri), field)),
+            "http.version" => self
+                .version()
+                .map(|version| Field::set_field(FieldType::String(version), field)),
+            "http.status" => self
+                .status()
+                .map(|status| Field::set_field(FieldType::Int16(status), field)),
+            _ => None,
+        }
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HttpDissector;
+
+impl Dissector for HttpDissector {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::HTTP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::TcpPort(HTTP_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        0
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        HTTP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Http::new(packet))
+    }
+
+    fn heuristic(&self, parent: &LayerIndex, payload: &[u8]) -> bool {
+        *parent == LayerIndex::TCP && is_http(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_request_line() {
+        let payload = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let http = Http::new(payload);
+
+        assert!(is_http(payload), "HTTP request");
+        assert_eq!(http.method(), Some("GET".to_string()), "Method");
+        assert_eq!(http.uri(), Some("/index.html".to_string()), "URI");
+        assert_eq!(http.status(), None, "No status on request");
+    }
+
+    #[test]
+    fn http_status_line() {
+        let payload = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n";
+        let http = Http::new(payload);
+
+        assert!(is_http(payload), "HTTP response");
+        assert_eq!(http.status(), Some(404), "Status");
+        assert_eq!(http.version(), Some("HTTP/1.1".to_string()), "Version");
+    }
+
+    #[test]
+    fn http_reject() {
+        assert!(!is_http(b"SSH-2.0-OpenSSH_9.6\r\n"), "SSH banner");
+        assert!(!is_http(b"GET /index.html"), "No line end");
+        assert!(!is_http(b"FETCH / HTTP/1.1\r\n"), "Unknown method");
+    }
+}
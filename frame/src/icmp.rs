@@ -1,8 +1,12 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
 use byteorder::{BigEndian, ByteOrder};
+use std::net::Ipv4Addr;
 /*
 Echo or Echo Reply Message
 
@@ -68,8 +72,51 @@ IP Fields:
       in the echo reply.
 
       Code 0 may be received from a gateway or a host.
+
+Error Messages (Destination Unreachable, Time Exceeded, Redirect...)
+
+  0               1               2               3
+  0 1 2 3 4 5 6 7 0 1 2 3 4 5 6 7 0 1 2 3 4 5 6 7 0 1 2 3 4 5 6 7
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |     Type      |     Code      |          Checksum             |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |   Unused / Gateway address (redirect) / Next-hop MTU (3/4)    |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |      Internet Header + 64 bits of Original Data Datagram      |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 */
 
+pub const ICMP_ECHO_REPLY: u8 = 0;
+pub const ICMP_DEST_UNREACHABLE: u8 = 3;
+pub const ICMP_SOURCE_QUENCH: u8 = 4;
+pub const ICMP_REDIRECT: u8 = 5;
+pub const ICMP_ECHO_REQUEST: u8 = 8;
+pub const ICMP_TIME_EXCEEDED: u8 = 11;
+pub const ICMP_PARAMETER_PROBLEM: u8 = 12;
+
+pub const ICMP_CODE_FRAG_NEEDED: u8 = 4;
+
+const ICMP_HEADER_LEN: usize = 8;
+const IP_MIN_HEADER_LEN: usize = 20;
+const IP_ICMP_PROTO: u8 = 0x01;
+const IP_TCP_PROTO: u8 = 0x06;
+const IP_UDP_PROTO: u8 = 0x11;
+
+const ICMP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("icmp.type", field_type::INT8, 0, 1),
+    FieldDef::at("icmp.code", field_type::INT8, 1, 1),
+    FieldDef::at("icmp.identifier", field_type::INT16, 4, 2),
+    FieldDef::at("icmp.seq_no", field_type::INT16, 6, 2),
+    FieldDef::new("icmp.packet", field_type::BYTE_ARRAY),
+    FieldDef::new("icmp.gateway", field_type::IPV4),
+    FieldDef::new("icmp.mtu", field_type::INT16),
+    FieldDef::new("icmp.orig.ip.src", field_type::IPV4),
+    FieldDef::new("icmp.orig.ip.dst", field_type::IPV4),
+    FieldDef::new("icmp.orig.protocol", field_type::INT8),
+    FieldDef::new("icmp.orig.sport", field_type::INT16),
+    FieldDef::new("icmp.orig.dport", field_type::INT16),
+];
+
 // #[derive(Default, Debug, Clone)]
 // pub struct ICMPBuilder {
 //     raw_packet: Vec<u8>,
@@ -130,8 +177,91 @@ impl<'a> Icmp<'a> {
         BigEndian::read_u16(&self.raw_packet[6..8])
     }
 
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.itype(),
+            ICMP_DEST_UNREACHABLE
+                | ICMP_SOURCE_QUENCH
+                | ICMP_REDIRECT
+                | ICMP_TIME_EXCEEDED
+                | ICMP_PARAMETER_PROBLEM
+        )
+    }
+
+    pub fn gateway(&self) -> Option<u32> {
+        if self.itype() == ICMP_REDIRECT {
+            Some(BigEndian::read_u32(&self.raw_packet[4..8]))
+        } else {
+            None
+        }
+    }
+
+    pub fn mtu(&self) -> Option<u16> {
+        if self.itype() == ICMP_DEST_UNREACHABLE && self.code() == ICMP_CODE_FRAG_NEEDED {
+            Some(BigEndian::read_u16(&self.raw_packet[6..8]))
+        } else {
+            None
+        }
+    }
+
+    //--- Original IP header carried by an error message, None when
+    //--- the message is not an error or the header is truncated
+    fn orig_ip(&self) -> Option<&'a [u8]> {
+        if !self.is_error() || self.raw_packet.len() < ICMP_HEADER_LEN + IP_MIN_HEADER_LEN {
+            return None;
+        }
+
+        let orig = &self.raw_packet[ICMP_HEADER_LEN..];
+        if orig[0] >> 4 != 4 {
+            return None;
+        }
+
+        Some(orig)
+    }
+
+    pub fn orig_ip_src(&self) -> Option<u32> {
+        self.orig_ip().map(|ip| BigEndian::read_u32(&ip[12..16]))
+    }
+
+    pub fn orig_ip_dst(&self) -> Option<u32> {
+        self.orig_ip().map(|ip| BigEndian::read_u32(&ip[16..20]))
+    }
+
+    pub fn orig_protocol(&self) -> Option<u8> {
+        self.orig_ip().map(|ip| ip[9])
+    }
+
+    //--- First 4 bytes of the original transport header, only for TCP and UDP
+    fn orig_ports(&self) -> Option<(u16, u16)> {
+        let ip = self.orig_ip()?;
+        if !matches!(ip[9], IP_TCP_PROTO | IP_UDP_PROTO) {
+            return None;
+        }
+
+        let hdr_len = ((ip[0] & 0x0f) as usize) * 4;
+        if hdr_len < IP_MIN_HEADER_LEN || ip.len() < hdr_len + 4 {
+            return None;
+        }
+
+        Some((
+            BigEndian::read_u16(&ip[hdr_len..hdr_len + 2]),
+            BigEndian::read_u16(&ip[hdr_len + 2..hdr_len + 4]),
+        ))
+    }
+
+    pub fn orig_sport(&self) -> Option<u16> {
+        self.orig_ports().map(|(sport, _)| sport)
+    }
+
+    pub fn orig_dport(&self) -> Option<u16> {
+        self.orig_ports().map(|(_, dport)| dport)
+    }
+
     pub fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
-        self.raw_packet[offset..offset + len].to_vec()
+        self.raw_packet
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+            .to_vec()
     }
 }
 
@@ -143,6 +273,27 @@ impl<'a> Layer for Icmp<'a> {
             "icmp.identifier" => Some(Field::set_field(FieldType::Int16(self.identifier()), field)),
 
             "icmp.seq_no" => Some(Field::set_field(FieldType::Int16(self.seq_no()), field)),
+            "icmp.gateway" => self
+                .gateway()
+                .map(|gw| Field::set_field(FieldType::Ipv4(gw, 32), field)),
+            "icmp.mtu" => self
+                .mtu()
+                .map(|mtu| Field::set_field(FieldType::Int16(mtu), field)),
+            "icmp.orig.ip.src" => self
+                .orig_ip_src()
+                .map(|ip| Field::set_field(FieldType::Ipv4(ip, 32), field)),
+            "icmp.orig.ip.dst" => self
+                .orig_ip_dst()
+                .map(|ip| Field::set_field(FieldType::Ipv4(ip, 32), field)),
+            "icmp.orig.protocol" => self
+                .orig_protocol()
+                .map(|proto| Field::set_field(FieldType::Int8(proto), field)),
+            "icmp.orig.sport" => self
+                .orig_sport()
+                .map(|port| Field::set_field(FieldType::Int16(port), field)),
+            "icmp.orig.dport" => self
+                .orig_dport()
+                .map(|port| Field::set_field(FieldType::Int16(port), field)),
             _ => None,
         }
     }
@@ -154,10 +305,58 @@ impl<'a> Layer for Icmp<'a> {
     fn get_name(&self) -> String {
         "icmp".to_string()
     }
+
+    fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
+        Icmp::payload_range(self, offset, len)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IcmpDissector;
+
+impl Dissector for IcmpDissector {
+    fn name(&self) -> &'static str {
+        "icmp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::ICMP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::IpProto(IP_ICMP_PROTO)]
+    }
+
+    fn min_len(&self) -> usize {
+        ICMP_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        ICMP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Icmp::new(packet))
+    }
 }
 
 impl<'a> PacketDisplay for Icmp<'a> {
     fn summary(&self) -> String {
+        if let (Some(src), Some(dst), Some(proto)) =
+            (self.orig_ip_src(), self.orig_ip_dst(), self.orig_protocol())
+        {
+            return format!(
+                "ICMP Error -> Type: {}, Code: {}, Orig: {}:{} -> {}:{} proto: {}",
+                self.itype(),
+                self.code(),
+                Ipv4Addr::from(src),
+                self.orig_sport().unwrap_or_default(),
+                Ipv4Addr::from(dst),
+                self.orig_dport().unwrap_or_default(),
+                proto,
+            );
+        }
+
         format!(
             "ICMP Echo -> Type: {}, Code: {}, Seq: {}",
             self.itype(),
@@ -167,6 +366,108 @@ impl<'a> PacketDisplay for Icmp<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "ICMP Echo Detail".to_string()
+        LayerNode::of(
+            IcmpDissector.name(),
+            IcmpDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_unreachable() -> Vec<u8> {
+        //--- Port unreachable for a UDP datagram 192.168.1.10:54321 -> 192.168.1.1:53
+        let packet: Vec<u8> = vec![
+            0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00, 0x3c, 0x00, 0x01,
+            0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8, 0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01,
+            0xd4, 0x31, 0x00, 0x35, 0x00, 0x28, 0x00, 0x00,
+        ];
+
+        packet
+    }
+
+    #[test]
+    fn icmp_orig_addresses() {
+        let packet = get_unreachable();
+        let icmp = Icmp::new(&packet);
+
+        assert!(icmp.is_error(), "Error message");
+        assert_eq!(icmp.orig_ip_src(), Some(0xc0a8010a), "Original IP source");
+        assert_eq!(
+            icmp.orig_ip_dst(),
+            Some(0xc0a80101),
+            "Original IP destination"
+        );
+        assert_eq!(
+            icmp.orig_protocol(),
+            Some(IP_UDP_PROTO),
+            "Original protocol"
+        );
+    }
+
+    #[test]
+    fn icmp_show_detail() {
+        let packet = get_unreachable();
+        let detail = Icmp::new(&packet).show_detail();
+
+        assert!(
+            detail.contains("icmp.type: 3 (int8) [0x0000, 1]"),
+            "Type: {}",
+            detail
+        );
+        assert!(
+            detail.contains("icmp.orig.ip.dst: 192.168.1.1/32 (ipv4)"),
+            "Original datagram: {}",
+            detail
+        );
+        assert!(!detail.contains("Detail"), "No placeholder: {}", detail);
+    }
+
+    #[test]
+    fn icmp_orig_ports() {
+        let packet = get_unreachable();
+        let icmp = Icmp::new(&packet);
+
+        assert_eq!(icmp.orig_sport(), Some(54321), "Original source port");
+        assert_eq!(icmp.orig_dport(), Some(53), "Original destination port");
+    }
+
+    #[test]
+    fn icmp_echo_no_orig() {
+        let packet: Vec<u8> = vec![0x08, 0x00, 0x00, 0x00, 0x12, 0x34, 0x00, 0x01];
+        let icmp = Icmp::new(&packet);
+
+        assert!(!icmp.is_error(), "Echo request");
+        assert_eq!(icmp.orig_ip_src(), None, "No original datagram");
+        assert!(icmp.get_field("icmp.orig.sport").is_none());
+    }
+
+    #[test]
+    fn icmp_frag_needed_mtu() {
+        let mut packet = get_unreachable();
+        packet[1] = ICMP_CODE_FRAG_NEEDED;
+        packet[6] = 0x05;
+        packet[7] = 0xdc;
+        let icmp = Icmp::new(&packet);
+
+        assert_eq!(icmp.mtu(), Some(1500), "Next-hop MTU");
+        assert_eq!(icmp.gateway(), None, "No gateway");
+    }
+
+    #[test]
+    fn icmp_redirect_gateway() {
+        let mut packet = get_unreachable();
+        packet[0] = ICMP_REDIRECT;
+        packet[1] = 0x01;
+        packet[4..8].copy_from_slice(&[0xc0, 0xa8, 0x01, 0xfe]);
+        let icmp = Icmp::new(&packet);
+
+        assert_eq!(icmp.gateway(), Some(0xc0a801fe), "Redirect gateway");
+        assert_eq!(icmp.mtu(), None, "No MTU");
     }
 }
@@ -1,9 +1,24 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
 use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
 use field::ipv4_address::IPv4;
 use field::pfield::{Field, FieldType};
 
+const ETHER_IPV4_PROTO: u16 = 0x0800;
+const IP_MIN_HEADER_LEN: usize = 20;
+
+const IP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("ip.src", field_type::IPV4, 12, 4),
+    FieldDef::at("ip.dst", field_type::IPV4, 16, 4),
+    FieldDef::at("ip.tos", field_type::INT8, 1, 1),
+    FieldDef::at("ip.ttl", field_type::INT8, 8, 1),
+    FieldDef::at("ip.protocol", field_type::INT8, 9, 1),
+    FieldDef::at("ip.hdr_len", field_type::INT8, 0, 1),
+];
+
 #[derive(Debug, Clone, Default)]
 pub struct IpFrame<'a> {
     ip_packet: &'a [u8],
@@ -67,6 +82,44 @@ impl<'a> Layer for IpFrame<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct IpDissector;
+
+impl Dissector for IpDissector {
+    fn name(&self) -> &'static str {
+        "ip"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::IPv4
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::EtherType(ETHER_IPV4_PROTO)]
+    }
+
+    fn min_len(&self) -> usize {
+        IP_MIN_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        IP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(IpFrame::new(packet))
+    }
+
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let ip = IpFrame::new(packet);
+
+        Some(Dispatch::new(
+            ip.header_len() as usize * 4,
+            vec![Trigger::IpProto(ip.proto())],
+        ))
+    }
+}
+
 impl<'a> PacketDisplay for IpFrame<'a> {
     fn summary(&self) -> String {
         format!(
@@ -77,7 +130,13 @@ impl<'a> PacketDisplay for IpFrame<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "UDP Detail".to_string()
+        LayerNode::of(
+            IpDissector.name(),
+            IpDissector.fields(),
+            self,
+            self.ip_packet.len(),
+        )
+        .to_string()
     }
 }
 #[cfg(test)]
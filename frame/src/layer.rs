@@ -1,8 +1,155 @@
+use crate::layer_index::LayerIndex;
 use dyn_clone::DynClone;
 use field::pfield::Field;
+use std::fmt;
+
+//--- Keys offered by a parent layer to select the child dissector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    LinkType(u32),
+    EtherType(u16),
+    IpProto(u8),
+    TcpPort(u16),
+    UdpPort(u16),
+}
+
+impl Trigger {
+    pub fn detection(&self) -> Detection {
+        match self {
+            Trigger::TcpPort(_) | Trigger::UdpPort(_) => Detection::Port,
+            _ => Detection::Header,
+        }
+    }
+}
+
+//--- How a layer was selected: protocol field of the parent header,
+//--- well known port or payload inspection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Detection {
+    #[default]
+    Header,
+    Port,
+    Heuristic,
+}
+
+impl fmt::Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            Detection::Header => "header",
+            Detection::Port => "port",
+            Detection::Heuristic => "heuristic",
+        };
+
+        write!(f, "{}", label)
+    }
+}
+
+//--- Reason a layer could not be decoded, the packet is then flagged
+//--- as malformed and the decoding stops at this layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    Truncated,
+    Invalid,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match self {
+            DecodeError::Truncated => "truncated",
+            DecodeError::Invalid => "invalid",
+        };
+
+        write!(f, "{}", label)
+    }
+}
+
+//--- Child layer start (offset) relative to the parent, bytes to drop at
+//--- the end (trailer) and the keys used to find the child dissector
+#[derive(Debug, Clone, Default)]
+pub struct Dispatch {
+    pub offset: usize,
+    pub trailer: usize,
+    pub triggers: Vec<Trigger>,
+}
+
+impl Dispatch {
+    pub fn new(offset: usize, triggers: Vec<Trigger>) -> Self {
+        Self {
+            offset,
+            trailer: 0,
+            triggers,
+        }
+    }
+}
+
+//--- Entry of a dissector field catalogue, field_type is one of field::field_type,
+//--- range is the (offset, len) in the layer for the fixed header fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldDef {
+    pub name: &'static str,
+    pub field_type: u16,
+    pub range: Option<(usize, usize)>,
+}
+
+impl FieldDef {
+    pub const fn new(name: &'static str, field_type: u16) -> Self {
+        Self {
+            name,
+            field_type,
+            range: None,
+        }
+    }
+
+    pub const fn at(name: &'static str, field_type: u16, offset: usize, len: usize) -> Self {
+        Self {
+            name,
+            field_type,
+            range: Some((offset, len)),
+        }
+    }
+}
 
 pub trait Layer: DynClone {
     fn get_name(&self) -> String;
     fn get_field(&self, field_name: &str) -> Option<Field>;
     fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>>;
+
+    fn payload_range(&self, _offset: usize, _len: usize) -> Vec<u8> {
+        Vec::new()
+    }
+
+    //--- (offset, len) in the layer of a field whose position depends on
+    //--- the packet content, the fixed ones are given by FieldDef::at
+    fn field_range(&self, _field_name: &str) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+//-------------------------------------------------------------
+//--- Static description of a protocol registered in registry,
+//--- decode() give access to the Layer of a packet
+//-------------------------------------------------------------
+pub trait Dissector: Sync {
+    //--- Field prefix, ex: "ip" for ip.src
+    fn name(&self) -> &'static str;
+    fn layer(&self) -> LayerIndex;
+    fn triggers(&self) -> &'static [Trigger];
+    fn min_len(&self) -> usize;
+    fn fields(&self) -> &'static [FieldDef];
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a>;
+
+    //--- Payload inspection, tried before the triggers
+    fn heuristic(&self, _parent: &LayerIndex, _payload: &[u8]) -> bool {
+        false
+    }
+
+    //--- Structural check beyond min_len, run once when the packet is dissected
+    fn check(&self, _packet: &[u8]) -> Result<(), DecodeError> {
+        Ok(())
+    }
+
+    //--- Child layer dispatch, None when this is the last layer
+    fn dispatch(&self, _packet: &[u8]) -> Option<Dispatch> {
+        None
+    }
 }
@@ -29,5 +29,7 @@ pub enum LayerIndex {
     BGP = 0x2_000_000,
     SMB = 0x4_000_000,
     RDP = 0x8_000_000,
+    RADIOTAP = 0x10_000_000,
+    WLAN = 0x20_000_000,
     FRAME = 0xffffffff,
 }
@@ -1,11 +1,14 @@
 pub mod arp;
 pub mod constant;
+pub mod detail;
 pub mod dhcp;
 pub mod dns;
 pub mod eth;
 // pub mod field_type;
 pub mod fields;
+pub mod flow;
 pub mod frame;
+pub mod http;
 pub mod icmp;
 pub mod ip;
 pub mod layer;
@@ -14,7 +17,14 @@ pub mod ntp;
 pub mod packet;
 pub mod packet_builder;
 pub mod packet_display;
+pub mod packet_id;
 pub mod print_hex;
+pub mod radiotap;
+pub mod registry;
 pub mod sip;
+pub mod smb;
+pub mod ssh;
 pub mod tcp;
+pub mod tls;
 pub mod udp;
+pub mod wlan;
@@ -1,7 +1,33 @@
-use crate::layer::Layer;
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
+const NTP_PORT: u16 = 123;
+const NTP_HEADER_LEN: usize = 48;
+const NTP_EXTENSION_LEN: usize = 4;
+
+const NTP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("ntp.leap_indicator", field_type::INT8, 0, 1),
+    FieldDef::at("ntp.version", field_type::INT8, 0, 1),
+    FieldDef::at("ntp.mode", field_type::INT8, 0, 1),
+    FieldDef::at("ntp.stratum", field_type::INT8, 1, 1),
+    FieldDef::at("ntp.poll", field_type::INT8, 2, 1),
+    FieldDef::at("ntp.precision", field_type::INT8, 3, 1),
+    FieldDef::at("ntp.root_delay", field_type::INT32, 4, 4),
+    FieldDef::at("ntp.root_dispersion", field_type::INT32, 8, 4),
+    FieldDef::at("ntp.ref_id", field_type::INT32, 12, 4),
+    FieldDef::at("ntp.ref_timestamp", field_type::INT64, 16, 8),
+    FieldDef::at("ntp.origin_timestamp", field_type::INT64, 24, 8),
+    FieldDef::at("ntp.recv_timestamp", field_type::INT64, 32, 8),
+    FieldDef::at("ntp.xmit_timestamp", field_type::INT64, 40, 8),
+    FieldDef::new("ntp.opt_extension", field_type::INT32),
+    FieldDef::new("ntp.key_id", field_type::BYTE_ARRAY),
+    FieldDef::new("ntp.msg_digest", field_type::BYTE_ARRAY),
+    FieldDef::new("ntp.mode_label", field_type::STRING),
+];
+
 #[derive(Debug, Clone)]
 pub struct Ntp<'a> {
     raw_data: &'a [u8],
@@ -73,7 +99,12 @@ impl<'a> Ntp<'a> {
     }
 
     pub fn msg_digest(&self) -> Vec<u8> {
-        self.raw_data[52..].to_vec()
+        self.raw_data.get(52..).unwrap_or_default().to_vec()
+    }
+
+    //--- Optional extension and authenticator follow the 48 bytes header
+    fn has_extension(&self) -> bool {
+        self.raw_data.len() >= NTP_HEADER_LEN + NTP_EXTENSION_LEN
     }
 
     fn mode_str(&self) -> String {
@@ -128,14 +159,12 @@ impl<'a> Layer for Ntp<'a> {
                 FieldType::Int64(self.xmit_timestamp()),
                 field,
             )),
-            "ntp.opt_extension" => Some(Field::set_field(
-                FieldType::Int32(self.optional_ext()),
-                field,
-            )),
-            "ntp.key_id" => Some(Field::set_field(
-                FieldType::ByteArray(self.msg_digest()),
-                field,
-            )),
+            "ntp.opt_extension" => self
+                .has_extension()
+                .then(|| Field::set_field(FieldType::Int32(self.optional_ext()), field)),
+            "ntp.key_id" => self
+                .has_extension()
+                .then(|| Field::set_field(FieldType::ByteArray(self.msg_digest()), field)),
             _ => None,
         }
     }
@@ -145,6 +174,35 @@ impl<'a> Layer for Ntp<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct NtpDissector;
+
+impl Dissector for NtpDissector {
+    fn name(&self) -> &'static str {
+        "ntp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::NTP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::UdpPort(NTP_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        NTP_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        NTP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Ntp::new(packet))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
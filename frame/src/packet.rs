@@ -1,26 +1,31 @@
-use crate::dhcp::Dhcp;
+use crate::detail::{FieldNode, LayerNode, PacketTree};
 use crate::eth::EtherFrame;
-use crate::fields;
-use crate::frame::Frame;
+use crate::frame::{FRAME_FIELDS, Frame};
 use crate::icmp::Icmp;
 use crate::ip::IpFrame;
-use crate::layer::Layer;
+use crate::layer::{DecodeError, Detection, Dissector, FieldDef, Layer, Trigger};
 use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
+use crate::packet_id::PacketId;
+use crate::radiotap::Radiotap;
+use crate::registry;
+use crate::ssh::{self, Ssh};
 use crate::tcp::Tcp;
 use crate::udp::UdpFrame;
-use crate::{arp::Arp, dns::Dns, ntp::Ntp};
-use field::pfield::Field;
+use crate::wlan::Wlan;
+use anyhow::Result;
+use field::pfield::{Field, FieldType};
 use indexmap::IndexMap;
-
-use byteorder::{BigEndian, ByteOrder};
-
-const ETHER_IPV4_PROTO: u16 = 0x0800;
-const ETHER_ARP_PROTO: u16 = 0x0806;
+use log::debug;
 
 const IP_TCP_PROTO: u8 = 0x06;
 const IP_UDP_PROTO: u8 = 0x11;
 const IP_ICMP_PROTO: u8 = 0x01;
+const SSH_NAME: &str = "ssh";
+
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_IEEE802_11: u32 = 105;
+pub const LINKTYPE_IEEE802_11_RADIOTAP: u32 = 127;
 
 #[derive(Debug, Clone)]
 pub struct LayerInfo {
@@ -32,11 +37,17 @@ pub struct LayerInfo {
 #[derive(Default, Debug, Clone)]
 pub struct Packet {
     raw_packet: Vec<u8>,
-    arp_packet: Option<Arp>,
     pub file_id: u32,
-    pub pkt_ptr: u32,
+    pub pkt_ptr: u64,
     header: [u8; 16],
     little_endian: bool,
+    ts_nanosecond: bool,
+    link_type: u32,
+    detection: Detection,
+    error_layer: Option<&'static str>,
+    interface_id: Option<u32>,
+    interface_name: Option<String>,
+    comment: Option<String>,
 
     frame_list: IndexMap<LayerIndex, LayerInfo>,
 }
@@ -49,7 +60,7 @@ impl Packet {
     pub fn get_bytes(&self) -> Option<Field> {
         if !self.raw_packet.is_empty() {
             Some(Field::set_field(
-                field::pfield::FieldType::ByteArray(self.raw_packet.clone()),
+                FieldType::ByteArray(self.raw_packet.clone()),
                 "packet",
             ))
         } else {
@@ -57,6 +68,10 @@ impl Packet {
         }
     }
 
+    pub fn raw_bytes(&self) -> &[u8] {
+        &self.raw_packet
+    }
+
     pub fn add_layer(&mut self, layer: LayerInfo) {
         self.frame_list.insert(layer.layer_type.clone(), layer);
     }
@@ -113,24 +128,68 @@ impl Packet {
     fn get_frame_packet(&self) -> Option<Frame<'_>> {
         self.get_layer_bytes(LayerIndex::FRAME)
             .as_ref()
-            .map(|raw_pkt| Frame::new(raw_pkt, self.little_endian))
+            .map(|raw_pkt| Frame::new(raw_pkt, self.little_endian, self.ts_nanosecond))
     }
 
-    fn get_dns_packet(&self) -> Option<Dns<'_>> {
-        self.get_layer_bytes(LayerIndex::DNS)
+    fn get_radiotap_packet(&self) -> Option<Radiotap<'_>> {
+        self.get_layer_bytes(LayerIndex::RADIOTAP)
             .as_ref()
-            .map(|raw_pkt| Dns::new(raw_pkt))
+            .map(|raw_pkt| Radiotap::new(raw_pkt))
     }
 
-    fn get_dhcp_packet(&self) -> Option<Dhcp<'_>> {
-        self.get_layer_bytes(LayerIndex::DHCP)
+    fn get_wlan_packet(&self) -> Option<Wlan<'_>> {
+        self.get_layer_bytes(LayerIndex::WLAN)
             .as_ref()
-            .map(|raw_pkt| Dhcp::new(raw_pkt))
+            .map(|raw_pkt| Wlan::new(raw_pkt))
     }
-    fn get_ntp_packet(&self) -> Option<Ntp<'_>> {
-        self.get_layer_bytes(LayerIndex::NTP)
-            .as_ref()
-            .map(|raw_pkt| Ntp::new(raw_pkt))
+
+    pub fn link_type(&self) -> u32 {
+        self.link_type
+    }
+
+    //--- Pcap record header as read from the file, in the file byte order
+    pub fn header(&self) -> &[u8; 16] {
+        &self.header
+    }
+
+    pub fn is_little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    //--- Sub-second part of the record header in nanoseconds (0xa1b23c4d magic)
+    pub fn set_ts_nanosecond(&mut self, nanosecond: bool) {
+        self.ts_nanosecond = nanosecond;
+    }
+
+    pub fn is_ts_nanosecond(&self) -> bool {
+        self.ts_nanosecond
+    }
+
+    //--- Capture interface and packet comment, only known for pcapng files
+    pub fn set_interface(&mut self, interface_id: u32, interface_name: Option<String>) {
+        self.interface_id = Some(interface_id);
+        self.interface_name = interface_name;
+    }
+
+    pub fn set_comment(&mut self, comment: Option<String>) {
+        self.comment = comment;
+    }
+
+    pub fn interface_id(&self) -> Option<u32> {
+        self.interface_id
+    }
+
+    pub fn interface_name(&self) -> Option<&str> {
+        self.interface_name.as_deref()
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn timestamp_ns(&self) -> u64 {
+        self.get_frame_packet()
+            .map_or(0, |frame| frame.timestamp_ns())
     }
 
     pub fn print_layers(&self) {
@@ -142,11 +201,13 @@ impl Packet {
         packet: Vec<u8>,
         header: [u8; 16],
         file_id: u32,
-        pkt_ptr: u32,
+        pkt_ptr: u64,
         little_endian: bool,
+        link_type: u32,
     ) {
         self.header = header;
         self.little_endian = little_endian;
+        self.link_type = link_type;
         self.add_layer(LayerInfo {
             layer_type: LayerIndex::FRAME,
             start_pos: 0,
@@ -160,15 +221,7 @@ impl Packet {
         self.file_id = file_id;
         self.pkt_ptr = pkt_ptr;
 
-        self.raw_packet = packet.clone();
-
-        let header = BigEndian::read_u16(&self.raw_packet[12..14]);
-
-        let vo: usize = if header == 0x8100 { 18 } else { 14 };
-
-        let ip_header_len: usize = (self.raw_packet[vo] as usize & 0x0f) * 4;
-
-        let ether = EtherFrame::new(&packet[0..vo]);
+        self.raw_packet = packet;
 
         //--- Added frame layer
         self.add_layer(LayerInfo {
@@ -177,111 +230,115 @@ impl Packet {
             end_pos: self.raw_packet.len(),
         });
 
-        //--- Added ethernat layer
-        self.add_layer(LayerInfo {
-            layer_type: LayerIndex::ETH,
-            start_pos: 0,
-            end_pos: vo,
-        });
-
-        if ether.ethertype() == ETHER_ARP_PROTO {
-            let mut arp_packet = Arp::default();
-            arp_packet.set_packet(self.raw_packet[vo..].to_vec());
+        //--- Unknown link types are decoded as ethernet
+        self.dissect(
+            vec![
+                Trigger::LinkType(link_type),
+                Trigger::LinkType(LINKTYPE_ETHERNET),
+            ],
+            0,
+            self.raw_packet.len(),
+        );
+    }
+
+    //---------------------------------------------------------------
+    //--- Walk down the layers, each dissector tell where its child
+    //--- start and the triggers used to select it in the registry
+    //---------------------------------------------------------------
+    fn dissect(&mut self, mut triggers: Vec<Trigger>, mut start_pos: usize, mut end_pos: usize) {
+        let mut parent = LayerIndex::FRAME;
+
+        //--- An empty payload can still be labelled from its port
+        while start_pos <= end_pos {
+            let payload = &self.raw_packet[start_pos..end_pos];
+            let Some((dissector, detection)) = registry::find_child(&parent, &triggers, payload)
+            else {
+                break;
+            };
+
+            if payload.len() < dissector.min_len() {
+                self.set_malformed(dissector, DecodeError::Truncated);
+                break;
+            }
 
-            self.arp_packet = Some(arp_packet);
+            let checked = dissector.check(payload);
 
-            //--- Add ARP layer
             self.add_layer(LayerInfo {
-                layer_type: LayerIndex::ARP,
-                start_pos: vo,
-                end_pos: self.raw_packet.len() - vo,
+                layer_type: dissector.layer(),
+                start_pos,
+                end_pos,
             });
-        }
+            self.detection = detection;
 
-        if ether.ethertype() == ETHER_IPV4_PROTO {
-            //--- Add IPV4 layer
-            self.add_layer(LayerInfo {
-                layer_type: LayerIndex::IPv4,
-                start_pos: vo,
-                end_pos: vo + ip_header_len,
-            });
-        }
+            if let Err(error) = checked {
+                self.set_malformed(dissector, error);
+                break;
+            }
 
-        self.process_ipv4(vo, ip_header_len);
-    }
-
-    fn process_ipv4(&mut self, vo: usize, ip_header_len: usize) {
-        if let Some(ip_layer) = &self.get_layer_bytes(LayerIndex::IPv4) {
-            let p = IpFrame::new(ip_layer);
-            if let Some(proto) = p.get_field("ip.protocol") {
-                match proto.to_u8() {
-                    IP_TCP_PROTO => {
-                        //--- Add TCP layer
-                        self.add_layer(LayerInfo {
-                            layer_type: LayerIndex::TCP,
-                            start_pos: vo + ip_header_len,
-                            end_pos: self.raw_packet.len(),
-                        });
-                    }
-
-                    IP_UDP_PROTO => {
-                        //--- Add UDP layer
-                        self.add_layer(LayerInfo {
-                            layer_type: LayerIndex::UDP,
-                            start_pos: vo + ip_header_len,
-                            end_pos: self.raw_packet.len(),
-                        });
-
-                        if let Some(dns) = self.get_udp_packet()
-                            && dns.is_dns()
-                        {
-                            self.add_layer(LayerInfo {
-                                layer_type: LayerIndex::DNS,
-                                start_pos: vo + ip_header_len + dns.header_len(),
-                                end_pos: self.raw_packet.len(),
-                            });
-                        }
-
-                        if let Some(dhcp) = self.get_udp_packet()
-                            && dhcp.is_dhcp()
-                        {
-                            self.add_layer(LayerInfo {
-                                layer_type: LayerIndex::DHCP,
-                                start_pos: vo + ip_header_len + dhcp.header_len(),
-                                end_pos: self.raw_packet.len(),
-                            });
-                        }
-                        if let Some(ntp) = self.get_udp_packet()
-                            && ntp.is_ntp()
-                        {
-                            self.add_layer(LayerInfo {
-                                layer_type: LayerIndex::NTP,
-                                start_pos: vo + ip_header_len + ntp.header_len(),
-                                end_pos: self.raw_packet.len(),
-                            });
-                        }
-                    }
-                    IP_ICMP_PROTO => {
-                        //--- Add ICMP layer
-                        self.add_layer(LayerInfo {
-                            layer_type: LayerIndex::ICMP,
-                            start_pos: vo + ip_header_len,
-                            end_pos: self.raw_packet.len(),
-                        });
-                    }
-
-                    _ => {}
-                }
+            let Some(dispatch) = dissector.dispatch(&self.raw_packet[start_pos..end_pos]) else {
+                break;
+            };
+            let payload_len = end_pos - start_pos;
+
+            //--- Header length field shorter than the header or past the end
+            if dispatch.offset < dissector.min_len() {
+                self.set_malformed(dissector, DecodeError::Invalid);
+                break;
             }
+            if dispatch.offset + dispatch.trailer > payload_len {
+                self.set_malformed(dissector, DecodeError::Truncated);
+                break;
+            }
+
+            parent = dissector.layer();
+            triggers = dispatch.triggers;
+            start_pos += dispatch.offset;
+            end_pos = end_pos.saturating_sub(dispatch.trailer);
         }
     }
 
+    //--- The layers above the failing one are not decoded
+    fn set_malformed(&mut self, dissector: &dyn Dissector, error: DecodeError) {
+        debug!(
+            "Malformed {} layer ({}), file: {} ptr: {}",
+            dissector.name(),
+            error,
+            self.file_id,
+            self.pkt_ptr
+        );
+        self.error_layer = Some(dissector.name());
+    }
+
+    pub fn is_malformed(&self) -> bool {
+        self.error_layer.is_some()
+    }
+
+    pub fn error_layer(&self) -> Option<&'static str> {
+        self.error_layer
+    }
+
+    //--- Bitmask of the decoded layers as stored in the packet index
+    pub fn layer_mask(&self) -> u32 {
+        self.frame_list
+            .keys()
+            .filter(|layer| **layer != LayerIndex::FRAME)
+            .fold(0, |mask, layer| mask | layer.clone() as u32)
+    }
+
     pub fn has_ethernet(&self) -> bool {
         self.has_layer(LayerIndex::ETH)
     }
 
+    pub fn has_radiotap(&self) -> bool {
+        self.has_layer(LayerIndex::RADIOTAP)
+    }
+
+    pub fn has_wlan(&self) -> bool {
+        self.has_layer(LayerIndex::WLAN)
+    }
+
     pub fn has_arp(&self) -> bool {
-        self.arp_packet.is_some()
+        self.has_layer(LayerIndex::ARP)
     }
 
     pub fn has_ipv4(&self) -> bool {
@@ -301,25 +358,13 @@ impl Packet {
     }
 
     pub fn has_https(&self) -> bool {
-        if let Some(pkt) = self.get_tcp_packet() {
-            pkt.is_https()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::HTTPS)
     }
     pub fn has_http(&self) -> bool {
-        if let Some(pkt) = self.get_tcp_packet() {
-            pkt.is_http()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::HTTP)
     }
     pub fn has_ssh(&self) -> bool {
-        if let Some(pkt) = self.get_tcp_packet() {
-            pkt.is_ssh()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::SSH)
     }
     pub fn has_telnet(&self) -> bool {
         if let Some(pkt) = self.get_tcp_packet() {
@@ -338,11 +383,7 @@ impl Packet {
     }
 
     pub fn has_smb(&self) -> bool {
-        if let Some(pkt) = self.get_tcp_packet() {
-            pkt.is_smb()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::SMB)
     }
 
     pub fn has_smtp(&self) -> bool {
@@ -354,11 +395,7 @@ impl Packet {
     }
 
     pub fn has_dns(&self) -> bool {
-        if let Some(pkt) = &self.get_udp_packet() {
-            pkt.is_dns()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::DNS)
     }
 
     pub fn has_snmp(&self) -> bool {
@@ -370,145 +407,160 @@ impl Packet {
     }
 
     pub fn has_ntp(&self) -> bool {
-        if let Some(pkt) = &self.get_udp_packet() {
-            pkt.is_ntp()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::NTP)
     }
 
     pub fn has_dhcp(&self) -> bool {
-        if let Some(pkt) = &self.get_udp_packet() {
-            pkt.is_dhcp()
-        } else {
-            false
-        }
+        self.has_layer(LayerIndex::DHCP)
     }
 
-    fn field_type(&self, field: &str, field_base: u32) -> bool {
-        let base: Vec<&str> = field.split(".").collect();
-
-        if base.len() == 2 {
-            match field_base {
-                fields::FRAME_BASE => base[0] == "frame",
-                fields::ETH_BASE => base[0] == "eth",
-                fields::ARP_BASE => base[0] == "arp",
-                fields::IPV4_BASE => base[0] == "ip",
-                fields::TCP_BASE => base[0] == "tcp",
-                fields::UDP_BASE => base[0] == "udp",
-                fields::ICMP_BASE => base[0] == "icmp",
-                fields::DNS_BASE => base[0] == "dns",
-                fields::DHCP_BASE => base[0] == "dhcp",
-                fields::NTP_BASE => base[0] == "ntp",
-                _ => false,
+    pub fn get_field(&self, field: &str) -> Option<Field> {
+        match registry::field_proto(field) {
+            registry::FRAME_NAME => self.get_frame_field(field),
+            SSH_NAME => self.get_ssh_field(field),
+            proto => {
+                let dissector = registry::by_name(proto)?;
+                let pkt_bytes = self.get_layer_bytes(dissector.layer())?;
+
+                dissector.decode(pkt_bytes).get_field(field)
             }
-        } else {
-            false
         }
     }
 
-    pub fn get_field(&self, field: &str) -> Option<Field> {
-        if self.field_type(field, fields::ETH_BASE) && self.has_layer(LayerIndex::ETH) {
-            if let Some(eth_packet) = self.get_eth_packet() {
-                eth_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::ARP_BASE) && self.arp_packet.is_some() {
-            self.arp_packet.as_ref().unwrap().get_field(field)
-        } else if self.field_type(field, fields::TCP_BASE) && self.has_layer(LayerIndex::TCP) {
-            if let Some(tcp_packet) = self.get_tcp_packet() {
-                tcp_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::IPV4_BASE) && self.has_layer(LayerIndex::IPv4) {
-            if let Some(ip_packet) = self.get_ipv4_packet() {
-                ip_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::UDP_BASE) && self.has_layer(LayerIndex::UDP) {
-            if let Some(udp_packet) = self.get_udp_packet() {
-                udp_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::FRAME_BASE) && self.has_layer(LayerIndex::FRAME) {
-            if let Some(frame_packet) = self.get_frame_packet() {
-                frame_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::ICMP_BASE) && self.has_layer(LayerIndex::ICMP) {
-            if let Some(pkt_bytes) = self.get_layer_bytes(LayerIndex::ICMP) {
-                let icmp = Icmp::new(pkt_bytes);
-                icmp.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::DNS_BASE) && self.has_layer(LayerIndex::DNS) {
-            if let Some(dns_packet) = self.get_dns_packet() {
-                dns_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::DHCP_BASE) && self.has_layer(LayerIndex::DHCP) {
-            if let Some(dhcp_packet) = self.get_dhcp_packet() {
-                dhcp_packet.get_field(field)
-            } else {
-                None
-            }
-        } else if self.field_type(field, fields::NTP_BASE) && self.has_layer(LayerIndex::NTP) {
-            if let Some(ntp_packet) = self.get_ntp_packet() {
-                ntp_packet.get_field(field)
-            } else {
-                None
-            }
-        } else {
-            None
+    fn get_frame_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "frame.file_id" => Some(Field::set_field(FieldType::Int32(self.file_id), field)),
+            "frame.pkt_ptr" => Some(Field::set_field(FieldType::Int64(self.pkt_ptr), field)),
+            "frame.id" => Some(Field::set_field(self.id_field(), field)),
+            "frame.packet_id" => Some(Field::set_field(
+                FieldType::String(self.packet_id().to_string()),
+                field,
+            )),
+            "frame.detection" => Some(Field::set_field(
+                FieldType::String(self.detection.to_string()),
+                field,
+            )),
+            "frame.malformed" => Some(Field::set_field(
+                FieldType::Bool(self.is_malformed()),
+                field,
+            )),
+            "frame.error_layer" => self
+                .error_layer
+                .map(|layer| Field::set_field(FieldType::String(layer.to_string()), field)),
+            "frame.interface_id" => self
+                .interface_id
+                .map(|id| Field::set_field(FieldType::Int32(id), field)),
+            "frame.interface_name" => self
+                .interface_name
+                .as_ref()
+                .map(|name| Field::set_field(FieldType::String(name.clone()), field)),
+            "frame.comment" => self
+                .comment
+                .as_ref()
+                .map(|comment| Field::set_field(FieldType::String(comment.clone()), field)),
+            _ => self.get_frame_packet()?.get_field(field),
         }
     }
 
-    pub fn get_id(&self) -> u64 {
-        let mut result: u64 = self.file_id as u64;
+    //--- Client and server fields depend on the direction of the segment
+    fn get_ssh_field(&self, field: &str) -> Option<Field> {
+        let pkt_bytes = self.get_layer_bytes(LayerIndex::SSH)?;
+        let tcp = self.get_tcp_packet()?;
 
-        result <<= 32;
-        result |= self.pkt_ptr as u64;
+        Ssh::with_direction(pkt_bytes, ssh::is_from_server(tcp.sport(), tcp.dport()))
+            .get_field(field)
+    }
 
-        result
+    pub fn packet_id(&self) -> PacketId {
+        PacketId::new(self.file_id, self.pkt_ptr)
     }
 
-    pub fn decode_id(&self, id: u64) -> (u32, u32) {
-        let file_id: u32 = (id >> 32) as u32;
-        let pkt_ptr: u32 = (id & 0x0000ffff) as u32;
+    //--- Numeric form of the packet id, an error past the v1 layout
+    pub fn get_id(&self) -> Result<u64> {
+        self.packet_id().to_u64()
+    }
 
-        (file_id, pkt_ptr)
+    //--- frame.id, the string form when the id has no numeric form
+    pub fn id_field(&self) -> FieldType {
+        match self.get_id() {
+            Ok(id) => FieldType::Int64(id),
+            Err(_) => FieldType::String(self.packet_id().to_string()),
+        }
     }
 
     pub fn get_field_byte(&self, field: String, offset: usize, len: usize) -> Vec<u8> {
-        if self.field_type(&field, fields::TCP_BASE) && self.has_layer(LayerIndex::TCP) {
-            if let Some(ip_packet) = self.get_tcp_packet() {
-                return ip_packet.payload_range(offset, len);
-            }
-        } else if self.field_type(&field, fields::UDP_BASE) && self.has_layer(LayerIndex::UDP) {
-            if let Some(udp_packet) = self.get_udp_packet() {
-                return udp_packet.payload_range(offset, len);
-            }
-        } else if self.field_type(&field, fields::ICMP_BASE) && self.has_layer(LayerIndex::ICMP) {
-            if let Some(pkt_bytes) = self.get_layer_bytes(LayerIndex::ICMP) {
-                let icmp = Icmp::new(pkt_bytes);
-                return icmp.payload_range(offset, len);
-            }
-        } else if self.field_type(&field, fields::ETH_BASE)
-            && self.has_layer(LayerIndex::ETH)
-            && let Some(eth) = self.get_eth_packet()
+        if let Some(dissector) = registry::by_name(registry::field_proto(&field))
+            && let Some(pkt_bytes) = self.get_layer_bytes(dissector.layer())
         {
-            return eth.payload_range(offset, len);
+            return dissector.decode(pkt_bytes).payload_range(offset, len);
+        }
+
+        Vec::new()
+    }
+
+    //-----------------------------------------------------------------
+    //--- Dissection tree, every decoded layer with its fields, a layer
+    //--- span from its start to the start of the next one
+    //-----------------------------------------------------------------
+    pub fn detail(&self) -> PacketTree {
+        let layers: Vec<&LayerInfo> = self.frame_list.values().collect();
+        let mut tree = PacketTree::default();
+
+        for (pos, info) in layers.iter().enumerate() {
+            if info.layer_type == LayerIndex::FRAME {
+                tree.layers.push(LayerNode {
+                    name: registry::FRAME_NAME.to_string(),
+                    offset: 0,
+                    len: self.raw_packet.len(),
+                    fields: self.detail_fields(FRAME_FIELDS, None, 0, 0),
+                });
+                continue;
+            }
+
+            let Some(dissector) = registry::by_layer(&info.layer_type) else {
+                continue;
+            };
+            let end_pos = layers
+                .get(pos + 1)
+                .map_or(info.end_pos, |next| next.start_pos);
+            let layer = dissector.decode(&self.raw_packet[info.start_pos..info.end_pos]);
+
+            tree.layers.push(LayerNode {
+                name: dissector.name().to_string(),
+                offset: info.start_pos,
+                len: end_pos - info.start_pos,
+                fields: self.detail_fields(
+                    dissector.fields(),
+                    Some(layer.as_ref()),
+                    info.start_pos,
+                    info.end_pos - info.start_pos,
+                ),
+            });
         }
 
-        let pkt: Vec<u8> = Vec::new();
-        pkt
+        tree
+    }
+
+    //--- Fields present in the packet, the range is kept when inside the layer bytes
+    fn detail_fields(
+        &self,
+        fields: &[FieldDef],
+        layer: Option<&dyn Layer>,
+        start_pos: usize,
+        layer_len: usize,
+    ) -> Vec<FieldNode> {
+        fields
+            .iter()
+            .filter_map(|def| {
+                let field = self.get_field(def.name)?;
+                let range = layer
+                    .and_then(|layer| layer.field_range(def.name))
+                    .or(def.range)
+                    .filter(|(offset, len)| offset + len <= layer_len);
+
+                Some(FieldNode::new(def.name, &field, range, start_pos))
+            })
+            .collect()
     }
 }
 
@@ -520,9 +572,15 @@ impl PacketDisplay for Packet {
             result += &eth.summary();
         }
 
-        if self.has_layer(LayerIndex::ETH)
-            && let Some(ip) = self.get_ipv4_packet()
-        {
+        if let Some(radiotap) = &self.get_radiotap_packet() {
+            result += &radiotap.summary();
+        }
+
+        if let Some(wlan) = &self.get_wlan_packet() {
+            result += &wlan.summary();
+        }
+
+        if let Some(ip) = self.get_ipv4_packet() {
             result += &format!("  {}{}", ip.summary(), "\n");
         }
 
@@ -551,11 +609,399 @@ impl PacketDisplay for Packet {
         result
     }
     fn show_detail(&self) -> String {
-        "Packet detail".to_string()
+        self.detail().to_string()
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    fn get_radiotap_packet() -> (Vec<u8>, [u8; 16]) {
+        let packet: Vec<u8> = vec![
+            0x00, 0x00, 0x0f, 0x00, 0x2e, 0x00, 0x00, 0x00, 0x10, 0x02, 0x85, 0x09, 0xa0, 0x00,
+            0xbd, 0x08, 0x01, 0x2c, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0xa0, 0xb0, 0xc0,
+            0xd0, 0xe0, 0xf0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x20, 0x00, 0xaa, 0xaa, 0x03,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, 0x40,
+            0x11, 0x00, 0x00, 0xc0, 0xa8, 0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01, 0xd4, 0x31, 0x27,
+            0x0f, 0x00, 0x08, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+
+        let header: [u8; 16] = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4f, 0x00, 0x00, 0x00, 0x4f, 0x00,
+            0x00, 0x00,
+        ];
+
+        (packet, header)
+    }
+
+    #[test]
+    fn radiotap_layers() {
+        let (packet, header) = get_radiotap_packet();
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_IEEE802_11_RADIOTAP);
+
+        assert!(pkt.has_radiotap(), "Radiotap layer");
+        assert!(pkt.has_wlan(), "802.11 layer");
+        assert!(pkt.has_ipv4(), "IPv4 layer");
+        assert!(pkt.has_udp(), "UDP layer");
+        assert!(!pkt.has_ethernet(), "No ethernet layer");
+    }
+
+    #[test]
+    fn radiotap_fields() {
+        let (packet, header) = get_radiotap_packet();
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_IEEE802_11_RADIOTAP);
+
+        assert_eq!(pkt.get_field("radiotap.channel").unwrap().to_u16(), 6);
+        assert_eq!(
+            pkt.get_field("wlan.bssid").unwrap().to_mac(),
+            0x001122334455,
+            "BSSID"
+        );
+        assert_eq!(
+            pkt.get_field("ip.src").unwrap().to_u32(),
+            0xc0a8010a,
+            "IP source"
+        );
+        assert_eq!(
+            pkt.get_field("udp.dport").unwrap().to_u16(),
+            9999,
+            "UDP port"
+        );
+    }
+
+    #[test]
+    fn radiotap_layer_mask() {
+        let (packet, header) = get_radiotap_packet();
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_IEEE802_11_RADIOTAP);
+
+        let mask = LayerIndex::RADIOTAP as u32
+            | LayerIndex::WLAN as u32
+            | LayerIndex::IPv4 as u32
+            | LayerIndex::UDP as u32;
+        assert_eq!(pkt.layer_mask(), mask, "Layer mask");
+    }
+
+    #[test]
+    fn icmp_error_fields() {
+        let packet: Vec<u8> = vec![
+            0x00, 0x0c, 0x29, 0x24, 0xb8, 0xaf, 0xe8, 0x1c, 0xba, 0x17, 0x7d, 0x6a, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x38, 0x00, 0x01, 0x00, 0x00, 0x40, 0x01, 0x00, 0x00, 0xc0, 0xa8,
+            0x01, 0x01, 0xc0, 0xa8, 0x01, 0x0a, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x45, 0x00, 0x00, 0x3c, 0x00, 0x01, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01, 0xd4, 0x31, 0x00, 0x35, 0x00, 0x28, 0x00, 0x00,
+        ];
+        let header: [u8; 16] = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x00, 0x00, 0x00, 0x46, 0x00,
+            0x00, 0x00,
+        ];
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_icmp(), "ICMP layer");
+        assert_eq!(
+            pkt.get_field("icmp.orig.ip.src").unwrap().to_u32(),
+            0xc0a8010a,
+            "Original IP source"
+        );
+        assert_eq!(
+            pkt.get_field("icmp.orig.dport").unwrap().to_u16(),
+            53,
+            "Original destination port"
+        );
+    }
+
+    fn get_tcp_packet(sport: u16, dport: u16, payload: &[u8]) -> (Vec<u8>, [u8; 16]) {
+        let mut packet: Vec<u8> = vec![
+            0x00, 0x0c, 0x29, 0x24, 0xb8, 0xaf, 0xe8, 0x1c, 0xba, 0x17, 0x7d, 0x6a, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x28, 0x00, 0x01, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00, 0xc0, 0xa8,
+            0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01,
+        ];
+        packet.extend_from_slice(&sport.to_be_bytes());
+        packet.extend_from_slice(&dport.to_be_bytes());
+        packet.extend_from_slice(&[
+            0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x50, 0x18, 0x20, 0x00, 0x00, 0x00,
+            0x00, 0x00,
+        ]);
+        packet.extend_from_slice(payload);
+
+        let mut header = [0u8; 16];
+        header[8..12].copy_from_slice(&(packet.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(packet.len() as u32).to_le_bytes());
+
+        (packet, header)
+    }
+
+    #[test]
+    fn detection_heuristic() {
+        let (packet, header) =
+            get_tcp_packet(50000, 8080, b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n");
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_http(), "HTTP on port 8080");
+        assert_eq!(
+            pkt.get_field("frame.detection").unwrap().to_string(),
+            "heuristic",
+            "Detection method"
+        );
+        assert_eq!(
+            pkt.get_field("http.uri").unwrap().to_string(),
+            "/index.html",
+            "HTTP uri"
+        );
+    }
+
+    #[test]
+    fn detection_port_fallback() {
+        let (packet, header) = get_tcp_packet(50000, 443, &[]);
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_https(), "Empty segment on port 443");
+        assert_eq!(
+            pkt.get_field("frame.detection").unwrap().to_string(),
+            "port",
+            "Detection method"
+        );
+    }
+
+    #[test]
+    fn detection_ssh_on_https_port() {
+        let (packet, header) = get_tcp_packet(443, 50000, b"SSH-2.0-OpenSSH_9.6\r\n");
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_ssh(), "SSH banner");
+        assert!(!pkt.has_https(), "Not TLS");
+    }
+
+    #[test]
+    fn ssh_banner_fields() {
+        let (packet, header) = get_tcp_packet(22, 50000, b"SSH-2.0-OpenSSH_9.6\r\n");
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert_eq!(
+            pkt.get_field("ssh.server_banner").unwrap().to_string(),
+            "SSH-2.0-OpenSSH_9.6",
+            "Server banner"
+        );
+        assert!(
+            pkt.get_field("ssh.client_banner").is_none(),
+            "No client banner"
+        );
+
+        let (packet, header) = get_tcp_packet(50000, 22, b"SSH-2.0-libssh_0.9.6\r\n");
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert_eq!(
+            pkt.get_field("ssh.client_banner").unwrap().to_string(),
+            "SSH-2.0-libssh_0.9.6",
+            "Client banner"
+        );
+    }
+
+    fn get_udp_packet(sport: u16, dport: u16, payload: &[u8]) -> (Vec<u8>, [u8; 16]) {
+        let mut packet: Vec<u8> = vec![
+            0x00, 0x0c, 0x29, 0x24, 0xb8, 0xaf, 0xe8, 0x1c, 0xba, 0x17, 0x7d, 0x6a, 0x08, 0x00,
+            0x45, 0x00, 0x00, 0x28, 0x00, 0x01, 0x40, 0x00, 0x40, 0x11, 0x00, 0x00, 0xc0, 0xa8,
+            0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01,
+        ];
+        packet.extend_from_slice(&sport.to_be_bytes());
+        packet.extend_from_slice(&dport.to_be_bytes());
+        packet.extend_from_slice(&((payload.len() + 8) as u16).to_be_bytes());
+        packet.extend_from_slice(&[0x00, 0x00]);
+        packet.extend_from_slice(payload);
+
+        let mut header = [0u8; 16];
+        header[8..12].copy_from_slice(&(packet.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&(packet.len() as u32).to_le_bytes());
+
+        (packet, header)
+    }
+
+    #[test]
+    fn malformed_flag() {
+        let (packet, header) = get_udp_packet(50000, 53, &[]);
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_udp(), "UDP layer");
+        assert!(
+            pkt.is_malformed(),
+            "Empty payload on the DNS port is a truncated DNS header"
+        );
+        assert_eq!(pkt.error_layer(), Some("dns"), "Error layer");
+        assert!(!pkt.has_dns(), "No DNS layer");
+
+        let (packet, header) = get_tcp_packet(50000, 8080, b"hello");
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(!pkt.is_malformed(), "Well formed packet");
+        assert_eq!(
+            pkt.get_field("frame.malformed").unwrap().to_u64(),
+            0,
+            "frame.malformed"
+        );
+        assert!(
+            pkt.get_field("frame.error_layer").is_none(),
+            "No error layer"
+        );
+    }
+
+    #[test]
+    fn malformed_tcp_header_len() {
+        let (mut packet, header) = get_tcp_packet(50000, 443, &[]);
+        //--- Data offset of 15 words on a 20 bytes header
+        packet[46] = 0xf0;
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_tcp(), "TCP layer kept");
+        assert!(!pkt.has_https(), "Nothing decoded above TCP");
+        assert_eq!(
+            pkt.get_field("frame.error_layer").unwrap().to_string(),
+            "tcp",
+            "Error layer"
+        );
+        assert_eq!(
+            pkt.get_field("tcp.sport").unwrap().to_u16(),
+            50000,
+            "Source port"
+        );
+    }
+
+    #[test]
+    fn malformed_dns_pointer_loop() {
+        //--- One question whose name is a pointer to itself
+        let payload: Vec<u8> = vec![
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x0c,
+            0x00, 0x01, 0x00, 0x01,
+        ];
+        let (packet, header) = get_udp_packet(50000, 53, &payload);
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+
+        assert!(pkt.has_dns(), "DNS header is readable");
+        assert!(pkt.is_malformed(), "Malformed DNS");
+        assert_eq!(pkt.error_layer(), Some("dns"), "Error layer");
+        assert_eq!(pkt.get_field("dns.id").unwrap().to_u16(), 0x1234, "DNS id");
+    }
+
+    #[test]
+    fn detail_tree() {
+        let (packet, header) =
+            get_tcp_packet(50000, 80, b"GET /index.html HTTP/1.1\r\nHost: a\r\n\r\n");
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_ETHERNET);
+        let tree = pkt.detail();
+
+        let names: Vec<&str> = tree.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["frame", "eth", "ip", "tcp", "http"], "Layers");
+
+        let tcp = tree.layer("tcp").unwrap();
+        assert_eq!((tcp.offset, tcp.len), (34, 20), "TCP header range");
+
+        let sport = tree.field("tcp.sport").unwrap();
+        assert_eq!((sport.offset, sport.len), (Some(34), Some(2)), "Port range");
+        assert_eq!(sport.value, 50000, "Port value");
+        assert_eq!(sport.field_type, "int16", "Port type");
+
+        let eth_type = tree.field("eth.type").unwrap();
+        assert_eq!(eth_type.offset, Some(12), "Ethertype offset");
+
+        let method = tree.field("http.method").unwrap();
+        assert_eq!(method.value, "GET", "HTTP method");
+        assert_eq!(method.offset, None, "No offset on parsed fields");
+
+        assert!(
+            tree.field("frame.timestamp").unwrap().offset.is_none(),
+            "Pcap header"
+        );
+        assert!(
+            pkt.show_detail()
+                .contains("    ip.src: 192.168.1.10/32 (ipv4) [0x001a, 4]"),
+            "Text tree"
+        );
+    }
+
+    #[test]
+    fn detail_wlan_address() {
+        let (packet, header) = get_radiotap_packet();
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_IEEE802_11_RADIOTAP);
+        let tree = pkt.detail();
+
+        let wlan = tree.layer("wlan").unwrap();
+        let bssid = tree.field("wlan.bssid").unwrap();
+
+        assert_eq!(
+            bssid.offset.map(|offset| offset - wlan.offset),
+            Some(4),
+            "ToDS BSSID is address 1"
+        );
+    }
+
+    #[test]
+    fn packet_id_round_trip() {
+        let mut pkt = Packet::new();
+        pkt.file_id = 7;
+        pkt.pkt_ptr = 0x0012_3456;
+        assert_eq!(
+            PacketId::from_u64(pkt.get_id().unwrap()).unwrap(),
+            PacketId::new(7, 0x0012_3456),
+            "Pointer past 64 KiB"
+        );
+
+        pkt.pkt_ptr = 0x1_0000_0040;
+        assert_eq!(
+            PacketId::from_u64(pkt.get_id().unwrap()).unwrap(),
+            PacketId::new(7, 0x1_0000_0040),
+            "Pointer past 4 GiB"
+        );
+        assert_eq!(
+            pkt.get_field("frame.packet_id").unwrap().to_string(),
+            "7:4294967360",
+            "String form"
+        );
+
+        pkt.pkt_ptr = 1 << 40;
+        assert!(pkt.get_id().is_err(), "No numeric form");
+        assert_eq!(
+            pkt.get_field("frame.id").unwrap().to_string(),
+            "7:1099511627776",
+            "frame.id in string form"
+        );
+    }
+
+    #[test]
+    fn timestamp_resolution() {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], 1_700_000_000);
+        LittleEndian::write_u32(&mut header[4..8], 250_000);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(vec![0; 60], header, 1, 24, true, LINKTYPE_ETHERNET);
+        assert_eq!(
+            pkt.get_field("frame.timestamp_ns").unwrap().to_u64(),
+            1_700_000_000_250_000_000,
+            "Microsecond file"
+        );
+
+        pkt.set_ts_nanosecond(true);
+        assert_eq!(
+            pkt.timestamp_ns(),
+            1_700_000_000_000_250_000,
+            "Nanosecond file"
+        );
+    }
+}
@@ -30,7 +30,7 @@ pub struct PktLayer {
 pub struct PacketBuilder {
     layers_list: HashMap<LayerName, PktLayer>,
     pub file_id: u32,
-    pub pkt_ptr: u32,
+    pub pkt_ptr: u64,
     pub raw_packet: Vec<u8>,
     pub header: Vec<u8>,
 }
@@ -0,0 +1,154 @@
+use anyhow::{Result, anyhow};
+use std::fmt;
+use std::str::FromStr;
+
+//--- Bit 63 of a numeric id gives its layout
+const V1_FLAG: u64 = 1 << 63;
+const V1_OFFSET_BITS: u32 = 40;
+const V1_FILE_BITS: u32 = 23;
+const V0_FILE_BITS: u32 = 31;
+
+//------------------------------------------------------------------
+//--- Id of a packet, the file id of its segment and the byte offset of
+//--- its record in the segment. The string form file:offset is exact.
+//--- The numeric form has two layouts:
+//---   v0, bit 63 clear: file id in bits 32-62, offset in bits 0-31,
+//---       the only layout before segments could pass 4 GiB
+//---   v1, bit 63 set: file id in bits 40-62, offset in bits 0-39
+//--- A packet keeps its v0 id while its offset fits in 32 bits, so v1
+//--- ids with an offset below 4 GiB are reserved and rejected
+//------------------------------------------------------------------
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PacketId {
+    pub file_id: u32,
+    pub offset: u64,
+}
+
+impl PacketId {
+    pub fn new(file_id: u32, offset: u64) -> Self {
+        Self { file_id, offset }
+    }
+
+    pub fn to_u64(&self) -> Result<u64> {
+        let file_id = self.file_id as u64;
+
+        if self.offset <= u32::MAX as u64 && file_id >> V0_FILE_BITS == 0 {
+            Ok(file_id << 32 | self.offset)
+        } else if self.offset >> V1_OFFSET_BITS == 0 && file_id >> V1_FILE_BITS == 0 {
+            Ok(V1_FLAG | file_id << V1_OFFSET_BITS | self.offset)
+        } else {
+            Err(anyhow!("Packet id {} has no numeric form", self))
+        }
+    }
+
+    pub fn from_u64(id: u64) -> Result<Self> {
+        if id & V1_FLAG == 0 {
+            return Ok(Self::new((id >> 32) as u32, id & 0xffff_ffff));
+        }
+
+        let offset = id & ((1 << V1_OFFSET_BITS) - 1);
+        if offset <= u32::MAX as u64 {
+            return Err(anyhow!("Reserved packet id: {}", id));
+        }
+
+        Ok(Self::new(
+            ((id & !V1_FLAG) >> V1_OFFSET_BITS) as u32,
+            offset,
+        ))
+    }
+}
+
+impl fmt::Display for PacketId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file_id, self.offset)
+    }
+}
+
+//--- file:offset or a numeric id of either layout
+impl FromStr for PacketId {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim();
+
+        match value.split_once(':') {
+            Some((file_id, offset)) => {
+                let file_id = file_id
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("Invalid file id in packet id: {}", value))?;
+                let offset = offset
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("Invalid offset in packet id: {}", value))?;
+
+                Ok(Self::new(file_id, offset))
+            }
+            None => Self::from_u64(
+                value
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("Invalid packet id: {}", value))?,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_layouts() {
+        let small = PacketId::new(7, 0x0012_3456);
+        assert_eq!(small.to_u64().unwrap(), 7 << 32 | 0x0012_3456, "v0 layout");
+        assert_eq!(
+            PacketId::from_u64(small.to_u64().unwrap()).unwrap(),
+            small,
+            "v0 round trip"
+        );
+
+        let large = PacketId::new(7, 5 << 32);
+        let id = large.to_u64().unwrap();
+        assert!(id & V1_FLAG != 0, "v1 past 4 GiB");
+        assert_eq!(PacketId::from_u64(id).unwrap(), large, "v1 round trip");
+        assert!(
+            PacketId::from_u64(V1_FLAG | 7 << V1_OFFSET_BITS | 0x0012_3456).is_err(),
+            "v1 id of a v0 packet reserved"
+        );
+        assert_eq!(
+            PacketId::from_u64(u64::MAX).unwrap(),
+            PacketId::new((1 << V1_FILE_BITS) - 1, (1 << V1_OFFSET_BITS) - 1),
+            "Largest v1 id"
+        );
+
+        assert!(
+            PacketId::new(1 << V1_FILE_BITS, 5 << 32).to_u64().is_err(),
+            "File id too large for v1"
+        );
+        assert!(
+            PacketId::new(7, 1 << V1_OFFSET_BITS).to_u64().is_err(),
+            "Offset too large for v1"
+        );
+    }
+
+    #[test]
+    fn string_form() {
+        let id = PacketId::new(12, 6_000_000_000);
+        assert_eq!(id.to_string(), "12:6000000000", "Display");
+        assert_eq!("12:6000000000".parse::<PacketId>().unwrap(), id, "Parse");
+        assert_eq!(
+            id.to_u64()
+                .unwrap()
+                .to_string()
+                .parse::<PacketId>()
+                .unwrap(),
+            id,
+            "Numeric id"
+        );
+
+        let reserved = (V1_FLAG | 12 << V1_OFFSET_BITS | 24).to_string();
+        for invalid in [
+            "", "12:", ":24", "12:x", "12:24:36", "-1:24", "abc", &reserved,
+        ] {
+            assert!(invalid.parse::<PacketId>().is_err(), "Invalid: {}", invalid);
+        }
+    }
+}
@@ -34,6 +34,15 @@ pub fn format_hex(bytes: Vec<u8>) -> String {
     result
 }
 
+//--- Line and column of a byte in the format_hex output, used to
+//--- highlight the byte range of a field of the detail tree
+pub fn hex_position(offset: usize) -> (usize, usize) {
+    let column = offset % 16;
+    let separator = if column >= 8 { 1 } else { 0 };
+
+    (offset / 16, 6 + column * 3 + separator)
+}
+
 pub fn print_hex(bytes: Vec<u8>) {
     println!("{}", format_hex(bytes));
 }
@@ -88,6 +97,23 @@ mod tests {
         print_hex(bytes)
     }
 
+    #[test]
+    fn test_hex_position() {
+        let bytes: Vec<u8> = (0..40).collect();
+        let dump = format_hex(bytes);
+        let lines: Vec<&str> = dump.lines().collect();
+
+        for offset in [0, 7, 8, 15, 16, 39] {
+            let (line, column) = hex_position(offset);
+            assert_eq!(
+                &lines[line][column..column + 2],
+                format!("{:02x}", offset),
+                "Byte {} position",
+                offset
+            );
+        }
+    }
+
     #[test]
     fn test_32_chars() {
         let mut bytes: Vec<u8> = Vec::new();
@@ -0,0 +1,364 @@
+use crate::detail::LayerNode;
+use crate::layer::{Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use crate::packet::{LINKTYPE_IEEE802_11, LINKTYPE_IEEE802_11_RADIOTAP};
+use crate::packet_display::PacketDisplay;
+use byteorder::{ByteOrder, LittleEndian};
+use field::field_type;
+use field::pfield::{Field, FieldType};
+
+/*
+Radiotap header (linktype 127)
+
+  0               1               2               3
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |    Version    |      Pad      |            Length             |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |                         Present flags                         |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ |     Fields, each aligned on its natural boundary ...
+ +-+-+-+-+-
+
+ All multi bytes values are little endian. Bit 31 of a present word
+ indicate that another present word follow.
+*/
+
+const PRESENT_TSFT: u32 = 0;
+const PRESENT_FLAGS: u32 = 1;
+const PRESENT_RATE: u32 = 2;
+const PRESENT_CHANNEL: u32 = 3;
+const PRESENT_FHSS: u32 = 4;
+const PRESENT_DBM_SIGNAL: u32 = 5;
+const PRESENT_DBM_NOISE: u32 = 6;
+const PRESENT_EXT: u32 = 31;
+
+const FLAGS_FCS: u8 = 0x10;
+const FCS_LEN: usize = 4;
+
+pub const RADIOTAP_MIN_LEN: usize = 8;
+
+const RADIOTAP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("radiotap.version", field_type::INT8, 0, 1),
+    FieldDef::at("radiotap.len", field_type::INT16, 2, 2),
+    FieldDef::at("radiotap.present", field_type::INT32, 4, 4),
+    FieldDef::new("radiotap.flags", field_type::INT8),
+    FieldDef::new("radiotap.rate", field_type::INT32),
+    FieldDef::new("radiotap.channel", field_type::INT16),
+    FieldDef::new("radiotap.frequency", field_type::INT16),
+    FieldDef::new("radiotap.channel_flags", field_type::INT16),
+    FieldDef::new("radiotap.signal_dbm", field_type::SINT8),
+    FieldDef::new("radiotap.noise_dbm", field_type::SINT8),
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct Radiotap<'a> {
+    raw_packet: &'a [u8],
+    flags: u8,
+    rate: u8,
+    frequency: u16,
+    channel_flags: u16,
+    signal_dbm: Option<i8>,
+    noise_dbm: Option<i8>,
+}
+
+impl<'a> Radiotap<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        let mut slf = Self {
+            raw_packet: packet,
+            ..Default::default()
+        };
+
+        slf.decode_fields();
+
+        slf
+    }
+
+    pub fn version(&self) -> u8 {
+        self.raw_packet[0]
+    }
+
+    pub fn header_len(&self) -> usize {
+        LittleEndian::read_u16(&self.raw_packet[2..4]) as usize
+    }
+
+    pub fn present(&self) -> u32 {
+        LittleEndian::read_u32(&self.raw_packet[4..8])
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    pub fn has_fcs(&self) -> bool {
+        self.flags & FLAGS_FCS == FLAGS_FCS
+    }
+
+    //--- Data rate in kbps, radiotap store it in 500kbps units
+    pub fn rate(&self) -> u32 {
+        self.rate as u32 * 500
+    }
+
+    pub fn frequency(&self) -> u16 {
+        self.frequency
+    }
+
+    pub fn channel_flags(&self) -> u16 {
+        self.channel_flags
+    }
+
+    pub fn channel(&self) -> u16 {
+        freq_to_channel(self.frequency)
+    }
+
+    pub fn signal_dbm(&self) -> Option<i8> {
+        self.signal_dbm
+    }
+
+    pub fn noise_dbm(&self) -> Option<i8> {
+        self.noise_dbm
+    }
+
+    //----------------------------------------------------
+    //--- Walk the present bitmap up to the fields we need
+    //----------------------------------------------------
+    fn decode_fields(&mut self) {
+        let hdr_len = self.header_len().min(self.raw_packet.len());
+        let present = self.present();
+
+        //--- Skip the extended present words
+        let mut ptr: usize = 4;
+        while ptr + 4 <= hdr_len
+            && LittleEndian::read_u32(&self.raw_packet[ptr..ptr + 4]) & (1 << PRESENT_EXT) != 0
+        {
+            ptr += 4;
+        }
+        ptr += 4;
+
+        for bit in PRESENT_TSFT..=PRESENT_DBM_NOISE {
+            if present & (1 << bit) == 0 {
+                continue;
+            }
+
+            let (align, size) = match bit {
+                PRESENT_TSFT => (8, 8),
+                PRESENT_FLAGS => (1, 1),
+                PRESENT_RATE => (1, 1),
+                PRESENT_CHANNEL => (2, 4),
+                PRESENT_FHSS => (1, 2),
+                _ => (1, 1),
+            };
+
+            ptr = ptr.next_multiple_of(align);
+            if ptr + size > hdr_len {
+                break;
+            }
+
+            match bit {
+                PRESENT_FLAGS => self.flags = self.raw_packet[ptr],
+                PRESENT_RATE => self.rate = self.raw_packet[ptr],
+                PRESENT_CHANNEL => {
+                    self.frequency = LittleEndian::read_u16(&self.raw_packet[ptr..ptr + 2]);
+                    self.channel_flags = LittleEndian::read_u16(&self.raw_packet[ptr + 2..ptr + 4]);
+                }
+                PRESENT_DBM_SIGNAL => self.signal_dbm = Some(self.raw_packet[ptr] as i8),
+                PRESENT_DBM_NOISE => self.noise_dbm = Some(self.raw_packet[ptr] as i8),
+                _ => {}
+            }
+
+            ptr += size;
+        }
+    }
+}
+
+fn freq_to_channel(freq: u16) -> u16 {
+    match freq {
+        2484 => 14,
+        2412..=2472 => (freq - 2407) / 5,
+        5000..=5895 => (freq - 5000) / 5,
+        5955..=7115 => (freq - 5950) / 5,
+        _ => 0,
+    }
+}
+
+impl<'a> Layer for Radiotap<'a> {
+    fn get_name(&self) -> String {
+        "radiotap".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "radiotap.version" => Some(Field::set_field(FieldType::Int8(self.version()), field)),
+            "radiotap.len" => Some(Field::set_field(
+                FieldType::Int16(self.header_len() as u16),
+                field,
+            )),
+            "radiotap.present" => Some(Field::set_field(FieldType::Int32(self.present()), field)),
+            "radiotap.flags" => Some(Field::set_field(FieldType::Int8(self.flags()), field)),
+            "radiotap.rate" => Some(Field::set_field(FieldType::Int32(self.rate()), field)),
+            "radiotap.channel" => Some(Field::set_field(FieldType::Int16(self.channel()), field)),
+            "radiotap.frequency" => {
+                Some(Field::set_field(FieldType::Int16(self.frequency()), field))
+            }
+            "radiotap.channel_flags" => Some(Field::set_field(
+                FieldType::Int16(self.channel_flags()),
+                field,
+            )),
+            "radiotap.signal_dbm" => self
+                .signal_dbm()
+                .map(|dbm| Field::set_field(FieldType::SInt8(dbm), field)),
+            "radiotap.noise_dbm" => self
+                .noise_dbm()
+                .map(|dbm| Field::set_field(FieldType::SInt8(dbm), field)),
+            _ => None,
+        }
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RadiotapDissector;
+
+impl Dissector for RadiotapDissector {
+    fn name(&self) -> &'static str {
+        "radiotap"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::RADIOTAP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::LinkType(LINKTYPE_IEEE802_11_RADIOTAP)]
+    }
+
+    fn min_len(&self) -> usize {
+        RADIOTAP_MIN_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        RADIOTAP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Radiotap::new(packet))
+    }
+
+    //--- The FCS, when present, is appended after the 802.11 frame
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let radiotap = Radiotap::new(packet);
+
+        Some(Dispatch {
+            offset: radiotap.header_len(),
+            trailer: if radiotap.has_fcs() { FCS_LEN } else { 0 },
+            triggers: vec![Trigger::LinkType(LINKTYPE_IEEE802_11)],
+        })
+    }
+}
+
+impl<'a> PacketDisplay for Radiotap<'a> {
+    fn summary(&self) -> String {
+        format!(
+            "Radiotap -> Channel: {} ({} MHz), Rate: {}kbps, Signal: {}dBm\n",
+            self.channel(),
+            self.frequency(),
+            self.rate(),
+            self.signal_dbm().unwrap_or_default(),
+        )
+    }
+
+    fn show_detail(&self) -> String {
+        LayerNode::of(
+            RadiotapDissector.name(),
+            RadiotapDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_packet() -> Vec<u8> {
+        let packet: Vec<u8> = vec![
+            0x00, 0x00, 0x0f, 0x00, 0x2e, 0x00, 0x00, 0x00, 0x10, 0x02, 0x85, 0x09, 0xa0, 0x00,
+            0xbd,
+        ];
+
+        packet
+    }
+
+    #[test]
+    fn radiotap_header_len() {
+        let packet = get_packet();
+        let pkt = Radiotap::new(&packet);
+
+        assert_eq!(pkt.header_len(), 15, "Radiotap length");
+        assert!(pkt.has_fcs(), "FCS flag");
+    }
+
+    #[test]
+    fn radiotap_channel() {
+        let packet = get_packet();
+        let pkt = Radiotap::new(&packet);
+
+        assert_eq!(pkt.frequency(), 2437, "Frequency");
+        assert_eq!(pkt.channel(), 6, "Channel");
+    }
+
+    #[test]
+    fn radiotap_rate() {
+        let packet = get_packet();
+        let pkt = Radiotap::new(&packet);
+
+        assert_eq!(pkt.rate(), 1000, "Rate in kbps");
+    }
+
+    #[test]
+    fn radiotap_signal() {
+        let packet = get_packet();
+        let pkt = Radiotap::new(&packet);
+
+        assert_eq!(pkt.signal_dbm(), Some(-67), "Signal dBm");
+        assert_eq!(pkt.noise_dbm(), None, "Noise dBm");
+    }
+
+    #[test]
+    fn radiotap_show_detail() {
+        let packet = get_packet();
+        let detail = Radiotap::new(&packet).show_detail();
+
+        assert!(
+            detail.starts_with("radiotap [0x0000, 15 bytes]"),
+            "Layer line: {}",
+            detail
+        );
+        assert!(
+            detail.contains("radiotap.len: 15 (int16) [0x0002, 2]"),
+            "Fixed field range: {}",
+            detail
+        );
+        assert!(
+            detail.contains("radiotap.signal_dbm: -67"),
+            "Signed field: {}",
+            detail
+        );
+    }
+
+    #[test]
+    fn radiotap_extended_present() {
+        //--- Two present words, flags and rate in the first one
+        let packet: Vec<u8> = vec![
+            0x00, 0x00, 0x0e, 0x00, 0x06, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c,
+        ];
+        let pkt = Radiotap::new(&packet);
+
+        assert_eq!(pkt.flags(), 0, "Flags");
+        assert_eq!(pkt.rate(), 6000, "Rate in kbps");
+    }
+}
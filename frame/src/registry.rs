@@ -0,0 +1,165 @@
+use crate::arp::ArpDissector;
+use crate::dhcp::DhcpDissector;
+use crate::dns::DnsDissector;
+use crate::eth::EthDissector;
+use crate::frame::FRAME_FIELDS;
+use crate::http::HttpDissector;
+use crate::icmp::IcmpDissector;
+use crate::ip::IpDissector;
+use crate::layer::{Detection, Dissector, FieldDef, Trigger};
+use crate::layer_index::LayerIndex;
+use crate::ntp::NtpDissector;
+use crate::radiotap::RadiotapDissector;
+use crate::smb::SmbDissector;
+use crate::ssh::SshDissector;
+use crate::tcp::TcpDissector;
+use crate::tls::TlsDissector;
+use crate::udp::UdpDissector;
+use crate::wlan::WlanDissector;
+
+pub const FRAME_NAME: &str = "frame";
+
+//--------------------------------------------------------------
+//--- Every dissector known to the packet decoder, adding a
+//--- protocol is a matter of adding its dissector to this list
+//--------------------------------------------------------------
+static DISSECTORS: &[&dyn Dissector] = &[
+    &EthDissector,
+    &RadiotapDissector,
+    &WlanDissector,
+    &ArpDissector,
+    &IpDissector,
+    &TcpDissector,
+    &UdpDissector,
+    &IcmpDissector,
+    &DnsDissector,
+    &DhcpDissector,
+    &NtpDissector,
+    &TlsDissector,
+    &HttpDissector,
+    &SshDissector,
+    &SmbDissector,
+];
+
+pub fn dissectors() -> &'static [&'static dyn Dissector] {
+    DISSECTORS
+}
+
+pub fn by_name(name: &str) -> Option<&'static dyn Dissector> {
+    DISSECTORS.iter().find(|d| d.name() == name).copied()
+}
+
+pub fn by_layer(layer: &LayerIndex) -> Option<&'static dyn Dissector> {
+    DISSECTORS.iter().find(|d| d.layer() == *layer).copied()
+}
+
+//--- Protocol part of a field name, ex: "icmp" for icmp.orig.ip.src
+pub fn field_proto(field: &str) -> &str {
+    field.split('.').next().unwrap_or_default()
+}
+
+//--- Layer a field belongs to, used to build the search index
+pub fn field_layer(field: &str) -> Option<LayerIndex> {
+    match field_proto(field) {
+        FRAME_NAME => Some(LayerIndex::FRAME),
+        proto => by_name(proto).map(|d| d.layer()),
+    }
+}
+
+//--- Child dissector, the payload heuristics are tried first then the
+//--- triggers in order, the port triggers being the fallback
+pub fn find_child(
+    parent: &LayerIndex,
+    triggers: &[Trigger],
+    payload: &[u8],
+) -> Option<(&'static dyn Dissector, Detection)> {
+    if let Some(dissector) = DISSECTORS.iter().find(|d| d.heuristic(parent, payload)) {
+        return Some((*dissector, Detection::Heuristic));
+    }
+
+    for trigger in triggers {
+        if let Some(dissector) = DISSECTORS.iter().find(|d| d.triggers().contains(trigger)) {
+            return Some((*dissector, trigger.detection()));
+        }
+    }
+
+    None
+}
+
+pub fn field_defs() -> impl Iterator<Item = &'static FieldDef> {
+    FRAME_FIELDS
+        .iter()
+        .chain(DISSECTORS.iter().flat_map(|d| d.fields().iter()))
+}
+
+pub fn field_def(field: &str) -> Option<&'static FieldDef> {
+    field_defs().find(|f| f.name == field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_unique_names() {
+        for dissector in dissectors() {
+            let count = dissectors()
+                .iter()
+                .filter(|d| d.name() == dissector.name() || d.layer() == dissector.layer())
+                .count();
+
+            assert_eq!(count, 1, "Duplicate dissector {}", dissector.name());
+        }
+    }
+
+    #[test]
+    fn registry_fields_prefix() {
+        for dissector in dissectors() {
+            for field in dissector.fields() {
+                assert_eq!(field_proto(field.name), dissector.name(), "{}", field.name);
+            }
+        }
+    }
+
+    #[test]
+    fn registry_field_layer() {
+        assert_eq!(field_layer("ip.src"), Some(LayerIndex::IPv4));
+        assert_eq!(field_layer("icmp.orig.ip.src"), Some(LayerIndex::ICMP));
+        assert_eq!(field_layer("frame.timestamp"), Some(LayerIndex::FRAME));
+        assert_eq!(field_layer("foo.bar"), None);
+    }
+
+    #[test]
+    fn registry_find_child() {
+        let (udp, detection) =
+            find_child(&LayerIndex::IPv4, &[Trigger::IpProto(0x11)], &[]).unwrap();
+        assert_eq!(udp.layer(), LayerIndex::UDP);
+        assert_eq!(detection, Detection::Header);
+
+        let (dns, detection) = find_child(
+            &LayerIndex::UDP,
+            &[Trigger::UdpPort(40000), Trigger::UdpPort(53)],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(dns.layer(), LayerIndex::DNS);
+        assert_eq!(detection, Detection::Port);
+
+        assert!(find_child(&LayerIndex::TCP, &[Trigger::TcpPort(40000)], &[]).is_none());
+    }
+
+    #[test]
+    fn registry_heuristic_before_port() {
+        let payload = b"SSH-2.0-OpenSSH_9.6\r\n";
+
+        let (ssh, detection) =
+            find_child(&LayerIndex::TCP, &[Trigger::TcpPort(443)], payload).unwrap();
+        assert_eq!(ssh.layer(), LayerIndex::SSH);
+        assert_eq!(detection, Detection::Heuristic);
+
+        let (tls, detection) =
+            find_child(&LayerIndex::TCP, &[Trigger::TcpPort(443)], &[0x17]).unwrap();
+        assert_eq!(tls.layer(), LayerIndex::HTTPS);
+        assert_eq!(detection, Detection::Port);
+    }
+}
@@ -1,25 +1,32 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{FieldDef, Layer};
 use crate::packet_display::PacketDisplay;
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
+//--- Request line fields, SIP is not a registered dissector
+const SIP_FIELDS: &[FieldDef] = &[
+    FieldDef::new("method", field_type::STRING),
+    FieldDef::new("request_uri", field_type::STRING),
+    FieldDef::new("version", field_type::STRING),
+];
+
 // use byteorder::{BigEndian, ByteOrder};
 
 fn process_method(packet: &[u8]) -> (String, String, String) {
-    // let mut cmd_line: Vec<String> = Vec::new();
-    let mut temp_str = String::new();
-    let mut cmd: Vec<&str> = Vec::new();
-
-    for b in packet {
-        if *b != b'\r' {
-            temp_str.push(*b as char);
-        } else {
-            cmd = temp_str.split(' ').collect();
-            break;
-        }
-    }
-
-    println!("Requests: {}:{:?}", cmd.len(), cmd);
-    (cmd[0].to_string(), cmd[1].to_string(), cmd[2].to_string())
+    let end = packet
+        .iter()
+        .position(|b| *b == b'\r')
+        .unwrap_or(packet.len());
+    let line = String::from_utf8_lossy(&packet[..end]);
+    let mut cmd = line.split(' ').map(|part| part.to_string());
+
+    //--- Missing parts of a truncated request line are left empty
+    (
+        cmd.next().unwrap_or_default(),
+        cmd.next().unwrap_or_default(),
+        cmd.next().unwrap_or_default(),
+    )
 }
 
 #[derive(Default, Debug, Clone)]
@@ -54,7 +61,10 @@ impl<'a> Sip<'a> {
     }
 
     pub fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
-        self.raw_packet[offset..offset + len].to_vec()
+        self.raw_packet
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+            .to_vec()
     }
 }
 
@@ -86,7 +96,7 @@ impl<'a> PacketDisplay for Sip<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "ICMP Echo Detail".to_string()
+        LayerNode::of("sip", SIP_FIELDS, self, self.raw_packet.len()).to_string()
     }
 }
 
@@ -164,6 +174,17 @@ mod tests {
         );
     }
 
+    #[test]
+    fn sip_truncated_request_line() {
+        let pkt = Sip::new(b"OPTIONS\r\n");
+
+        assert_eq!(pkt.method(), "OPTIONS", "Method");
+        assert_eq!(pkt.request_uri(), "", "No request URI");
+        assert_eq!(pkt.version(), "", "No version");
+
+        assert_eq!(Sip::new(&[]).method(), "", "Empty packet");
+    }
+
     #[test]
     fn sip_version() {
         let packet = get_packet();
@@ -0,0 +1,164 @@
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use byteorder::{ByteOrder, LittleEndian};
+use field::field_type;
+use field::pfield::{Field, FieldType};
+
+/*
+SMB over direct TCP (445) or NetBIOS session (139)
+
+ +--------+--------------------------+-----------------------+
+ |  Type  |  Length (24 bits)        | 0xFF|0xFE|0xFD 'SMB'  | ...
+ +--------+--------------------------+-----------------------+
+     1                 3                        4
+*/
+
+const SMB_PORT: u16 = 445;
+const NETBIOS_PORT: u16 = 139;
+
+const NETBIOS_SESSION_MESSAGE: u8 = 0x00;
+const NETBIOS_HEADER_LEN: usize = 4;
+const SMB_MAGIC_LEN: usize = 4;
+
+const SMB1_MAGIC: [u8; 4] = [0xff, b'S', b'M', b'B'];
+const SMB2_MAGIC: [u8; 4] = [0xfe, b'S', b'M', b'B'];
+const SMB3_TRANSFORM_MAGIC: [u8; 4] = [0xfd, b'S', b'M', b'B'];
+
+const SMB1_COMMAND_OFFSET: usize = 4;
+const SMB2_COMMAND_OFFSET: usize = 12;
+
+const SMB_FIELDS: &[FieldDef] = &[
+    FieldDef::new("smb.version", field_type::INT8),
+    FieldDef::new("smb.command", field_type::INT16),
+];
+
+fn smb_header(payload: &[u8]) -> Option<&[u8]> {
+    if payload.len() < NETBIOS_HEADER_LEN + SMB_MAGIC_LEN || payload[0] != NETBIOS_SESSION_MESSAGE {
+        return None;
+    }
+
+    Some(&payload[NETBIOS_HEADER_LEN..])
+}
+
+pub fn is_smb(payload: &[u8]) -> bool {
+    smb_header(payload).is_some_and(|hdr| {
+        let magic = &hdr[..SMB_MAGIC_LEN];
+        magic == SMB1_MAGIC || magic == SMB2_MAGIC || magic == SMB3_TRANSFORM_MAGIC
+    })
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Smb<'a> {
+    raw_packet: &'a [u8],
+}
+
+impl<'a> Smb<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self { raw_packet: packet }
+    }
+
+    pub fn version(&self) -> Option<u8> {
+        let hdr = smb_header(self.raw_packet)?;
+
+        match <[u8; SMB_MAGIC_LEN]>::try_from(&hdr[..SMB_MAGIC_LEN]).ok()? {
+            SMB1_MAGIC => Some(1),
+            SMB2_MAGIC => Some(2),
+            SMB3_TRANSFORM_MAGIC => Some(3),
+            _ => None,
+        }
+    }
+
+    pub fn command(&self) -> Option<u16> {
+        let hdr = smb_header(self.raw_packet)?;
+
+        match self.version()? {
+            1 if hdr.len() > SMB1_COMMAND_OFFSET => Some(hdr[SMB1_COMMAND_OFFSET] as u16),
+            2 if hdr.len() >= SMB2_COMMAND_OFFSET + 2 => Some(LittleEndian::read_u16(
+                &hdr[SMB2_COMMAND_OFFSET..SMB2_COMMAND_OFFSET + 2],
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Layer for Smb<'a> {
+    fn get_name(&self) -> String {
+        "smb".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "smb.version" => self
+                .version()
+                .map(|version| Field::set_field(FieldType::Int8(version), field)),
+            "smb.command" => self
+                .command()
+                .map(|cmd| Field::set_field(FieldType::Int16(cmd), field)),
+            _ => None,
+        }
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SmbDissector;
+
+impl Dissector for SmbDissector {
+    fn name(&self) -> &'static str {
+        "smb"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::SMB
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::TcpPort(SMB_PORT), Trigger::TcpPort(NETBIOS_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        0
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        SMB_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Smb::new(packet))
+    }
+
+    fn heuristic(&self, parent: &LayerIndex, payload: &[u8]) -> bool {
+        *parent == LayerIndex::TCP && is_smb(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smb2_negotiate() {
+        let payload: Vec<u8> = vec![
+            0x00, 0x00, 0x00, 0x40, 0xfe, 0x53, 0x4d, 0x42, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        let smb = Smb::new(&payload);
+
+        assert!(is_smb(&payload), "SMB2 magic");
+        assert_eq!(smb.version(), Some(2), "SMB version");
+        assert_eq!(smb.command(), Some(0), "Negotiate command");
+    }
+
+    #[test]
+    fn smb_reject() {
+        assert!(
+            !is_smb(&[0x00, 0x00, 0x00, 0x10, 0x00, 0x53, 0x4d, 0x42]),
+            "Bad magic"
+        );
+        assert!(!is_smb(&[0x00, 0x00]), "Truncated");
+    }
+}
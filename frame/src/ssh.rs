@@ -0,0 +1,409 @@
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
+use field::pfield::{Field, FieldType};
+
+/*
+SSH binary packet, RFC 4253 section 6, carrying a KEXINIT (section 7.1)
+
+ +----------------+---------+------+--------------+--------------------------+
+ | Packet length  | Padding | Code |    Cookie    | 10 x (uint32 len, names) | ...
+ +----------------+---------+------+--------------+--------------------------+
+         4             1        1        16
+*/
+
+pub const SSH_PORT: u16 = 22;
+const BANNER_PREFIX: &[u8] = b"SSH-";
+const BANNER_VERSIONS: [&[u8]; 3] = [b"SSH-2.0-", b"SSH-1.99-", b"SSH-1.5-"];
+const MAX_BANNER_LEN: usize = 255;
+
+const SSH_MSG_KEXINIT: u8 = 20;
+const PACKET_HEADER_LEN: usize = 5;
+const COOKIE_LEN: usize = 16;
+const MAX_PACKET_LEN: usize = 35000;
+
+//--- Name-lists of a KEXINIT, in wire order
+const KEX_ALGORITHMS: usize = 0;
+const HOST_KEY_ALGORITHMS: usize = 1;
+const ENCRYPTION_C2S: usize = 2;
+const ENCRYPTION_S2C: usize = 3;
+const MAC_C2S: usize = 4;
+const MAC_S2C: usize = 5;
+const COMPRESSION_C2S: usize = 6;
+const COMPRESSION_S2C: usize = 7;
+const NAME_LIST_COUNT: usize = 10;
+
+const SSH_FIELDS: &[FieldDef] = &[
+    FieldDef::new("ssh.banner", field_type::STRING),
+    FieldDef::new("ssh.client_banner", field_type::STRING),
+    FieldDef::new("ssh.server_banner", field_type::STRING),
+    FieldDef::new("ssh.kex_algorithms", field_type::STRING),
+    FieldDef::new("ssh.host_key_algorithms", field_type::STRING),
+    FieldDef::new("ssh.encryption_c2s", field_type::STRING),
+    FieldDef::new("ssh.encryption_s2c", field_type::STRING),
+    FieldDef::new("ssh.mac_c2s", field_type::STRING),
+    FieldDef::new("ssh.mac_s2c", field_type::STRING),
+    FieldDef::new("ssh.compression_c2s", field_type::STRING),
+    FieldDef::new("ssh.compression_s2c", field_type::STRING),
+    FieldDef::new("ssh.hassh", field_type::STRING),
+    FieldDef::new("ssh.hassh_server", field_type::STRING),
+];
+
+//--- Protocol version exchange, RFC 4253 section 4.2
+pub fn is_ssh_banner(payload: &[u8]) -> bool {
+    BANNER_VERSIONS.iter().any(|v| payload.starts_with(v))
+}
+
+//--- Segment sent by the server: from the SSH port, or from the lowest
+//--- port when neither side uses it
+pub fn is_from_server(sport: u16, dport: u16) -> bool {
+    sport == SSH_PORT || (dport != SSH_PORT && sport < dport)
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Ssh<'a> {
+    raw_packet: &'a [u8],
+    from_server: bool,
+}
+
+impl<'a> Ssh<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self {
+            raw_packet: packet,
+            from_server: false,
+        }
+    }
+
+    pub fn with_direction(packet: &'a [u8], from_server: bool) -> Self {
+        Self {
+            raw_packet: packet,
+            from_server,
+        }
+    }
+
+    //--- Identification string without the CR LF
+    pub fn banner(&self) -> Option<String> {
+        if !self.raw_packet.starts_with(BANNER_PREFIX) {
+            return None;
+        }
+
+        let window = &self.raw_packet[..self.raw_packet.len().min(MAX_BANNER_LEN)];
+        let end = window
+            .iter()
+            .position(|b| *b == b'\r' || *b == b'\n')
+            .unwrap_or(window.len());
+
+        Some(String::from_utf8_lossy(&window[..end]).to_string())
+    }
+
+    pub fn client_banner(&self) -> Option<String> {
+        self.banner().filter(|_| !self.from_server)
+    }
+
+    pub fn server_banner(&self) -> Option<String> {
+        self.banner().filter(|_| self.from_server)
+    }
+
+    //--- Binary packets start after the banner line when both share the segment
+    fn binary_packet(&self) -> &'a [u8] {
+        if !self.raw_packet.starts_with(BANNER_PREFIX) {
+            return self.raw_packet;
+        }
+
+        match self.raw_packet.iter().position(|b| *b == b'\n') {
+            Some(pos) => &self.raw_packet[pos + 1..],
+            None => &[],
+        }
+    }
+
+    pub fn kex_init(&self) -> Option<KexInit<'a>> {
+        KexInit::parse(self.binary_packet())
+    }
+
+    //--- HASSH of the client KEXINIT
+    pub fn hassh(&self) -> Option<String> {
+        if self.from_server {
+            return None;
+        }
+
+        self.kex_init().map(|kex| kex.hassh())
+    }
+
+    //--- HASSHServer of the server KEXINIT
+    pub fn hassh_server(&self) -> Option<String> {
+        if !self.from_server {
+            return None;
+        }
+
+        self.kex_init().map(|kex| kex.hassh_server())
+    }
+
+    fn name_list(&self, index: usize) -> Option<String> {
+        self.kex_init().map(|kex| kex.name_lists[index].to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct KexInit<'a> {
+    name_lists: [&'a str; NAME_LIST_COUNT],
+}
+
+impl<'a> KexInit<'a> {
+    pub fn parse(packet: &'a [u8]) -> Option<Self> {
+        if packet.len() < PACKET_HEADER_LEN + 1 + COOKIE_LEN {
+            return None;
+        }
+
+        let packet_len = BigEndian::read_u32(&packet[0..4]) as usize;
+        if packet_len > MAX_PACKET_LEN || packet[PACKET_HEADER_LEN] != SSH_MSG_KEXINIT {
+            return None;
+        }
+
+        //--- The name-lists may be truncated by the capture, they are
+        //--- bounded by the packet length and the captured bytes
+        let end = packet.len().min(4 + packet_len);
+        let mut pos = PACKET_HEADER_LEN + 1 + COOKIE_LEN;
+        let mut name_lists: [&str; NAME_LIST_COUNT] = [""; NAME_LIST_COUNT];
+
+        for name_list in name_lists.iter_mut() {
+            if pos + 4 > end {
+                return None;
+            }
+
+            let len = BigEndian::read_u32(&packet[pos..pos + 4]) as usize;
+            pos += 4;
+            if len > end - pos {
+                return None;
+            }
+
+            *name_list = std::str::from_utf8(&packet[pos..pos + len]).ok()?;
+            pos += len;
+        }
+
+        Some(Self { name_lists })
+    }
+
+    pub fn kex_algorithms(&self) -> &'a str {
+        self.name_lists[KEX_ALGORITHMS]
+    }
+
+    //--- md5 of "kex;encryption;mac;compression" client to server
+    pub fn hassh(&self) -> String {
+        self.fingerprint(ENCRYPTION_C2S, MAC_C2S, COMPRESSION_C2S)
+    }
+
+    //--- md5 of "kex;encryption;mac;compression" server to client
+    pub fn hassh_server(&self) -> String {
+        self.fingerprint(ENCRYPTION_S2C, MAC_S2C, COMPRESSION_S2C)
+    }
+
+    fn fingerprint(&self, encryption: usize, mac: usize, compression: usize) -> String {
+        let algorithms = [
+            self.name_lists[KEX_ALGORITHMS],
+            self.name_lists[encryption],
+            self.name_lists[mac],
+            self.name_lists[compression],
+        ]
+        .join(";");
+
+        format!("{:x}", md5::compute(algorithms))
+    }
+}
+
+impl<'a> Layer for Ssh<'a> {
+    fn get_name(&self) -> String {
+        "ssh".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "ssh.banner" => self.banner(),
+            "ssh.client_banner" => self.client_banner(),
+            "ssh.server_banner" => self.server_banner(),
+            "ssh.kex_algorithms" => self.name_list(KEX_ALGORITHMS),
+            "ssh.host_key_algorithms" => self.name_list(HOST_KEY_ALGORITHMS),
+            "ssh.encryption_c2s" => self.name_list(ENCRYPTION_C2S),
+            "ssh.encryption_s2c" => self.name_list(ENCRYPTION_S2C),
+            "ssh.mac_c2s" => self.name_list(MAC_C2S),
+            "ssh.mac_s2c" => self.name_list(MAC_S2C),
+            "ssh.compression_c2s" => self.name_list(COMPRESSION_C2S),
+            "ssh.compression_s2c" => self.name_list(COMPRESSION_S2C),
+            "ssh.hassh" => self.hassh(),
+            "ssh.hassh_server" => self.hassh_server(),
+            _ => None,
+        }
+        .map(|value| Field::set_field(FieldType::String(value), field))
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SshDissector;
+
+impl Dissector for SshDissector {
+    fn name(&self) -> &'static str {
+        "ssh"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::SSH
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::TcpPort(SSH_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        0
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        SSH_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Ssh::new(packet))
+    }
+
+    fn heuristic(&self, parent: &LayerIndex, payload: &[u8]) -> bool {
+        *parent == LayerIndex::TCP && is_ssh_banner(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_banner() {
+        let payload = b"SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13\r\n";
+
+        assert!(is_ssh_banner(payload), "SSH banner");
+        assert_eq!(
+            Ssh::new(payload).banner(),
+            Some("SSH-2.0-OpenSSH_9.6p1 Ubuntu-3ubuntu13".to_string()),
+            "Banner text"
+        );
+    }
+
+    fn get_kex_init() -> Vec<u8> {
+        let name_lists = [
+            "curve25519-sha256,diffie-hellman-group14-sha256",
+            "ssh-ed25519",
+            "aes128-ctr,aes256-gcm@openssh.com",
+            "aes128-ctr",
+            "hmac-sha2-256",
+            "hmac-sha2-256",
+            "none",
+            "none,zlib@openssh.com",
+            "",
+            "",
+        ];
+
+        let mut payload: Vec<u8> = vec![SSH_MSG_KEXINIT];
+        payload.extend_from_slice(&[0x5a; COOKIE_LEN]);
+        for name_list in name_lists {
+            payload.extend_from_slice(&(name_list.len() as u32).to_be_bytes());
+            payload.extend_from_slice(name_list.as_bytes());
+        }
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+
+        let padding = 4;
+        let mut packet: Vec<u8> = ((payload.len() + padding + 1) as u32)
+            .to_be_bytes()
+            .to_vec();
+        packet.push(padding as u8);
+        packet.extend_from_slice(&payload);
+        packet.extend_from_slice(&[0; 4]);
+
+        packet
+    }
+
+    #[test]
+    fn ssh_banner_direction() {
+        let payload = b"SSH-2.0-libssh_0.9.6\r\n";
+
+        assert_eq!(
+            Ssh::with_direction(payload, false).client_banner(),
+            Some("SSH-2.0-libssh_0.9.6".to_string()),
+            "Client banner"
+        );
+        assert_eq!(
+            Ssh::with_direction(payload, false).server_banner(),
+            None,
+            "No server banner from client"
+        );
+        assert!(is_from_server(22, 50000), "Server port");
+        assert!(!is_from_server(50000, 22), "Client port");
+        assert!(is_from_server(2222, 50000), "Lowest port");
+    }
+
+    #[test]
+    fn ssh_kex_init() {
+        let packet = get_kex_init();
+        let client = Ssh::with_direction(&packet, false);
+        let server = Ssh::with_direction(&packet, true);
+
+        assert_eq!(
+            client.get_field("ssh.kex_algorithms").unwrap().to_string(),
+            "curve25519-sha256,diffie-hellman-group14-sha256",
+            "KEX algorithms"
+        );
+        assert_eq!(
+            client
+                .get_field("ssh.host_key_algorithms")
+                .unwrap()
+                .to_string(),
+            "ssh-ed25519",
+            "Host key algorithms"
+        );
+        assert_eq!(
+            client.hassh(),
+            Some("b21aaabfc9d2e45bafeef69aa7e7fada".to_string()),
+            "HASSH"
+        );
+        assert_eq!(client.hassh_server(), None, "No HASSHServer from client");
+        assert_eq!(
+            server.hassh_server(),
+            Some("f8a56ebd9a68241d0999d22f6a434103".to_string()),
+            "HASSHServer"
+        );
+    }
+
+    #[test]
+    fn ssh_kex_init_after_banner() {
+        let mut packet = b"SSH-2.0-OpenSSH_9.6\r\n".to_vec();
+        packet.extend_from_slice(&get_kex_init());
+        let ssh = Ssh::new(&packet);
+
+        assert_eq!(
+            ssh.banner(),
+            Some("SSH-2.0-OpenSSH_9.6".to_string()),
+            "Banner"
+        );
+        assert!(ssh.kex_init().is_some(), "KEXINIT after banner");
+    }
+
+    #[test]
+    fn ssh_kex_init_truncated() {
+        let packet = get_kex_init();
+
+        for len in 0..packet.len() - 9 {
+            assert!(
+                KexInit::parse(&packet[..len]).is_none(),
+                "Truncated at {}",
+                len
+            );
+        }
+    }
+
+    #[test]
+    fn ssh_reject() {
+        assert!(!is_ssh_banner(b"SSH-3.0-foo\r\n"), "Unknown version");
+        assert!(!is_ssh_banner(b"HTTP/1.1 200 OK\r\n"), "HTTP");
+    }
+}
@@ -1,7 +1,10 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{DecodeError, Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
 
 use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
 const OPTION_EOL: u16 = 0;
@@ -12,6 +15,37 @@ const OPTION_SACK: u16 = 4;
 const OPTION_SACK_OPT: u16 = 5;
 const OPTION_TIMESTAMP: u16 = 8;
 
+const IP_TCP_PROTO: u8 = 0x06;
+const TCP_MIN_HEADER_LEN: usize = 20;
+
+const TCP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("tcp.sport", field_type::INT16, 0, 2),
+    FieldDef::at("tcp.dport", field_type::INT16, 2, 2),
+    FieldDef::at("tcp.ackno", field_type::INT32, 8, 4),
+    FieldDef::at("tcp.seqno", field_type::INT32, 4, 4),
+    FieldDef::at("tcp.flags_ack", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.flags_push", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.flags_syn", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.flags_reset", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.flags_fin", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.flags_urg", field_type::BOOL, 13, 1),
+    FieldDef::at("tcp.winsize", field_type::INT16, 14, 2),
+    FieldDef::at("tcp.hdrlen", field_type::INT8, 12, 1),
+    FieldDef::new("tcp.payload_len", field_type::INT16),
+    FieldDef::new("tcp.options_wscale", field_type::INT8),
+    FieldDef::new("tcp.options_wscale.multiplier", field_type::INT16),
+    FieldDef::new("tcp.options_sack", field_type::BOOL),
+    FieldDef::new("tcp.options_sack.count", field_type::INT16),
+    FieldDef::new("tcp.options_sack.le", field_type::INT32),
+    FieldDef::new("tcp.options_sack.re", field_type::INT32),
+    FieldDef::new("tcp.options_mss", field_type::INT16),
+    FieldDef::new("tcp.options_timestamp", field_type::INT32),
+    FieldDef::new("tcp.options_timestamp.tsval", field_type::INT32),
+    FieldDef::new("tcp.options_timestamp.tsecr", field_type::INT32),
+    FieldDef::new("tcp.packet", field_type::BYTE_ARRAY),
+    FieldDef::new("tcp.proto_name", field_type::STRING),
+];
+
 #[derive(Debug, Default, Clone)]
 pub struct SackOpt {
     left: u32,
@@ -38,6 +72,7 @@ pub struct Options {
 pub struct Tcp<'a> {
     raw_packet: &'a [u8],
     options: Options,
+    error: Option<DecodeError>,
 }
 
 impl<'a> Tcp<'a> {
@@ -45,13 +80,19 @@ impl<'a> Tcp<'a> {
         let mut slf = Self {
             raw_packet: packet,
             options: Options::default(),
+            error: None,
         };
 
-        slf.decode_options();
+        slf.error = slf.decode_options().err();
 
         slf
     }
 
+    //--- Options decoded before an error are kept
+    pub fn error(&self) -> Option<DecodeError> {
+        self.error
+    }
+
     pub fn seq_no(&self) -> u32 {
         BigEndian::read_u32(&self.raw_packet[4..8])
     }
@@ -98,7 +139,9 @@ impl<'a> Tcp<'a> {
     }
 
     pub fn payload_len(&self) -> u16 {
-        self.raw_packet[(self.hdr_len() * 4) as usize..].len() as u16
+        self.raw_packet
+            .get((self.hdr_len() * 4) as usize..)
+            .map_or(0, |payload| payload.len() as u16)
     }
 
     pub fn payload(&self) -> Vec<u8> {
@@ -106,7 +149,10 @@ impl<'a> Tcp<'a> {
     }
 
     pub fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
-        self.raw_packet[offset..offset + len].to_vec()
+        self.raw_packet
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+            .to_vec()
     }
 
     pub fn dport(&self) -> u16 {
@@ -120,81 +166,73 @@ impl<'a> Tcp<'a> {
     //----------------------------------------------------
     //--- TCP Options
     //----------------------------------------------------
-    fn decode_options(&mut self) {
-        if self.hdr_len() > 5 {
-            let start_pos = 20;
-            let end_pos = self.hdr_len() as usize * 4;
-            let options = self.raw_packet[start_pos..end_pos].to_vec();
-            let mut ptr: usize = 0;
-
-            while ptr < options.len() {
-                match options[ptr] as u16 {
-                    OPTION_NOP | OPTION_EOL => {
-                        ptr += 1;
-                    }
-                    OPTION_MSS => {
-                        ptr += 1;
-                        ptr += 1;
-                        self.options.mss = BigEndian::read_u16(&options[ptr..ptr + 2]);
-                        ptr += 2;
-                    }
-                    OPTION_WINSCALE => {
-                        ptr += 1;
-                        ptr += 1;
-                        self.options.winscale = options[ptr];
-                        self.options.win_multiplier = u16::pow(2, options[ptr] as u32);
-                        ptr += 1;
-                    }
-                    OPTION_SACK => {
-                        ptr += 1;
-                        self.options.sack = true;
-                        ptr += 1;
-                    }
-                    OPTION_SACK_OPT => {
-                        ptr += 1;
-                        let size = options[ptr];
-                        ptr += 1;
-                        let count: u8 = (size - 2) / 8;
-                        let mut index = 0;
-
-                        while index < count {
-                            let sack_opt = SackOpt {
-                                left: BigEndian::read_u32(&options[ptr..ptr + 4]),
-                                right: BigEndian::read_u32(&options[ptr + 4..ptr + 8]),
-                            };
-                            self.options.sack_list.push(sack_opt);
-                            ptr += 8;
-                            index += 1;
-                        }
-                    }
-                    OPTION_TIMESTAMP => {
-                        ptr += 1;
-                        ptr += 1;
-                        self.options.timestamp = Timestamp {
-                            tsval: BigEndian::read_u32(&options[ptr..ptr + 4]),
-                            tsecr: BigEndian::read_u32(&options[ptr + 4..ptr + 8]),
-                        };
-                        ptr += 8;
-                    }
-                    _ => {
-                        println!(
-                            "Options not found: {:x?}:{:x?}",
-                            options[ptr..].to_vec(),
-                            options
-                        );
-                        break;
+    fn decode_options(&mut self) -> Result<(), DecodeError> {
+        let end_pos = self.hdr_len() as usize * 4;
+        if end_pos <= TCP_MIN_HEADER_LEN {
+            return Ok(());
+        }
+
+        let options = self
+            .raw_packet
+            .get(TCP_MIN_HEADER_LEN..end_pos)
+            .ok_or(DecodeError::Truncated)?;
+        let mut ptr: usize = 0;
+
+        while ptr < options.len() {
+            let kind = options[ptr] as u16;
+            if kind == OPTION_NOP || kind == OPTION_EOL {
+                ptr += 1;
+                continue;
+            }
+
+            //--- Kind, length including kind and length, value
+            let size = *options.get(ptr + 1).ok_or(DecodeError::Truncated)? as usize;
+            if size < 2 {
+                return Err(DecodeError::Invalid);
+            }
+            let value = options
+                .get(ptr + 2..ptr + size)
+                .ok_or(DecodeError::Truncated)?;
+
+            match (kind, value.len()) {
+                (OPTION_MSS, 2) => {
+                    self.options.mss = BigEndian::read_u16(value);
+                }
+                (OPTION_WINSCALE, 1) => {
+                    self.options.winscale = value[0];
+                    self.options.win_multiplier =
+                        2u16.checked_pow(value[0] as u32).unwrap_or(u16::MAX);
+                }
+                (OPTION_SACK, 0) => {
+                    self.options.sack = true;
+                }
+                (OPTION_SACK_OPT, len) if len % 8 == 0 => {
+                    for block in value.chunks_exact(8) {
+                        self.options.sack_list.push(SackOpt {
+                            left: BigEndian::read_u32(&block[0..4]),
+                            right: BigEndian::read_u32(&block[4..8]),
+                        });
                     }
                 }
+                (OPTION_TIMESTAMP, 8) => {
+                    self.options.timestamp = Timestamp {
+                        tsval: BigEndian::read_u32(&value[0..4]),
+                        tsecr: BigEndian::read_u32(&value[4..8]),
+                    };
+                }
+                (
+                    OPTION_MSS | OPTION_WINSCALE | OPTION_SACK | OPTION_SACK_OPT | OPTION_TIMESTAMP,
+                    _,
+                ) => {
+                    return Err(DecodeError::Invalid);
+                }
+                _ => {}
             }
 
-            // for (k, v) in self.option_list.iter() {
-            //     println!("Options: {:x}:{:x}", k, v);
-            // }
-            // println!("--------------------------------");
-            // for s in &self.sack_list {
-            //     println!("Sack: {:?}", s);
-            // }
+            ptr += size;
         }
+
+        Ok(())
     }
 
     pub fn is_https(&self) -> bool {
@@ -301,6 +339,52 @@ impl<'a> Layer for Tcp<'a> {
     fn get_name(&self) -> String {
         "tcp".to_string()
     }
+
+    fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
+        Tcp::payload_range(self, offset, len)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TcpDissector;
+
+impl Dissector for TcpDissector {
+    fn name(&self) -> &'static str {
+        "tcp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::TCP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::IpProto(IP_TCP_PROTO)]
+    }
+
+    fn min_len(&self) -> usize {
+        TCP_MIN_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        TCP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Tcp::new(packet))
+    }
+
+    fn check(&self, packet: &[u8]) -> Result<(), DecodeError> {
+        Tcp::new(packet).error().map_or(Ok(()), Err)
+    }
+
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let tcp = Tcp::new(packet);
+
+        Some(Dispatch::new(
+            tcp.hdr_len() as usize * 4,
+            vec![Trigger::TcpPort(tcp.dport()), Trigger::TcpPort(tcp.sport())],
+        ))
+    }
 }
 
 impl<'a> PacketDisplay for Tcp<'a> {
@@ -315,7 +399,13 @@ impl<'a> PacketDisplay for Tcp<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "TCP Detail".to_string()
+        LayerNode::of(
+            TcpDissector.name(),
+            TcpDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
     }
 }
 
@@ -0,0 +1,144 @@
+use crate::layer::{Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
+use field::pfield::{Field, FieldType};
+
+/*
+TLS record header
+
+  0               1               2               3               4
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+ | Content type  |    Version (major.minor)      |            Length             |
+ +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+*/
+
+const CONTENT_CHANGE_CIPHER_SPEC: u8 = 20;
+const CONTENT_HEARTBEAT: u8 = 24;
+const VERSION_MAJOR: u8 = 3;
+const VERSION_MINOR_MAX: u8 = 4;
+const MAX_RECORD_LEN: u16 = 0x4800;
+
+const HTTPS_PORT: u16 = 443;
+const TLS_RECORD_HEADER_LEN: usize = 5;
+
+const TLS_FIELDS: &[FieldDef] = &[
+    FieldDef::at("tls.content_type", field_type::INT8, 0, 1),
+    FieldDef::at("tls.version", field_type::INT16, 1, 2),
+    FieldDef::at("tls.length", field_type::INT16, 3, 2),
+];
+
+//--- Record header signature, valid content type, version 3.x and a sane length
+pub fn is_tls(payload: &[u8]) -> bool {
+    payload.len() >= TLS_RECORD_HEADER_LEN
+        && (CONTENT_CHANGE_CIPHER_SPEC..=CONTENT_HEARTBEAT).contains(&payload[0])
+        && payload[1] == VERSION_MAJOR
+        && payload[2] <= VERSION_MINOR_MAX
+        && BigEndian::read_u16(&payload[3..5]) <= MAX_RECORD_LEN
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Tls<'a> {
+    raw_packet: &'a [u8],
+}
+
+impl<'a> Tls<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self { raw_packet: packet }
+    }
+
+    fn has_record(&self) -> bool {
+        self.raw_packet.len() >= TLS_RECORD_HEADER_LEN
+    }
+
+    pub fn content_type(&self) -> Option<u8> {
+        self.has_record().then(|| self.raw_packet[0])
+    }
+
+    pub fn version(&self) -> Option<u16> {
+        self.has_record()
+            .then(|| BigEndian::read_u16(&self.raw_packet[1..3]))
+    }
+
+    pub fn length(&self) -> Option<u16> {
+        self.has_record()
+            .then(|| BigEndian::read_u16(&self.raw_packet[3..5]))
+    }
+}
+
+impl<'a> Layer for Tls<'a> {
+    fn get_name(&self) -> String {
+        "tls".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "tls.content_type" => self
+                .content_type()
+                .map(|ctype| Field::set_field(FieldType::Int8(ctype), field)),
+            "tls.version" => self
+                .version()
+                .map(|version| Field::set_field(FieldType::Int16(version), field)),
+            "tls.length" => self
+                .length()
+                .map(|len| Field::set_field(FieldType::Int16(len), field)),
+            _ => None,
+        }
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TlsDissector;
+
+impl Dissector for TlsDissector {
+    fn name(&self) -> &'static str {
+        "tls"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::HTTPS
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::TcpPort(HTTPS_PORT)]
+    }
+
+    fn min_len(&self) -> usize {
+        0
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        TLS_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Tls::new(packet))
+    }
+
+    fn heuristic(&self, parent: &LayerIndex, payload: &[u8]) -> bool {
+        *parent == LayerIndex::TCP && is_tls(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_client_hello() {
+        let payload: Vec<u8> = vec![0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc];
+
+        assert!(is_tls(&payload), "TLS handshake record");
+        assert_eq!(Tls::new(&payload).version(), Some(0x0301), "TLS version");
+    }
+
+    #[test]
+    fn tls_reject_plain_text() {
+        assert!(!is_tls(b"GET / HTTP/1.1\r\n"), "HTTP request");
+        assert!(!is_tls(&[0x16, 0x03]), "Truncated record");
+    }
+}
@@ -1,9 +1,21 @@
-use crate::layer::Layer;
+use crate::detail::LayerNode;
+use crate::layer::{Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
 use crate::packet_display::PacketDisplay;
 use byteorder::{BigEndian, ByteOrder};
+use field::field_type;
 use field::pfield::{Field, FieldType};
 
 const UDP_HEADER_LEN: usize = 8;
+const IP_UDP_PROTO: u8 = 0x11;
+
+const UDP_FIELDS: &[FieldDef] = &[
+    FieldDef::at("udp.sport", field_type::INT16, 0, 2),
+    FieldDef::at("udp.dport", field_type::INT16, 2, 2),
+    FieldDef::at("udp.length", field_type::INT16, 4, 2),
+    FieldDef::at("udp.checksum", field_type::INT16, 6, 2),
+    FieldDef::new("udp.packet", field_type::BYTE_ARRAY),
+];
 
 #[derive(Debug, Default, Clone)]
 pub struct UdpFrame<'a> {
@@ -40,7 +52,10 @@ impl<'a> UdpFrame<'a> {
     }
 
     pub fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
-        self.raw_packet[offset..offset + len].to_vec()
+        self.raw_packet
+            .get(offset..offset.saturating_add(len))
+            .unwrap_or_default()
+            .to_vec()
     }
 
     pub fn is_dns(&self) -> bool {
@@ -77,6 +92,48 @@ impl<'a> Layer for UdpFrame<'a> {
     fn get_name(&self) -> String {
         "Udp".to_string()
     }
+
+    fn payload_range(&self, offset: usize, len: usize) -> Vec<u8> {
+        UdpFrame::payload_range(self, offset, len)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UdpDissector;
+
+impl Dissector for UdpDissector {
+    fn name(&self) -> &'static str {
+        "udp"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::UDP
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::IpProto(IP_UDP_PROTO)]
+    }
+
+    fn min_len(&self) -> usize {
+        UDP_HEADER_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        UDP_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(UdpFrame::new(packet))
+    }
+
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let udp = UdpFrame::new(packet);
+
+        Some(Dispatch::new(
+            UDP_HEADER_LEN,
+            vec![Trigger::UdpPort(udp.dport()), Trigger::UdpPort(udp.sport())],
+        ))
+    }
 }
 
 impl<'a> PacketDisplay for UdpFrame<'a> {
@@ -89,7 +146,13 @@ impl<'a> PacketDisplay for UdpFrame<'a> {
     }
 
     fn show_detail(&self) -> String {
-        "UDP Detail".to_string()
+        LayerNode::of(
+            UdpDissector.name(),
+            UdpDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
     }
 }
 
@@ -0,0 +1,499 @@
+use crate::detail::LayerNode;
+use crate::layer::{Dispatch, Dissector, FieldDef, Layer, Trigger};
+use crate::layer_index::LayerIndex;
+use crate::packet::LINKTYPE_IEEE802_11;
+use crate::packet_display::PacketDisplay;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use field::field_type;
+use field::mac_address::MacAddr;
+use field::pfield::{Field, FieldType};
+
+/*
+IEEE 802.11 MAC header
+
+ +-------+----------+-------+-------+-------+---------+-------+---------+---------+
+ | Frame | Duration | Addr1 | Addr2 | Addr3 | Seq ctl | Addr4 | QoS ctl | HT ctl  |
+ |  ctl  |          |       |       |       |         |       |         |         |
+ +-------+----------+-------+-------+-------+---------+-------+---------+---------+
+     2        2         6       6       6        2        6        2         4
+
+ Addr4 is only present when both ToDS and FromDS are set, QoS control only on
+ QoS data frames and HT control when the order flag is set on a QoS data frame.
+*/
+
+pub const WLAN_TYPE_MGMT: u8 = 0;
+pub const WLAN_TYPE_CTRL: u8 = 1;
+pub const WLAN_TYPE_DATA: u8 = 2;
+
+const SUBTYPE_PROBE_REQ: u8 = 4;
+const SUBTYPE_PROBE_RESP: u8 = 5;
+const SUBTYPE_BEACON: u8 = 8;
+
+const FLAG_TO_DS: u8 = 0x01;
+const FLAG_FROM_DS: u8 = 0x02;
+const FLAG_PROTECTED: u8 = 0x40;
+const FLAG_ORDER: u8 = 0x80;
+
+const TAG_SSID: u8 = 0;
+
+const MGMT_HEADER_LEN: usize = 24;
+const BEACON_FIXED_LEN: usize = 12;
+
+const LLC_SNAP: [u8; 6] = [0xaa, 0xaa, 0x03, 0x00, 0x00, 0x00];
+pub const LLC_SNAP_LEN: usize = 8;
+
+pub const WLAN_MIN_LEN: usize = 10;
+
+const WLAN_FIELDS: &[FieldDef] = &[
+    FieldDef::at("wlan.fc", field_type::INT16, 0, 2),
+    FieldDef::at("wlan.type", field_type::INT8, 0, 1),
+    FieldDef::at("wlan.subtype", field_type::INT8, 0, 1),
+    FieldDef::new("wlan.type_label", field_type::STRING),
+    FieldDef::at("wlan.flags", field_type::INT8, 1, 1),
+    FieldDef::at("wlan.to_ds", field_type::BOOL, 1, 1),
+    FieldDef::at("wlan.from_ds", field_type::BOOL, 1, 1),
+    FieldDef::at("wlan.protected", field_type::BOOL, 1, 1),
+    FieldDef::at("wlan.duration", field_type::INT16, 2, 2),
+    FieldDef::at("wlan.seq_no", field_type::INT16, 22, 2),
+    FieldDef::at("wlan.ra", field_type::MACADDR, 4, 6),
+    FieldDef::at("wlan.ta", field_type::MACADDR, 10, 6),
+    FieldDef::new("wlan.bssid", field_type::MACADDR),
+    FieldDef::new("wlan.sa", field_type::MACADDR),
+    FieldDef::new("wlan.da", field_type::MACADDR),
+    FieldDef::new("wlan.ssid", field_type::STRING),
+];
+
+#[derive(Debug, Default, Clone)]
+pub struct Wlan<'a> {
+    raw_packet: &'a [u8],
+}
+
+impl<'a> Wlan<'a> {
+    pub fn new(packet: &'a [u8]) -> Self {
+        Self { raw_packet: packet }
+    }
+
+    pub fn frame_control(&self) -> u16 {
+        LittleEndian::read_u16(&self.raw_packet[0..2])
+    }
+
+    pub fn version(&self) -> u8 {
+        self.raw_packet[0] & 0x03
+    }
+
+    pub fn ftype(&self) -> u8 {
+        (self.raw_packet[0] >> 2) & 0x03
+    }
+
+    pub fn subtype(&self) -> u8 {
+        self.raw_packet[0] >> 4
+    }
+
+    pub fn flags(&self) -> u8 {
+        self.raw_packet[1]
+    }
+
+    pub fn to_ds(&self) -> bool {
+        self.flags() & FLAG_TO_DS == FLAG_TO_DS
+    }
+
+    pub fn from_ds(&self) -> bool {
+        self.flags() & FLAG_FROM_DS == FLAG_FROM_DS
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.flags() & FLAG_PROTECTED == FLAG_PROTECTED
+    }
+
+    pub fn duration(&self) -> u16 {
+        LittleEndian::read_u16(&self.raw_packet[2..4])
+    }
+
+    fn address_offset(index: usize) -> Option<usize> {
+        match index {
+            1 => Some(4),
+            2 => Some(10),
+            3 => Some(16),
+            4 => Some(24),
+            _ => None,
+        }
+    }
+
+    fn address(&self, index: usize) -> Option<u64> {
+        let start = Self::address_offset(index)?;
+
+        if index == 4 && !(self.to_ds() && self.from_ds()) {
+            return None;
+        }
+
+        if start + 6 <= self.header_len() && start + 6 <= self.raw_packet.len() {
+            Some(BigEndian::read_u48(&self.raw_packet[start..start + 6]))
+        } else {
+            None
+        }
+    }
+
+    pub fn addr1(&self) -> Option<u64> {
+        self.address(1)
+    }
+
+    pub fn addr2(&self) -> Option<u64> {
+        self.address(2)
+    }
+
+    pub fn addr3(&self) -> Option<u64> {
+        self.address(3)
+    }
+
+    pub fn addr4(&self) -> Option<u64> {
+        self.address(4)
+    }
+
+    //--- Address meaning depend on the ToDS/FromDS bits
+    fn da_index(&self) -> Option<usize> {
+        match (self.ftype(), self.to_ds(), self.from_ds()) {
+            (WLAN_TYPE_CTRL, _, _) => None,
+            (_, false, _) => Some(1),
+            (_, true, _) => Some(3),
+        }
+    }
+
+    fn sa_index(&self) -> Option<usize> {
+        match (self.ftype(), self.to_ds(), self.from_ds()) {
+            (WLAN_TYPE_CTRL, _, _) => Some(2),
+            (_, _, false) => Some(2),
+            (_, false, true) => Some(3),
+            (_, true, true) => Some(4),
+        }
+    }
+
+    fn bssid_index(&self) -> Option<usize> {
+        match (self.ftype(), self.to_ds(), self.from_ds()) {
+            (WLAN_TYPE_CTRL, _, _) => None,
+            (_, false, false) => Some(3),
+            (_, true, false) => Some(1),
+            (_, false, true) => Some(2),
+            (_, true, true) => None,
+        }
+    }
+
+    pub fn da(&self) -> Option<u64> {
+        self.da_index().and_then(|index| self.address(index))
+    }
+
+    pub fn sa(&self) -> Option<u64> {
+        self.sa_index().and_then(|index| self.address(index))
+    }
+
+    pub fn bssid(&self) -> Option<u64> {
+        self.bssid_index().and_then(|index| self.address(index))
+    }
+
+    pub fn seq_no(&self) -> Option<u16> {
+        if self.ftype() != WLAN_TYPE_CTRL && self.raw_packet.len() >= MGMT_HEADER_LEN {
+            Some(LittleEndian::read_u16(&self.raw_packet[22..24]) >> 4)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_qos_data(&self) -> bool {
+        self.ftype() == WLAN_TYPE_DATA && self.subtype() & 0x08 == 0x08
+    }
+
+    //--- Null function frames carry no payload
+    pub fn has_payload(&self) -> bool {
+        self.ftype() == WLAN_TYPE_DATA && self.subtype() & 0x04 == 0
+    }
+
+    pub fn header_len(&self) -> usize {
+        match self.ftype() {
+            WLAN_TYPE_CTRL => match self.subtype() {
+                //--- CTS and ACK
+                12 | 13 => 10,
+                _ => 16,
+            },
+            WLAN_TYPE_DATA => {
+                let mut len = MGMT_HEADER_LEN;
+                if self.to_ds() && self.from_ds() {
+                    len += 6;
+                }
+                if self.is_qos_data() {
+                    len += 2;
+                    if self.flags() & FLAG_ORDER == FLAG_ORDER {
+                        len += 4;
+                    }
+                }
+                len
+            }
+            _ => MGMT_HEADER_LEN,
+        }
+    }
+
+    //--- Ethertype carried in the LLC/SNAP header of an unprotected data frame
+    pub fn llc_ethertype(&self) -> Option<u16> {
+        if !self.has_payload() || self.is_protected() {
+            return None;
+        }
+
+        let start = self.header_len();
+        if start + LLC_SNAP_LEN <= self.raw_packet.len()
+            && self.raw_packet[start..start + 6] == LLC_SNAP
+        {
+            Some(BigEndian::read_u16(
+                &self.raw_packet[start + 6..start + LLC_SNAP_LEN],
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn ssid(&self) -> Option<String> {
+        if self.ftype() != WLAN_TYPE_MGMT {
+            return None;
+        }
+
+        let mut ptr = match self.subtype() {
+            SUBTYPE_BEACON | SUBTYPE_PROBE_RESP => MGMT_HEADER_LEN + BEACON_FIXED_LEN,
+            SUBTYPE_PROBE_REQ => MGMT_HEADER_LEN,
+            _ => return None,
+        };
+
+        while ptr + 2 <= self.raw_packet.len() {
+            let tag = self.raw_packet[ptr];
+            let len = self.raw_packet[ptr + 1] as usize;
+            ptr += 2;
+
+            if ptr + len > self.raw_packet.len() {
+                break;
+            }
+
+            if tag == TAG_SSID {
+                return Some(String::from_utf8_lossy(&self.raw_packet[ptr..ptr + len]).to_string());
+            }
+
+            ptr += len;
+        }
+
+        None
+    }
+
+    fn type_str(&self) -> String {
+        let type_str = match self.ftype() {
+            WLAN_TYPE_MGMT => "Management",
+            WLAN_TYPE_CTRL => "Control",
+            WLAN_TYPE_DATA => "Data",
+            _ => "Extension",
+        };
+
+        type_str.to_string()
+    }
+}
+
+impl<'a> Layer for Wlan<'a> {
+    fn get_name(&self) -> String {
+        "wlan".to_string()
+    }
+
+    fn get_field(&self, field: &str) -> Option<Field> {
+        match field {
+            "wlan.fc" => Some(Field::set_field(
+                FieldType::Int16(self.frame_control()),
+                field,
+            )),
+            "wlan.type" => Some(Field::set_field(FieldType::Int8(self.ftype()), field)),
+            "wlan.subtype" => Some(Field::set_field(FieldType::Int8(self.subtype()), field)),
+            "wlan.type_label" => Some(Field::set_field(FieldType::String(self.type_str()), field)),
+            "wlan.flags" => Some(Field::set_field(FieldType::Int8(self.flags()), field)),
+            "wlan.to_ds" => Some(Field::set_field(FieldType::Bool(self.to_ds()), field)),
+            "wlan.from_ds" => Some(Field::set_field(FieldType::Bool(self.from_ds()), field)),
+            "wlan.protected" => Some(Field::set_field(
+                FieldType::Bool(self.is_protected()),
+                field,
+            )),
+            "wlan.duration" => Some(Field::set_field(FieldType::Int16(self.duration()), field)),
+            "wlan.seq_no" => self
+                .seq_no()
+                .map(|seq| Field::set_field(FieldType::Int16(seq), field)),
+            "wlan.ra" => self
+                .addr1()
+                .map(|addr| Field::set_field(FieldType::MacAddr(addr), field)),
+            "wlan.ta" => self
+                .addr2()
+                .map(|addr| Field::set_field(FieldType::MacAddr(addr), field)),
+            "wlan.bssid" => self
+                .bssid()
+                .map(|addr| Field::set_field(FieldType::MacAddr(addr), field)),
+            "wlan.sa" => self
+                .sa()
+                .map(|addr| Field::set_field(FieldType::MacAddr(addr), field)),
+            "wlan.da" => self
+                .da()
+                .map(|addr| Field::set_field(FieldType::MacAddr(addr), field)),
+            "wlan.ssid" => self
+                .ssid()
+                .map(|ssid| Field::set_field(FieldType::String(ssid), field)),
+            _ => None,
+        }
+    }
+
+    fn get_field_bytes(&self, _field_name: String) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn field_range(&self, field_name: &str) -> Option<(usize, usize)> {
+        let index = match field_name {
+            "wlan.bssid" => self.bssid_index(),
+            "wlan.sa" => self.sa_index(),
+            "wlan.da" => self.da_index(),
+            _ => None,
+        }?;
+
+        Self::address_offset(index).map(|offset| (offset, 6))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct WlanDissector;
+
+impl Dissector for WlanDissector {
+    fn name(&self) -> &'static str {
+        "wlan"
+    }
+
+    fn layer(&self) -> LayerIndex {
+        LayerIndex::WLAN
+    }
+
+    fn triggers(&self) -> &'static [Trigger] {
+        &[Trigger::LinkType(LINKTYPE_IEEE802_11)]
+    }
+
+    fn min_len(&self) -> usize {
+        WLAN_MIN_LEN
+    }
+
+    fn fields(&self) -> &'static [FieldDef] {
+        WLAN_FIELDS
+    }
+
+    fn decode<'a>(&self, packet: &'a [u8]) -> Box<dyn Layer + 'a> {
+        Box::new(Wlan::new(packet))
+    }
+
+    fn dispatch(&self, packet: &[u8]) -> Option<Dispatch> {
+        let wlan = Wlan::new(packet);
+
+        wlan.llc_ethertype().map(|ethertype| {
+            Dispatch::new(
+                wlan.header_len() + LLC_SNAP_LEN,
+                vec![Trigger::EtherType(ethertype)],
+            )
+        })
+    }
+}
+
+impl<'a> PacketDisplay for Wlan<'a> {
+    fn summary(&self) -> String {
+        let mut result = format!(
+            "WLAN -> Type: {}, Subtype: {}",
+            self.type_str(),
+            self.subtype()
+        );
+
+        if let Some(bssid) = self.bssid() {
+            result += &format!(", BSSID: {}", MacAddr::set_from_int(&bssid));
+        }
+
+        if let Some(ssid) = self.ssid() {
+            result += &format!(", SSID: {}", ssid);
+        }
+
+        result + "\n"
+    }
+
+    fn show_detail(&self) -> String {
+        LayerNode::of(
+            WlanDissector.name(),
+            WlanDissector.fields(),
+            self,
+            self.raw_packet.len(),
+        )
+        .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_beacon() -> Vec<u8> {
+        let packet: Vec<u8> = vec![
+            0x80, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x11, 0x22, 0x33,
+            0x44, 0x55, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x64, 0x00, 0x11, 0x04, 0x00, 0x08, 0x70, 0x61, 0x63, 0x6b,
+            0x65, 0x74, 0x64, 0x62, 0x01, 0x01, 0x82,
+        ];
+
+        packet
+    }
+
+    fn get_data() -> Vec<u8> {
+        let packet: Vec<u8> = vec![
+            0x08, 0x01, 0x2c, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0xa0, 0xb0, 0xc0, 0xd0,
+            0xe0, 0xf0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x20, 0x00, 0xaa, 0xaa, 0x03, 0x00,
+            0x00, 0x00, 0x08, 0x00,
+        ];
+
+        packet
+    }
+
+    #[test]
+    fn wlan_beacon_type() {
+        let packet = get_beacon();
+        let pkt = Wlan::new(&packet);
+
+        assert_eq!(pkt.ftype(), WLAN_TYPE_MGMT, "Management type");
+        assert_eq!(pkt.subtype(), SUBTYPE_BEACON, "Beacon subtype");
+        assert_eq!(pkt.header_len(), 24, "Header len");
+    }
+
+    #[test]
+    fn wlan_beacon_ssid() {
+        let packet = get_beacon();
+        let pkt = Wlan::new(&packet);
+
+        assert_eq!(pkt.ssid(), Some("packetdb".to_string()), "SSID");
+    }
+
+    #[test]
+    fn wlan_beacon_addresses() {
+        let packet = get_beacon();
+        let pkt = Wlan::new(&packet);
+
+        assert_eq!(pkt.da(), Some(0xffffffffffff), "Destination address");
+        assert_eq!(pkt.sa(), Some(0x001122334455), "Source address");
+        assert_eq!(pkt.bssid(), Some(0x001122334455), "BSSID");
+    }
+
+    #[test]
+    fn wlan_data_to_ds_addresses() {
+        let packet = get_data();
+        let pkt = Wlan::new(&packet);
+
+        assert!(pkt.to_ds(), "ToDS flag");
+        assert_eq!(pkt.bssid(), Some(0x001122334455), "BSSID");
+        assert_eq!(pkt.sa(), Some(0xa0b0c0d0e0f0), "Source address");
+        assert_eq!(pkt.da(), Some(0x010203040506), "Destination address");
+        assert_eq!(pkt.seq_no(), Some(2), "Sequence number");
+    }
+
+    #[test]
+    fn wlan_data_llc_snap() {
+        let packet = get_data();
+        let pkt = Wlan::new(&packet);
+
+        assert_eq!(pkt.llc_ethertype(), Some(0x0800), "LLC ethertype");
+        assert_eq!(pkt.ssid(), None, "No SSID on data frame");
+    }
+}
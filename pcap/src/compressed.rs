@@ -0,0 +1,482 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+
+//------------------------------------------------------------------
+//--- Segment compressed in independent zstd blocks. Packet pointers
+//--- are offsets in the uncompressed stream so the .pidx and proto
+//--- indexes of a plain segment stay valid once it is compressed.
+//---
+//--- Header:  magic (BE), block size, table offset, block count
+//--- Blocks:  compressed length, uncompressed length, zstd frame
+//--- Table:   uncompressed start, file offset, both lengths per block
+//---
+//--- The table is written when the segment is closed, a segment left
+//--- open by a crash is read by walking the block headers
+//------------------------------------------------------------------
+pub const COMPRESSED_MAGIC: u32 = 0x50445a31;
+pub const DEFAULT_BLOCK_SIZE: usize = 256 * 1024;
+pub const DEFAULT_LEVEL: i32 = 3;
+//--- Largest block size a reader accepts from a segment header
+pub const MAX_BLOCK_SIZE: usize = 64 * 1024 * 1024;
+
+const FILE_HEADER_LEN: u64 = 24;
+const BLOCK_HEADER_LEN: u64 = 8;
+const TABLE_ENTRY_LEN: usize = 24;
+
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    start: u64,
+    offset: u64,
+    compressed_len: u32,
+    len: u32,
+}
+
+impl Block {
+    fn end(&self) -> u64 {
+        self.start + self.len as u64
+    }
+}
+
+fn invalid_data(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg.to_string())
+}
+
+pub fn is_compressed(magic_no: u32) -> bool {
+    magic_no == COMPRESSED_MAGIC
+}
+
+//------------------------------------------------------------------
+//--- Read and seek in the uncompressed stream, one block is kept
+//--- decompressed so sequential reads decompress each block once
+//------------------------------------------------------------------
+pub struct CompressedReader {
+    file: File,
+    block_size: usize,
+    //--- False for a segment left open, its blocks were walked
+    finished: bool,
+    blocks: Vec<Block>,
+    position: u64,
+    cache_index: Option<usize>,
+    cache: Vec<u8>,
+}
+
+impl CompressedReader {
+    pub fn open(mut file: File) -> Result<Self> {
+        let file_len = file.metadata()?.len();
+        let mut header = [0u8; FILE_HEADER_LEN as usize];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        if !is_compressed(BigEndian::read_u32(&header[0..4])) {
+            return Err(invalid_data("Not a compressed segment"));
+        }
+
+        let block_size = LittleEndian::read_u32(&header[4..8]) as usize;
+        if block_size == 0 || block_size > MAX_BLOCK_SIZE {
+            return Err(invalid_data("Compressed segment block size out of range"));
+        }
+
+        let table_offset = LittleEndian::read_u64(&header[8..16]);
+        let block_count = LittleEndian::read_u32(&header[16..20]) as usize;
+        let table_end = table_offset + (block_count * TABLE_ENTRY_LEN) as u64;
+
+        let finished = table_offset >= FILE_HEADER_LEN && table_end <= file_len;
+        let blocks = if finished {
+            Self::read_table(&mut file, table_offset, block_count)?
+        } else {
+            Self::scan_blocks(&mut file, file_len)?
+        };
+
+        Ok(Self {
+            file,
+            block_size,
+            finished,
+            blocks,
+            position: 0,
+            cache_index: None,
+            cache: Vec::new(),
+        })
+    }
+
+    fn read_table(file: &mut File, table_offset: u64, block_count: usize) -> Result<Vec<Block>> {
+        let mut table = vec![0u8; block_count * TABLE_ENTRY_LEN];
+        file.seek(SeekFrom::Start(table_offset))?;
+        file.read_exact(&mut table)?;
+
+        Ok(table
+            .chunks_exact(TABLE_ENTRY_LEN)
+            .map(|entry| Block {
+                start: LittleEndian::read_u64(&entry[0..8]),
+                offset: LittleEndian::read_u64(&entry[8..16]),
+                compressed_len: LittleEndian::read_u32(&entry[16..20]),
+                len: LittleEndian::read_u32(&entry[20..24]),
+            })
+            .collect())
+    }
+
+    //--- Without a table the blocks are walked up to the first incomplete one
+    fn scan_blocks(file: &mut File, file_len: u64) -> Result<Vec<Block>> {
+        let mut blocks: Vec<Block> = Vec::new();
+        let mut offset = FILE_HEADER_LEN;
+        let mut start: u64 = 0;
+        let mut header = [0u8; BLOCK_HEADER_LEN as usize];
+
+        while offset + BLOCK_HEADER_LEN <= file_len {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut header)?;
+            let compressed_len = LittleEndian::read_u32(&header[0..4]);
+            let len = LittleEndian::read_u32(&header[4..8]);
+
+            if compressed_len == 0 || offset + BLOCK_HEADER_LEN + compressed_len as u64 > file_len {
+                break;
+            }
+
+            blocks.push(Block {
+                start,
+                offset,
+                compressed_len,
+                len,
+            });
+            start += len as u64;
+            offset += BLOCK_HEADER_LEN + compressed_len as u64;
+        }
+
+        Ok(blocks)
+    }
+
+    pub fn uncompressed_len(&self) -> u64 {
+        self.blocks.last().map_or(0, |block| block.end())
+    }
+
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    fn load_block(&mut self, index: usize) -> Result<()> {
+        if self.cache_index == Some(index) {
+            return Ok(());
+        }
+
+        //--- Lengths come from the file, a block never holds more than the
+        //--- block size it was written with
+        let block = self.blocks[index];
+        if block.len as usize > self.block_size {
+            return Err(invalid_data("Compressed block larger than the block size"));
+        }
+
+        let mut frame = vec![0u8; block.compressed_len as usize];
+        self.file
+            .seek(SeekFrom::Start(block.offset + BLOCK_HEADER_LEN))?;
+        self.file.read_exact(&mut frame)?;
+
+        self.cache = zstd::bulk::decompress(&frame, self.block_size)?;
+        if self.cache.len() != block.len as usize {
+            return Err(invalid_data("Compressed block length mismatch"));
+        }
+        self.cache_index = Some(index);
+
+        Ok(())
+    }
+}
+
+impl Read for CompressedReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let index = self
+            .blocks
+            .partition_point(|block| block.end() <= self.position);
+
+        if index >= self.blocks.len() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.load_block(index)?;
+        let block = self.blocks[index];
+        let from = (self.position - block.start) as usize;
+        let count = buf.len().min(self.cache.len() - from);
+
+        buf[..count].copy_from_slice(&self.cache[from..from + count]);
+        self.position += count as u64;
+
+        Ok(count)
+    }
+}
+
+impl Seek for CompressedReader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => offset as i128,
+            SeekFrom::End(offset) => self.uncompressed_len() as i128 + offset as i128,
+            SeekFrom::Current(offset) => self.position as i128 + offset as i128,
+        };
+
+        if position < 0 {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Seek before the start of the segment",
+            ));
+        }
+
+        self.position = position as u64;
+        Ok(self.position)
+    }
+}
+
+//------------------------------------------------------------------
+//--- Writer of a compressed segment, bytes are buffered up to the
+//--- block size. The table is written by finish, or when dropped
+//------------------------------------------------------------------
+pub struct CompressedWriter<W: Write + Seek> {
+    writer: W,
+    level: i32,
+    block_size: usize,
+    buffer: Vec<u8>,
+    blocks: Vec<Block>,
+    offset: u64,
+    start: u64,
+    finished: bool,
+}
+
+impl<W: Write + Seek> CompressedWriter<W> {
+    pub fn new(mut writer: W, block_size: usize, level: i32) -> Result<Self> {
+        let block_size = match block_size {
+            0 => DEFAULT_BLOCK_SIZE,
+            size if size > MAX_BLOCK_SIZE => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Block size {} above {}", size, MAX_BLOCK_SIZE),
+                ));
+            }
+            size => size,
+        };
+
+        let mut header = [0u8; FILE_HEADER_LEN as usize];
+        BigEndian::write_u32(&mut header[0..4], COMPRESSED_MAGIC);
+        LittleEndian::write_u32(&mut header[4..8], block_size as u32);
+        writer.write_all(&header)?;
+
+        Ok(Self {
+            writer,
+            level,
+            block_size,
+            buffer: Vec::with_capacity(block_size),
+            blocks: Vec::new(),
+            offset: FILE_HEADER_LEN,
+            start: 0,
+            finished: false,
+        })
+    }
+
+    fn write_block(&mut self, len: usize) -> Result<()> {
+        let frame = zstd::bulk::compress(&self.buffer[..len], self.level)?;
+        let mut header = [0u8; BLOCK_HEADER_LEN as usize];
+        LittleEndian::write_u32(&mut header[0..4], frame.len() as u32);
+        LittleEndian::write_u32(&mut header[4..8], len as u32);
+
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&frame)?;
+        self.blocks.push(Block {
+            start: self.start,
+            offset: self.offset,
+            compressed_len: frame.len() as u32,
+            len: len as u32,
+        });
+
+        self.offset += BLOCK_HEADER_LEN + frame.len() as u64;
+        self.start += len as u64;
+        self.buffer.drain(..len);
+
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+
+        if !self.buffer.is_empty() {
+            self.write_block(self.buffer.len())?;
+        }
+
+        let mut table = Vec::with_capacity(self.blocks.len() * TABLE_ENTRY_LEN);
+        for block in &self.blocks {
+            let mut entry = [0u8; TABLE_ENTRY_LEN];
+            LittleEndian::write_u64(&mut entry[0..8], block.start);
+            LittleEndian::write_u64(&mut entry[8..16], block.offset);
+            LittleEndian::write_u32(&mut entry[16..20], block.compressed_len);
+            LittleEndian::write_u32(&mut entry[20..24], block.len);
+            table.extend_from_slice(&entry);
+        }
+        self.writer.write_all(&table)?;
+
+        let mut header = [0u8; 12];
+        LittleEndian::write_u64(&mut header[0..8], self.offset);
+        LittleEndian::write_u32(&mut header[8..12], self.blocks.len() as u32);
+        self.writer.seek(SeekFrom::Start(8))?;
+        self.writer.write_all(&header)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while self.buffer.len() >= self.block_size {
+            self.write_block(self.block_size)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    //--- Only complete blocks are written, a partial one waits for finish
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for CompressedWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+//--- Compress a plain segment in place, readers holding the plain file
+//--- keep reading it until they close it. False when already compressed
+pub fn compress_file(fname: &str, block_size: usize, level: i32) -> Result<bool> {
+    //--- Segments in a source folder are linked from db_path, the link is kept
+    let fname = &fs::canonicalize(fname)?.to_string_lossy().to_string();
+    let mut plain = File::open(fname)?;
+    let mut magic = [0u8; 4];
+    if plain.read_exact(&mut magic).is_ok() && is_compressed(BigEndian::read_u32(&magic)) {
+        return Ok(false);
+    }
+    plain.seek(SeekFrom::Start(0))?;
+
+    let tmp_name = format!("{}.tmp", fname);
+    let tmp_file = File::create(&tmp_name)?;
+    {
+        let mut writer = CompressedWriter::new(&tmp_file, block_size, level)?;
+        std::io::copy(&mut plain, &mut writer)?;
+        writer.finish()?;
+    }
+    tmp_file.sync_all()?;
+    fs::rename(&tmp_name, fname)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn temp_file(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("{}_{}.pcap", name, std::process::id()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn content() -> Vec<u8> {
+        (0..100_000u32)
+            .flat_map(|value| value.to_be_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn seek_in_blocks() {
+        let fname = temp_file("seek_in_blocks");
+        let content = content();
+        let mut writer = CompressedWriter::new(File::create(&fname).unwrap(), 4096, 1).unwrap();
+        writer.write_all(&content).unwrap();
+        drop(writer);
+
+        let mut reader = CompressedReader::open(File::open(&fname).unwrap()).unwrap();
+        assert_eq!(reader.blocks.len(), 98, "Block count");
+        assert_eq!(reader.uncompressed_len(), 400_000, "Uncompressed length");
+
+        let mut value = [0u8; 8];
+        reader.seek(SeekFrom::Start(4094)).unwrap();
+        reader.read_exact(&mut value).unwrap();
+        assert_eq!(&value, &content[4094..4102], "Read across blocks");
+
+        reader.seek(SeekFrom::End(-4)).unwrap();
+        reader.read_exact(&mut value[..4]).unwrap();
+        assert_eq!(BigEndian::read_u32(&value), 99_999, "Last value");
+        assert_eq!(reader.read(&mut value).unwrap(), 0, "End of stream");
+
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    fn unfinished_segment() {
+        let content = content();
+        let mut output = Cursor::new(Vec::new());
+        let mut writer = CompressedWriter::new(&mut output, 64 * 1024, 1).unwrap();
+        writer.write_all(&content).unwrap();
+        //--- Crash before the table, the partial block is lost
+        writer.finished = true;
+        drop(writer);
+
+        let fname = temp_file("unfinished_segment");
+        std::fs::write(&fname, output.get_ref()).unwrap();
+        let mut reader = CompressedReader::open(File::open(&fname).unwrap()).unwrap();
+        let mut stream = Vec::new();
+        reader.read_to_end(&mut stream).unwrap();
+
+        assert_eq!(stream.len(), 6 * 64 * 1024, "Complete blocks");
+        assert_eq!(&stream[..], &content[..stream.len()], "Recovered content");
+
+        assert!(
+            !compress_file(&fname, 0, DEFAULT_LEVEL).unwrap(),
+            "Not compressed twice"
+        );
+        assert_eq!(
+            std::fs::read(&fname).unwrap(),
+            output.into_inner(),
+            "Already compressed"
+        );
+
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    fn oversized_block() {
+        let mut output = Cursor::new(Vec::new());
+        let mut writer = CompressedWriter::new(&mut output, 4096, 1).unwrap();
+        writer.write_all(&content()[..4096]).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        //--- Block header and table entry claim more than the block size
+        let mut segment = output.into_inner();
+        let table = segment.len() - TABLE_ENTRY_LEN;
+        LittleEndian::write_u32(&mut segment[table + 20..table + 24], 1 << 30);
+        let block = FILE_HEADER_LEN as usize;
+        LittleEndian::write_u32(&mut segment[block + 4..block + 8], 1 << 30);
+
+        let fname = temp_file("oversized_block");
+        std::fs::write(&fname, &segment).unwrap();
+        let mut reader = CompressedReader::open(File::open(&fname).unwrap()).unwrap();
+        let mut stream = Vec::new();
+        let error = reader.read_to_end(&mut stream).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData, "Block too large");
+
+        LittleEndian::write_u32(&mut segment[4..8], u32::MAX);
+        std::fs::write(&fname, &segment).unwrap();
+        assert!(
+            CompressedReader::open(File::open(&fname).unwrap()).is_err(),
+            "Block size out of range"
+        );
+
+        std::fs::remove_file(fname).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
-pub mod packet_id;
+pub mod compressed;
 pub mod packet_ptr;
+pub mod pcap_writer;
 pub mod pcapfile;
+pub mod pcapng;
+pub mod pcapng_writer;
+pub mod repair;
 pub mod seek_packet;
+pub mod segment;
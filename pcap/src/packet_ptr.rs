@@ -1,5 +1,5 @@
 #[derive(Default, Debug, Clone)]
 pub struct PacketPtr {
     pub file_id: u32,
-    pub pkt_ptr: Vec<u32>,
+    pub pkt_ptr: Vec<u64>,
 }
@@ -0,0 +1,228 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use frame::packet::Packet;
+use std::io::{Error, ErrorKind, Result, Write};
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_NS_MAGIC: u32 = 0xa1b23c4d;
+const VERSION_MAJOR: u16 = 2;
+const VERSION_MINOR: u16 = 4;
+const SNAPLEN: u32 = 0x0004_0000;
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_MICRO: u64 = 1_000;
+
+//--- Global header in the byte order and resolution of the records that follow
+pub fn global_header(link_type: u32, little_endian: bool, nanosecond: bool) -> [u8; 24] {
+    let mut header = [0u8; 24];
+    let magic = if nanosecond {
+        PCAP_NS_MAGIC
+    } else {
+        PCAP_MAGIC
+    };
+
+    if little_endian {
+        LittleEndian::write_u32(&mut header[0..4], magic);
+        LittleEndian::write_u16(&mut header[4..6], VERSION_MAJOR);
+        LittleEndian::write_u16(&mut header[6..8], VERSION_MINOR);
+        LittleEndian::write_u32(&mut header[16..20], SNAPLEN);
+        LittleEndian::write_u32(&mut header[20..24], link_type);
+    } else {
+        BigEndian::write_u32(&mut header[0..4], magic);
+        BigEndian::write_u16(&mut header[4..6], VERSION_MAJOR);
+        BigEndian::write_u16(&mut header[6..8], VERSION_MINOR);
+        BigEndian::write_u32(&mut header[16..20], SNAPLEN);
+        BigEndian::write_u32(&mut header[20..24], link_type);
+    }
+
+    header
+}
+
+//--- Record header in a given byte order and resolution
+fn record_header(pkt: &Packet, little_endian: bool, nanosecond: bool) -> [u8; 16] {
+    let ts_ns = pkt.timestamp_ns();
+    let fraction = if nanosecond {
+        ts_ns % NANOS_PER_SEC
+    } else {
+        (ts_ns % NANOS_PER_SEC) / NANOS_PER_MICRO
+    };
+    let orig_len = pkt
+        .get_field("frame.origlen")
+        .map_or(pkt.raw_bytes().len() as u32, |field| field.to_u32());
+    let values = [
+        (ts_ns / NANOS_PER_SEC) as u32,
+        fraction as u32,
+        pkt.raw_bytes().len() as u32,
+        orig_len,
+    ];
+
+    let mut header = [0u8; 16];
+    for (index, value) in values.iter().enumerate() {
+        if little_endian {
+            LittleEndian::write_u32(&mut header[index * 4..], *value);
+        } else {
+            BigEndian::write_u32(&mut header[index * 4..], *value);
+        }
+    }
+
+    header
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileFormat {
+    link_type: u32,
+    little_endian: bool,
+    nanosecond: bool,
+}
+
+impl FileFormat {
+    fn of(pkt: &Packet) -> Self {
+        Self {
+            link_type: pkt.link_type(),
+            little_endian: pkt.is_little_endian(),
+            nanosecond: pkt.is_ts_nanosecond(),
+        }
+    }
+}
+
+//------------------------------------------------------------------
+//--- Classic pcap writer, the file byte order and resolution are the
+//--- ones of the first packet written. Record headers are copied as
+//--- read, packets from a file in another format get a new header
+//------------------------------------------------------------------
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    format: Option<FileFormat>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: None,
+        }
+    }
+
+    pub fn write_packet(&mut self, pkt: &Packet) -> Result<()> {
+        let pkt_format = FileFormat::of(pkt);
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                self.writer.write_all(&global_header(
+                    pkt_format.link_type,
+                    pkt_format.little_endian,
+                    pkt_format.nanosecond,
+                ))?;
+                self.format = Some(pkt_format);
+                pkt_format
+            }
+        };
+
+        //--- A classic pcap file has one link type, pcapng has one per interface
+        if pkt_format.link_type != format.link_type {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Link type {} in a link type {} pcap file, use pcapng",
+                    pkt_format.link_type, format.link_type
+                ),
+            ));
+        }
+
+        if pkt_format == format {
+            self.writer.write_all(pkt.header())?;
+        } else {
+            self.writer
+                .write_all(&record_header(pkt, format.little_endian, format.nanosecond))?;
+        }
+        self.writer.write_all(pkt.raw_bytes())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+//--- Single packet as a one record pcap file
+pub fn packet_to_pcap(pkt: &Packet) -> Vec<u8> {
+    let mut writer = PcapWriter::new(Vec::new());
+    let _ = writer.write_packet(pkt);
+
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame::packet::LINKTYPE_ETHERNET;
+
+    #[test]
+    fn one_record_pcap() {
+        let data: Vec<u8> = vec![0xaa; 60];
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], 1_700_000_000);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(data, header, 1, 24, true, LINKTYPE_ETHERNET);
+        let pcap = packet_to_pcap(&pkt);
+
+        assert_eq!(pcap.len(), 24 + 16 + 60, "File size");
+        assert_eq!(
+            &pcap[0..4],
+            &[0xd4, 0xc3, 0xb2, 0xa1],
+            "Little endian magic"
+        );
+        assert_eq!(LittleEndian::read_u32(&pcap[20..24]), 1, "Link type");
+        assert_eq!(&pcap[24..40], &header, "Record header");
+    }
+
+    #[test]
+    fn nanosecond_magic() {
+        let header = global_header(1, false, true);
+        let format = crate::pcapfile::get_format(BigEndian::read_u32(&header[0..4]));
+
+        assert!(format.nanosecond, "Nanosecond resolution");
+        assert!(!format.little_endian, "Big endian");
+        assert_eq!(
+            crate::pcapfile::get_format(LittleEndian::read_u32(&header[0..4])),
+            crate::pcapfile::PcapFormat {
+                little_endian: true,
+                nanosecond: true
+            },
+            "Swapped magic"
+        );
+    }
+
+    #[test]
+    fn mixed_formats() {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], 1_700_000_000);
+        LittleEndian::write_u32(&mut header[4..8], 250_000);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+
+        let mut micro = Packet::new();
+        micro.set_packet(vec![0xaa; 60], header, 1, 24, true, LINKTYPE_ETHERNET);
+        let mut nano = micro.clone();
+        nano.set_ts_nanosecond(true);
+        let mut wlan = Packet::new();
+        wlan.set_packet(vec![0xaa; 60], header, 1, 24, true, 105);
+
+        let mut writer = PcapWriter::new(Vec::new());
+        writer.write_packet(&micro).unwrap();
+        writer.write_packet(&nano).unwrap();
+        assert!(writer.write_packet(&wlan).is_err(), "Link type mismatch");
+        let pcap = writer.into_inner();
+
+        assert_eq!(&pcap[24..40], &header, "Header kept");
+        assert_eq!(
+            LittleEndian::read_u32(&pcap[104..108]),
+            250,
+            "Nanoseconds as microseconds"
+        );
+    }
+}
@@ -1,47 +1,121 @@
+use crate::pcapng::{BLOCK_SHB, PcapngFile, is_pcapng};
+use crate::segment::SegmentReader;
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use frame::packet::Packet;
-use std::fs::File;
-use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::io::{BufReader, Read, Result, Seek, SeekFrom};
+
+//--- Magic numbers as read big endian from the global header
+pub const HEADER_BE: u32 = 0xa1b2c3d4;
+pub const HEADER_LE: u32 = 0xd4c3b2a1;
+pub const HEADER_NS_BE: u32 = 0xa1b23c4d;
+pub const HEADER_NS_LE: u32 = 0x4d3cb2a1;
+
+//--- Byte order and timestamp resolution of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcapFormat {
+    pub little_endian: bool,
+    pub nanosecond: bool,
+}
+
+//--- Unknown magic numbers are read as little endian microseconds
+pub fn get_format(magic_no: u32) -> PcapFormat {
+    PcapFormat {
+        little_endian: !matches!(magic_no, HEADER_BE | HEADER_NS_BE),
+        nanosecond: matches!(magic_no, HEADER_NS_BE | HEADER_NS_LE),
+    }
+}
 
-const HEADER_BE: u32 = 0xa1b2c3d4;
-const HEADER_LE: u32 = 0xd4c3b2a1;
+//--- Classic pcap in any byte order or resolution, or pcapng
+pub fn is_capture_file(fname: &str) -> bool {
+    let mut magic = [0; 4];
+
+    SegmentReader::open(fname)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && matches!(
+            BigEndian::read_u32(&magic),
+            HEADER_BE | HEADER_LE | HEADER_NS_BE | HEADER_NS_LE | BLOCK_SHB
+        )
+}
+
+//--- Record size is the included length, the original length is larger
+//--- when the capture was truncated by the snap length
+pub fn get_record_size(format: PcapFormat, pheader: &[u8; 16]) -> usize {
+    if format.little_endian {
+        LittleEndian::read_u32(&pheader[8..12]) as usize
+    } else {
+        BigEndian::read_u32(&pheader[8..12]) as usize
+    }
+}
 
 pub struct PcapFile {
     _filename: String,
-    file: BufReader<File>,
+    file: BufReader<SegmentReader>,
     header_read: bool,
-    pkt_ptr: u32,
+    pkt_ptr: u64,
     file_id: u32,
     magic_no: u32,
+    link_type: u32,
+    pcapng: Option<PcapngFile>,
+}
+
+//--- Linktype is the last field of the global header and follow the file byte order
+pub fn get_link_type(magic_no: u32, gheader: &[u8; 24]) -> u32 {
+    if get_format(magic_no).little_endian {
+        LittleEndian::read_u32(&gheader[20..24])
+    } else {
+        BigEndian::read_u32(&gheader[20..24])
+    }
 }
 
 impl PcapFile {
-    pub fn new(file_id: u32, db_path: &str) -> Self {
-        let fname = &format!("{}/{}.pcap", db_path, file_id);
-        Self {
+    //--- Any pcap or pcapng file, packets are numbered with file_id
+    pub fn open(fname: &str, file_id: u32) -> Result<Self> {
+        let mut file = BufReader::new(SegmentReader::open(fname)?);
+
+        //--- Segments keep the .pcap name whatever the format, pcapng is
+        //--- recognized by its section header
+        let mut magic = [0; 4];
+        let pcapng =
+            if file.read_exact(&mut magic).is_ok() && is_pcapng(BigEndian::read_u32(&magic)) {
+                Some(PcapngFile::open(fname, file_id)?)
+            } else {
+                None
+            };
+        file.rewind()?;
+
+        Ok(Self {
             _filename: fname.to_owned(),
             file_id,
-            file: BufReader::new(File::open(fname).unwrap()),
+            file,
             header_read: false,
             pkt_ptr: 0,
             magic_no: HEADER_LE,
-        }
+            link_type: 1,
+            pcapng,
+        })
+    }
+
+    pub fn is_pcapng(&self) -> bool {
+        self.pcapng.is_some()
     }
 
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<Packet> {
+        if let Some(pcapng) = &mut self.pcapng {
+            return pcapng.next();
+        }
+
         let mut gheader = [0; 24];
         let mut pheader = [0; 16];
         let mut data = Vec::new();
-        let mut little_endian: bool = true;
-
-        let psize: usize;
 
         if !self.header_read {
-            self.file.read_exact(&mut gheader).unwrap();
+            self.file.read_exact(&mut gheader).ok()?;
             self.header_read = true;
             self.pkt_ptr += 24;
             self.magic_no = BigEndian::read_u32(&gheader[0..4]);
+            self.link_type = get_link_type(self.magic_no, &gheader);
         }
 
         if self.file.read_exact(&mut pheader).is_err() {
@@ -50,69 +124,120 @@ impl PcapFile {
 
         //--- Must determine what to do when psize is zero. The file pointer will
         //--- not advance and we will spin on our self
-        match self.magic_no {
-            HEADER_LE => {
-                psize = LittleEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = true;
-            }
-            HEADER_BE => {
-                psize = BigEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = false;
-            }
-            _ => psize = LittleEndian::read_u32(&pheader[12..16]) as usize,
-        }
+        let format = get_format(self.magic_no);
+        let psize = get_record_size(format, &pheader);
 
+        //--- A truncated last record ends the file
         data.resize(psize, 0);
-        self.file.read_exact(&mut data).unwrap();
+        self.file.read_exact(&mut data).ok()?;
 
         let mut pkt = Packet::new();
-        pkt.set_packet(data, pheader, self.file_id, self.pkt_ptr, little_endian);
-        self.pkt_ptr += 16 + (psize as u32);
+        pkt.set_packet(
+            data,
+            pheader,
+            self.file_id,
+            self.pkt_ptr,
+            format.little_endian,
+            self.link_type,
+        );
+        pkt.set_ts_nanosecond(format.nanosecond);
+        self.pkt_ptr += 16 + psize as u64;
 
         Some(pkt)
     }
 
-    pub fn seek(&mut self, ptr: u32) -> Option<Packet> {
+    pub fn seek(&mut self, ptr: u64) -> Option<Packet> {
+        if let Some(pcapng) = &mut self.pcapng {
+            return pcapng.seek(ptr);
+        }
+
         let mut gheader = [0; 24];
         let mut pheader = [0; 16];
         let mut data = Vec::new();
-        let mut little_endian: bool = true;
-
-        let psize: usize;
 
         if !self.header_read {
-            self.file.read_exact(&mut gheader).unwrap();
+            self.file.read_exact(&mut gheader).ok()?;
             self.header_read = true;
             self.pkt_ptr += 24;
             self.magic_no = BigEndian::read_u32(&gheader[0..4]);
+            self.link_type = get_link_type(self.magic_no, &gheader);
+        }
+
+        //--- A pointer inside the global header can't be a packet
+        if ptr < 24 {
+            return None;
         }
 
-        let _ = self.file.seek(SeekFrom::Start(ptr as u64));
+        let _ = self.file.seek(SeekFrom::Start(ptr));
         if self.file.read_exact(&mut pheader).is_err() {
             return None;
         }
 
         //--- Must determine what to do when psize is zero. The file pointer will
         //--- not advance and we will spin on our self
-        match self.magic_no {
-            HEADER_LE => {
-                psize = LittleEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = true;
-            }
-            HEADER_BE => {
-                psize = BigEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = false;
-            }
-            _ => psize = LittleEndian::read_u32(&pheader[12..16]) as usize,
+        let format = get_format(self.magic_no);
+        let psize = get_record_size(format, &pheader);
+
+        //--- A pointer off a record boundary give a size past the end of file
+        let file_len = self.file.get_ref().uncompressed_len();
+        if ptr + 16 + psize as u64 > file_len {
+            return None;
         }
 
         data.resize(psize, 0);
-        self.file.read_exact(&mut data).unwrap();
+        if self.file.read_exact(&mut data).is_err() {
+            return None;
+        }
 
         let mut pkt = Packet::new();
-        pkt.set_packet(data, pheader, self.file_id, ptr, little_endian);
-        // self.pkt_ptr += 16 + (psize as u32);
+        pkt.set_packet(
+            data,
+            pheader,
+            self.file_id,
+            ptr,
+            format.little_endian,
+            self.link_type,
+        );
+        pkt.set_ts_nanosecond(format.nanosecond);
 
         Some(pkt)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap_writer::global_header;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn truncated_last_record() {
+        let mut content = global_header(1, true, false).to_vec();
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[8..12], 4);
+        LittleEndian::write_u32(&mut header[12..16], 1500);
+        content.extend_from_slice(&header);
+        content.extend_from_slice(&[1, 2, 3, 4]);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+        content.extend_from_slice(&header);
+        content.extend_from_slice(&[0; 10]);
+
+        let path = std::env::temp_dir().join(format!("truncated_{}.pcap", std::process::id()));
+        File::create(&path).unwrap().write_all(&content).unwrap();
+        let fname = path.to_string_lossy().to_string();
+
+        assert!(is_capture_file(&fname), "Capture file");
+        let mut pfile = PcapFile::open(&fname, 1).unwrap();
+        assert!(!pfile.is_pcapng(), "Classic pcap");
+        assert_eq!(
+            pfile.next().unwrap().raw_bytes(),
+            &[1, 2, 3, 4],
+            "Snap length record"
+        );
+        assert!(pfile.next().is_none(), "Truncated record");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,520 @@
+use crate::segment::SegmentReader;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use frame::packet::Packet;
+use std::io::{BufReader, Read, Result, Seek, SeekFrom};
+
+//--- Block types, the section header type reads the same in both byte orders
+pub const BLOCK_SHB: u32 = 0x0a0d0d0a;
+pub const BLOCK_IDB: u32 = 0x0000_0001;
+pub const BLOCK_SPB: u32 = 0x0000_0003;
+pub const BLOCK_NRB: u32 = 0x0000_0004;
+pub const BLOCK_ISB: u32 = 0x0000_0005;
+pub const BLOCK_EPB: u32 = 0x0000_0006;
+pub const BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+
+//--- Option codes
+pub const OPT_ENDOFOPT: u16 = 0;
+pub const OPT_COMMENT: u16 = 1;
+pub const IF_NAME: u16 = 2;
+pub const IF_DESCRIPTION: u16 = 3;
+pub const IF_TSRESOL: u16 = 9;
+pub const IF_TSOFFSET: u16 = 14;
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+const MIN_BLOCK_LEN: u64 = 12;
+
+pub fn is_pcapng(magic_no: u32) -> bool {
+    magic_no == BLOCK_SHB
+}
+
+//--- Interface description, timestamps are counted in units per second
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub link_type: u32,
+    pub snap_len: u32,
+    pub name: Option<String>,
+    pub ts_units: u64,
+    pub ts_offset: i64,
+}
+
+impl Interface {
+    //--- if_tsresol is a power of 10, or a power of 2 when the msb is set
+    fn set_ts_resol(&mut self, resol: u8) {
+        let exponent = (resol & 0x7f) as u32;
+        let base: u64 = if resol & 0x80 != 0 { 2 } else { 10 };
+
+        if let Some(units) = base.checked_pow(exponent) {
+            self.ts_units = units;
+        }
+    }
+
+    pub fn timestamp_ns(&self, ts: u64) -> u64 {
+        let ns = ts as u128 * NANOS_PER_SEC / self.ts_units as u128;
+        let offset = self.ts_offset as i128 * NANOS_PER_SEC as i128;
+
+        (ns as i128 + offset).clamp(0, u64::MAX as i128) as u64
+    }
+}
+
+//--- Interface ids are local to the section they are described in
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub offset: u64,
+    pub little_endian: bool,
+    pub interfaces: Vec<Interface>,
+}
+
+//--- Block read at a pointer, packets are decoded, anything else is skipped
+enum Block {
+    Packet(Box<Packet>),
+    Other,
+}
+
+fn read_u16(little_endian: bool, buf: &[u8]) -> u16 {
+    if little_endian {
+        LittleEndian::read_u16(buf)
+    } else {
+        BigEndian::read_u16(buf)
+    }
+}
+
+fn read_u32(little_endian: bool, buf: &[u8]) -> u32 {
+    if little_endian {
+        LittleEndian::read_u32(buf)
+    } else {
+        BigEndian::read_u32(buf)
+    }
+}
+
+//--- Options are code, length and a value padded to 32 bits
+fn read_options(little_endian: bool, buf: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut options = Vec::new();
+    let mut pos = 0;
+
+    while pos + 4 <= buf.len() {
+        let code = read_u16(little_endian, &buf[pos..]);
+        let len = read_u16(little_endian, &buf[pos + 2..]) as usize;
+        pos += 4;
+
+        if code == OPT_ENDOFOPT || pos + len > buf.len() {
+            break;
+        }
+
+        options.push((code, &buf[pos..pos + len]));
+        pos += len.next_multiple_of(4);
+    }
+
+    options
+}
+
+fn option_string(value: &[u8]) -> String {
+    String::from_utf8_lossy(value)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+fn read_interface(little_endian: bool, body: &[u8]) -> Option<Interface> {
+    if body.len() < 8 {
+        return None;
+    }
+
+    let mut interface = Interface {
+        link_type: read_u16(little_endian, &body[0..2]) as u32,
+        snap_len: read_u32(little_endian, &body[4..8]),
+        name: None,
+        ts_units: 1_000_000,
+        ts_offset: 0,
+    };
+
+    for (code, value) in read_options(little_endian, &body[8..]) {
+        match code {
+            IF_NAME => interface.name = Some(option_string(value)),
+            IF_DESCRIPTION if interface.name.is_none() => {
+                interface.name = Some(option_string(value))
+            }
+            IF_TSRESOL if !value.is_empty() => interface.set_ts_resol(value[0]),
+            IF_TSOFFSET if value.len() == 8 => {
+                let high = read_u32(little_endian, &value[0..4]) as u64;
+                let low = read_u32(little_endian, &value[4..8]) as u64;
+                interface.ts_offset = (high << 32 | low) as i64;
+            }
+            _ => {}
+        }
+    }
+
+    Some(interface)
+}
+
+//--- Block type and total length, the section header decides the byte order
+fn read_block_header(
+    file: &mut BufReader<SegmentReader>,
+    little_endian: bool,
+) -> Option<(u32, bool, u64)> {
+    let mut header = [0u8; 12];
+    file.read_exact(&mut header).ok()?;
+
+    let block_type = BigEndian::read_u32(&header[0..4]);
+    let little_endian = if block_type == BLOCK_SHB {
+        match LittleEndian::read_u32(&header[8..12]) {
+            BYTE_ORDER_MAGIC => true,
+            _ if BigEndian::read_u32(&header[8..12]) == BYTE_ORDER_MAGIC => false,
+            _ => return None,
+        }
+    } else {
+        little_endian
+    };
+
+    Some((
+        read_u32(little_endian, &header[0..4]),
+        little_endian,
+        read_u32(little_endian, &header[4..8]) as u64,
+    ))
+}
+
+//--- Sections and interfaces of the whole file, a truncated block ends
+//--- the scan so a file still being written can be read
+pub fn read_sections(filename: &str) -> Result<Vec<Section>> {
+    let mut file = BufReader::new(SegmentReader::open(filename)?);
+    let file_len = file.get_ref().uncompressed_len();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut pos: u64 = 0;
+    let mut little_endian = true;
+
+    while pos + MIN_BLOCK_LEN <= file_len {
+        file.seek(SeekFrom::Start(pos))?;
+        let Some((block_type, block_le, block_len)) = read_block_header(&mut file, little_endian)
+        else {
+            break;
+        };
+
+        if block_len < MIN_BLOCK_LEN || block_len % 4 != 0 || pos + block_len > file_len {
+            break;
+        }
+
+        match block_type {
+            BLOCK_SHB => {
+                little_endian = block_le;
+                sections.push(Section {
+                    offset: pos,
+                    little_endian,
+                    interfaces: Vec::new(),
+                });
+            }
+            BLOCK_IDB => {
+                let mut body = vec![0u8; (block_len - MIN_BLOCK_LEN) as usize];
+                file.seek(SeekFrom::Start(pos + 8))?;
+                file.read_exact(&mut body)?;
+
+                if let (Some(section), Some(interface)) =
+                    (sections.last_mut(), read_interface(little_endian, &body))
+                {
+                    section.interfaces.push(interface);
+                }
+            }
+            _ => {}
+        }
+
+        pos += block_len;
+    }
+
+    Ok(sections)
+}
+
+//------------------------------------------------------------------
+//--- Pcapng reader, packet pointers are the offsets of the packet
+//--- blocks so they can be stored in the .pidx like classic records
+//------------------------------------------------------------------
+pub struct PcapngFile {
+    file: BufReader<SegmentReader>,
+    file_id: u32,
+    file_len: u64,
+    position: u64,
+    next_ptr: u64,
+    sections: Vec<Section>,
+}
+
+impl PcapngFile {
+    pub fn open(filename: &str, file_id: u32) -> Result<Self> {
+        let sections = read_sections(filename)?;
+        let file = BufReader::new(SegmentReader::open(filename)?);
+        let file_len = file.get_ref().uncompressed_len();
+
+        Ok(Self {
+            file,
+            file_id,
+            file_len,
+            position: 0,
+            next_ptr: 0,
+            sections,
+        })
+    }
+
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Packet> {
+        loop {
+            let ptr = self.next_ptr;
+            let (block, block_len) = self.read_block(ptr)?;
+            self.next_ptr = ptr + block_len;
+
+            if let Block::Packet(pkt) = block {
+                return Some(*pkt);
+            }
+        }
+    }
+
+    pub fn seek(&mut self, ptr: u64) -> Option<Packet> {
+        match self.read_block(ptr)? {
+            (Block::Packet(pkt), _) => Some(*pkt),
+            (Block::Other, _) => None,
+        }
+    }
+
+    fn section(&self, ptr: u64) -> Option<&Section> {
+        self.sections
+            .iter()
+            .rev()
+            .find(|section| section.offset <= ptr)
+    }
+
+    fn read_block(&mut self, ptr: u64) -> Option<(Block, u64)> {
+        if ptr + MIN_BLOCK_LEN > self.file_len {
+            return None;
+        }
+
+        //--- Relative seeks keep the buffer on forward reads
+        self.file
+            .seek_relative(ptr as i64 - self.position as i64)
+            .ok()?;
+        self.position = ptr;
+
+        let little_endian = self.section(ptr).is_none_or(|s| s.little_endian);
+        let (block_type, _, block_len) = read_block_header(&mut self.file, little_endian)?;
+        self.position += MIN_BLOCK_LEN;
+
+        //--- A pointer off a block boundary give a length past the end of file
+        if block_len < MIN_BLOCK_LEN || block_len % 4 != 0 || ptr + block_len > self.file_len {
+            return None;
+        }
+
+        if block_type != BLOCK_EPB && block_type != BLOCK_SPB {
+            return Some((Block::Other, block_len));
+        }
+
+        //--- Block body follows the type and length, the header read 4 bytes of it
+        let mut body = vec![0u8; (block_len - MIN_BLOCK_LEN) as usize];
+        self.file.seek_relative(-4).ok()?;
+        self.file.read_exact(&mut body).ok()?;
+        self.position = ptr + 8 + body.len() as u64;
+
+        let pkt = match block_type {
+            BLOCK_EPB => self.enhanced_packet(ptr, &body),
+            _ => self.simple_packet(ptr, &body),
+        };
+
+        Some((
+            pkt.map_or(Block::Other, |pkt| Block::Packet(Box::new(pkt))),
+            block_len,
+        ))
+    }
+
+    fn enhanced_packet(&self, ptr: u64, body: &[u8]) -> Option<Packet> {
+        let section = self.section(ptr)?;
+        let little_endian = section.little_endian;
+
+        if body.len() < 20 {
+            return None;
+        }
+
+        let interface_id = read_u32(little_endian, &body[0..4]);
+        let interface = section.interfaces.get(interface_id as usize)?;
+        let ts_high = read_u32(little_endian, &body[4..8]) as u64;
+        let ts_low = read_u32(little_endian, &body[8..12]) as u64;
+        let cap_len = read_u32(little_endian, &body[12..16]) as usize;
+        let orig_len = read_u32(little_endian, &body[16..20]);
+
+        let data_end = 20 + cap_len;
+        if data_end > body.len() {
+            return None;
+        }
+
+        let comment: Vec<String> = read_options(
+            little_endian,
+            &body[data_end.next_multiple_of(4).min(body.len())..],
+        )
+        .into_iter()
+        .filter(|(code, _)| *code == OPT_COMMENT)
+        .map(|(_, value)| option_string(value))
+        .collect();
+
+        let mut pkt = self.build_packet(
+            ptr,
+            interface,
+            interface.timestamp_ns(ts_high << 32 | ts_low),
+            orig_len,
+            body[20..data_end].to_vec(),
+        );
+        pkt.set_interface(interface_id, interface.name.clone());
+        if !comment.is_empty() {
+            pkt.set_comment(Some(comment.join("\n")));
+        }
+
+        Some(pkt)
+    }
+
+    //--- Simple packets belong to the first interface and carry no timestamp
+    fn simple_packet(&self, ptr: u64, body: &[u8]) -> Option<Packet> {
+        let section = self.section(ptr)?;
+        let interface = section.interfaces.first()?;
+
+        if body.len() < 4 {
+            return None;
+        }
+
+        let orig_len = read_u32(section.little_endian, &body[0..4]);
+        let mut cap_len = (orig_len as usize).min(body.len() - 4);
+        if interface.snap_len > 0 {
+            cap_len = cap_len.min(interface.snap_len as usize);
+        }
+
+        let mut pkt = self.build_packet(ptr, interface, 0, orig_len, body[4..4 + cap_len].to_vec());
+        pkt.set_interface(0, interface.name.clone());
+
+        Some(pkt)
+    }
+
+    //--- The frame layer reads a classic record header, one is built in
+    //--- little endian with a nanosecond fraction
+    fn build_packet(
+        &self,
+        ptr: u64,
+        interface: &Interface,
+        ts_ns: u64,
+        orig_len: u32,
+        data: Vec<u8>,
+    ) -> Packet {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], (ts_ns / NANOS_PER_SEC as u64) as u32);
+        LittleEndian::write_u32(&mut header[4..8], (ts_ns % NANOS_PER_SEC as u64) as u32);
+        LittleEndian::write_u32(&mut header[8..12], data.len() as u32);
+        LittleEndian::write_u32(&mut header[12..16], orig_len);
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(data, header, self.file_id, ptr, true, interface.link_type);
+        pkt.set_ts_nanosecond(true);
+
+        pkt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcapng_writer::{PcapngWriter, Record};
+    use std::fs::File;
+    use std::io::Write;
+
+    fn temp_file(name: &str, content: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("{}_{}.pcap", name, std::process::id()));
+        File::create(&path).unwrap().write_all(content).unwrap();
+
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn read_written_pcapng() {
+        let data = vec![0xaa; 60];
+        let mut writer = PcapngWriter::new(Vec::new());
+        writer
+            .write_record(&Record {
+                link_type: 1,
+                interface_name: Some("eth0"),
+                timestamp_ns: 1_700_000_000_123_456_789,
+                orig_len: 60,
+                data: &data,
+                comment: Some("first"),
+            })
+            .unwrap();
+        writer
+            .write_record(&Record {
+                link_type: 1,
+                interface_name: Some("eth1"),
+                timestamp_ns: 1_700_000_001_000_000_000,
+                orig_len: 1500,
+                data: &data[..30],
+                comment: None,
+            })
+            .unwrap();
+
+        let filename = temp_file("read_written_pcapng", &writer.into_inner());
+        let mut pfile = PcapngFile::open(&filename, 3).unwrap();
+
+        assert_eq!(pfile.sections().len(), 1, "One section");
+        assert_eq!(pfile.sections()[0].interfaces.len(), 2, "Two interfaces");
+
+        let first = pfile.next().unwrap();
+        let second = pfile.next().unwrap();
+        assert!(pfile.next().is_none(), "End of file");
+
+        assert_eq!(first.timestamp_ns(), 1_700_000_000_123_456_789, "Timestamp");
+        assert_eq!(first.interface_name(), Some("eth0"), "Interface name");
+        assert_eq!(
+            first.get_field("frame.comment").unwrap().to_string(),
+            "first",
+            "Comment"
+        );
+        assert_eq!(
+            second.get_field("frame.interface_id").unwrap().to_u32(),
+            1,
+            "Interface id"
+        );
+        assert_eq!(
+            second.get_field("frame.origlen").unwrap().to_u32(),
+            1500,
+            "Original length"
+        );
+        assert!(second.comment().is_none(), "No comment");
+
+        let seek = pfile.seek(second.pkt_ptr).unwrap();
+        assert_eq!(seek.raw_bytes(), second.raw_bytes(), "Seek by pointer");
+        assert!(
+            pfile.seek(second.pkt_ptr + 4).is_none(),
+            "Off block pointer"
+        );
+
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn big_endian_microsecond() {
+        let mut file: Vec<u8> = Vec::new();
+        //--- Section header
+        file.extend_from_slice(&[0x0a, 0x0d, 0x0d, 0x0a, 0, 0, 0, 28, 0x1a, 0x2b, 0x3c, 0x4d]);
+        file.extend_from_slice(&[0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        file.extend_from_slice(&[0, 0, 0, 28]);
+        //--- Interface without if_tsresol
+        file.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 20, 0, 1, 0, 0, 0, 0, 0, 0]);
+        file.extend_from_slice(&[0, 0, 0, 20]);
+        //--- Name resolution block is skipped
+        file.extend_from_slice(&[0, 0, 0, 4, 0, 0, 0, 16, 0, 0, 0, 0, 0, 0, 0, 16]);
+        //--- Enhanced packet, 1.5 seconds and 4 bytes of data
+        file.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 36, 0, 0, 0, 0, 0, 0, 0, 0]);
+        file.extend_from_slice(&[0, 0x16, 0xe3, 0x60, 0, 0, 0, 4, 0, 0, 0, 4]);
+        file.extend_from_slice(&[1, 2, 3, 4, 0, 0, 0, 36]);
+
+        let filename = temp_file("big_endian_microsecond", &file);
+        let mut pfile = PcapngFile::open(&filename, 1).unwrap();
+        let pkt = pfile.next().unwrap();
+
+        assert!(!pfile.sections()[0].little_endian, "Big endian section");
+        assert_eq!(pkt.pkt_ptr, 64, "Block offset");
+        assert_eq!(pkt.timestamp_ns(), 1_500_000_000, "Microsecond resolution");
+        assert_eq!(pkt.raw_bytes(), &[1, 2, 3, 4], "Packet data");
+        assert!(pkt.interface_name().is_none(), "No interface name");
+
+        std::fs::remove_file(filename).unwrap();
+    }
+}
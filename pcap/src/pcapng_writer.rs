@@ -0,0 +1,254 @@
+use crate::pcapng::{
+    BLOCK_EPB, BLOCK_IDB, BLOCK_SHB, BYTE_ORDER_MAGIC, IF_NAME, IF_TSRESOL, OPT_COMMENT,
+    OPT_ENDOFOPT,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use frame::packet::Packet;
+use std::io::{Result, Write};
+
+const VERSION_MAJOR: u16 = 1;
+const VERSION_MINOR: u16 = 0;
+const SNAPLEN: u32 = 0x0004_0000;
+const TSRESOL_NS: u8 = 9;
+
+//--- One packet to write, timestamps are always written in nanoseconds
+pub struct Record<'a> {
+    pub link_type: u32,
+    pub interface_name: Option<&'a str>,
+    pub timestamp_ns: u64,
+    pub orig_len: u32,
+    pub data: &'a [u8],
+    pub comment: Option<&'a str>,
+}
+
+fn push_u16(block: &mut Vec<u8>, value: u16) {
+    let mut buf = [0u8; 2];
+    LittleEndian::write_u16(&mut buf, value);
+    block.extend_from_slice(&buf);
+}
+
+fn push_u32(block: &mut Vec<u8>, value: u32) {
+    let mut buf = [0u8; 4];
+    LittleEndian::write_u32(&mut buf, value);
+    block.extend_from_slice(&buf);
+}
+
+fn push_padded(block: &mut Vec<u8>, value: &[u8]) {
+    block.extend_from_slice(value);
+    block.resize(block.len().next_multiple_of(4), 0);
+}
+
+fn push_option(block: &mut Vec<u8>, code: u16, value: &[u8]) {
+    push_u16(block, code);
+    push_u16(block, value.len() as u16);
+    push_padded(block, value);
+}
+
+//--- Type, total length, body and total length again
+fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+    let block_len = (body.len() + 12) as u32;
+    let mut block = Vec::with_capacity(block_len as usize);
+
+    push_u32(&mut block, block_type);
+    push_u32(&mut block, block_len);
+    block.extend_from_slice(body);
+    push_u32(&mut block, block_len);
+
+    block
+}
+
+//------------------------------------------------------------------
+//--- Pcapng writer in little endian, one section with an interface
+//--- block added the first time a link type and name pair is seen
+//------------------------------------------------------------------
+pub struct PcapngWriter<W: Write> {
+    writer: W,
+    header_written: bool,
+    interfaces: Vec<(u32, Option<String>)>,
+    position: u64,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            header_written: false,
+            interfaces: Vec::new(),
+            position: 0,
+        }
+    }
+
+    pub fn write_packet(&mut self, pkt: &Packet) -> Result<()> {
+        self.write_packet_with_comment(pkt, pkt.comment())
+    }
+
+    pub fn write_packet_with_comment(&mut self, pkt: &Packet, comment: Option<&str>) -> Result<()> {
+        let orig_len = pkt
+            .get_field("frame.origlen")
+            .map_or(pkt.raw_bytes().len() as u32, |field| field.to_u32());
+
+        self.write_record(&Record {
+            link_type: pkt.link_type(),
+            interface_name: pkt.interface_name(),
+            timestamp_ns: pkt.timestamp_ns(),
+            orig_len,
+            data: pkt.raw_bytes(),
+            comment,
+        })
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> Result<()> {
+        if !self.header_written {
+            self.write_section_header()?;
+            self.header_written = true;
+        }
+
+        let interface_id = self.interface_id(record.link_type, record.interface_name)?;
+
+        let mut body = Vec::with_capacity(record.data.len() + 32);
+        push_u32(&mut body, interface_id);
+        push_u32(&mut body, (record.timestamp_ns >> 32) as u32);
+        push_u32(&mut body, record.timestamp_ns as u32);
+        push_u32(&mut body, record.data.len() as u32);
+        push_u32(&mut body, record.orig_len);
+        push_padded(&mut body, record.data);
+
+        //--- One option per line, the reader joins them back with newlines
+        if let Some(comment) = record.comment {
+            for line in comment.split('\n') {
+                push_option(&mut body, OPT_COMMENT, line.as_bytes());
+            }
+            push_option(&mut body, OPT_ENDOFOPT, &[]);
+        }
+
+        self.write_block(&block(BLOCK_EPB, &body))
+    }
+
+    //--- Bytes written, the last packet block ends there
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn write_block(&mut self, block: &[u8]) -> Result<()> {
+        self.writer.write_all(block)?;
+        self.position += block.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    //--- Section length is unknown (-1) as the file is written as a stream
+    fn write_section_header(&mut self) -> Result<()> {
+        let mut body = Vec::with_capacity(16);
+        push_u32(&mut body, BYTE_ORDER_MAGIC);
+        push_u16(&mut body, VERSION_MAJOR);
+        push_u16(&mut body, VERSION_MINOR);
+        body.extend_from_slice(&[0xff; 8]);
+
+        self.write_block(&block(BLOCK_SHB, &body))
+    }
+
+    fn interface_id(&mut self, link_type: u32, name: Option<&str>) -> Result<u32> {
+        if let Some(index) = self
+            .interfaces
+            .iter()
+            .position(|(lt, nm)| *lt == link_type && nm.as_deref() == name)
+        {
+            return Ok(index as u32);
+        }
+
+        let mut body = Vec::with_capacity(32);
+        push_u16(&mut body, link_type as u16);
+        push_u16(&mut body, 0);
+        push_u32(&mut body, SNAPLEN);
+        if let Some(name) = name {
+            push_option(&mut body, IF_NAME, name.as_bytes());
+        }
+        push_option(&mut body, IF_TSRESOL, &[TSRESOL_NS]);
+        push_option(&mut body, OPT_ENDOFOPT, &[]);
+
+        self.write_block(&block(BLOCK_IDB, &body))?;
+        self.interfaces.push((link_type, name.map(str::to_string)));
+
+        Ok(self.interfaces.len() as u32 - 1)
+    }
+}
+
+//--- Single packet as a one section pcapng file
+pub fn packet_to_pcapng(pkt: &Packet) -> Vec<u8> {
+    let mut writer = PcapngWriter::new(Vec::new());
+    let _ = writer.write_packet(pkt);
+
+    writer.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use frame::packet::LINKTYPE_ETHERNET;
+
+    #[test]
+    fn classic_packet_to_pcapng() {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], 1_700_000_000);
+        LittleEndian::write_u32(&mut header[4..8], 500_000);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 60);
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(vec![0xaa; 60], header, 1, 24, true, LINKTYPE_ETHERNET);
+        let pcapng = packet_to_pcapng(&pkt);
+
+        //--- Section header 28, interface 32, enhanced packet 92
+        assert_eq!(pcapng.len(), 28 + 32 + 92, "File size");
+        assert_eq!(&pcapng[0..4], &[0x0a, 0x0d, 0x0d, 0x0a], "Section header");
+        assert_eq!(
+            LittleEndian::read_u32(&pcapng[28..32]),
+            BLOCK_IDB,
+            "Interface"
+        );
+        assert_eq!(LittleEndian::read_u32(&pcapng[60..64]), BLOCK_EPB, "Packet");
+
+        let ts = (LittleEndian::read_u32(&pcapng[72..76]) as u64) << 32
+            | LittleEndian::read_u32(&pcapng[76..80]) as u64;
+        assert_eq!(ts, 1_700_000_000_500_000_000, "Nanosecond timestamp");
+    }
+
+    #[test]
+    fn block_position() {
+        let mut writer = PcapngWriter::new(Vec::new());
+        let record = Record {
+            link_type: LINKTYPE_ETHERNET,
+            interface_name: Some("eth0"),
+            timestamp_ns: 1_700_000_000_000_000_000,
+            orig_len: 60,
+            data: &[0xaa; 60],
+            comment: None,
+        };
+
+        writer.write_record(&record).unwrap();
+        let first_end = writer.position();
+        writer.write_record(&record).unwrap();
+        let epb_len = writer.position() - first_end;
+
+        let pcapng = writer.into_inner();
+        assert_eq!(pcapng.len() as u64, first_end + epb_len, "Bytes written");
+        assert_eq!(
+            LittleEndian::read_u32(&pcapng[(first_end - epb_len) as usize..]),
+            BLOCK_EPB,
+            "First packet block"
+        );
+        assert_eq!(
+            LittleEndian::read_u32(&pcapng[first_end as usize..]),
+            BLOCK_EPB,
+            "Second packet block"
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use crate::compressed::CompressedWriter;
+use crate::pcapfile::{get_format, get_record_size};
+use crate::pcapng::{BYTE_ORDER_MAGIC, is_pcapng};
+use crate::segment::SegmentReader;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Result, Seek, SeekFrom};
+
+const PCAP_HEADER_LEN: u64 = 24;
+const PCAP_RECORD_LEN: u64 = 16;
+//--- Block type and total length
+const BLOCK_HEADER_LEN: u64 = 8;
+
+//--- Bytes skipped, fewer than asked at the end of the stream
+fn skip<R: Read>(reader: &mut R, len: u64) -> Result<bool> {
+    Ok(io::copy(&mut reader.take(len), &mut io::sink())? == len)
+}
+
+//------------------------------------------------------------------
+//--- Length of the complete records of a pcap or pcapng stream, the
+//--- bytes after it are a record cut by a crash. A stream without a
+//--- complete global header has no complete record
+//------------------------------------------------------------------
+pub fn complete_length<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut gheader = [0u8; PCAP_HEADER_LEN as usize];
+    if reader.read_exact(&mut gheader[..12]).is_err() {
+        return Ok(0);
+    }
+
+    if is_pcapng(BigEndian::read_u32(&gheader[0..4])) {
+        let little_endian = LittleEndian::read_u32(&gheader[8..12]) == BYTE_ORDER_MAGIC;
+        let read_u32 = |buf: &[u8]| {
+            if little_endian {
+                LittleEndian::read_u32(buf)
+            } else {
+                BigEndian::read_u32(buf)
+            }
+        };
+
+        //--- The section header is the first block, 12 bytes already read
+        let mut length: u64 = 0;
+        let mut block_len = read_u32(&gheader[4..8]) as u64;
+        let mut read = 12;
+        loop {
+            if block_len < 12 || !block_len.is_multiple_of(4) || !skip(reader, block_len - read)? {
+                return Ok(length);
+            }
+            length += block_len;
+
+            let mut header = [0u8; BLOCK_HEADER_LEN as usize];
+            if reader.read_exact(&mut header).is_err() {
+                return Ok(length);
+            }
+            block_len = read_u32(&header[4..8]) as u64;
+            read = BLOCK_HEADER_LEN;
+        }
+    }
+
+    if reader.read_exact(&mut gheader[12..]).is_err() {
+        return Ok(0);
+    }
+    let format = get_format(BigEndian::read_u32(&gheader[0..4]));
+
+    let mut length = PCAP_HEADER_LEN;
+    let mut pheader = [0u8; PCAP_RECORD_LEN as usize];
+    while reader.read_exact(&mut pheader).is_ok() {
+        let psize = get_record_size(format, &pheader) as u64;
+        if !skip(reader, psize)? {
+            break;
+        }
+        length += PCAP_RECORD_LEN + psize;
+    }
+
+    Ok(length)
+}
+
+//------------------------------------------------------------------
+//--- Segment left open by a crash. A plain segment is truncated after
+//--- its last complete record, a compressed one is written again with
+//--- its complete records and its block table. Returns the bytes of the
+//--- pcap stream removed, None when the segment was complete
+//------------------------------------------------------------------
+pub fn repair_segment(fname: &str, level: i32) -> Result<Option<u64>> {
+    //--- Segments in a source folder are linked from db_path, the link is kept
+    let fname = &fs::canonicalize(fname)?.to_string_lossy().to_string();
+    let mut segment = BufReader::new(SegmentReader::open(fname)?);
+    let total = segment.get_ref().uncompressed_len();
+    let complete = complete_length(&mut segment)?;
+
+    if complete == total && segment.get_ref().is_finished() {
+        return Ok(None);
+    }
+
+    match segment.into_inner() {
+        SegmentReader::Plain(_) => {
+            OpenOptions::new()
+                .write(true)
+                .open(fname)?
+                .set_len(complete)?;
+        }
+        SegmentReader::Compressed(mut reader) => {
+            let tmp_name = format!("{}.tmp", fname);
+            let tmp_file = File::create(&tmp_name)?;
+            {
+                let mut writer = CompressedWriter::new(&tmp_file, reader.block_size(), level)?;
+                reader.seek(SeekFrom::Start(0))?;
+                io::copy(&mut reader.by_ref().take(complete), &mut writer)?;
+                writer.finish()?;
+            }
+            tmp_file.sync_all()?;
+            fs::rename(&tmp_name, fname)?;
+        }
+    }
+
+    Ok(Some(total - complete))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap_writer::global_header;
+    use crate::pcapng_writer::{PcapngWriter, Record};
+    use std::io::Cursor;
+
+    fn pcap_record(len: usize) -> Vec<u8> {
+        let mut record = vec![0u8; 16];
+        LittleEndian::write_u32(&mut record[8..12], len as u32);
+        LittleEndian::write_u32(&mut record[12..16], len as u32);
+        record.extend(vec![0xaa; len]);
+        record
+    }
+
+    #[test]
+    fn pcap_length() {
+        let mut content = global_header(1, true, false).to_vec();
+        content.extend(pcap_record(60));
+        content.extend(pcap_record(100));
+        let complete = content.len() as u64;
+
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content)).unwrap(),
+            complete,
+            "Complete file"
+        );
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content[..10])).unwrap(),
+            0,
+            "Partial header"
+        );
+
+        content.extend(&pcap_record(80)[..50]);
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content)).unwrap(),
+            complete,
+            "Partial record"
+        );
+        content.truncate(complete as usize + 7);
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content)).unwrap(),
+            complete,
+            "Partial record header"
+        );
+    }
+
+    #[test]
+    fn pcapng_length() {
+        let mut writer = PcapngWriter::new(Vec::new());
+        for _ in 0..2 {
+            writer
+                .write_record(&Record {
+                    link_type: 1,
+                    interface_name: None,
+                    timestamp_ns: 1_700_000_000_000_000_000,
+                    orig_len: 60,
+                    data: &[0xaa; 60],
+                    comment: None,
+                })
+                .unwrap();
+        }
+        let complete = writer.position();
+        let mut content = writer.into_inner();
+
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content)).unwrap(),
+            complete,
+            "Complete file"
+        );
+
+        //--- First 40 bytes of a 92 bytes packet block
+        let partial = content[content.len() - 92..][..40].to_vec();
+        content.extend(partial);
+        assert_eq!(
+            complete_length(&mut Cursor::new(&content)).unwrap(),
+            complete,
+            "Partial block"
+        );
+    }
+
+    #[test]
+    fn repair_plain_and_compressed() {
+        let mut content = global_header(1, true, false).to_vec();
+        for _ in 0..100 {
+            content.extend(pcap_record(200));
+        }
+        let complete = content.len();
+        content.extend(&pcap_record(200)[..30]);
+
+        let fname = std::env::temp_dir()
+            .join(format!("repair_{}.pcap", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        fs::write(&fname, &content).unwrap();
+
+        assert_eq!(
+            repair_segment(&fname, 1).unwrap(),
+            Some(30),
+            "Plain repaired"
+        );
+        assert_eq!(
+            fs::read(&fname).unwrap(),
+            &content[..complete],
+            "Plain truncated"
+        );
+        assert_eq!(repair_segment(&fname, 1).unwrap(), None, "Plain complete");
+
+        //--- Compressed segment left without its table, the last block cuts a record
+        let mut output = Cursor::new(Vec::new());
+        let mut writer = CompressedWriter::new(&mut output, 1000, 1).unwrap();
+        io::copy(&mut &content[..], &mut writer).unwrap();
+        std::mem::forget(writer);
+        fs::write(&fname, output.get_ref()).unwrap();
+
+        let removed = repair_segment(&fname, 1).unwrap().unwrap();
+        let mut reader = SegmentReader::open(&fname).unwrap();
+        let mut stream = Vec::new();
+        reader.read_to_end(&mut stream).unwrap();
+        assert!(reader.is_finished(), "Block table written");
+        assert_eq!(
+            stream.len() as u64,
+            (content.len() / 1000 * 1000) as u64 - removed,
+            "Complete blocks kept"
+        );
+        assert_eq!(
+            complete_length(&mut Cursor::new(&stream)).unwrap(),
+            stream.len() as u64,
+            "Complete records"
+        );
+        assert_eq!(&stream[..], &content[..stream.len()], "Same records");
+        assert_eq!(
+            repair_segment(&fname, 1).unwrap(),
+            None,
+            "Compressed complete"
+        );
+
+        fs::remove_file(fname).unwrap();
+    }
+}
@@ -1,48 +1,59 @@
 use byteorder::BigEndian;
-use byteorder::{ByteOrder, LittleEndian};
+use byteorder::ByteOrder;
 use frame::packet::Packet;
-use std::fs::File;
-use std::io::BufReader;
 use std::io::prelude::*;
+use std::io::{BufReader, Result};
 
 use crate::packet_ptr::PacketPtr;
-use dblib::config;
+use crate::pcapfile::{HEADER_LE, get_format, get_link_type, get_record_size};
+use crate::pcapng::{PcapngFile, is_pcapng};
+use crate::segment::SegmentReader;
 
-const HEADER_BE: u32 = 0xa1b2c3d4;
-const HEADER_LE: u32 = 0xd4c3b2a1;
-
-pub struct SeekPacket<'a> {
-    file: BufReader<File>,
+pub struct SeekPacket {
+    file: BufReader<SegmentReader>,
     index: usize,
-    plist: &'a PacketPtr,
+    plist: PacketPtr,
     data: Vec<u8>,
     relative_ptr: u64,
     psize: usize,
     magic_no: u32,
+    link_type: u32,
+    pcapng: Option<PcapngFile>,
 }
 
-impl<'a> SeekPacket<'a> {
-    pub fn new(packet_list: &'a PacketPtr) -> Self {
-        let fname = &format!("{}/{}.pcap", config::CONFIG.db_path, packet_list.file_id);
-
-        let mut magic_file = BufReader::new(File::open(fname).unwrap());
+impl SeekPacket {
+    //--- Packets of the list read from the segment file, an error when the
+    //--- segment is gone
+    pub fn open(fname: &str, packet_list: PacketPtr) -> Result<Self> {
+        let mut file = BufReader::new(SegmentReader::open(fname)?);
         let mut gheader = [0; 24];
 
-        let magic_no: u32 = if magic_file.read_exact(&mut gheader).is_ok() {
-            BigEndian::read_u32(&gheader[0..4])
+        let (magic_no, link_type) = if file.read_exact(&mut gheader).is_ok() {
+            let magic_no = BigEndian::read_u32(&gheader[0..4]);
+            (magic_no, get_link_type(magic_no, &gheader))
         } else {
-            HEADER_LE
+            (HEADER_LE, 1)
         };
+        let pcapng = if is_pcapng(magic_no) {
+            PcapngFile::open(fname, packet_list.file_id).ok()
+        } else {
+            None
+        };
+
+        //--- Packet pointers are offsets from the start of the file
+        file.rewind()?;
 
-        Self {
-            file: BufReader::new(File::open(fname).unwrap()),
+        Ok(Self {
+            file,
             index: 0,
             plist: packet_list,
             data: Vec::new(),
             relative_ptr: 0,
             psize: 0,
             magic_no,
-        }
+            link_type,
+            pcapng,
+        })
     }
 
     #[allow(clippy::should_implement_trait)]
@@ -51,10 +62,16 @@ impl<'a> SeekPacket<'a> {
             return None;
         }
 
+        if let Some(pcapng) = &mut self.pcapng {
+            let pkt = pcapng.seek(self.plist.pkt_ptr[self.index]);
+            self.index += 1;
+            return pkt;
+        }
+
         let mut pheader = [0; 16];
-        let ptr = self.plist.pkt_ptr[self.index] as u64 - self.relative_ptr;
+        let ptr = self.plist.pkt_ptr[self.index] - self.relative_ptr;
 
-        self.file.seek_relative(ptr as i64).unwrap();
+        self.file.seek_relative(ptr as i64).ok()?;
 
         if self.file.read_exact(&mut pheader).is_err() {
             return None;
@@ -62,18 +79,8 @@ impl<'a> SeekPacket<'a> {
 
         //--- Must determine what to do when psize is zero. The file pointer will
         //--- not advance and we will spin on our self
-        let mut little_endian = true;
-        match self.magic_no {
-            HEADER_LE => {
-                self.psize = LittleEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = true;
-            }
-            HEADER_BE => {
-                self.psize = BigEndian::read_u32(&pheader[12..16]) as usize;
-                little_endian = false;
-            }
-            _ => self.psize = 0,
-        }
+        let format = get_format(self.magic_no);
+        self.psize = get_record_size(format, &pheader);
 
         // self.psize = BigEndian::read_u32(&pheader[12..16]) as usize;
         self.data.resize(self.psize, 0);
@@ -82,7 +89,7 @@ impl<'a> SeekPacket<'a> {
             return None;
         }
 
-        self.relative_ptr = self.file.stream_position().unwrap();
+        self.relative_ptr = self.file.stream_position().ok()?;
 
         let mut pkt = Packet::new();
         pkt.set_packet(
@@ -90,8 +97,10 @@ impl<'a> SeekPacket<'a> {
             pheader,
             self.plist.file_id,
             self.plist.pkt_ptr[self.index],
-            little_endian,
+            format.little_endian,
+            self.link_type,
         );
+        pkt.set_ts_nanosecond(format.nanosecond);
         self.index += 1;
 
         Some(pkt)
@@ -111,3 +120,46 @@ impl<'a> SeekPacket<'a> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap_writer::PcapWriter;
+    use byteorder::LittleEndian;
+    use frame::packet::LINKTYPE_ETHERNET;
+
+    #[test]
+    fn seek_listed_packets() {
+        let mut writer = PcapWriter::new(Vec::new());
+        for secs in 1..=3u32 {
+            let mut header = [0u8; 16];
+            LittleEndian::write_u32(&mut header[0..4], secs);
+            LittleEndian::write_u32(&mut header[8..12], 60);
+            LittleEndian::write_u32(&mut header[12..16], 60);
+            let mut pkt = Packet::new();
+            pkt.set_packet(vec![0xaa; 60], header, 9, 0, true, LINKTYPE_ETHERNET);
+            writer.write_packet(&pkt).unwrap();
+        }
+
+        let fname = std::env::temp_dir()
+            .join(format!("seek_listed_packets_{}.pcap", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(&fname, writer.into_inner()).unwrap();
+
+        let plist = PacketPtr {
+            file_id: 9,
+            pkt_ptr: vec![24, 24 + 2 * (16 + 60)],
+        };
+        let mut seek_pkt = SeekPacket::open(&fname, plist.clone()).unwrap();
+        let first = seek_pkt.next().unwrap();
+        let last = seek_pkt.next().unwrap();
+
+        assert_eq!(first.timestamp_ns(), 1_000_000_000, "First packet");
+        assert_eq!(last.timestamp_ns(), 3_000_000_000, "Skipped packet");
+        assert!(seek_pkt.next().is_none(), "End of list");
+
+        std::fs::remove_file(&fname).unwrap();
+        assert!(SeekPacket::open(&fname, plist).is_err(), "Segment gone");
+    }
+}
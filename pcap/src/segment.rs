@@ -0,0 +1,149 @@
+use crate::compressed::{CompressedReader, CompressedWriter, DEFAULT_LEVEL, is_compressed};
+use byteorder::{BigEndian, ByteOrder};
+use dblib::config::CONFIG;
+use std::fs::{self, File, TryLockError};
+use std::io::{BufWriter, Read, Result, Seek, SeekFrom, Write};
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+//------------------------------------------------------------------
+//--- Segment file as stored on disk, plain or compressed. Readers see
+//--- the uncompressed pcap or pcapng stream in both cases
+//------------------------------------------------------------------
+pub enum SegmentReader {
+    Plain(File),
+    Compressed(CompressedReader),
+}
+
+impl SegmentReader {
+    pub fn open(fname: &str) -> Result<Self> {
+        let mut file = File::open(fname)?;
+        let mut magic = [0u8; 4];
+        let compressed =
+            file.read_exact(&mut magic).is_ok() && is_compressed(BigEndian::read_u32(&magic));
+        file.rewind()?;
+
+        if compressed {
+            Ok(SegmentReader::Compressed(CompressedReader::open(file)?))
+        } else {
+            Ok(SegmentReader::Plain(file))
+        }
+    }
+
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, SegmentReader::Compressed(_))
+    }
+
+    //--- Plain segments have nothing to finish
+    pub fn is_finished(&self) -> bool {
+        match self {
+            SegmentReader::Plain(_) => true,
+            SegmentReader::Compressed(reader) => reader.is_finished(),
+        }
+    }
+
+    //--- Length of the pcap stream, packet pointers are checked against it
+    pub fn uncompressed_len(&self) -> u64 {
+        match self {
+            SegmentReader::Plain(file) => file.metadata().map_or(0, |m| m.len()),
+            SegmentReader::Compressed(reader) => reader.uncompressed_len(),
+        }
+    }
+}
+
+impl Read for SegmentReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        match self {
+            SegmentReader::Plain(file) => file.read(buf),
+            SegmentReader::Compressed(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for SegmentReader {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        match self {
+            SegmentReader::Plain(file) => file.seek(pos),
+            SegmentReader::Compressed(reader) => reader.seek(pos),
+        }
+    }
+}
+
+//------------------------------------------------------------------
+//--- Segment file being written, plain or compressed. Finish writes
+//--- what is buffered, and the block table of a compressed segment,
+//--- and returns the error a drop would lose
+//------------------------------------------------------------------
+pub enum SegmentFile {
+    Plain(BufWriter<File>),
+    Compressed(CompressedWriter<File>),
+}
+
+impl SegmentFile {
+    pub fn finish(&mut self) -> Result<()> {
+        match self {
+            SegmentFile::Plain(writer) => writer.flush(),
+            SegmentFile::Compressed(writer) => writer.finish(),
+        }
+    }
+}
+
+impl Write for SegmentFile {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match self {
+            SegmentFile::Plain(writer) => writer.write(buf),
+            SegmentFile::Compressed(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match self {
+            SegmentFile::Plain(writer) => writer.flush(),
+            SegmentFile::Compressed(writer) => writer.flush(),
+        }
+    }
+}
+
+//--- Level from the configuration, 0 is the zstd default
+pub fn compression_level() -> i32 {
+    match CONFIG.compression_level {
+        0 => DEFAULT_LEVEL,
+        level => level,
+    }
+}
+
+//--- True while the writer of a segment or of an index holds its lock
+pub fn is_locked(fname: &str) -> bool {
+    File::open(fname).is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+//--- New segment file {db_path}/{file_id}.pcap, compressed when
+//--- segment_compression is set. A segment stored in another folder is
+//--- linked from db_path so readers always find it by its file id. The
+//--- file is locked until the writer is dropped, recovery leaves it alone
+pub fn create_segment(file_id: u32, folder: &str) -> Result<SegmentFile> {
+    let fname = format!("{}/{}.pcap", &CONFIG.db_path, file_id);
+    let file = if folder.is_empty() {
+        File::create(&fname)?
+    } else {
+        let folder = Path::new(&CONFIG.db_path).join(folder);
+        fs::create_dir_all(&folder)?;
+
+        let stored = folder.join(format!("{}.pcap", file_id));
+        let file = File::create(&stored)?;
+        let _ = fs::remove_file(&fname);
+        symlink(fs::canonicalize(&stored)?, &fname)?;
+        file
+    };
+    file.lock()?;
+
+    if CONFIG.segment_compression {
+        Ok(SegmentFile::Compressed(CompressedWriter::new(
+            file,
+            CONFIG.block_size,
+            compression_level(),
+        )?))
+    } else {
+        Ok(SegmentFile::Plain(BufWriter::new(file)))
+    }
+}
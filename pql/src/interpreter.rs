@@ -3,15 +3,17 @@ use std::fmt::Display;
 
 use crate::parse::{Expression, Operator, PqlStatement};
 use field::ipv4_address::IPv4;
-use field::pfield::FieldType;
+use field::pfield::{Field, FieldType};
+use frame::flow::FlowRecord;
 use frame::packet::Packet;
-use pcap::packet_ptr::PacketPtr;
+use log::debug;
 use pcap::seek_packet::SeekPacket;
 use regex::Regex;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Object {
     Integer(u64),
+    SignedInteger(i64),
     Boolean(bool),
     IPv4(u32, u8),
     Timestamp(u32),
@@ -32,6 +34,7 @@ impl Display for Object {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             Object::Integer(value) => write!(f, "Int:{}", value),
+            Object::SignedInteger(value) => write!(f, "Signed:{}", value),
             Object::IPv4(addr, mask) => write!(f, "IPv4: {}/{}", addr, mask),
             Object::Boolean(value) => write!(f, "Bool: {}", value),
             Object::_Label(value) => write!(f, "Label: {}", value),
@@ -49,6 +52,7 @@ impl Object {
     pub fn debug_type(&self) -> String {
         match self {
             Object::Integer(_) => "Integer",
+            Object::SignedInteger(_) => "SignedInteger",
             Object::IPv4(_, _) => "IPv4",
             Object::Boolean(_) => "Boolean",
             Object::_Label(_) => "Label",
@@ -83,6 +87,31 @@ impl Object {
     }
 }
 
+//--- What a filter reads its labels from, a packet or a flow record
+pub trait FieldSource {
+    fn field(&self, name: &str) -> Option<Field>;
+
+    fn field_bytes(&self, _name: &str, _offset: usize, _len: usize) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+impl FieldSource for Packet {
+    fn field(&self, name: &str) -> Option<Field> {
+        self.get_field(name)
+    }
+
+    fn field_bytes(&self, name: &str, offset: usize, len: usize) -> Vec<u8> {
+        self.get_field_byte(name.to_string(), offset, len)
+    }
+}
+
+impl FieldSource for FlowRecord {
+    fn field(&self, name: &str) -> Option<Field> {
+        self.get_field(name)
+    }
+}
+
 pub struct Interpreter {
     model: PqlStatement,
     // model: Expression,
@@ -94,8 +123,7 @@ impl Interpreter {
         Self { model }
     }
 
-    pub fn run_pgm_seek(&self, packet_list: &PacketPtr, top_limit: usize) -> (usize, Vec<Packet>) {
-        let mut seek_pkt = SeekPacket::new(packet_list);
+    pub fn run_pgm_seek(&self, mut seek_pkt: SeekPacket, top_limit: usize) -> (usize, Vec<Packet>) {
         let mut counter: usize = 0;
         // let mut packet_ptr: Vec<Record> = Vec::new();
         let mut result: Vec<Packet> = Vec::new();
@@ -120,35 +148,70 @@ impl Interpreter {
     }
 
     pub fn eval(&self, pkt: &Packet) -> bool {
-        let result = self.eval_expression(&self.model.filter, pkt).unwrap();
-        result == TRUE
+        if let Some(interval) = &self.model.interval
+            && !interval.contains(pkt.timestamp_ns())
+        {
+            return false;
+        }
+
+        match self.eval_expression(&self.model.filter, pkt) {
+            Ok(result) => result == TRUE,
+            Err(err) => {
+                debug!("Filter error on packet {}: {}", pkt.packet_id(), err);
+                false
+            }
+        }
+    }
+
+    //--- A flow matches an interval it overlaps
+    pub fn eval_flow(&self, flow: &FlowRecord) -> bool {
+        if let Some(interval) = &self.model.interval
+            && (flow.end_ns < interval.from || flow.start_ns > interval.to)
+        {
+            return false;
+        }
+
+        match self.eval_expression(&self.model.filter, flow) {
+            Ok(result) => result == TRUE,
+            Err(err) => {
+                debug!("Filter error on flow {}: {}", flow.first_packet, err);
+                false
+            }
+        }
     }
 
-    fn eval_expression(&self, expression: &Expression, pkt: &Packet) -> Result<Object, EvalError> {
+    fn eval_expression<S: FieldSource>(
+        &self,
+        expression: &Expression,
+        pkt: &S,
+    ) -> Result<Object, EvalError> {
         match expression {
             Expression::Integer(i) => Ok(Object::Integer(*i as u64)),
             Expression::ArrayLong(values) => Ok(Object::LongArray(values.clone())),
             Expression::Long(i) => Ok(Object::Integer(*i)),
+            Expression::SignedInteger(i) => Ok(Object::SignedInteger(*i)),
             Expression::String(s) => Ok(Object::String(s.clone())),
             Expression::Timestamp(t) => Ok(Object::Timestamp(*t)),
             Expression::Label(value) => {
                 // debug!("Label: {}", value);
-                let field_value = pkt.get_field(value).unwrap();
+                //--- Field absent from this packet, ex: malformed or other protocol
+                let Some(field_value) = pkt.field(value) else {
+                    return Ok(Object::Null);
+                };
                 match field_value.field {
                     FieldType::Int8(_) => Ok(Object::Integer(field_value.to_u64())),
                     FieldType::Int16(_) => Ok(Object::Integer(field_value.to_u64())),
                     FieldType::Int32(_) => Ok(Object::Integer(field_value.to_u64())),
                     FieldType::Int64(_) => Ok(Object::Integer(field_value.to_u64())),
+                    FieldType::SInt8(_) => Ok(Object::SignedInteger(field_value.to_i64())),
                     FieldType::String(_) => Ok(Object::String(field_value.to_string())),
 
                     _ => Ok(Object::Integer(field_value.to_u64())),
                 }
             }
-            Expression::LabelByte(field, offset, len) => Ok(Object::ByteArray(pkt.get_field_byte(
-                field.clone(),
-                *offset,
-                *len,
-            ))),
+            Expression::LabelByte(field, offset, len) => {
+                Ok(Object::ByteArray(pkt.field_bytes(field, *offset, *len)))
+            }
             Expression::Array(array_values) => Ok(Object::ByteArray(array_values.clone())),
             Expression::IPv4(addr, mask) => Ok(Object::IPv4(*addr, *mask)),
             Expression::MacAddress(addr) => Ok(Object::MacAddress(*addr)),
@@ -173,6 +236,16 @@ impl Interpreter {
             (Object::Integer(i), Object::Integer(j)) => {
                 self.eval_integer_infix_expression(operator, *i, *j)
             }
+            //--- Signed fields compare with signed and unsigned values
+            (Object::SignedInteger(i), Object::SignedInteger(j)) => {
+                self.eval_signed_infix_expression(operator, *i as i128, *j as i128)
+            }
+            (Object::SignedInteger(i), Object::Integer(j)) => {
+                self.eval_signed_infix_expression(operator, *i as i128, *j as i128)
+            }
+            (Object::Integer(i), Object::SignedInteger(j)) => {
+                self.eval_signed_infix_expression(operator, *i as i128, *j as i128)
+            }
             (Object::String(i), Object::String(j)) => {
                 self.eval_string_infix_expression(operator, i, j)
             }
@@ -197,6 +270,8 @@ impl Interpreter {
             (Object::Integer(b0), Object::Boolean(b1)) => {
                 self.eval_boolean_infix_expression(operator, *b0 == 1, *b1)
             }
+            //--- A comparison with a missing field is false
+            (Object::Null, _) | (_, Object::Null) => Ok(FALSE),
             // (Object::Str(s0), Object::Str(s1)) => eval_string_infix_expression(operator, s0, s1),
             (_, _) => Err(EvalError::TypeMismatch(
                 format!("{} {} {}", left.debug_type(), operator, right.debug_type()),
@@ -305,6 +380,26 @@ impl Interpreter {
         }
     }
 
+    fn eval_signed_infix_expression(
+        &self,
+        operator: &Operator,
+        i: i128,
+        j: i128,
+    ) -> Result<Object, EvalError> {
+        match operator {
+            Operator::LT => Ok(Object::get_bool(i < j)),
+            Operator::LE => Ok(Object::get_bool(i <= j)),
+            Operator::GT => Ok(Object::get_bool(i > j)),
+            Operator::GE => Ok(Object::get_bool(i >= j)),
+            Operator::Equal => Ok(Object::get_bool(i == j)),
+            Operator::NE => Ok(Object::get_bool(i != j)),
+            _ => Err(EvalError::UnknownOperator(
+                format!("{}", operator),
+                "eval_signed_infix_expression".to_string(),
+            )),
+        }
+    }
+
     fn eval_string_infix_expression(
         &self,
         operator: &Operator,
@@ -325,9 +420,7 @@ impl Interpreter {
     }
 
     fn like_string(&self, source: &str, target: &str) -> bool {
-        let pattern = Regex::new(target).unwrap();
-
-        pattern.is_match(source)
+        Regex::new(target).is_ok_and(|pattern| pattern.is_match(source))
     }
 
     fn eval_boolean_infix_expression(
@@ -381,3 +474,51 @@ impl Display for EvalError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+    use frame::packet::LINKTYPE_IEEE802_11_RADIOTAP;
+
+    //--- Radiotap header with a signal of -67 dBm, 802.11 data, IPv4 and UDP
+    fn radiotap_packet() -> Packet {
+        let packet: Vec<u8> = vec![
+            0x00, 0x00, 0x0f, 0x00, 0x2e, 0x00, 0x00, 0x00, 0x10, 0x02, 0x85, 0x09, 0xa0, 0x00,
+            0xbd, 0x08, 0x01, 0x2c, 0x00, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0xa0, 0xb0, 0xc0,
+            0xd0, 0xe0, 0xf0, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x20, 0x00, 0xaa, 0xaa, 0x03,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x45, 0x00, 0x00, 0x1c, 0x00, 0x01, 0x00, 0x00, 0x40,
+            0x11, 0x00, 0x00, 0xc0, 0xa8, 0x01, 0x0a, 0xc0, 0xa8, 0x01, 0x01, 0xd4, 0x31, 0x27,
+            0x0f, 0x00, 0x08, 0x00, 0x00, 0xde, 0xad, 0xbe, 0xef,
+        ];
+        let header: [u8; 16] = [
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x4f, 0x00, 0x00, 0x00, 0x4f, 0x00,
+            0x00, 0x00,
+        ];
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(packet, header, 0, 0, true, LINKTYPE_IEEE802_11_RADIOTAP);
+        pkt
+    }
+
+    fn matches(filter: &str, pkt: &Packet) -> bool {
+        let pql = format!("select radiotap.signal_dbm from * where {} top 1", filter);
+        let model = Parse::new().parse_select(&pql).unwrap();
+
+        Interpreter::new(model).eval(pkt)
+    }
+
+    #[test]
+    fn signed_dbm_filter() {
+        let pkt = radiotap_packet();
+
+        assert!(matches("radiotap.signal_dbm == -67", &pkt), "Equal");
+        assert!(matches("radiotap.signal_dbm > -70", &pkt), "Above -70 dBm");
+        assert!(!matches("radiotap.signal_dbm > -60", &pkt), "Below -60 dBm");
+        assert!(matches("radiotap.signal_dbm < 0", &pkt), "Negative");
+        assert!(
+            matches("radiotap.signal_dbm >= -67 and radiotap.channel == 6", &pkt),
+            "With an unsigned field"
+        );
+    }
+}
@@ -10,7 +10,10 @@ use field::ipv4_address::{IPv4, from_string_to_ip};
 use field::mac_address::MacAddr;
 use frame::constant::NetConstant;
 use frame::fields::is_field_valid;
+use frame::flow::{FLOWS, is_flow_field};
 use frame::layer_index::LayerIndex;
+use frame::packet_id::PacketId;
+use frame::registry::field_layer;
 
 use log::debug;
 use std::collections::HashSet;
@@ -69,10 +72,27 @@ impl fmt::Display for Operator {
     }
 }
 
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+//--- Bounds are inclusive and in nanoseconds since the epoch
 #[derive(Debug, Clone)]
 pub struct Interval {
-    pub from: u32,
-    pub to: u32,
+    pub from: u64,
+    pub to: u64,
+}
+
+impl Interval {
+    pub fn from_secs(&self) -> u32 {
+        (self.from / NANOS_PER_SEC) as u32
+    }
+
+    pub fn to_secs(&self) -> u32 {
+        (self.to / NANOS_PER_SEC) as u32
+    }
+
+    pub fn contains(&self, ts_ns: u64) -> bool {
+        ts_ns >= self.from && ts_ns <= self.to
+    }
 }
 
 impl fmt::Display for Interval {
@@ -109,7 +129,7 @@ pub struct PqlStatement {
     pub has_distinct: bool,
     pub aggr_list: Vec<Aggregate>,
     pub groupby_fields: Vec<SelectField>,
-    pub id_search: Vec<u64>,
+    pub id_search: Vec<PacketId>,
     prev_label: String,
     prev_op: Operator,
 }
@@ -126,6 +146,11 @@ impl PqlStatement {
     pub fn has_id_search(&self) -> bool {
         !self.id_search.is_empty()
     }
+
+    //--- select ... from flows runs on the flow tables, not the packets
+    pub fn is_flow_query(&self) -> bool {
+        self.from.iter().any(|source| source == FLOWS)
+    }
 }
 
 impl fmt::Display for PqlStatement {
@@ -194,6 +219,7 @@ pub enum Expression {
     Boolean(bool),
     Integer(u32),
     Long(u64),
+    SignedInteger(i64),
     IPv4(u32, u8),
     Timestamp(u32),
     MacAddress(u64),
@@ -215,6 +241,7 @@ impl fmt::Display for Expression {
             }
             Self::Integer(value) => write!(f, "Integer({})", value),
             Self::Long(value) => write!(f, "Logn({})", value),
+            Self::SignedInteger(value) => write!(f, "Signed({})", value),
             Self::Timestamp(value) => write!(f, "Timestamp({})", value),
             Self::IPv4(ip_addr, cidr) => write!(f, "IPv4({})", IPv4::new(*ip_addr, *cidr)),
             Self::Boolean(value) => write!(f, "Bool: {}", value),
@@ -386,6 +413,7 @@ impl Parse {
         self.token_list = tokenizer.tokenize(pql).clone();
 
         if self.expect(Keyword::Select).is_some() {
+            let mut select_tokens: Vec<Token> = Vec::new();
             loop {
                 if self.accept(Keyword::Distinct).is_some() {
                     self.query.has_distinct = true;
@@ -396,7 +424,10 @@ impl Parse {
                 } else if let Some(sfield) = self.expect(Keyword::Identifier) {
                     if is_field_valid(&sfield.value) {
                         debug!("FIELD: {} valid", sfield.value);
-                        self.query.select.push(SelectField { name: sfield.value });
+                        self.query.select.push(SelectField {
+                            name: sfield.value.clone(),
+                        });
+                        select_tokens.push(sfield);
                     } else {
                         debug!("FIELD: {} NOT valid", sfield.value);
                         self.error_list.push(ErrorMsg {
@@ -413,10 +444,19 @@ impl Parse {
                 }
             }
 
-            if self.expect(Keyword::From).is_some() {
+            let from_token = self.expect(Keyword::From);
+            if from_token.is_some() {
                 debug!("From");
-                while let Some(ffield) = self.expect(Keyword::Identifier) {
-                    self.query.from.push(ffield.value);
+                loop {
+                    //--- Capture source names, a wildcard is every source
+                    if let Some(wildcard) = self.accept(Keyword::Wildcard) {
+                        self.query.from.push(wildcard.value);
+                    } else if let Some(ffield) = self.expect(Keyword::Identifier) {
+                        self.query.from.push(ffield.value);
+                    } else {
+                        break;
+                    }
+
                     if self.peek(Keyword::Comma) {
                         self.accept(Keyword::Comma);
                     } else {
@@ -424,6 +464,9 @@ impl Parse {
                     }
                 }
             }
+            for token in select_tokens.iter() {
+                self.check_field_source(token);
+            }
 
             if self.expect(Keyword::Where).is_some() {
                 debug!("Where");
@@ -433,8 +476,8 @@ impl Parse {
             if self.peek(Keyword::Interval) {
                 debug!("Interval");
                 self.accept(Keyword::Interval);
-                let mut ts_start: u32 = 0;
-                let mut ts_end: u32 = 0;
+                let mut ts_start: u64 = 0;
+                let mut ts_end: u64 = 0;
                 if self.peek(Keyword::Timestamp)
                     && let Some(_start_ts) = self.expect(Keyword::Timestamp)
                 {
@@ -453,7 +496,12 @@ impl Parse {
                 if self.peek(Keyword::Timestamp) {
                     let _end_ts = self.expect(Keyword::Timestamp).unwrap();
                     if let Some(end_ts) = self.get_timestamp(&_end_ts.value) {
-                        ts_end = end_ts;
+                        //--- Without a fraction the whole last second is included
+                        ts_end = if _end_ts.value.contains('.') {
+                            end_ts
+                        } else {
+                            end_ts + NANOS_PER_SEC - 1
+                        };
                     }
                 }
 
@@ -519,6 +567,9 @@ impl Parse {
 
             debug!("End of process for select");
             self.query.search_type = self.field_type.clone();
+            if let Some(from_token) = from_token {
+                self.check_flow_query(&from_token);
+            }
         }
 
         if self.error_list.is_empty() {
@@ -529,14 +580,17 @@ impl Parse {
         }
     }
 
-    fn get_now(&self) -> u32 {
-        Local::now().timestamp() as u32
+    fn get_now(&self) -> u64 {
+        Local::now().timestamp_nanos_opt().unwrap_or_default() as u64
     }
 
-    fn get_now_ts(&self, offset: u8, modifier: &str) -> u32 {
+    fn get_now_ts(&self, offset: u8, modifier: &str) -> u64 {
         let result = Local::now();
 
         let duration: Duration = match modifier {
+            "ns" => Duration::nanoseconds(offset as i64),
+            "us" => Duration::microseconds(offset as i64),
+            "ms" => Duration::milliseconds(offset as i64),
             "s" => Duration::seconds(offset as i64),
             "m" => Duration::minutes(offset as i64),
             "h" => Duration::hours(offset as i64),
@@ -545,16 +599,19 @@ impl Parse {
             _ => Duration::seconds(0),
         };
 
-        (result - duration).timestamp() as u32
+        (result - duration)
+            .timestamp_nanos_opt()
+            .unwrap_or_default() as u64
     }
 
-    fn get_timestamp(&self, timestamp: &str) -> Option<u32> {
-        let ts_result = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S");
+    //--- Fraction of second is optional: 2024-02-01 14:35:00.250
+    fn get_timestamp(&self, timestamp: &str) -> Option<u64> {
+        let ts_result = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S%.f");
 
         match ts_result {
             Ok(ts) => {
-                let rawts = Local.from_local_datetime(&ts).unwrap();
-                Some(rawts.timestamp() as u32)
+                let rawts = Local.from_local_datetime(&ts).single()?;
+                rawts.timestamp_nanos_opt().map(|ns| ns as u64)
             }
             Err(_) => None,
         }
@@ -704,6 +761,8 @@ impl Parse {
     fn parse_factor(&mut self) -> Option<Expression> {
         if self.peek(Keyword::Integer) {
             self.parse_int()
+        } else if self.peek(Keyword::Minus) {
+            self.parse_negative_int()
         } else if self.peek(Keyword::String) {
             self.parse_string()
         } else if self.peek(Keyword::Timestamp) {
@@ -771,6 +830,24 @@ impl Parse {
         }
     }
 
+    //--- Negative values of signed fields, ex: radiotap.signal_dbm > -70
+    fn parse_negative_int(&mut self) -> Option<Expression> {
+        self.accept(Keyword::Minus)?;
+        let tok = self.expect(Keyword::Integer)?;
+
+        match tok.value.parse::<i64>() {
+            Ok(value) => Some(Expression::SignedInteger(-value)),
+            Err(_) => {
+                self.error_list.push(ErrorMsg {
+                    message: format!("Invalid negative integer: -{}", tok.value),
+                    line: tok.line,
+                    column: tok.column,
+                });
+                None
+            }
+        }
+    }
+
     fn parse_constant(&mut self) -> Option<Expression> {
         if let Some(tok) = self.accept(Keyword::Constant) {
             match tok.value.as_str() {
@@ -944,7 +1021,14 @@ impl Parse {
                     index_values.push(long_value);
 
                     if self.query.prev_label == "frame.id" && self.query.prev_op == Operator::In {
-                        self.query.id_search.push(long_value);
+                        match PacketId::from_u64(long_value) {
+                            Ok(id) => self.query.id_search.push(id),
+                            Err(msg) => self.error_list.push(ErrorMsg {
+                                message: msg.to_string(),
+                                line: tok.line,
+                                column: tok.column,
+                            }),
+                        }
                     }
                 }
                 if !self.peek(Keyword::Comma) {
@@ -996,6 +1080,7 @@ impl Parse {
             self.query.filter_fields.push(SelectField {
                 name: tok.value.clone(),
             });
+            self.check_field_source(&tok);
             if self.peek(Keyword::IndexStart) {
                 return self.parse_label_byte(tok);
             }
@@ -1008,19 +1093,48 @@ impl Parse {
         None
     }
 
+    //--- Flow fields only on flows and packet fields only on packets
+    fn check_field_source(&mut self, tok: &Token) {
+        let flow_field = is_flow_field(&tok.value);
+        let message = if self.query.is_flow_query() && !flow_field {
+            format!("Field {} is not a flow field", tok.value)
+        } else if !self.query.is_flow_query() && flow_field {
+            format!("Field {} needs: from {}", tok.value, FLOWS)
+        } else {
+            return;
+        };
+
+        self.error_list.push(ErrorMsg {
+            message,
+            line: tok.line,
+            column: tok.column,
+        });
+    }
+
+    fn check_flow_query(&mut self, from_token: &Token) {
+        if !self.query.is_flow_query() {
+            return;
+        }
+
+        let mut messages: Vec<String> = Vec::new();
+        if self.query.from.len() > 1 {
+            messages.push(format!("{} is the only source of a flow query", FLOWS));
+        }
+        if self.query.has_aggregate() || self.query.has_groupby() {
+            messages.push("Aggregates and group by are not supported on flows".to_string());
+        }
+        for message in messages {
+            self.error_list.push(ErrorMsg {
+                message,
+                line: from_token.line,
+                column: from_token.column,
+            });
+        }
+    }
+
     fn add_type(&mut self, field: &str) {
-        if field.find('.').is_some() {
-            let field_type: Vec<&str> = field.split(".").collect();
-            match field_type[0] {
-                "eth" => self.field_type.insert(LayerIndex::ETH),
-                "arp" => self.field_type.insert(LayerIndex::ARP),
-                "ip" => self.field_type.insert(LayerIndex::IPv4),
-                // "ipv4" => self.field_type.insert(IndexField::IpV4),
-                "icmp" => self.field_type.insert(LayerIndex::ICMP),
-                "udp" => self.field_type.insert(LayerIndex::UDP),
-                "tcp" => self.field_type.insert(LayerIndex::TCP),
-                _ => false,
-            };
+        if let Some(layer) = field_layer(field) {
+            self.field_type.insert(layer);
         }
     }
 
@@ -1070,4 +1184,80 @@ mod tests {
         assert_eq!(1, sql.select.len(), "Select");
         assert_eq!(1, sql.from.len(), "From");
     }
+
+    #[test]
+    fn test_from_wildcard() {
+        let mut parse = Parse::new();
+        let sql = parse
+            .parse_select("select ip.src from s1, * where ip.ttl == 64")
+            .unwrap();
+
+        assert_eq!(sql.from, vec!["s1", "*"], "Source and wildcard");
+    }
+
+    #[test]
+    fn test_from_flows() {
+        let sql = Parse::new()
+            .parse_select("select flow.src, flow.bytes from flows where flow.dport == 53 top 10")
+            .unwrap();
+        assert!(sql.is_flow_query(), "Flow query");
+        assert_eq!(sql.select.len(), 2, "Flow fields");
+
+        let errors = Parse::new()
+            .parse_select("select flow.src, ip.ttl from flows where ip.src == 10.0.0.1")
+            .unwrap_err();
+        assert_eq!(errors.len(), 2, "Packet fields on flows: {:?}", errors);
+
+        let errors = Parse::new()
+            .parse_select("select ip.src from s1 where flow.packets > 10")
+            .unwrap_err();
+        assert_eq!(errors.len(), 1, "Flow field on packets: {:?}", errors);
+
+        assert!(
+            Parse::new()
+                .parse_select("select flow.src from flows, s1 where flow.packets > 10")
+                .is_err(),
+            "Flows mixed with a capture source"
+        );
+    }
+
+    #[test]
+    fn test_id_search() {
+        let sql = Parse::new()
+            .parse_select(
+                "select frame.id from * where frame.id in [4294967320, 9223373157841240064]",
+            )
+            .unwrap();
+        assert_eq!(
+            sql.id_search,
+            vec![PacketId::new(1, 24), PacketId::new(1, 5 << 32)],
+            "v0 and v1 ids"
+        );
+
+        let errors = Parse::new()
+            .parse_select("select frame.id from * where frame.id in [9223373136366403608]")
+            .unwrap_err();
+        assert_eq!(errors.len(), 1, "Reserved id: {:?}", errors);
+    }
+
+    #[test]
+    fn test_interval_fraction() {
+        let mut parse = Parse::new();
+        let pql_test = "select ip.src from s1 where ip.ttl == 64 interval 2024-02-01 14:35:00.250 to 2024-02-01 14:35:01";
+        let sql = parse.parse_select(pql_test).unwrap();
+        let interval = sql.interval.unwrap();
+
+        assert_eq!(
+            interval.to - interval.from,
+            1_749_999_999,
+            "Interval length"
+        );
+        assert_eq!(
+            interval.to_secs() - interval.from_secs(),
+            1,
+            "Interval seconds"
+        );
+        assert!(interval.contains(interval.from + 1), "Inside interval");
+        assert!(!interval.contains(interval.from - 1), "Before interval");
+    }
 }
@@ -566,6 +566,17 @@ impl Preparser {
                 }
             }
 
+            //--- Optional fraction of second
+            if self.peek_at(0, Keyword::Period).is_some()
+                && self.peek_at(1, Keyword::Integer).is_some()
+            {
+                for _ in 0..2 {
+                    if let Some(tok) = self.advance() {
+                        timestamp.push_str(&tok.value);
+                    }
+                }
+            }
+
             let token = Token {
                 token: Keyword::Timestamp,
                 value: timestamp,
@@ -663,6 +674,18 @@ mod tests {
         assert!(result.len() == 2);
         assert!(result[0].token == Keyword::Timestamp);
     }
+
+    #[test]
+    fn timestamp_fraction_token() {
+        let mut t = Lexer::new();
+        let line = "2024-02-01 14:35:00.250";
+        let token_list: &Vec<Token> = t.tokenize(line);
+        let mut parser = Preparser::default();
+        let result = parser.parse(token_list.clone());
+        assert!(result.len() == 2, "Fraction merged in timestamp");
+        assert!(result[0].token == Keyword::Timestamp);
+        assert!(result[0].value.ends_with(":00.250"), "Fraction value");
+    }
     #[test]
     fn ipv4_token() {
         let mut t = Lexer::new();
@@ -10,8 +10,8 @@ use log::debug;
 
 pub struct QueryResult {
     model: PqlStatement,
-    ts_start: u32,
-    ts_end: u32,
+    ts_start: u64,
+    ts_end: u64,
     result: Cursor,
     offset: usize,
     groupby: GroupBy,
@@ -22,7 +22,7 @@ pub struct QueryResult {
 impl QueryResult {
     pub fn new(model: PqlStatement) -> Self {
         Self {
-            ts_start: u32::MAX,
+            ts_start: u64::MAX,
             ts_end: 0,
             result: Cursor::default(),
             offset: 0,
@@ -33,8 +33,11 @@ impl QueryResult {
         }
     }
 
+    //--- Packets asked by id are all returned, with or without top
     pub fn count_reach(&self) -> bool {
-        if self.model.has_groupby() {
+        if self.model.has_id_search() {
+            false
+        } else if self.model.has_groupby() {
             self.groupby.count_reach()
         } else if self.model.has_distinct {
             self.distinct_list.len() >= self.model.top
@@ -68,23 +71,23 @@ impl QueryResult {
             }
         }
 
-        let pkt_id = pkt.get_id();
-        record.add(Field::set_field(FieldType::Int64(pkt_id), "frame.id"));
+        record.add(Field::set_field(pkt.id_field(), "frame.id"));
 
         if let Some(ts_temp) = pkt.get_field("frame.timestamp") {
             let mut ts = ts_temp;
             ts.name = "frame.timestamp".to_string();
             record.add(ts.clone());
-
-            if ts.to_u32() < self.ts_start {
-                self.ts_start = ts.to_u32();
-            }
-
-            if ts.to_u32() > self.ts_end {
-                self.ts_end = ts.to_u32();
-            }
         }
 
+        //--- Sub-second ordering key, whatever the segment resolution
+        let ts_ns = pkt.timestamp_ns();
+        record.add(Field::set_field(
+            FieldType::Int64(ts_ns),
+            "frame.timestamp_ns",
+        ));
+        self.ts_start = self.ts_start.min(ts_ns);
+        self.ts_end = self.ts_end.max(ts_ns);
+
         if self.model.has_distinct {
             if !self.distinct_list.contains(&distinct_key) {
                 self.result.add_record(record);
@@ -220,3 +223,24 @@ impl GroupBy {
         self.result.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::Parse;
+
+    #[test]
+    fn id_search_without_top() {
+        let model = Parse::new()
+            .parse_select("select frame.id from * where frame.id in [4294967296, 4294967297]")
+            .unwrap();
+        assert_eq!(model.top, 0, "No top");
+
+        let mut query_result = QueryResult::new(model);
+        assert!(!query_result.count_reach(), "Search runs without top");
+        query_result.add(Packet::new());
+        query_result.add(Packet::new());
+        assert!(!query_result.count_reach(), "Every id is returned");
+        assert_eq!(query_result.get_result().len(), 2, "Both packets");
+    }
+}
@@ -1,90 +1,656 @@
+use crate::ingest::{Counters, Overflow, Snapshot, queue_size};
 use crate::packet_ref::PacketRef;
-use anyhow::Result;
-use byteorder::{LittleEndian, WriteBytesExt};
-use database::dbconfig::DBConfig;
+use crate::rotation::RotationPolicy;
+use crate::shutdown;
+use anyhow::{Result, anyhow};
+use database::capture_source::CaptureSource;
+use database::capture_stats::{CaptureStats, SegmentStats};
+use database::catalog;
+use database::file_manager::compress_closed_segments;
 use database::index_manager::IndexManager;
+use database::live_index::LiveIndex;
+use database::recovery::recover;
+use database::reindex::upgrade_indexes;
+use database::retention::Retention;
 use dblib::config::CONFIG;
-use pcap::Capture;
-use std::fs::File;
-use std::io::{BufWriter, Write};
-use std::sync::mpsc;
-use std::thread;
+use pcap::{Active, Capture, Precision};
+use pcapdb::pcap_writer::global_header;
+use pcapdb::pcapng_writer::{PcapngWriter, Record};
+use pcapdb::segment::{SegmentFile, create_segment};
+use std::ffi::{c_int, c_void};
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::mpsc::{self, RecvTimeoutError, SyncSender, TrySendError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const MAX_PACKETS_PER_FILE: u32 = 50_000;
+//--- Enhanced packet block without its data and options
+const EPB_LEN: usize = 32;
+const PCAP_RECORD_LEN: usize = 16;
+const CAPTURE_TIMEOUT_MS: i32 = 500;
+//--- Longest time a written packet waits before it is searchable
+const LIVE_FLUSH: Duration = Duration::from_secs(1);
+//--- Pcap statistics are read every STATS_POLL and saved every STATS_SAVE
+const STATS_POLL: Duration = Duration::from_secs(1);
+const STATS_SAVE: Duration = Duration::from_secs(10);
+pub const LINKTYPE_ETHERNET: u32 = 1;
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_MICRO: u64 = 1_000;
+//--- PCAP_TSTAMP_PRECISION_NANO
+const TSTAMP_PRECISION_NANO: c_int = 1;
 
-const GLOBAL_HDR: [u8; 24] = [
-    0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
-    0x00, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00,
-];
+//--- Not exposed by the pcap crate, libpcap is linked through it
+unsafe extern "C" {
+    fn pcap_get_tstamp_precision(handle: *mut c_void) -> c_int;
+}
+
+//--- Segment file, classic pcap records or pcapng blocks. The file keeps
+//--- the .pcap name, readers recognize pcapng by its section header and
+//--- compressed segments by their magic. Classic pcap keeps its position
+enum Segment {
+    Pcap(SegmentFile, u64),
+    Pcapng(PcapngWriter<SegmentFile>),
+}
 
-pub fn capture(device_name: &str) -> Result<()> {
-    println!("Capture device: {}", device_name);
-    let (tx_packet, rx_packet) = mpsc::channel();
-    let (tx_db, rx_db) = mpsc::channel();
+impl Segment {
+    fn create(file_no: u32, folder: &str, global_hdr: &[u8; 24]) -> Result<Self> {
+        let mut bin_file = create_segment(file_no, folder)?;
 
-    thread::spawn(move || {
-        let index_mgr: IndexManager = IndexManager::default();
-        for file_id in rx_db {
-            index_mgr.index_one_file(file_id);
+        if CONFIG.capture_pcapng {
+            Ok(Segment::Pcapng(PcapngWriter::new(bin_file)))
+        } else {
+            bin_file.write_all(global_hdr)?;
+            Ok(Segment::Pcap(bin_file, global_hdr.len() as u64))
+        }
+    }
+
+    //--- Pointer and bytes of the record, the interface blocks of pcapng
+    //--- are left out of the bytes
+    fn write(
+        &mut self,
+        pkt: &PacketRef,
+        device_name: &str,
+        link_type: u32,
+        nanosecond: bool,
+    ) -> Result<(u64, u64)> {
+        match self {
+            Segment::Pcap(bin_file, position) => {
+                bin_file.write_all(&pkt.header())?;
+                bin_file.write_all(&pkt.packet)?;
+
+                let ptr = *position;
+                let bytes = (PCAP_RECORD_LEN + pkt.packet.len()) as u64;
+                *position += bytes;
+                Ok((ptr, bytes))
+            }
+            Segment::Pcapng(writer) => {
+                let fraction = if nanosecond {
+                    pkt.ts_fraction as u64
+                } else {
+                    pkt.ts_fraction as u64 * NANOS_PER_MICRO
+                };
+
+                writer.write_record(&Record {
+                    link_type,
+                    interface_name: Some(device_name),
+                    timestamp_ns: pkt.timestamp as u64 * NANOS_PER_SEC + fraction,
+                    orig_len: pkt.orig_len,
+                    data: &pkt.packet,
+                    comment: None,
+                })?;
+
+                let bytes = (EPB_LEN + pkt.packet.len().next_multiple_of(4)) as u64;
+                Ok((writer.position() - bytes, bytes))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Segment::Pcap(bin_file, _) => bin_file.flush(),
+            Segment::Pcapng(writer) => writer.flush(),
+        }
+    }
+
+    //--- Everything written is on disk, the file lock is released
+    fn close(self) -> std::io::Result<()> {
+        let mut bin_file = match self {
+            Segment::Pcap(bin_file, _) => bin_file,
+            Segment::Pcapng(writer) => writer.into_inner(),
+        };
+
+        bin_file.finish()
+    }
+}
+
+//--- Work of the indexer thread, in the order the writer did it
+enum IndexEvent {
+    Open(u32),
+    Packet(u64, PacketRef),
+    Flushed,
+    Closed(u32, SegmentStats),
+}
+
+//--- New segment of a source, allocated in the catalog first
+fn open_segment(
+    source: &CaptureSource,
+    link_type: u32,
+    global_hdr: &[u8; 24],
+) -> Result<(u32, Segment)> {
+    let file_no = catalog::allocate(source.id, link_type)?;
+    let segment = Segment::create(file_no, &source.folder, global_hdr)?;
+
+    Ok((file_no, segment))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+//------------------------------------------------------------------
+//--- Writer and indexer threads shared by every capture source. The
+//--- writer rotates segments and gives each packet with its pointer to
+//--- the indexer. The open segment is flushed every LIVE_FLUSH so its
+//--- live index, and the packets, are searchable while it is written.
+//--- Compressed segments are searchable up to their last full block.
+//--- Both queues are bounded, a full packet queue drops or blocks by the
+//--- overflow policy, a full indexer queue always blocks the writer.
+//--- Nanosecond tells if the timestamp fractions of the packets sent
+//--- are nanoseconds or microseconds
+//------------------------------------------------------------------
+pub struct Pipeline {
+    tx_packet: SyncSender<PacketRef>,
+    overflow: Overflow,
+    counters: Arc<Counters>,
+    writer: JoinHandle<()>,
+    indexer: JoinHandle<()>,
+}
+
+impl Pipeline {
+    pub fn start(
+        source: &CaptureSource,
+        link_type: u32,
+        overflow: Overflow,
+        nanosecond: bool,
+    ) -> Self {
+        let (tx_packet, rx_packet) = mpsc::sync_channel::<PacketRef>(queue_size());
+        let (tx_db, rx_db) = mpsc::sync_channel::<IndexEvent>(queue_size());
+        let counters = Arc::new(Counters::default());
+        let global_hdr = global_header(link_type, true, nanosecond);
+        let policy = RotationPolicy::from_source(source);
+
+        let indexer = thread::spawn(move || {
+            let index_mgr: IndexManager = IndexManager::default();
+            let mut live: Option<LiveIndex> = None;
+
+            for event in rx_db {
+                match event {
+                    IndexEvent::Open(file_id) => {
+                        live = LiveIndex::create(file_id)
+                            .inspect_err(|msg| {
+                                eprintln!("Error creating live index {}: {}", file_id, msg)
+                            })
+                            .ok();
+                    }
+                    IndexEvent::Packet(ptr, pkt) => {
+                        if let Some(live) = &mut live {
+                            let pkt = pkt.into_packet(live.file_id(), ptr, link_type, nanosecond);
+                            live.add(&pkt);
+                        }
+                    }
+                    IndexEvent::Flushed => {
+                        if let Some(Err(msg)) = live.as_mut().map(|live| live.publish()) {
+                            eprintln!("Error writing live index: {}", msg);
+                        }
+                    }
+                    //--- Without a live index the closed segment is read again
+                    IndexEvent::Closed(file_id, stats) => {
+                        if let Err(msg) = catalog::close(file_id, stats.dropped()) {
+                            eprintln!("Error closing segment {}: {}", file_id, msg);
+                        }
+                        match live.take() {
+                            Some(live) => {
+                                if let Err(msg) = live.close() {
+                                    eprintln!("Error closing live index {}: {}", file_id, msg);
+                                }
+                            }
+                            None => {
+                                index_mgr.index_one_file(file_id);
+                            }
+                        }
+
+                        if stats.dropped() > 0 {
+                            eprintln!(
+                                "Segment {} is incomplete, {} packets dropped",
+                                file_id,
+                                stats.dropped()
+                            );
+                        }
+                        if let Err(msg) = stats.save() {
+                            eprintln!("Error saving stats of segment {}: {}", file_id, msg);
+                        }
+                    }
+                }
+            }
+        });
+
+        let source = source.clone();
+        let writer_counters = Arc::clone(&counters);
+        let writer = thread::spawn(move || {
+            let counters = writer_counters;
+            let mut pkt_count: usize = 0;
+            let mut bytes: u64 = 0;
+            let mut first_packet: Option<Instant> = None;
+            let mut last_flush = Instant::now();
+            let mut unflushed = false;
+            let mut open_failed = false;
+
+            //--- A segment is opened by its first packet and allocated in the
+            //--- catalog with its source before it is written. Its counters start at the
+            //--- snapshot taken when it is opened
+            let mut segment: Option<(u32, Segment)> = None;
+            let mut opened = (0, Snapshot::default());
+            let close_stats = |file_id: u32, opened: &(u64, Snapshot), packets, bytes| {
+                let counts = counters.snapshot().since(&opened.1);
+                SegmentStats {
+                    file_id,
+                    capture_id: source.id,
+                    opened_at: opened.0,
+                    closed_at: now_secs(),
+                    packets: packets as u64,
+                    bytes,
+                    queue_dropped: counts.queue_dropped,
+                    kernel_dropped: counts.kernel_dropped,
+                    if_dropped: counts.if_dropped,
+                    queue_high_water: counts.queue_high_water,
+                    write_errors: counts.write_errors,
+                }
+            };
+            //--- The segment is complete on disk before its index, a failed
+            //--- close is counted in its stats
+            let close =
+                |file_no: u32, writer: Segment, opened: &(u64, Snapshot), packets, bytes| {
+                    if let Err(msg) = writer.close() {
+                        eprintln!("Error closing segment {}: {}", file_no, msg);
+                        counters.write_failed();
+                    }
+                    let stats = close_stats(file_no, opened, packets, bytes);
+                    tx_db
+                        .send(IndexEvent::Closed(file_no, stats))
+                        .map_err(|_| anyhow!("Indexer stopped"))
+                };
+
+            //--- Ends with the source, or with the indexer
+            let mut write = || -> Result<()> {
+                loop {
+                    //--- Without packets the segment is still closed at its age
+                    //--- and flushed for the live index
+                    let age = first_packet.map(|first| first.elapsed());
+                    let flush_wait =
+                        unflushed.then(|| LIVE_FLUSH.saturating_sub(last_flush.elapsed()));
+                    let received = match [policy.wait(age), flush_wait].into_iter().flatten().min()
+                    {
+                        Some(wait) => rx_packet.recv_timeout(wait),
+                        None => rx_packet.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+
+                    //--- A packet that can not be written is counted and the
+                    //--- segment is closed, the next packet opens a new one
+                    let mut failed = false;
+                    match received {
+                        Ok(pkt) => {
+                            counters.dequeued();
+                            if segment.is_none() {
+                                counters.reset_high_water();
+                                opened = (now_secs(), counters.snapshot());
+                                match open_segment(&source, link_type, &global_hdr) {
+                                    Ok((file_no, writer)) => {
+                                        open_failed = false;
+                                        tx_db
+                                            .send(IndexEvent::Open(file_no))
+                                            .map_err(|_| anyhow!("Indexer stopped"))?;
+                                        segment = Some((file_no, writer));
+                                    }
+                                    Err(msg) => {
+                                        //--- Logged once until a segment opens again
+                                        if !open_failed {
+                                            eprintln!(
+                                                "Error opening a segment of {}: {}",
+                                                source.name, msg
+                                            );
+                                        }
+                                        open_failed = true;
+                                        counters.write_failed();
+                                        continue;
+                                    }
+                                }
+                            }
+
+                            if let Some((file_no, writer)) = &mut segment {
+                                match writer.write(&pkt, &source.iface, link_type, nanosecond) {
+                                    Ok((ptr, size)) => {
+                                        tx_db
+                                            .send(IndexEvent::Packet(ptr, pkt))
+                                            .map_err(|_| anyhow!("Indexer stopped"))?;
+
+                                        pkt_count += 1;
+                                        bytes += size;
+                                        first_packet.get_or_insert_with(Instant::now);
+                                        unflushed = true;
+                                    }
+                                    Err(msg) => {
+                                        eprintln!("Error writing segment {}: {}", file_no, msg);
+                                        counters.write_failed();
+                                        failed = true;
+                                    }
+                                }
+                            }
+                        }
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                    }
+
+                    let age = first_packet.map(|first| first.elapsed());
+                    if (failed || policy.should_rotate(pkt_count, bytes, age))
+                        && let Some((file_no, writer)) = segment.take()
+                    {
+                        close(file_no, writer, &opened, pkt_count, bytes)?;
+
+                        pkt_count = 0;
+                        bytes = 0;
+                        first_packet = None;
+                        unflushed = false;
+                    } else if unflushed
+                        && last_flush.elapsed() >= LIVE_FLUSH
+                        && let Some((file_no, writer)) = &mut segment
+                    {
+                        match writer.flush() {
+                            Ok(()) => tx_db
+                                .send(IndexEvent::Flushed)
+                                .map_err(|_| anyhow!("Indexer stopped"))?,
+                            Err(msg) => eprintln!("Error flushing segment {}: {}", file_no, msg),
+                        }
+                        last_flush = Instant::now();
+                        unflushed = false;
+                    }
+                }
+            };
+            let result = write();
+
+            //--- End of the source, the last segment is closed and indexed
+            let result = match segment.take() {
+                Some((file_no, writer)) => {
+                    result.and(close(file_no, writer, &opened, pkt_count, bytes))
+                }
+                None => result,
+            };
+            if let Err(msg) = result {
+                eprintln!("Segment writer of {} stopped: {}", source.name, msg);
+            }
+        });
+
+        Self {
+            tx_packet,
+            overflow,
+            counters,
+            writer,
+            indexer,
+        }
+    }
+
+    pub fn send(&self, pkt: PacketRef) -> Result<()> {
+        self.counters.enqueued();
+        match self.overflow {
+            Overflow::Block => self.tx_packet.send(pkt)?,
+            Overflow::Drop => match self.tx_packet.try_send(pkt) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => self.counters.dropped(),
+                Err(TrySendError::Disconnected(_)) => {
+                    return Err(anyhow!("Segment writer stopped"));
+                }
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn counters(&self) -> &Counters {
+        &self.counters
+    }
+
+    //--- Wait until every packet sent is written and indexed
+    pub fn finish(self) -> Result<()> {
+        drop(self.tx_packet);
+        self.writer
+            .join()
+            .map_err(|_| anyhow!("Segment writer thread panicked"))?;
+        self.indexer
+            .join()
+            .map_err(|_| anyhow!("Indexer thread panicked"))?;
+
+        Ok(())
+    }
+}
+
+//--- Segments left open by a crash are repaired and indexed before
+//--- new segments are written. Segments with an index of an older
+//--- version are indexed again in the background and are searchable
+//--- once done
+pub fn start_recovery() -> Result<()> {
+    let stats = recover()?;
+    if !stats.indexed.is_empty() || !stats.removed.is_empty() || !stats.master.is_empty() {
+        println!(
+            "Recovered segments: {} indexed, {} removed, {} added to the catalog",
+            stats.indexed.len(),
+            stats.removed.len(),
+            stats.master.len()
+        );
+    }
+
+    thread::spawn(|| match upgrade_indexes() {
+        Ok(file_ids) if !file_ids.is_empty() => {
+            println!("Upgraded the indexes of {} segments", file_ids.len())
         }
+        Ok(_) => {}
+        Err(msg) => eprintln!("Error upgrading indexes: {}", msg),
     });
 
-    thread::spawn(move || {
-        let mut file_no: u32;
-        let mut pkt_count: u32 = 0;
-        let mut dbconfig: DBConfig = DBConfig::default();
-        let mut header: Vec<u8> = Vec::with_capacity(16);
-
-        file_no = dbconfig.next_fileid().unwrap();
-        let mut bin_file =
-            BufWriter::new(File::create(format!("{}/{}.pcap", &CONFIG.db_path, file_no)).unwrap());
-        bin_file.write_all(&GLOBAL_HDR).unwrap();
-
-        for p in rx_packet {
-            if pkt_count >= MAX_PACKETS_PER_FILE {
-                tx_db.send(file_no).unwrap();
-
-                pkt_count = 0;
-                file_no = dbconfig.next_fileid().unwrap();
-                bin_file = BufWriter::new(
-                    File::create(format!("{}/{}.pcap", &CONFIG.db_path, file_no)).unwrap(),
-                );
-                bin_file.write_all(&GLOBAL_HDR).unwrap();
+    Ok(())
+}
+
+//--- Segments closed before compression was enabled are converted in
+//--- the background, new segments are written compressed
+pub fn start_compression() {
+    if CONFIG.segment_compression {
+        thread::spawn(|| {
+            if let Err(msg) = compress_closed_segments() {
+                eprintln!("Error compressing segments: {}", msg);
             }
+        });
+    }
+}
 
-            let pkt: PacketRef = p;
-            pkt_count += 1;
+//--- Seconds between retention runs when retention_interval is not set
+const RETENTION_INTERVAL: u64 = 600;
 
-            header.clear();
-            header.write_u32::<LittleEndian>(pkt.timestamp).unwrap();
-            header.write_u32::<LittleEndian>(pkt.ts_us).unwrap();
-            header.write_u32::<LittleEndian>(pkt.cap_len).unwrap();
-            header.write_u32::<LittleEndian>(pkt.orig_len).unwrap();
+//--- Retention runs while the capture writes new segments
+pub fn start_retention() {
+    if CONFIG.retention_max_age == 0
+        && CONFIG.retention_max_bytes == 0
+        && !CaptureSource::list().is_ok_and(|sources| {
+            sources
+                .iter()
+                .any(|source| source.retention_age > 0 || source.retention_bytes > 0)
+        })
+    {
+        return;
+    }
 
-            bin_file.write_all(&header).unwrap();
-            bin_file.write_all(&pkt.packet).unwrap();
+    let interval = match CONFIG.retention_interval {
+        0 => RETENTION_INTERVAL,
+        secs => secs,
+    };
+    thread::spawn(move || {
+        let mut retention = Retention::default();
+        loop {
+            if let Err(msg) = retention.enforce() {
+                eprintln!("Error applying retention: {}", msg);
+            }
+            thread::sleep(Duration::from_secs(interval));
         }
     });
+}
+
+//------------------------------------------------------------------
+//--- Capture of comma separated source names, or * for every source
+//--- of the capture table, each source in its own thread. A name not
+//--- in the table is a device captured with the default settings
+//------------------------------------------------------------------
+pub fn capture(names: &str) -> Result<()> {
+    let sources: Vec<CaptureSource> = if names.trim() == "*" {
+        CaptureSource::list()?
+    } else {
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                Ok(
+                    CaptureSource::by_name(name)?.unwrap_or_else(|| CaptureSource {
+                        iface: name.to_string(),
+                        ..Default::default()
+                    }),
+                )
+            })
+            .collect::<Result<Vec<CaptureSource>>>()?
+    };
+
+    if sources.is_empty() {
+        return Err(anyhow!("No capture source to start"));
+    }
 
-    println!("Starting capture on interface: {}", device_name);
-    let mut cap = Capture::from_device(device_name)
-        .unwrap()
-        .promisc(true)
-        .open()
-        .unwrap();
+    shutdown::install()?;
+    start_recovery()?;
+    start_compression();
+    start_retention();
+    let captures: Vec<_> = sources
+        .into_iter()
+        .map(|source| thread::spawn(move || (source.name.clone(), capture_source(&source))))
+        .collect();
+
+    for capture in captures {
+        match capture.join() {
+            Ok((_, Ok(()))) => {}
+            Ok((name, Err(msg))) => eprintln!("Error capturing {}: {}", name, msg),
+            Err(_) => eprintln!("Capture thread panicked"),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn capture_source(source: &CaptureSource) -> Result<()> {
+    if source.iface.is_empty() {
+        return Err(anyhow!("Capture source {} has no interface", source.name));
+    }
+
+    println!("Capture device: {}", source.iface);
+    let mut cap = Capture::from_device(source.iface.as_str())?.promisc(true);
+    if CONFIG.capture_nanosecond {
+        cap = cap.precision(Precision::Nano);
+    }
+    //--- The read timeout lets the loop see a shutdown on an idle link
+    let mut cap = cap.timeout(CAPTURE_TIMEOUT_MS).open()?;
+    if !source.filter.is_empty() {
+        cap.filter(&source.filter, true)?;
+    }
+    let nanosecond = CONFIG.capture_nanosecond && granted_nanosecond(&cap);
+    if CONFIG.capture_nanosecond && !nanosecond {
+        eprintln!(
+            "Nanosecond timestamps not supported on {}, capturing in microseconds",
+            source.iface
+        );
+    }
+
+    println!("Starting capture on interface: {}", source.iface);
+    let pipeline = Pipeline::start(
+        source,
+        LINKTYPE_ETHERNET,
+        Overflow::from_config(),
+        nanosecond,
+    );
+    let started_at = now_secs();
+    let mut last_poll = Instant::now();
+    let mut last_save = Instant::now();
 
     println!("Starting packet capture");
-    while let Ok(packet) = cap.next_packet() {
+    while !shutdown::requested() {
+        if last_poll.elapsed() >= STATS_POLL {
+            poll_stats(&mut cap, pipeline.counters());
+            last_poll = Instant::now();
+        }
+        if last_save.elapsed() >= STATS_SAVE {
+            save_stats(source, started_at, &pipeline.counters().snapshot());
+            last_save = Instant::now();
+        }
+
+        let packet = match cap.next_packet() {
+            Ok(packet) => packet,
+            Err(pcap::Error::TimeoutExpired) => continue,
+            Err(_) => break,
+        };
         let pkt = PacketRef {
             orig_len: packet.header.len,
             cap_len: packet.header.caplen,
             timestamp: packet.header.ts.tv_sec as u32,
-            ts_us: packet.header.ts.tv_usec as u32,
+            //--- tv_usec holds nanoseconds when the precision granted is Nano
+            ts_fraction: packet.header.ts.tv_usec as u32,
             packet: packet.data.to_vec(),
         };
 
-        tx_packet.send(pkt)?;
+        pipeline.send(pkt)?;
     }
 
+    println!("Closing capture on interface: {}", source.iface);
+    poll_stats(&mut cap, pipeline.counters());
+    let counts = pipeline.counters().snapshot();
+    pipeline.finish()?;
+    save_stats(source, started_at, &counts);
+
     Ok(())
 }
+
+//--- Precision the device gave the handle, a request for nanoseconds
+//--- is only a request: its result is dropped by the pcap crate
+fn granted_nanosecond(cap: &Capture<Active>) -> bool {
+    unsafe { pcap_get_tstamp_precision(cap.as_ptr().cast()) == TSTAMP_PRECISION_NANO }
+}
+
+fn poll_stats(cap: &mut Capture<Active>, counters: &Counters) {
+    match cap.stats() {
+        Ok(stat) => counters.set_kernel(&stat),
+        Err(msg) => eprintln!("Error reading capture statistics: {}", msg),
+    }
+}
+
+fn save_stats(source: &CaptureSource, started_at: u64, counts: &Snapshot) {
+    let stats = CaptureStats {
+        iface: source.iface.clone(),
+        capture_id: source.id,
+        started_at,
+        updated_at: now_secs(),
+        received: counts.received,
+        queue_dropped: counts.queue_dropped,
+        kernel_received: counts.kernel_received,
+        kernel_dropped: counts.kernel_dropped,
+        if_dropped: counts.if_dropped,
+        queue_high_water: counts.queue_peak,
+        write_errors: counts.write_errors,
+    };
+
+    if let Err(msg) = stats.save() {
+        eprintln!("Error saving capture stats of {}: {}", source.iface, msg);
+    }
+}
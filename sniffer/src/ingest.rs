@@ -0,0 +1,175 @@
+use dblib::config::CONFIG;
+use pcap::Stat;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+//--- Packets queued for the writer when capture_queue_size is not set
+const QUEUE_SIZE: usize = 10_000;
+
+pub fn queue_size() -> usize {
+    match CONFIG.capture_queue_size {
+        0 => QUEUE_SIZE,
+        size => size,
+    }
+}
+
+//------------------------------------------------------------------
+//--- What the capture does with a packet when the writer queue is
+//--- full. Drop keeps reading the handle and counts the packet, Block
+//--- waits for the writer and leaves the kernel buffer to fill
+//------------------------------------------------------------------
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Drop,
+    Block,
+}
+
+impl Overflow {
+    pub fn from_config() -> Self {
+        match CONFIG.capture_overflow.to_lowercase().as_str() {
+            "block" => Overflow::Block,
+            _ => Overflow::Drop,
+        }
+    }
+}
+
+//--- Counters at one point of the capture, every count since it started
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Snapshot {
+    pub received: u64,
+    pub queue_dropped: u64,
+    pub queue_high_water: u64,
+    pub queue_peak: u64,
+    pub kernel_received: u64,
+    pub kernel_dropped: u64,
+    pub if_dropped: u64,
+    pub write_errors: u64,
+}
+
+impl Snapshot {
+    //--- Counts between an earlier snapshot and this one
+    pub fn since(&self, start: &Snapshot) -> Snapshot {
+        Snapshot {
+            received: self.received.saturating_sub(start.received),
+            queue_dropped: self.queue_dropped.saturating_sub(start.queue_dropped),
+            queue_high_water: self.queue_high_water,
+            queue_peak: self.queue_peak,
+            kernel_received: self.kernel_received.saturating_sub(start.kernel_received),
+            kernel_dropped: self.kernel_dropped.saturating_sub(start.kernel_dropped),
+            if_dropped: self.if_dropped.saturating_sub(start.if_dropped),
+            write_errors: self.write_errors.saturating_sub(start.write_errors),
+        }
+    }
+}
+
+//------------------------------------------------------------------
+//--- Counters shared by the capture loop and the writer. The depth is
+//--- the packets in the writer queue, its high water mark is reset by
+//--- the writer when it opens a segment, the peak never is
+//------------------------------------------------------------------
+#[derive(Debug, Default)]
+pub struct Counters {
+    received: AtomicU64,
+    queue_dropped: AtomicU64,
+    depth: AtomicU64,
+    high_water: AtomicU64,
+    peak: AtomicU64,
+    kernel_received: AtomicU64,
+    kernel_dropped: AtomicU64,
+    if_dropped: AtomicU64,
+    write_errors: AtomicU64,
+}
+
+impl Counters {
+    //--- Counted before the send so the writer never sees a negative depth
+    pub fn enqueued(&self) {
+        self.received.fetch_add(1, Ordering::Relaxed);
+        let depth = self.depth.fetch_add(1, Ordering::Relaxed) + 1;
+        self.high_water.fetch_max(depth, Ordering::Relaxed);
+        self.peak.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    //--- Packet counted by enqueued that did not fit in the queue
+    pub fn dropped(&self) {
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+        self.queue_dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dequeued(&self) {
+        self.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    //--- Packet dequeued that no segment holds, or a segment not closed cleanly
+    pub fn write_failed(&self) {
+        self.write_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn reset_high_water(&self) {
+        self.high_water
+            .store(self.depth.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    //--- Pcap statistics count from the opening of the handle, in 32 bits
+    pub fn set_kernel(&self, stat: &Stat) {
+        self.kernel_received
+            .store(stat.received as u64, Ordering::Relaxed);
+        self.kernel_dropped
+            .store(stat.dropped as u64, Ordering::Relaxed);
+        self.if_dropped
+            .store(stat.if_dropped as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            received: self.received.load(Ordering::Relaxed),
+            queue_dropped: self.queue_dropped.load(Ordering::Relaxed),
+            queue_high_water: self.high_water.load(Ordering::Relaxed),
+            queue_peak: self.peak.load(Ordering::Relaxed),
+            kernel_received: self.kernel_received.load(Ordering::Relaxed),
+            kernel_dropped: self.kernel_dropped.load(Ordering::Relaxed),
+            if_dropped: self.if_dropped.load(Ordering::Relaxed),
+            write_errors: self.write_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_counters() {
+        let counters = Counters::default();
+        for _ in 0..3 {
+            counters.enqueued();
+        }
+        counters.dequeued();
+        counters.enqueued();
+        counters.dropped();
+
+        let start = counters.snapshot();
+        assert_eq!(start.received, 4, "Packets received");
+        assert_eq!(start.queue_dropped, 1, "Packets dropped");
+        assert_eq!(start.queue_high_water, 3, "Deepest queue");
+
+        counters.reset_high_water();
+        counters.dequeued();
+        counters.enqueued();
+        counters.dropped();
+        counters.set_kernel(&Stat {
+            received: 10,
+            dropped: 2,
+            if_dropped: 1,
+        });
+
+        counters.write_failed();
+
+        let segment = counters.snapshot().since(&start);
+        assert_eq!(segment.received, 1, "Received in the segment");
+        assert_eq!(segment.queue_dropped, 1, "Dropped in the segment");
+        assert_eq!(segment.queue_high_water, 2, "Deepest queue in the segment");
+        assert_eq!(segment.queue_peak, 3, "Deepest queue of the capture");
+        assert_eq!(segment.kernel_dropped, 2, "Kernel drops in the segment");
+        assert_eq!(segment.if_dropped, 1, "Interface drops in the segment");
+        assert_eq!(segment.write_errors, 1, "Write errors in the segment");
+    }
+}
@@ -1,2 +1,6 @@
 pub mod capture;
+pub mod ingest;
 pub mod packet_ref;
+pub mod replay;
+pub mod rotation;
+pub mod shutdown;
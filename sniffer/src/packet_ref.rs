@@ -1,8 +1,41 @@
+use byteorder::{ByteOrder, LittleEndian};
+use frame::packet::Packet;
+
 #[derive(Default, Debug, Clone)]
 pub struct PacketRef {
     pub packet: Vec<u8>,
     pub orig_len: u32,
     pub cap_len: u32,
     pub timestamp: u32,
-    pub ts_us: u32,
+    //--- Micro or nanoseconds, depending on the capture precision
+    pub ts_fraction: u32,
+}
+
+impl PacketRef {
+    //--- Record header of classic pcap segments
+    pub fn header(&self) -> [u8; 16] {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], self.timestamp);
+        LittleEndian::write_u32(&mut header[4..8], self.ts_fraction);
+        LittleEndian::write_u32(&mut header[8..12], self.cap_len);
+        LittleEndian::write_u32(&mut header[12..16], self.orig_len);
+
+        header
+    }
+
+    //--- Packet as a search reads it back from its segment
+    pub fn into_packet(
+        self,
+        file_id: u32,
+        pkt_ptr: u64,
+        link_type: u32,
+        nanosecond: bool,
+    ) -> Packet {
+        let header = self.header();
+        let mut pkt = Packet::new();
+        pkt.set_packet(self.packet, header, file_id, pkt_ptr, true, link_type);
+        pkt.set_ts_nanosecond(nanosecond);
+
+        pkt
+    }
 }
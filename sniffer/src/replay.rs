@@ -0,0 +1,167 @@
+use crate::capture::{Pipeline, start_compression, start_recovery};
+use crate::ingest::Overflow;
+use crate::packet_ref::PacketRef;
+use crate::shutdown;
+use anyhow::Result;
+use database::capture_source::CaptureSource;
+use dblib::config::CONFIG;
+use frame::packet::Packet;
+use pcapdb::pcapfile::PcapFile;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+const NANOS_PER_MICRO: u64 = 1_000;
+const SHUTDOWN_CHECK: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Default)]
+pub struct ReplayStats {
+    pub packets: usize,
+    pub skipped: usize,
+}
+
+//--- Delay from the start of the replay when a packet is due, None when
+//--- packets are sent as fast as they are read
+pub fn replay_offset(first_ns: u64, ts_ns: u64, speed: f64) -> Option<Duration> {
+    if speed <= 0.0 {
+        return None;
+    }
+
+    let elapsed = ts_ns.saturating_sub(first_ns) as f64 / speed;
+    Some(Duration::from_nanos(elapsed as u64))
+}
+
+//--- Packet read from a file as the capture handle would give it
+pub fn to_packet_ref(pkt: &Packet, nanosecond: bool) -> PacketRef {
+    let ts_ns = pkt.timestamp_ns();
+    let fraction = if nanosecond {
+        ts_ns % NANOS_PER_SEC
+    } else {
+        (ts_ns % NANOS_PER_SEC) / NANOS_PER_MICRO
+    };
+    let cap_len = pkt.raw_bytes().len() as u32;
+
+    PacketRef {
+        packet: pkt.raw_bytes().to_vec(),
+        orig_len: pkt
+            .get_field("frame.origlen")
+            .map_or(cap_len, |field| field.to_u32()),
+        cap_len,
+        timestamp: (ts_ns / NANOS_PER_SEC) as u32,
+        ts_fraction: fraction as u32,
+    }
+}
+
+//------------------------------------------------------------------
+//--- Replay of a pcap or pcapng file through the capture pipeline.
+//--- A speed of 1.0 keeps the original pacing, 2.0 is twice as fast
+//--- and 0 sends the packets as fast as they are read. Segments have
+//--- one link type, packets of another link type are skipped. The
+//--- segments belong to the source, or to none when it is not saved
+//------------------------------------------------------------------
+pub fn replay(fname: &str, speed: f64, source: &CaptureSource) -> Result<ReplayStats> {
+    println!("Replay file: {}", fname);
+    let mut pfile = PcapFile::open(fname, 0)?;
+    let mut stats = ReplayStats::default();
+
+    let Some(first) = pfile.next() else {
+        return Ok(stats);
+    };
+
+    let link_type = first.link_type();
+    let first_ns = first.timestamp_ns();
+    let mut source = source.clone();
+    if source.iface.is_empty() {
+        source.iface = fname.to_string();
+    }
+
+    shutdown::install()?;
+    start_recovery()?;
+    start_compression();
+    //--- A file is read as fast as the writer takes it, nothing is dropped
+    let pipeline = Pipeline::start(
+        &source,
+        link_type,
+        Overflow::Block,
+        CONFIG.capture_nanosecond,
+    );
+    let start = Instant::now();
+    let mut next = Some(first);
+
+    while let Some(pkt) = next {
+        if shutdown::requested() {
+            break;
+        }
+
+        if pkt.link_type() != link_type {
+            stats.skipped += 1;
+        } else {
+            //--- Long gaps are waited in steps to see a shutdown
+            if let Some(due) = replay_offset(first_ns, pkt.timestamp_ns(), speed) {
+                while !shutdown::requested() && due > start.elapsed() {
+                    thread::sleep((due - start.elapsed()).min(SHUTDOWN_CHECK));
+                }
+            }
+
+            pipeline.send(to_packet_ref(&pkt, CONFIG.capture_nanosecond))?;
+            stats.packets += 1;
+        }
+
+        next = pfile.next();
+    }
+
+    pipeline.finish()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{ByteOrder, LittleEndian};
+
+    #[test]
+    fn pacing() {
+        let first = 1_700_000_000 * NANOS_PER_SEC;
+
+        assert_eq!(replay_offset(first, first + 500, 0.0), None, "Unpaced");
+        assert_eq!(
+            replay_offset(first, first + 2 * NANOS_PER_SEC, 1.0),
+            Some(Duration::from_secs(2)),
+            "Original speed"
+        );
+        assert_eq!(
+            replay_offset(first, first + 2 * NANOS_PER_SEC, 4.0),
+            Some(Duration::from_millis(500)),
+            "Four times faster"
+        );
+        assert_eq!(
+            replay_offset(first, first - 10, 1.0),
+            Some(Duration::ZERO),
+            "Timestamp going back"
+        );
+    }
+
+    #[test]
+    fn packet_ref_resolution() {
+        let mut header = [0u8; 16];
+        LittleEndian::write_u32(&mut header[0..4], 1_700_000_000);
+        LittleEndian::write_u32(&mut header[4..8], 123_456_789);
+        LittleEndian::write_u32(&mut header[8..12], 60);
+        LittleEndian::write_u32(&mut header[12..16], 1514);
+
+        let mut pkt = Packet::new();
+        pkt.set_packet(vec![0xaa; 60], header, 1, 24, true, 1);
+        pkt.set_ts_nanosecond(true);
+
+        let micro = to_packet_ref(&pkt, false);
+        assert_eq!(micro.timestamp, 1_700_000_000, "Seconds");
+        assert_eq!(micro.ts_fraction, 123_456, "Microseconds");
+        assert_eq!(micro.cap_len, 60, "Captured length");
+        assert_eq!(micro.orig_len, 1514, "Original length");
+        assert_eq!(
+            to_packet_ref(&pkt, true).ts_fraction,
+            123_456_789,
+            "Nanoseconds"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use database::capture_source::CaptureSource;
+use dblib::config::CONFIG;
+use std::time::Duration;
+
+//--- Packets per segment when neither the source nor db_segment_size set it
+const MAX_PACKETS_PER_FILE: usize = 50_000;
+
+//------------------------------------------------------------------
+//--- When the open segment is closed, any limit reached rotates it.
+//--- The age is counted from the first packet of the segment so an
+//--- idle link does not create empty segments
+//------------------------------------------------------------------
+#[derive(Debug, Clone, PartialEq)]
+pub struct RotationPolicy {
+    pub max_packets: usize,
+    //--- 0 for no limit, bytes of the pcap stream before compression
+    pub max_bytes: u64,
+    pub max_age: Option<Duration>,
+}
+
+impl RotationPolicy {
+    //--- Source settings first, then the configuration defaults
+    pub fn from_source(source: &CaptureSource) -> Self {
+        let max_packets = match (source.packets_per_file, CONFIG.db_segment_size) {
+            (0, 0) => MAX_PACKETS_PER_FILE,
+            (0, size) => size,
+            (count, _) => count,
+        };
+        let max_bytes = match source.max_bytes {
+            0 => CONFIG.segment_max_bytes,
+            bytes => bytes,
+        };
+        let max_age = match source.max_age {
+            0 => CONFIG.segment_max_age,
+            seconds => seconds,
+        };
+
+        Self {
+            max_packets,
+            max_bytes,
+            max_age: (max_age > 0).then(|| Duration::from_secs(max_age)),
+        }
+    }
+
+    pub fn should_rotate(&self, packets: usize, bytes: u64, age: Option<Duration>) -> bool {
+        if packets == 0 {
+            return false;
+        }
+
+        packets >= self.max_packets
+            || (self.max_bytes > 0 && bytes >= self.max_bytes)
+            || matches!((self.max_age, age), (Some(max_age), Some(age)) if age >= max_age)
+    }
+
+    //--- How long the writer can wait for a packet before the age limit
+    pub fn wait(&self, age: Option<Duration>) -> Option<Duration> {
+        match (self.max_age, age) {
+            (Some(max_age), Some(age)) => Some(max_age.saturating_sub(age)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let policy = RotationPolicy {
+            max_packets: 100,
+            max_bytes: 10_000,
+            max_age: Some(Duration::from_secs(60)),
+        };
+        let age = |secs| Some(Duration::from_secs(secs));
+
+        assert!(!policy.should_rotate(0, 0, age(120)), "Empty segment");
+        assert!(!policy.should_rotate(99, 9_999, age(59)), "Under limits");
+        assert!(policy.should_rotate(100, 0, age(1)), "Packet count");
+        assert!(policy.should_rotate(1, 10_000, age(1)), "Size");
+        assert!(policy.should_rotate(1, 100, age(60)), "Age");
+    }
+
+    #[test]
+    fn wait_for_age() {
+        let policy = RotationPolicy {
+            max_packets: 100,
+            max_bytes: 0,
+            max_age: Some(Duration::from_secs(60)),
+        };
+
+        assert_eq!(policy.wait(None), None, "No packet yet");
+        assert_eq!(
+            policy.wait(Some(Duration::from_secs(45))),
+            Some(Duration::from_secs(15)),
+            "Remaining age"
+        );
+        assert_eq!(
+            policy.wait(Some(Duration::from_secs(90))),
+            Some(Duration::ZERO),
+            "Past the age"
+        );
+        assert!(
+            !RotationPolicy {
+                max_bytes: 0,
+                max_age: None,
+                ..policy
+            }
+            .should_rotate(99, u64::MAX, Some(Duration::MAX)),
+            "Only the packet count"
+        );
+    }
+}
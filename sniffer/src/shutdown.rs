@@ -0,0 +1,34 @@
+use anyhow::Result;
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Once};
+
+static SHUTDOWN: LazyLock<Arc<AtomicBool>> = LazyLock::new(|| Arc::new(AtomicBool::new(false)));
+static INSTALL: Once = Once::new();
+
+//------------------------------------------------------------------
+//--- SIGINT, SIGTERM and SIGQUIT ask the captures to stop, their open
+//--- segments are then closed and indexed. A second signal while they
+//--- close exits at once
+//------------------------------------------------------------------
+pub fn install() -> Result<()> {
+    let mut result = Ok(());
+
+    INSTALL.call_once(|| {
+        for signal in TERM_SIGNALS {
+            result = flag::register_conditional_shutdown(*signal, 1, Arc::clone(&SHUTDOWN))
+                .and_then(|_| flag::register(*signal, Arc::clone(&SHUTDOWN)))
+                .map(|_| ());
+            if result.is_err() {
+                break;
+            }
+        }
+    });
+
+    Ok(result?)
+}
+
+pub fn requested() -> bool {
+    SHUTDOWN.load(Ordering::Relaxed)
+}
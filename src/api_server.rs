@@ -1,11 +1,30 @@
 use actix_cors::Cors;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use database::capture_source::CaptureSource;
+use database::capture_stats::{CaptureStats, SegmentStats};
+use database::catalog;
 use database::dbengine::DbEngine;
+use database::export::ExportOptions;
+use database::import::{Importer, import_request_path};
+use database::retention::{deletion_log, hold_log, set_legal_hold};
+use dblib::config::CONFIG;
+use frame::packet::Packet;
+use frame::packet_id::PacketId;
+use frame::print_hex::format_hex;
+use pcap::pcap_writer::packet_to_pcap;
 use serde_json::Value;
 use serde_json::json;
 use std::collections::BTreeMap;
+use std::io::{self, BufWriter, Write};
+
+use actix_web::web::Bytes;
+use futures_util::stream;
+use tokio::sync::mpsc;
 
 use actix_web::{
-    App, HttpResponse, HttpServer, Responder, http::header, middleware::Logger, post, web,
+    App, HttpRequest, HttpResponse, HttpServer, Responder, get, http::header, middleware::Logger,
+    post, web,
 };
 
 use actix_web::web::Json;
@@ -55,6 +74,466 @@ async fn execute(name: web::Json<Command>) -> impl Responder {
     // HttpResponse::Ok().json(result)
 }
 
+fn packet_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({
+      "success": false,
+      "result": "Packet not found"
+    }))
+}
+
+//--- Packet id of the path, file:offset or its numeric form
+fn parse_packet_id(id: &str) -> Result<PacketId, Box<HttpResponse>> {
+    id.parse::<PacketId>().map_err(|e| {
+        Box::new(HttpResponse::BadRequest().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })))
+    })
+}
+
+//--- Pcap record header fields, the timestamp is split in seconds and microseconds
+fn record_header(pkt: &Packet) -> Value {
+    let mut header = json!({});
+
+    for field in [
+        "frame.timestamp",
+        "frame.offset",
+        "frame.inclen",
+        "frame.origlen",
+    ] {
+        if let Some(value) = pkt.get_field(field) {
+            header[field.trim_start_matches("frame.")] = value.to_json();
+        }
+    }
+
+    header
+}
+
+#[get("/packets/{id}")]
+async fn packet_by_id(id: web::Path<String>) -> HttpResponse {
+    let id = match parse_packet_id(&id) {
+        Ok(id) => id,
+        Err(response) => return *response,
+    };
+    let db = DbEngine::new();
+
+    let Some(pkt) = db.get_packet(id) else {
+        return packet_not_found();
+    };
+
+    HttpResponse::Ok().json(json!({
+      "success": true,
+      "result": {
+        "id": match pkt.get_id() {
+            Ok(numeric) => json!(numeric),
+            Err(_) => json!(id.to_string()),
+        },
+        "packet_id": id.to_string(),
+        "file_id": id.file_id,
+        "pkt_ptr": id.offset,
+        "link_type": pkt.link_type(),
+        "header": record_header(&pkt),
+        "bytes": STANDARD.encode(pkt.raw_bytes()),
+        "hex": format_hex(pkt.raw_bytes().to_vec()),
+        "tree": pkt.detail().to_json()
+      }
+    }))
+}
+
+#[get("/packets/{id}/detail")]
+async fn packet_detail(id: web::Path<String>) -> HttpResponse {
+    let id = match parse_packet_id(&id) {
+        Ok(id) => id,
+        Err(response) => return *response,
+    };
+    let db = DbEngine::new();
+
+    match db.get_packet(id) {
+        Some(pkt) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": pkt.detail().to_json()
+        })),
+
+        None => packet_not_found(),
+    }
+}
+
+#[get("/packets/{id}/pcap")]
+async fn packet_pcap(id: web::Path<String>) -> HttpResponse {
+    let id = match parse_packet_id(&id) {
+        Ok(id) => id,
+        Err(response) => return *response,
+    };
+    let db = DbEngine::new();
+
+    match db.get_packet(id) {
+        Some(pkt) => HttpResponse::Ok()
+            .content_type("application/vnd.tcpdump.pcap")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}-{}.pcap\"", id.file_id, id.offset),
+            ))
+            .body(packet_to_pcap(&pkt)),
+
+        None => packet_not_found(),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportRequest {
+    command: String,
+    #[serde(flatten)]
+    options: ExportOptions,
+}
+
+//--- Export chunks are sent to the response as they are written
+const EXPORT_CHUNK: usize = 64 * 1024;
+const EXPORT_CHUNKS_QUEUED: usize = 4;
+
+type ExportChunk = Result<Bytes, String>;
+
+//--- Writer of an export running off the async workers, a write fails
+//--- when the client is gone so the export stops
+struct ChannelWriter {
+    tx: mpsc::Sender<ExportChunk>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.tx
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Export client gone"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//--- Packets are streamed as the segments are read. An error before the
+//--- first chunk is a bad request, a later one ends the response early
+#[post("/export")]
+async fn export(request: web::Json<ExportRequest>) -> HttpResponse {
+    let request = request.into_inner();
+    let format = request.options.format;
+    let (tx, mut rx) = mpsc::channel::<ExportChunk>(EXPORT_CHUNKS_QUEUED);
+
+    let export_task = web::block(move || {
+        let mut db = DbEngine::new();
+        let mut writer = BufWriter::with_capacity(EXPORT_CHUNK, ChannelWriter { tx: tx.clone() });
+
+        let result = db.export(&request.command, &request.options, &mut writer);
+        let result = result.and_then(|_| {
+            writer
+                .flush()
+                .map_err(|error| format!("Error writing export: {}", error))
+        });
+        if let Err(e) = result {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+    actix_web::rt::spawn(export_task);
+
+    let first = match rx.recv().await {
+        Some(Ok(chunk)) => Some(chunk),
+        Some(Err(e)) => {
+            return HttpResponse::BadRequest().json(json!({
+              "success": false,
+              "result": e
+            }));
+        }
+        None => None,
+    };
+
+    let chunks = stream::unfold((first, rx), |(first, mut rx)| async move {
+        let chunk = match first {
+            Some(chunk) => Ok(chunk),
+            None => match rx.recv().await? {
+                Ok(chunk) => Ok(chunk),
+                Err(e) => {
+                    eprintln!("Export stopped: {}", e);
+                    Err(actix_web::error::ErrorInternalServerError(e))
+                }
+            },
+        };
+        Some((chunk, (None, rx)))
+    });
+
+    HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"export.{}\"", format.extension()),
+        ))
+        .streaming(chunks)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImportRequest {
+    path: String,
+}
+
+//--- File or directory inside the configured import_path, indexing runs
+//--- off the async workers
+#[post("/import")]
+async fn import(request: web::Json<ImportRequest>) -> HttpResponse {
+    let path = match import_request_path(&request.into_inner().path) {
+        Ok(path) => path.to_string_lossy().into_owned(),
+        Err(e) => {
+            return HttpResponse::Forbidden().json(json!({
+              "success": false,
+              "result": e.to_string()
+            }));
+        }
+    };
+
+    match web::block(move || Importer::default().import(&path)).await {
+        Ok(Ok(stats)) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": stats
+        })),
+        Ok(Err(e)) => HttpResponse::BadRequest().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+#[get("/sources")]
+async fn sources() -> HttpResponse {
+    match CaptureSource::list() {
+        Ok(sources) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": sources
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+//--- Add a capture source or update the one with the same name, only
+//--- with the hold_token as the source folder and retention are set
+#[post("/sources")]
+async fn save_source(http_request: HttpRequest, request: web::Json<CaptureSource>) -> HttpResponse {
+    if !token_authorized(&http_request) {
+        return HttpResponse::Unauthorized().json(json!({
+          "success": false,
+          "result": "Capture source changes need the hold token"
+        }));
+    }
+    let mut source = request.into_inner();
+
+    match source.save() {
+        Ok(()) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": source
+        })),
+        Err(e) => HttpResponse::BadRequest().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+//--- Segments deleted by retention, oldest first
+#[get("/retention/log")]
+async fn retention_log() -> HttpResponse {
+    match deletion_log() {
+        Ok(log) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": log
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+//--- Legal holds placed and released, oldest first
+#[get("/hold/log")]
+async fn legal_hold_log() -> HttpResponse {
+    match hold_log() {
+        Ok(log) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": log
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+//--- Totals of every capture, saved while it runs
+#[get("/stats/captures")]
+async fn capture_stats() -> HttpResponse {
+    match CaptureStats::list() {
+        Ok(stats) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": stats
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SegmentStatsQuery {
+    source: Option<String>,
+}
+
+//--- Id of the named capture source, None for every source
+fn source_id(source: &Option<String>) -> Result<Option<u32>, Box<HttpResponse>> {
+    match source {
+        Some(name) => match CaptureSource::by_name(name) {
+            Ok(Some(source)) => Ok(Some(source.id)),
+            Ok(None) => Err(Box::new(HttpResponse::NotFound().json(json!({
+              "success": false,
+              "result": format!("Capture source {} not found", name)
+            })))),
+            Err(e) => Err(Box::new(HttpResponse::InternalServerError().json(json!({
+              "success": false,
+              "result": e.to_string()
+            })))),
+        },
+        None => Ok(None),
+    }
+}
+
+//--- Catalog rows of the segments, of one source when it is named
+#[get("/segments")]
+async fn segments(query: web::Query<SegmentStatsQuery>) -> HttpResponse {
+    let capture_id = match source_id(&query.source) {
+        Ok(capture_id) => capture_id,
+        Err(response) => return *response,
+    };
+
+    match catalog::list(capture_id) {
+        Ok(entries) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": entries
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+//--- Counters of the closed capture segments, of one source when it is
+//--- named. A segment with drops is not complete
+#[get("/stats/segments")]
+async fn segment_stats(query: web::Query<SegmentStatsQuery>) -> HttpResponse {
+    let capture_id = match source_id(&query.source) {
+        Ok(capture_id) => capture_id,
+        Err(response) => return *response,
+    };
+
+    match SegmentStats::list(capture_id) {
+        Ok(stats) => {
+            let result: Vec<Value> = stats
+                .iter()
+                .map(|segment| {
+                    let mut value = json!(segment);
+                    value["complete"] = json!(segment.dropped() == 0);
+                    value
+                })
+                .collect();
+
+            HttpResponse::Ok().json(json!({
+              "success": true,
+              "result": result
+            }))
+        }
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct HoldRequest {
+    file_id: u32,
+    hold: bool,
+    //--- Who asks for the change, kept in the retention log
+    user: String,
+}
+
+//--- Bearer token of the request against hold_token, compared in
+//--- constant time. No token configured refuses every request
+fn token_authorized(request: &HttpRequest) -> bool {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    let expected = CONFIG.hold_token.as_bytes();
+
+    !expected.is_empty()
+        && token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+//--- Place or release the legal hold of a segment, only with the
+//--- hold_token. The user and the client address are logged
+#[post("/hold")]
+async fn hold(http_request: HttpRequest, request: web::Json<HoldRequest>) -> HttpResponse {
+    let request = request.into_inner();
+
+    if !token_authorized(&http_request) {
+        return HttpResponse::Unauthorized().json(json!({
+          "success": false,
+          "result": "Legal hold changes need the hold token"
+        }));
+    }
+    if request.user.trim().is_empty() {
+        return HttpResponse::BadRequest().json(json!({
+          "success": false,
+          "result": "Legal hold changes need a user"
+        }));
+    }
+
+    let actor = format!(
+        "{} ({})",
+        request.user.trim(),
+        http_request
+            .peer_addr()
+            .map_or("unknown".to_string(), |addr| addr.ip().to_string())
+    );
+    let file_id = request.file_id;
+
+    match web::block(move || set_legal_hold(file_id, request.hold, &actor)).await {
+        Ok(Ok(())) => HttpResponse::Ok().json(json!({
+          "success": true,
+          "result": file_id
+        })),
+        Ok(Err(e)) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({
+          "success": false,
+          "result": e.to_string()
+        })),
+    }
+}
+
 #[post("/login")]
 async fn login(Json(user): Json<User>) -> HttpResponse {
     println!("User info: {:?}", user);
@@ -108,6 +587,19 @@ pub async fn web_main() -> std::io::Result<()> {
             )
             .wrap(Logger::default())
             .service(execute)
+            .service(packet_by_id)
+            .service(packet_detail)
+            .service(packet_pcap)
+            .service(import)
+            .service(export)
+            .service(sources)
+            .service(save_source)
+            .service(retention_log)
+            .service(legal_hold_log)
+            .service(hold)
+            .service(capture_stats)
+            .service(segment_stats)
+            .service(segments)
             .service(login)
     })
     // .bind("0.0.0.0:9001")?
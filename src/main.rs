@@ -2,12 +2,20 @@ pub mod api_server;
 pub mod jwebtoken;
 
 use crate::api_server::web_main;
+use database::capture_source::CaptureSource;
 use database::dbengine::DbEngine;
+use database::file_manager::compress_closed_segments;
+use database::import::Importer;
 use database::init_db::InitDb;
+use database::recovery::recover;
+use database::reindex::{ReindexTarget, reindex};
+use database::retention::{Retention, set_legal_hold};
 use datastore::test_db::test_db;
 use dblib::config::CONFIG;
 use log::info;
 use sniffer::capture::capture;
+use sniffer::replay::replay;
+use sniffer::shutdown;
 use std::{env, process};
 
 use clap::Parser;
@@ -26,6 +34,43 @@ struct Args {
 
     #[arg(short, long, default_value_t = false)]
     testdb: bool,
+
+    #[arg(long, default_value_t = String::new())]
+    import: String,
+
+    #[arg(long, default_value_t = false)]
+    compress: bool,
+
+    #[arg(long, default_value_t = String::new())]
+    replay: String,
+
+    //--- 1.0 for the original pacing, 0 for as fast as possible
+    #[arg(long, default_value_t = 0.0)]
+    replay_speed: f64,
+
+    //--- Capture source the replayed segments belong to
+    #[arg(long, default_value_t = String::new())]
+    source: String,
+
+    //--- Delete the segments past the retention limits of their source
+    #[arg(long, default_value_t = false)]
+    retention: bool,
+
+    //--- Place a segment under legal hold, or release it
+    #[arg(long)]
+    hold: Option<u32>,
+
+    #[arg(long)]
+    release: Option<u32>,
+
+    //--- Index again every segment (all), file ids (12,15) or the segments
+    //--- of a time range (FROM..TO, unix seconds or local time)
+    #[arg(long, default_value_t = String::new())]
+    reindex: String,
+
+    //--- Repair and index the segments left open by a crash
+    #[arg(long, default_value_t = false)]
+    recover: bool,
 }
 
 fn process_params() {
@@ -49,11 +94,119 @@ fn process_params() {
             Ok(()) => println!("Capture sucessfull"),
             Err(msg) => eprintln!("Error capturing: {}", msg),
         }
+
+        //--- Stopped by a signal, the segments are closed and indexed
+        if shutdown::requested() {
+            process::exit(0);
+        }
+    }
+
+    if !args.replay.is_empty() {
+        let source = match CaptureSource::by_name(&args.source) {
+            Ok(Some(source)) => source,
+            Ok(None) if args.source.is_empty() => CaptureSource::default(),
+            Ok(None) => {
+                eprintln!("Unknown capture source: {}", args.source);
+                process::exit(1);
+            }
+            Err(msg) => {
+                eprintln!("Error reading capture sources: {}", msg);
+                process::exit(1);
+            }
+        };
+
+        match replay(&args.replay, args.replay_speed, &source) {
+            Ok(stats) => println!(
+                "Replayed {} packets, {} skipped",
+                stats.packets, stats.skipped
+            ),
+            Err(msg) => eprintln!("Error replaying: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if !args.import.is_empty() {
+        match Importer::default().import(&args.import) {
+            Ok(stats) => println!(
+                "Imported {} packets from {} files in {} segments",
+                stats.packets,
+                stats.files,
+                stats.segments.len()
+            ),
+            Err(msg) => eprintln!("Error importing: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if args.compress {
+        match compress_closed_segments() {
+            Ok(segments) => println!("Compressed {} segments", segments.len()),
+            Err(msg) => eprintln!("Error compressing: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if let Some((file_id, hold)) = args
+        .hold
+        .map(|file_id| (file_id, true))
+        .or(args.release.map(|file_id| (file_id, false)))
+    {
+        //--- Local account of the operator for the retention log
+        let actor = format!(
+            "{} (cli)",
+            env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+        );
+        match set_legal_hold(file_id, hold, &actor) {
+            Ok(()) => println!("Segment {} legal hold: {}", file_id, hold),
+            Err(msg) => eprintln!("Error setting legal hold: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if args.recover {
+        match recover() {
+            Ok(stats) => println!(
+                "Recovery: {} segments repaired, {} indexed, {} removed, {} added to the catalog",
+                stats.repaired.len(),
+                stats.indexed.len(),
+                stats.removed.len(),
+                stats.master.len()
+            ),
+            Err(msg) => eprintln!("Error recovering segments: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if args.retention {
+        match Retention::default().enforce() {
+            Ok(purged) => println!("Retention deleted {} segments", purged.len()),
+            Err(msg) => eprintln!("Error applying retention: {}", msg),
+        }
+        process::exit(0);
     }
 
     if args.index {
         let db = DbEngine::new();
-        db.create_index();
+        match db.create_index() {
+            Ok(update) => println!(
+                "Indexed {} segments, {} recovered",
+                update.indexed.len(),
+                update.recovery.indexed.len()
+            ),
+            Err(msg) => eprintln!("Error indexing: {}", msg),
+        }
+        process::exit(0);
+    }
+
+    if !args.reindex.is_empty() {
+        match args
+            .reindex
+            .parse::<ReindexTarget>()
+            .and_then(|target| reindex(&target))
+        {
+            Ok(indexed) => println!("Indexed {} segments", indexed.len()),
+            Err(msg) => eprintln!("Error indexing: {}", msg),
+        }
         process::exit(0);
     }
 